@@ -0,0 +1,42 @@
+//! Tap-to-align calibration of tuning change times: while the piece is playing (or a recording of it), press enter at
+//! the moment each tuning change should happen, so that the tuning file can be updated to match the performance.
+
+use std::io::stdin;
+
+use crate::tuner::TuningData;
+
+/// Prompts for a tap (enter) at each tuning change of `tunings` after time `from`, in order of time. Enter `s` to skip
+/// a tuning change, or `q` to stop.
+///
+/// `clock` returns the current time of the piece in seconds. Returns the tapped times indexed the same as `tunings`,
+/// [`None`] for tuning changes that were not tapped.
+pub fn tap(tunings: &[TuningData], from: f64, clock: impl Fn() -> f64) -> Vec<Option<f64>> {
+    let mut order: Vec<usize> = (0..tunings.len())
+        .filter(|&i| tunings[i].time > from)
+        .collect();
+    order.sort_by(|&a, &b| tunings[a].time.total_cmp(&tunings[b].time));
+
+    let mut times = vec![None; tunings.len()];
+    for (n, &idx) in order.iter().enumerate() {
+        println!(
+            "[{}/{}] Tap enter at {} (s: skip, q: stop)",
+            n + 1,
+            order.len(),
+            tunings[idx].describe()
+        );
+        let mut input = String::new();
+        if stdin().read_line(&mut input).unwrap() == 0 {
+            break;
+        }
+        let time = clock();
+        match input.trim() {
+            "q" => break,
+            "s" => continue,
+            _ => {
+                println!("  -> {time:.3}s ({:+.3}s)", time - tunings[idx].time);
+                times[idx] = Some(time);
+            }
+        }
+    }
+    times
+}
@@ -0,0 +1,1079 @@
+//! Offline analysis of a MIDI file together with its tuning data.
+
+use std::fmt::{Display, Write as _};
+use std::fs;
+
+use crate::score::{Note, Score};
+use crate::tuner::{
+    key_monzo, key_name, pitch_class, snapshot_at, spelled_key_name, spelled_name, DeferPolicy,
+    PitchSpec, Spellings, Tuner, TuningData, TuningSnapshot, PRIMES_BY_INDEX, SEMITONE_NAMES,
+};
+
+/// How much each prime is used per bar.
+pub struct PrimeHeatMap {
+    /// Primes used anywhere in the piece (excluding 2), in increasing order. These are the columns of the heat map.
+    pub primes: Vec<u32>,
+    pub rows: Vec<HeatMapRow>,
+}
+
+pub struct HeatMapRow {
+    pub bar: usize,
+    /// Start time of the bar in seconds.
+    pub time: f64,
+    /// Weight of each prime in [`PrimeHeatMap::primes`].
+    ///
+    /// The weight of a prime is the sum of `|exponent| * seconds` over all notes sounding in the bar, where
+    /// `exponent` is the power of the prime in the note's monzo. E.g. 7/4 sounding for 2 seconds contributes 2.0 to
+    /// the weight of prime 7, while 49/32 sounding for 1 second also contributes 2.0.
+    pub weights: Vec<f64>,
+}
+
+/// Computes, per bar, which primes appear in the sounding monzos and with what weight.
+///
+/// Notes sounding across a tuning change are split at the tuning change, since the pitch bend is applied to the
+/// sounding note as well.
+pub fn prime_heat_map(score: &Score, snapshots: &[TuningSnapshot]) -> PrimeHeatMap {
+    // index = prime index in monzo.
+    let mut bar_weights: Vec<Vec<f64>> = vec![vec![]; score.bars.len()];
+
+    // Times where the monzo of a sounding note can change: tuning changes & bar lines.
+    let mut boundaries: Vec<f64> = snapshots.iter().map(|s| s.time).collect();
+    boundaries.extend(score.bars.iter().map(|b| b.start));
+    boundaries.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    boundaries.dedup();
+
+    for note in &score.notes {
+        let mut seg_start = note.start;
+        let first = boundaries.partition_point(|t| *t <= note.start);
+        let seg_ends = boundaries[first..]
+            .iter()
+            .copied()
+            .take_while(|t| *t < note.release)
+            .chain(std::iter::once(note.release));
+
+        for seg_end in seg_ends {
+            let dur = seg_end - seg_start;
+            // Tempered notes have no primes.
+            let pc_monzo = snapshot_at(snapshots, seg_start)
+                .and_then(|snapshot| snapshot.monzos[pitch_class(note.key)].as_ref());
+            if let Some(pc_monzo) = pc_monzo {
+                let monzo = key_monzo(pc_monzo, note.key);
+                let bar_idx = score.bar_at(seg_start).number - 1;
+                let weights = &mut bar_weights[bar_idx];
+                if weights.len() < monzo.len() {
+                    weights.resize(monzo.len(), 0.0);
+                }
+                // skip powers of 2, octave equivalence.
+                for (i, exp) in monzo.iter().enumerate().skip(1) {
+                    weights[i] += exp.unsigned_abs() as f64 * dur;
+                }
+            }
+            seg_start = seg_end;
+        }
+    }
+
+    let max_prime_idx = bar_weights.iter().map(|w| w.len()).max().unwrap_or(0);
+    let used_prime_idxs: Vec<usize> = (1..max_prime_idx)
+        .filter(|i| {
+            bar_weights
+                .iter()
+                .any(|w| w.get(*i).copied().unwrap_or(0.0) > 0.0)
+        })
+        .collect();
+
+    let rows = score
+        .bars
+        .iter()
+        .zip(bar_weights)
+        .map(|(bar, weights)| HeatMapRow {
+            bar: bar.number,
+            time: bar.start,
+            weights: used_prime_idxs
+                .iter()
+                .map(|i| weights.get(*i).copied().unwrap_or(0.0))
+                .collect(),
+        })
+        .collect();
+
+    PrimeHeatMap {
+        primes: used_prime_idxs
+            .iter()
+            .map(|i| PRIMES_BY_INDEX[*i])
+            .collect(),
+        rows,
+    }
+}
+
+impl PrimeHeatMap {
+    /// Writes the heat map as semicolon separated values (one row per bar, one column per prime).
+    pub fn write_csv(&self, path: &str) {
+        let mut csv = String::from("bar;time");
+        for p in &self.primes {
+            write!(csv, ";{p}").unwrap();
+        }
+        csv.push('\n');
+        for row in &self.rows {
+            write!(csv, "{};{:.3}", row.bar, row.time).unwrap();
+            for w in &row.weights {
+                write!(csv, ";{w:.3}").unwrap();
+            }
+            csv.push('\n');
+        }
+        fs::write(path, csv).unwrap_or_else(|e| panic!("Failed to write {path}: {e}"));
+    }
+
+    /// Writes the heat map as an SVG image. Time (bars) goes left to right, primes go bottom to top.
+    ///
+    /// Each prime's row is normalized to its own maximum weight, so that rarely used primes (e.g. 19, 31) are still
+    /// visible next to the ubiquitous 3 and 5.
+    pub fn write_svg(&self, path: &str) {
+        const CELL_W: usize = 6;
+        const CELL_H: usize = 24;
+        const LABEL_W: usize = 40;
+
+        let width = LABEL_W + CELL_W * self.rows.len();
+        let height = CELL_H * (self.primes.len() + 1);
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="sans-serif" font-size="12">"#
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<rect width="{width}" height="{height}" fill="black"/>"#
+        )
+        .unwrap();
+
+        for (p_idx, prime) in self.primes.iter().enumerate() {
+            let y = CELL_H * (self.primes.len() - 1 - p_idx);
+            writeln!(
+                svg,
+                r#"<text x="4" y="{}" fill="white">{prime}</text>"#,
+                y + CELL_H * 2 / 3
+            )
+            .unwrap();
+
+            let max = self
+                .rows
+                .iter()
+                .map(|r| r.weights[p_idx])
+                .fold(0.0, f64::max);
+            if max == 0.0 {
+                continue;
+            }
+
+            for (bar_idx, row) in self.rows.iter().enumerate() {
+                let w = row.weights[p_idx] / max;
+                if w == 0.0 {
+                    continue;
+                }
+                // black -> red -> yellow -> white
+                let r = (w * 3.0).min(1.0);
+                let g = (w * 3.0 - 1.0).clamp(0.0, 1.0);
+                let b = (w * 3.0 - 2.0).clamp(0.0, 1.0);
+                writeln!(
+                    svg,
+                    r#"<rect x="{}" y="{y}" width="{CELL_W}" height="{CELL_H}" fill="rgb({},{},{})"><title>Bar {}, prime {prime}: {:.3}</title></rect>"#,
+                    LABEL_W + bar_idx * CELL_W,
+                    (r * 255.0) as u8,
+                    (g * 255.0) as u8,
+                    (b * 255.0) as u8,
+                    row.bar,
+                    row.weights[p_idx],
+                )
+                .unwrap();
+            }
+        }
+
+        // Label every 10 bars along the bottom.
+        for (bar_idx, row) in self.rows.iter().enumerate() {
+            if row.bar % 10 == 0 {
+                writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" fill="white">{}</text>"#,
+                    LABEL_W + bar_idx * CELL_W,
+                    height - 6,
+                    row.bar
+                )
+                .unwrap();
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        fs::write(path, svg).unwrap_or_else(|e| panic!("Failed to write {path}: {e}"));
+    }
+}
+
+/// Length of the windows (in seconds) before & after each onset that are compared to detect harmony changes.
+const SEGMENT_WINDOW: f64 = 1.0;
+
+/// How different (0 to 1) the pitch class content before & after an onset must be to start a new segment.
+const SEGMENT_NOVELTY_THRESHOLD: f64 = 0.4;
+
+/// Segments shorter than this (in seconds) are merged into the previous segment.
+const MIN_SEGMENT_LENGTH: f64 = 1.0;
+
+/// Onsets closer than this (in seconds) are considered to be the same chord.
+pub const ONSET_TOLERANCE: f64 = 0.03;
+
+/// Pitch classes with less than this fraction of the most prominent pitch class's weight are ignored.
+const PITCH_CLASS_THRESHOLD: f64 = 0.25;
+
+/// How strongly each interval above a root (in semitones) implies that root.
+///
+/// Root, 5th & 3rds are the strongest indicators, followed by 7ths and extensions.
+const ROOT_TEMPLATE: [f64; 12] = [1.0, 0.0, 0.2, 0.5, 0.6, 0.1, 0.1, 0.8, 0.1, 0.3, 0.4, 0.2];
+
+/// A region of the MIDI file with a roughly constant harmony.
+pub struct Segment {
+    /// Start time in seconds.
+    pub start: f64,
+    /// End time in seconds.
+    pub end: f64,
+    /// Pitch classes that are prominent in this segment, in increasing order starting from A.
+    pub pitch_classes: Vec<usize>,
+    /// Pitch class of the lowest note played in this segment.
+    pub bass: Option<usize>,
+    /// Suggested functional root pitch class.
+    pub root: usize,
+    /// Prominent pitch classes of this segment that are held over (still sounding) from the previous segment.
+    ///
+    /// These are the notes that will audibly shift if their tuning is changed at the start of this segment, so they
+    /// are good candidates for common tones.
+    pub held_over: Vec<usize>,
+}
+
+/// Notes only count towards the novelty of an onset for at most this many seconds after their attack, so that
+/// pedal-held resonances don't smear out harmony changes.
+const ATTACK_LENGTH: f64 = 0.5;
+
+/// Total sounding duration of each pitch class (starting from A) within the time range `from..to`.
+///
+/// If `max_note_length` is given, each note only counts for at most that many seconds after its attack.
+fn chroma(score: &Score, from: f64, to: f64, max_note_length: Option<f64>) -> [f64; 12] {
+    let mut weights = [0.0; 12];
+    for note in &score.notes {
+        let release = match max_note_length {
+            Some(len) => note.release.min(note.start + len),
+            None => note.release,
+        };
+        let overlap = release.min(to) - note.start.max(from);
+        if overlap > 0.0 {
+            weights[pitch_class(note.key)] += overlap;
+        }
+    }
+    weights
+}
+
+/// 1 - cosine similarity of two pitch class profiles.
+fn chroma_distance(a: &[f64; 12], b: &[f64; 12]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 1.0;
+    }
+    1.0 - dot / (norm_a * norm_b)
+}
+
+/// Suggests the most likely functional root given the pitch class weights of a chord.
+pub fn suggest_root(weights: &[f64; 12], bass: Option<usize>) -> usize {
+    let max_weight = weights.iter().cloned().fold(0.0, f64::max);
+    (0..12)
+        .map(|root| {
+            let mut score: f64 = (0..12)
+                .map(|pc| weights[pc] * ROOT_TEMPLATE[(pc + 12 - root) % 12])
+                .sum();
+            if bass == Some(root) {
+                score += 0.5 * max_weight;
+            }
+            (root, score)
+        })
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .unwrap()
+        .0
+}
+
+/// Splits the MIDI file into segments of roughly constant harmony.
+///
+/// A new segment is started at an onset when the pitch class content of the [`SEGMENT_WINDOW`] before it is
+/// sufficiently different from the window after it.
+pub fn segment(score: &Score) -> Vec<Segment> {
+    let mut onsets: Vec<f64> = vec![];
+    for note in &score.notes {
+        if onsets
+            .last()
+            .is_none_or(|t| note.start - t > ONSET_TOLERANCE)
+        {
+            onsets.push(note.start);
+        }
+    }
+
+    let Some(&first_onset) = onsets.first() else {
+        return vec![];
+    };
+
+    let mut boundaries = vec![first_onset];
+    for &t in &onsets[1..] {
+        if t - boundaries.last().unwrap() < MIN_SEGMENT_LENGTH {
+            continue;
+        }
+        let before = chroma(score, t - SEGMENT_WINDOW, t, Some(ATTACK_LENGTH));
+        let after = chroma(score, t, t + SEGMENT_WINDOW, Some(ATTACK_LENGTH));
+        if chroma_distance(&before, &after) >= SEGMENT_NOVELTY_THRESHOLD {
+            boundaries.push(t);
+        }
+    }
+
+    let end_of_piece = score.notes.iter().map(|n| n.release).fold(0.0, f64::max);
+    boundaries.push(end_of_piece);
+
+    boundaries
+        .windows(2)
+        .map(|w| {
+            let (start, end) = (w[0], w[1]);
+            let weights = chroma(score, start, end, None);
+            let max_weight = weights.iter().cloned().fold(0.0, f64::max);
+            let pitch_classes: Vec<usize> = (0..12)
+                .filter(|pc| {
+                    weights[*pc] > 0.0 && weights[*pc] >= max_weight * PITCH_CLASS_THRESHOLD
+                })
+                .collect();
+
+            let bass = score
+                .notes
+                .iter()
+                .filter(|n| n.start >= start && n.start < end)
+                .map(|n| n.key)
+                .min()
+                .map(pitch_class);
+
+            let mut held_over: Vec<usize> = score
+                .notes
+                .iter()
+                .filter(|n| n.start < start - ONSET_TOLERANCE && n.release > start + ATTACK_LENGTH)
+                .map(|n| pitch_class(n.key))
+                .filter(|pc| pitch_classes.contains(pc))
+                .collect();
+            held_over.sort();
+            held_over.dedup();
+
+            Segment {
+                start,
+                end,
+                pitch_classes,
+                bass,
+                root: suggest_root(&weights, bass),
+                held_over,
+            }
+        })
+        .collect()
+}
+
+/// Formats a list of pitch classes as note names.
+fn pitch_class_names(pcs: &[usize]) -> String {
+    if pcs.is_empty() {
+        return "-".to_string();
+    }
+    pcs.iter()
+        .map(|pc| SEMITONE_NAMES[*pc])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Default 5-limit tuning of each interval above a root, used as a placeholder in skeleton timelines.
+pub const DEFAULT_5_LIMIT: [(i32, i32); 12] = [
+    (1, 1),
+    (16, 15),
+    (9, 8),
+    (6, 5),
+    (5, 4),
+    (4, 3),
+    (45, 32),
+    (3, 2),
+    (8, 5),
+    (5, 3),
+    (9, 5),
+    (15, 8),
+];
+
+/// Returns a human readable report of the segments (one line per segment).
+pub fn segment_report(score: &Score, segments: &[Segment]) -> String {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "{:>4}  {:>9}  {:>9}  {:>7}  {:<4}  {:<4}  {:<36}  held over",
+        "#", "time", "bar", "length", "root", "bass", "pitch classes"
+    )
+    .unwrap();
+    for (i, seg) in segments.iter().enumerate() {
+        writeln!(
+            report,
+            "{:>4}  {:>8.3}s  {:>9}  {:>6.2}s  {:<4}  {:<4}  {:<36}  {}",
+            i + 1,
+            seg.start,
+            score.position(seg.start),
+            seg.end - seg.start,
+            SEMITONE_NAMES[seg.root],
+            seg.bass.map_or("-", |b| SEMITONE_NAMES[b]),
+            pitch_class_names(&seg.pitch_classes),
+            pitch_class_names(&seg.held_over),
+        )
+        .unwrap();
+    }
+    report
+}
+
+/// Generates a skeleton tuning timeline (in the format of [`crate::tuning_file`]) from the segments.
+///
+/// Each segment becomes a tuning rooted on the suggested root, with placeholder 5-limit ratios for the pitch classes
+/// that sound in the segment. All other pitch classes keep their previous tuning.
+pub fn skeleton_timeline(score: &Score, segments: &[Segment]) -> String {
+    let mut timeline = String::new();
+    for (i, seg) in segments.iter().enumerate() {
+        writeln!(timeline, "tuning {:.3}", seg.start).unwrap();
+        writeln!(timeline, "bar {}", score.bar_at(seg.start).number).unwrap();
+        writeln!(timeline, "beat {}", score.beat_at(seg.start)).unwrap();
+        writeln!(
+            timeline,
+            "comment root {} ({}), held over: {}",
+            SEMITONE_NAMES[seg.root],
+            pitch_class_names(&seg.pitch_classes),
+            pitch_class_names(&seg.held_over),
+        )
+        .unwrap();
+        let (off_n, off_d) = DEFAULT_5_LIMIT[seg.root];
+        writeln!(timeline, "root {}", SEMITONE_NAMES[seg.root]).unwrap();
+        writeln!(timeline, "offset {off_n}/{off_d}").unwrap();
+        for (interval, (n, d)) in DEFAULT_5_LIMIT.iter().enumerate() {
+            let pc = (seg.root + interval) % 12;
+            // The first tuning must specify all notes.
+            if i == 0 || seg.pitch_classes.contains(&pc) {
+                writeln!(timeline, "{} {n}/{d}", SEMITONE_NAMES[pc]).unwrap();
+            }
+        }
+        timeline.push('\n');
+    }
+    timeline
+}
+
+/// Retunes of sustained notes smaller than this (in cents) are not reported.
+const MIN_SUSTAINED_RETUNE_CENTS: f64 = 1.0;
+
+/// A tuning change that retunes a pitch class while notes of that pitch class are still sounding (held down or by the
+/// sustain pedal), which will be heard as an audible pitch bend of the sustained notes.
+pub struct SustainedRetune {
+    /// Index of the tuning data in the [`Tuner`].
+    pub tuning_idx: usize,
+    /// Time of the tuning change.
+    pub time: f64,
+    /// [`crate::tuner::TuningData::annotation`] of the tuning change.
+    pub annotation: Option<String>,
+    /// Pitch class that is retuned (0 is A, 1 is Bb, etc...)
+    pub semitone: usize,
+    pub from: PitchSpec,
+    pub to: PitchSpec,
+    /// Keys of the sustained notes.
+    pub keys: Vec<u8>,
+    /// Time when the last of the sustained notes stops sounding.
+    pub sustained_until: f64,
+    /// Whether all the sustained notes are only held by the sustain pedal, i.e. their keys are released.
+    pub pedal_only: bool,
+    /// Time that this retune can be deferred to without affecting any notes, or [`None`] if a new note of the same
+    /// pitch class is played before the sustained notes stop sounding.
+    pub defer_to: Option<f64>,
+    /// Spellings in effect after the tuning change, for naming the pitch class & keys in reports.
+    pub spellings: Spellings,
+}
+
+impl SustainedRetune {
+    pub fn cents_jump(&self) -> f64 {
+        self.to.cents().unwrap() - self.from.cents().unwrap()
+    }
+}
+
+/// Finds tuning changes that retune pitch classes which are still sounding at the time of the change.
+pub fn sustained_retunes(score: &Score, tuner: &Tuner) -> Vec<SustainedRetune> {
+    let snapshots = tuner.snapshots();
+    let mut retunes = vec![];
+
+    for idx in 1..tuner.len() {
+        let td = &tuner[idx];
+        let prev = &snapshots[idx - 1];
+
+        for semitone in 0..12 {
+            let to = td.tuning[semitone];
+            let from = prev.tuning[semitone];
+            if to.is_keep() || to == from {
+                continue;
+            }
+            if (to.cents().unwrap() - from.cents().unwrap()).abs() < MIN_SUSTAINED_RETUNE_CENTS {
+                continue;
+            }
+
+            let sustained: Vec<&Note> = score
+                .notes
+                .iter()
+                .filter(|n| {
+                    pitch_class(n.key) == semitone
+                        && n.start < td.time - ONSET_TOLERANCE
+                        && n.release > td.time
+                })
+                .collect();
+            if sustained.is_empty() {
+                continue;
+            }
+
+            let sustained_until = sustained.iter().map(|n| n.release).fold(0.0, f64::max);
+            let pedal_only = sustained
+                .iter()
+                .all(|n| n.key_release <= td.time + ONSET_TOLERANCE);
+
+            // Deferring is only possible if no new notes of this pitch class need the new tuning, and no later
+            // tuning change retunes this pitch class before then.
+            let new_note_played = score.notes.iter().any(|n| {
+                pitch_class(n.key) == semitone
+                    && n.start >= td.time - ONSET_TOLERANCE
+                    && n.start < sustained_until
+            });
+            let retuned_again = (idx + 1..tuner.len())
+                .any(|i| tuner[i].time <= sustained_until && !tuner[i].tuning[semitone].is_keep());
+
+            let mut keys: Vec<u8> = sustained.iter().map(|n| n.key).collect();
+            keys.sort();
+            keys.dedup();
+
+            retunes.push(SustainedRetune {
+                tuning_idx: idx,
+                time: td.time,
+                annotation: td.annotation(),
+                semitone,
+                from,
+                to,
+                keys,
+                sustained_until,
+                pedal_only,
+                defer_to: if new_note_played || retuned_again {
+                    None
+                } else {
+                    Some(sustained_until)
+                },
+                spellings: snapshots[idx].spellings,
+            });
+        }
+    }
+
+    retunes
+}
+
+/// Splits tuning changes so that the retuning of sustained notes is deferred until they stop sounding, wherever
+/// possible and allowed by the [`DeferPolicy`] of the tuning change (`default` if it has none).
+///
+/// Returns the number of deferred retunes.
+pub fn defer_sustained_retunes(
+    tuner: &mut Tuner,
+    retunes: &[SustainedRetune],
+    default: DeferPolicy,
+) -> usize {
+    let mut deferred = 0;
+    // Deferring inserts new tuning data after the deferred one, which shifts the indices of everything after it. Apply
+    // in reverse order so that the indices of the remaining retunes stay valid.
+    for retune in retunes.iter().rev() {
+        let allowed = match tuner[retune.tuning_idx].defer.unwrap_or(default) {
+            // The whole tuning change is delayed by `retune_on_silence` instead.
+            DeferPolicy::Never | DeferPolicy::Silence => false,
+            DeferPolicy::Pedal => retune.pedal_only,
+            DeferPolicy::Sustained => true,
+        };
+        if let (true, Some(defer_to)) = (allowed, retune.defer_to) {
+            tuner.defer(retune.tuning_idx, retune.semitone, defer_to);
+            deferred += 1;
+        }
+    }
+    deferred
+}
+
+/// Returns the intervals `(from, to)` of at least `min_length` seconds when no notes are sounding (taking the pedals into
+/// account, see [`Note::release`]), between the first note & the last, in order of time.
+pub fn silences(score: &Score, min_length: f64) -> Vec<(f64, f64)> {
+    let mut silences = vec![];
+    let mut sounding_until = f64::NEG_INFINITY;
+    for note in &score.notes {
+        if note.start - sounding_until >= min_length && sounding_until.is_finite() {
+            silences.push((sounding_until, note.start));
+        }
+        sounding_until = sounding_until.max(note.release);
+    }
+    silences
+}
+
+/// Returns the first time from `from` until `until` (inclusive) when no notes are sounding and the sustain pedal is up,
+/// if any. Notes starting just before a time (within [`ONSET_TOLERANCE`]) are not counted as sounding at that time.
+pub fn next_silence(score: &Score, from: f64, until: f64) -> Option<f64> {
+    // Silence can only start when a note or the pedal is released.
+    let mut candidates: Vec<f64> = std::iter::once(from)
+        .chain(score.notes.iter().map(|n| n.release))
+        .chain(score.sustain_pedal.iter().map(|(_, up)| *up))
+        .filter(|t| (from..=until).contains(t))
+        .collect();
+    candidates.sort_by(f64::total_cmp);
+
+    candidates.into_iter().find(|&t| {
+        !score
+            .notes
+            .iter()
+            .any(|n| n.start < t - ONSET_TOLERANCE && n.release > t)
+            && !score
+                .sustain_pedal
+                .iter()
+                .any(|&(down, up)| down <= t && up > t)
+    })
+}
+
+/// Delays tuning changes with the [`DeferPolicy::Silence`] policy (`default` if they have none) to the next moment when
+/// no notes are sounding and the sustain pedal is up, if that is within `max_delay` seconds and before the next tuning
+/// change. Otherwise the tuning change is applied at its original time.
+///
+/// Returns the number of delayed tuning changes.
+pub fn retune_on_silence(
+    score: &Score,
+    tuner: &mut Tuner,
+    default: DeferPolicy,
+    max_delay: f64,
+) -> usize {
+    let mut delayed = 0;
+    for idx in 1..tuner.len() {
+        if tuner[idx].defer.unwrap_or(default) != DeferPolicy::Silence {
+            continue;
+        }
+        let time = tuner[idx].time;
+        let next = if idx + 1 < tuner.len() {
+            tuner[idx + 1].time
+        } else {
+            f64::INFINITY
+        };
+        if let Some(silence) = next_silence(score, time, time + max_delay) {
+            if silence > time && silence < next {
+                tuner.delay(idx, silence);
+                delayed += 1;
+            }
+        }
+    }
+    delayed
+}
+
+/// A tuning time moved onto a note onset or rest boundary by [`snap_times`].
+pub struct SnappedTime {
+    /// Time of the tuning in the tuning file.
+    pub from: f64,
+    /// Time snapped to, rounded down to the millisecond (as written in tuning files) so that it stays before the notes
+    /// starting at the boundary.
+    pub to: f64,
+    /// `onset` or `rest`.
+    pub boundary: &'static str,
+    /// [`crate::tuner::TuningData::annotation`] of the tuning.
+    pub annotation: Option<String>,
+}
+
+/// Snaps the time of each of `tunings` to the latest note onset or start of a rest (see [`silences`]) at most
+/// `tolerance` seconds before it, so that hand-measured times don't retune notes a few milliseconds after they start.
+/// Returns where each tuning moves to, in the same order, or [`None`] if it is already on a boundary or has none within
+/// the tolerance.
+pub fn snap_times(
+    score: &Score,
+    tunings: &[TuningData],
+    tolerance: f64,
+) -> Vec<Option<SnappedTime>> {
+    let floor_ms = |time: f64| (time * 1000.0).floor() / 1000.0;
+    let mut boundaries: Vec<(f64, &'static str)> = score
+        .notes
+        .iter()
+        .map(|note| (floor_ms(note.start), "onset"))
+        .chain(
+            silences(score, 0.0)
+                .into_iter()
+                .map(|(from, _)| (floor_ms(from), "rest")),
+        )
+        .collect();
+    // Rests before onsets at the same time, so that the onset is the latest boundary.
+    boundaries.sort_by(|a, b| a.0.total_cmp(&b.0).then(b.1.cmp(a.1)));
+    tunings
+        .iter()
+        .map(|td| {
+            let idx = boundaries.partition_point(|(time, _)| *time <= td.time);
+            let (to, boundary) = *boundaries[..idx].last()?;
+            (to < td.time && td.time - to <= tolerance).then(|| SnappedTime {
+                from: td.time,
+                to,
+                boundary,
+                annotation: td.annotation(),
+            })
+        })
+        .collect()
+}
+
+/// Returns a human readable report of the tuning times moved by [`snap_times`] (one line per tuning).
+pub fn snap_report(score: &Score, snapped: &[Option<SnappedTime>]) -> String {
+    let mut report = String::new();
+    for snapped in snapped.iter().flatten() {
+        write!(
+            report,
+            "{:>8.3}s -> {:>8.3}s (bar {:>8}): {:+.1}ms to the {}",
+            snapped.from,
+            snapped.to,
+            score.position(snapped.to),
+            (snapped.to - snapped.from) * 1000.0,
+            snapped.boundary
+        )
+        .unwrap();
+        match &snapped.annotation {
+            Some(annotation) => writeln!(report, " [{annotation}]").unwrap(),
+            None => writeln!(report).unwrap(),
+        }
+    }
+    report
+}
+
+/// Returns a human readable report of sustained retunes (one line per retune).
+pub fn sustained_retune_report(score: &Score, retunes: &[SustainedRetune]) -> String {
+    let mut report = String::new();
+    for retune in retunes {
+        let keys = retune
+            .keys
+            .iter()
+            .map(|k| spelled_key_name(&retune.spellings, *k))
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(
+            report,
+            "{:>8.3}s (bar {:>8}): {:<3} {} -> {} ({:+.1}c) while {keys} {} until {:.3}s",
+            retune.time,
+            score.position(retune.time),
+            spelled_name(&retune.spellings, retune.semitone),
+            retune.from,
+            retune.to,
+            retune.cents_jump(),
+            if retune.pedal_only {
+                "held by the pedal"
+            } else {
+                "sounding"
+            },
+            retune.sustained_until,
+        )
+        .unwrap();
+        match retune.defer_to {
+            Some(t) => write!(report, ", can defer to {t:.3}s").unwrap(),
+            None => write!(report, ", cannot defer").unwrap(),
+        }
+        match &retune.annotation {
+            Some(annotation) => writeln!(report, " [{annotation}]").unwrap(),
+            None => writeln!(report).unwrap(),
+        }
+    }
+    report
+}
+
+/// Cents of a just perfect fifth (3/2).
+const JUST_FIFTH_CENTS: f64 = 701.955;
+
+/// Cents of a just perfect fourth (4/3).
+const JUST_FOURTH_CENTS: f64 = 498.045;
+
+/// Notes must sound together for at least this long (in seconds) to be checked for clashes, so that legato
+/// overlaps aren't flagged.
+const MIN_CLASH_OVERLAP: f64 = 0.05;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClashKind {
+    /// A 12edo fifth that is tuned noticeably away from 3/2.
+    WolfFifth,
+    /// A 12edo fourth that is tuned noticeably away from 4/3.
+    WolfFourth,
+    /// Two different 12edo pitch classes tuned to (almost) the same pitch, modulo octaves.
+    Unison,
+}
+
+impl Display for ClashKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClashKind::WolfFifth => write!(f, "wolf fifth"),
+            ClashKind::WolfFourth => write!(f, "wolf fourth"),
+            ClashKind::Unison => write!(f, "clash"),
+        }
+    }
+}
+
+/// A pair of simultaneously sounding pitch classes that form a wolf interval or clash.
+///
+/// Repeated occurrences of the same pair within the same tuning are combined.
+pub struct Clash {
+    pub kind: ClashKind,
+    /// Time of the first occurrence.
+    pub time: f64,
+    /// Keys of the lower & upper note of the first occurrence.
+    pub keys: (u8, u8),
+    /// Tuned size of the interval (reduced to within an octave for fifths & fourths) in cents.
+    pub cents: f64,
+    /// Deviation from 3/2 or 4/3 for wolves, or from the unison/octave for clashes, in cents.
+    pub deviation: f64,
+    /// Number of times this pair sounds together within this tuning.
+    pub count: usize,
+}
+
+/// Finds simultaneously sounding pairs of notes that form wolf fifths/fourths or clashes.
+///
+/// - `wolf_window`: fifths & fourths deviating from 3/2 & 4/3 by an absolute amount of cents within this range
+///   are flagged. Deviations larger than the upper bound are assumed to be intentional, different intervals.
+/// - `clash_threshold`: different pitch classes tuned within this many cents of each other (modulo octaves) are
+///   flagged.
+pub fn clashes(
+    score: &Score,
+    snapshots: &[TuningSnapshot],
+    wolf_window: (f64, f64),
+    clash_threshold: f64,
+) -> Vec<Clash> {
+    let mut clashes: Vec<Clash> = vec![];
+
+    for (snap_idx, snapshot) in snapshots.iter().enumerate() {
+        let from = snapshot.time;
+        let to = snapshots
+            .get(snap_idx + 1)
+            .map_or(f64::INFINITY, |s| s.time);
+        let notes: Vec<&Note> = score
+            .notes
+            .iter()
+            .filter(|n| n.start < to && n.release > from)
+            .collect();
+
+        // Index into `clashes` of the first clash found in this tuning, for merging repeated occurrences.
+        let first_clash_idx = clashes.len();
+
+        for (i, a) in notes.iter().enumerate() {
+            for b in &notes[i + 1..] {
+                let overlap_start = a.start.max(b.start).max(from);
+                let overlap_end = a.release.min(b.release).min(to);
+                if overlap_end - overlap_start < MIN_CLASH_OVERLAP {
+                    continue;
+                }
+                let (low, high) = if a.key <= b.key { (a, b) } else { (b, a) };
+                let steps = (high.key - low.key) as usize;
+                let interval = snapshot.key_cents(high.key) - snapshot.key_cents(low.key);
+                let reduced = interval - 1200.0 * (steps / 12) as f64;
+
+                let (kind, cents, deviation) = match steps % 12 {
+                    7 => (ClashKind::WolfFifth, reduced, reduced - JUST_FIFTH_CENTS),
+                    5 => (ClashKind::WolfFourth, reduced, reduced - JUST_FOURTH_CENTS),
+                    0 => continue,
+                    _ => {
+                        let deviation = interval - 1200.0 * (interval / 1200.0).round();
+                        (ClashKind::Unison, interval, deviation)
+                    }
+                };
+
+                let flagged = match kind {
+                    ClashKind::Unison => deviation.abs() < clash_threshold,
+                    _ => deviation.abs() >= wolf_window.0 && deviation.abs() <= wolf_window.1,
+                };
+                if !flagged {
+                    continue;
+                }
+
+                let existing = clashes[first_clash_idx..].iter_mut().find(|c| {
+                    c.kind == kind
+                        && pitch_class(c.keys.0) == pitch_class(low.key)
+                        && pitch_class(c.keys.1) == pitch_class(high.key)
+                });
+                match existing {
+                    Some(c) => {
+                        c.count += 1;
+                        if overlap_start < c.time {
+                            c.time = overlap_start;
+                            c.keys = (low.key, high.key);
+                        }
+                    }
+                    None => clashes.push(Clash {
+                        kind,
+                        time: overlap_start,
+                        keys: (low.key, high.key),
+                        cents,
+                        deviation,
+                        count: 1,
+                    }),
+                }
+            }
+        }
+    }
+
+    clashes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    clashes
+}
+
+/// Returns a human readable report of wolves & clashes (one line per clash).
+pub fn clash_report(score: &Score, clashes: &[Clash]) -> String {
+    let mut report = String::new();
+    for clash in clashes {
+        writeln!(
+            report,
+            "{:>8.3}s (bar {:>8}): {:<11} {}-{} {:.1}c ({:+.1}c), {} time(s)",
+            clash.time,
+            score.position(clash.time),
+            clash.kind.to_string(),
+            key_name(clash.keys.0),
+            key_name(clash.keys.1),
+            clash.cents,
+            clash.deviation,
+            clash.count,
+        )
+        .unwrap();
+    }
+    report
+}
+
+/// How closely the pitches of a tuning are approximated by an EDO.
+pub struct EdoFit {
+    /// Number of equal divisions of the octave.
+    pub edo: u32,
+    /// Largest error of a pitch in cents, with the EDO transposed to fit the pitches best.
+    pub max_error: f64,
+}
+
+impl EdoFit {
+    /// [`EdoFit::max_error`] as a fraction of a step of the EDO, from 0 (exact) to 0.5 (as far off as it gets), so that
+    /// EDOs of different sizes can be compared.
+    pub fn relative_error(&self) -> f64 {
+        self.max_error * self.edo as f64 / 1200.0
+    }
+}
+
+/// Returns how closely `pitches` (in cents) are approximated by each of `edos`, modulo octaves.
+///
+/// The EDO is transposed to fit the pitches best: the largest error is half the smallest arc (of a step) that covers the
+/// pitches modulo the step size.
+pub fn edo_fits(pitches: &[f64], edos: &[u32]) -> Vec<EdoFit> {
+    edos.iter()
+        .map(|&edo| {
+            let step = 1200.0 / edo as f64;
+            let mut residues: Vec<f64> = pitches.iter().map(|c| c.rem_euclid(step)).collect();
+            residues.sort_by(f64::total_cmp);
+            let wrap_gap = residues
+                .first()
+                .map_or(step, |first| first + step - residues.last().unwrap());
+            let largest_gap = residues
+                .windows(2)
+                .map(|w| w[1] - w[0])
+                .fold(wrap_gap, f64::max);
+            EdoFit {
+                edo,
+                max_error: (step - largest_gap) / 2.0,
+            }
+        })
+        .collect()
+}
+
+/// How closely the pitch classes played in a tuning are approximated by each candidate EDO.
+pub struct EdoApproximation {
+    pub time: f64,
+    /// Description of the tuning, see [`crate::tuner::TuningData::describe`].
+    pub description: String,
+    /// Pitch classes (0 is A) played while the tuning is in effect.
+    pub pitch_classes: Vec<usize>,
+    /// In order of the candidate EDOs.
+    pub fits: Vec<EdoFit>,
+}
+
+impl EdoApproximation {
+    /// Returns the fit with the smallest error relative to the step size, the smaller EDO on ties.
+    pub fn best_fit(&self) -> &EdoFit {
+        self.fits
+            .iter()
+            .reduce(|best, fit| {
+                if fit.relative_error() < best.relative_error() - 1e-9 {
+                    fit
+                } else {
+                    best
+                }
+            })
+            .unwrap()
+    }
+}
+
+/// Finds how closely each tuning of `tuner` is approximated by each of `edos`, considering only the pitch classes
+/// played while it is in effect. Tunings in which nothing is played are skipped.
+pub fn edo_approximations(score: &Score, tuner: &Tuner, edos: &[u32]) -> Vec<EdoApproximation> {
+    let snapshots = tuner.snapshots();
+    let mut approximations = vec![];
+    for (idx, snapshot) in snapshots.iter().enumerate() {
+        let until = snapshots.get(idx + 1).map_or(f64::INFINITY, |s| s.time);
+        let mut pitch_classes: Vec<usize> = score
+            .notes
+            .iter()
+            .filter(|n| n.start >= snapshot.time && n.start < until)
+            .map(|n| pitch_class(n.key))
+            .collect();
+        pitch_classes.sort();
+        pitch_classes.dedup();
+        if pitch_classes.is_empty() {
+            continue;
+        }
+
+        let pitches: Vec<f64> = pitch_classes
+            .iter()
+            .map(|&pc| snapshot.tuning[pc].cents().unwrap())
+            .collect();
+        approximations.push(EdoApproximation {
+            time: snapshot.time,
+            description: tuner[idx].describe(),
+            pitch_classes,
+            fits: edo_fits(&pitches, edos),
+        });
+    }
+    approximations
+}
+
+/// Returns a human readable report of EDO approximations (one line per tuning), followed by how often each EDO fits
+/// best.
+pub fn edo_report(score: &Score, approximations: &[EdoApproximation]) -> String {
+    let mut report = String::new();
+    for approximation in approximations {
+        let best = approximation.best_fit();
+        let fits = approximation
+            .fits
+            .iter()
+            .map(|fit| format!("{}: {:4.1}c", fit.edo, fit.max_error))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(
+            report,
+            "{:>8.3}s (bar {:>8}): best {:>2}edo ({:.1}c, {:.0}% of a step) | {fits} | {} ({})",
+            approximation.time,
+            score.position(approximation.time),
+            best.edo,
+            best.max_error,
+            best.relative_error() * 100.0,
+            approximation.description,
+            pitch_class_names(&approximation.pitch_classes),
+        )
+        .unwrap();
+    }
+
+    let Some(first) = approximations.first() else {
+        return report;
+    };
+    writeln!(report, "\nBest fit:").unwrap();
+    for fit in &first.fits {
+        let count = approximations
+            .iter()
+            .filter(|a| a.best_fit().edo == fit.edo)
+            .count();
+        writeln!(
+            report,
+            "{:>4}edo: {count} of {} tunings",
+            fit.edo,
+            approximations.len()
+        )
+        .unwrap();
+    }
+    report
+}
@@ -0,0 +1,323 @@
+//! HTTP API of playback at [`crate::API_ADDR`], for shell scripts, OBS plugins & cue systems to poll or control the
+//! performer without implementing the websocket protocol, e.g. `curl -X POST localhost:8090/seek?bar=66`.
+//!
+//! - `GET /status`: `state` (`waiting`, `playing`, `paused` or `stopped`), `speed`, `position` & `tuning` of the
+//!   current performance.
+//! - `GET /position`: `time` in seconds, `bar` & `beat` of the playback position.
+//! - `GET /tuning`: the tuning in effect (`index` in the tuning timeline, `time`, `description`, `annotation`, and
+//!   the `pitches` of each semitone from A as in the websocket's `tuning` messages), null before the first.
+//! - `GET /history?pitch=<pitch class>`: every tuning of the pitch class (e.g. `C%23` or `Db`) in the tuning timeline
+//!   of the performance, with the `index` & `time` of its tuning change, `bar`, `beat`, `annotation`, `ratio` (or
+//!   null if tempered) & `cents` above A, and the `comma` (null for the first & tempered tunings) & `cents_change` it
+//!   is retuned by from its previous tuning.
+//! - `POST /play`: starts playback, like pressing enter, or resumes it when paused.
+//! - `POST /pause`: stops the transport. Notes that are sounding are left sounding.
+//! - `POST /seek?time=<seconds>` or `POST /seek?bar=<bar>`: moves playback there. The events before it are applied
+//!   without playing notes, as when starting from [`crate::START_FROM`].
+//!
+//! Responses are JSON. The control endpoints return the status.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use crate::diff;
+use crate::mdns;
+use crate::score::Score;
+use crate::sync::Transport;
+use crate::tuner::{parse_pitch_class, PitchSpec, Tuner, TuningData};
+
+/// State of playback shared between playback & the API.
+pub struct Playback {
+    /// Score of the piece being played, for the bars of positions.
+    score: Mutex<Arc<Score>>,
+    /// Transport of playback once it started.
+    transport: Mutex<Option<Transport>>,
+    /// Speed of the transport before it was paused, while paused.
+    paused_speed: Mutex<Option<f64>>,
+    /// Position playback should be moved to, until playback does so, see [`Playback::take_seek`].
+    seek: Mutex<Option<f64>>,
+    /// The tuning in effect, see the [module docs](self).
+    tuning: Mutex<Option<Value>>,
+    /// Tuning history of each pitch class from A, see [`Playback::set_timeline`].
+    history: Mutex<Vec<Value>>,
+    stopped: Mutex<bool>,
+    /// Starts playback while it is waiting to be started.
+    start: Mutex<mpsc::Sender<()>>,
+}
+
+impl Playback {
+    /// Resets the state for a new performance of `score`, waiting to be started.
+    pub fn waiting(&self, score: Score) {
+        *self.score.lock().unwrap() = Arc::new(score);
+        *self.transport.lock().unwrap() = None;
+        *self.paused_speed.lock().unwrap() = None;
+        *self.seek.lock().unwrap() = None;
+        *self.tuning.lock().unwrap() = None;
+        self.history.lock().unwrap().clear();
+        *self.stopped.lock().unwrap() = false;
+    }
+
+    /// Sets the transport of playback once it started.
+    pub fn started(&self, transport: Transport) {
+        *self.transport.lock().unwrap() = Some(transport);
+    }
+
+    pub fn stopped(&self) {
+        *self.stopped.lock().unwrap() = true;
+    }
+
+    /// Sets the tuning in effect, the `index`th of the tuning timeline, with the `pitches` of each semitone from A (see
+    /// [`crate::server::VisualizerMessage::Tuning`]).
+    pub fn set_tuning(
+        &self,
+        index: Option<usize>,
+        tuning: &TuningData,
+        pitches: Vec<(String, f64)>,
+    ) {
+        let pitches: Vec<Value> = pitches
+            .into_iter()
+            .map(|(ratio, cents)| json!({ "ratio": ratio, "cents": cents }))
+            .collect();
+        *self.tuning.lock().unwrap() = Some(json!({
+            "index": index,
+            "time": tuning.time,
+            "description": tuning.describe(),
+            "annotation": tuning.annotation(),
+            "pitches": pitches,
+        }));
+    }
+
+    /// Returns whether a seek is waiting to be carried out by playback.
+    pub fn seeking(&self) -> bool {
+        self.seek.lock().unwrap().is_some()
+    }
+
+    /// Returns the position to move playback to, if a seek was requested since the last call.
+    pub fn take_seek(&self) -> Option<f64> {
+        self.seek.lock().unwrap().take()
+    }
+
+    /// Sets the tuning timeline of the performance, for the tuning history of each pitch class (see [`diff::history`]).
+    pub fn set_timeline(&self, tuner: &Tuner) {
+        let score = self.score.lock().unwrap().clone();
+        let history = (0..12).map(|semitone| {
+            let entries: Vec<Value> = diff::history(tuner, semitone)
+                .iter()
+                .map(|entry| {
+                    let ratio = match entry.pitch {
+                        PitchSpec::Ratio(ratio) => Some(ratio.to_string()),
+                        _ => None,
+                    };
+                    let comma = entry
+                        .change
+                        .as_ref()
+                        .and_then(|change| change.comma())
+                        .map(|comma| comma.to_string());
+                    json!({
+                        "index": entry.idx,
+                        "time": entry.time,
+                        "bar": score.bar_at(entry.time).number,
+                        "beat": score.beat_at(entry.time),
+                        "annotation": tuner[entry.idx].annotation(),
+                        "ratio": ratio,
+                        "cents": entry.pitch.cents(),
+                        "comma": comma,
+                        "cents_change": entry.change.as_ref().map(|change| change.cents()),
+                    })
+                })
+                .collect();
+            Value::Array(entries)
+        });
+        *self.history.lock().unwrap() = history.collect();
+    }
+
+    fn state(&self) -> &'static str {
+        if *self.stopped.lock().unwrap() {
+            "stopped"
+        } else if self.transport.lock().unwrap().is_none() {
+            "waiting"
+        } else if self.paused_speed.lock().unwrap().is_some() {
+            "paused"
+        } else {
+            "playing"
+        }
+    }
+
+    fn position(&self, score: &Score) -> Value {
+        match &*self.transport.lock().unwrap() {
+            Some(transport) => {
+                let time = transport.time();
+                json!({ "time": time, "bar": score.bar_at(time).number, "beat": score.beat_at(time) })
+            }
+            None => Value::Null,
+        }
+    }
+
+    fn status(&self, score: &Score) -> Value {
+        let speed = self
+            .transport
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(Transport::speed);
+        json!({
+            "state": self.state(),
+            "speed": self.paused_speed.lock().unwrap().or(speed),
+            "position": self.position(score),
+            "tuning": self.tuning.lock().unwrap().clone(),
+        })
+    }
+
+    /// Starts playback if it is waiting, or resumes it if paused.
+    fn play(&self) {
+        if self.state() == "waiting" {
+            let _ = self.start.lock().unwrap().send(());
+        }
+        if let (Some(transport), Some(speed)) = (
+            &*self.transport.lock().unwrap(),
+            self.paused_speed.lock().unwrap().take(),
+        ) {
+            transport.set_speed(speed);
+        }
+    }
+
+    /// Pauses playback, returning whether it is playing.
+    fn pause(&self) -> bool {
+        let Some(transport) = &*self.transport.lock().unwrap() else {
+            return false;
+        };
+        let mut paused_speed = self.paused_speed.lock().unwrap();
+        if paused_speed.is_none() {
+            *paused_speed = Some(transport.speed());
+            transport.set_speed(0.0);
+        }
+        true
+    }
+}
+
+/// Serves the API over HTTP at `addr` on a new thread, returning the state of playback to be kept up to date by it,
+/// starting with a performance of `score`. `start` is sent to by `POST /play` while playback is waiting to be started.
+pub fn serve(addr: &str, start: mpsc::Sender<()>, score: Score) -> Arc<Playback> {
+    let listener = TcpListener::bind(addr)
+        .unwrap_or_else(|e| panic!("Failed to bind API endpoint {addr}: {e}"));
+    println!("Serving the playback API on http://{addr}/status");
+    mdns::advertise("_http._tcp", addr, "/status");
+    let playback = Arc::new(Playback {
+        score: Mutex::new(Arc::new(score)),
+        transport: Mutex::new(None),
+        paused_speed: Mutex::new(None),
+        seek: Mutex::new(None),
+        tuning: Mutex::new(None),
+        history: Mutex::new(vec![]),
+        stopped: Mutex::new(false),
+        start: Mutex::new(start),
+    });
+    {
+        let playback = playback.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let score = playback.score.lock().unwrap().clone();
+                if let Err(e) = handle(stream, &playback, &score) {
+                    println!("WARN: Failed to serve API request: {e}");
+                }
+            }
+        });
+    }
+    playback
+}
+
+/// Responds to the request of `stream`, see the [module docs](self).
+fn handle(mut stream: TcpStream, playback: &Playback, score: &Score) -> std::io::Result<()> {
+    let mut request = [0; 1024];
+    let len = stream.read(&mut request)?;
+    let request = String::from_utf8_lossy(&request[..len]);
+    let mut words = request.split_whitespace();
+    let (method, target) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .and_then(|(_, value)| value.parse::<f64>().ok())
+    };
+
+    let (status, body) = match (method, path) {
+        ("GET", "/status") => ("200 OK", playback.status(score)),
+        ("GET", "/position") => ("200 OK", playback.position(score)),
+        ("GET", "/tuning") => (
+            "200 OK",
+            playback
+                .tuning
+                .lock()
+                .unwrap()
+                .clone()
+                .unwrap_or(Value::Null),
+        ),
+        ("GET", "/history") => {
+            let pitch = query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "pitch")
+                .and_then(|(_, value)| parse_pitch_class(&value.replace("%23", "#")));
+            match pitch.map(|pitch| playback.history.lock().unwrap().get(pitch).cloned()) {
+                Some(Some(history)) => ("200 OK", history),
+                Some(None) => (
+                    "409 Conflict",
+                    json!({ "error": "The tuning timeline isn't loaded yet" }),
+                ),
+                None => (
+                    "400 Bad Request",
+                    json!({ "error": "Expected a pitch class, e.g. pitch=C%23" }),
+                ),
+            }
+        }
+        ("POST", "/play") => {
+            playback.play();
+            ("200 OK", playback.status(score))
+        }
+        ("POST", "/pause") => match playback.pause() {
+            true => ("200 OK", playback.status(score)),
+            false => (
+                "409 Conflict",
+                json!({ "error": "Playback hasn't started" }),
+            ),
+        },
+        ("POST", "/seek") => {
+            let bar =
+                param("bar").and_then(|bar| score.bars.iter().find(|b| b.number as f64 == bar));
+            match bar
+                .map(|bar| bar.start)
+                .or(param("time"))
+                .filter(|time| *time >= 0.0)
+            {
+                Some(time) => {
+                    *playback.seek.lock().unwrap() = Some(time);
+                    ("200 OK", playback.status(score))
+                }
+                None => (
+                    "400 Bad Request",
+                    json!({ "error": "Expected a time in seconds or a bar to seek to" }),
+                ),
+            }
+        }
+        (_, "/status" | "/position" | "/tuning" | "/history" | "/play" | "/pause" | "/seek") => (
+            "405 Method Not Allowed",
+            json!({ "error": format!("{method} is not allowed on {path}") }),
+        ),
+        _ => (
+            "404 Not Found",
+            json!({ "error": format!("No such endpoint: {path}") }),
+        ),
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
@@ -0,0 +1,284 @@
+//! Onset detection & pitch class tracking of a live (or recorded) acoustic piano, to follow its performance (see
+//! [`crate::follow`]). Requires the `live-audio` feature.
+//!
+//! Every [`HOP_LENGTH`] seconds, the energy of each piano key from [`LOWEST_KEY`] to [`HIGHEST_KEY`] is measured over
+//! the last [`FRAME_LENGTH`] seconds with a Goertzel filter tuned to the key. An onset is a peak of the spectral flux
+//! (the sum of the increases in log energy of all keys) that stands out from the flux of the preceding frames, and the
+//! pitch classes played are the increases of each key folded into one octave. Overtones of the notes played add some
+//! weight to other pitch classes, which the follower tolerates.
+
+use std::collections::VecDeque;
+use std::f64::consts::TAU;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+
+use crate::follow::Onset;
+use crate::tuner::pitch_class;
+
+/// Seconds of audio each frame measures the key energies of.
+const FRAME_LENGTH: f64 = 0.08;
+
+/// Seconds between the starts of frames.
+const HOP_LENGTH: f64 = 0.01;
+
+/// Lowest key tracked (C2). Lower keys are hard to tell apart in a frame, and their overtones are tracked anyway.
+const LOWEST_KEY: u8 = 36;
+
+/// Highest key tracked (C7).
+const HIGHEST_KEY: u8 = 96;
+
+/// Number of preceding frames whose median flux an onset has to stand out from.
+const FLUX_HISTORY: usize = 50;
+
+/// How many times the median flux of the preceding frames an onset's flux has to be.
+const ONSET_THRESHOLD: f64 = 3.0;
+
+/// Minimum flux of an onset, so that noise during silence isn't detected as onsets.
+const MIN_ONSET_FLUX: f64 = 1.0;
+
+/// Minimum seconds between onsets.
+const MIN_ONSET_INTERVAL: f64 = 0.05;
+
+/// Starts detecting onsets in the audio input device whose name contains `device` (or the default input device) on a
+/// new thread.
+pub fn listen(device: Option<&'static str>) -> mpsc::Receiver<Onset> {
+    let (onset_sender, onsets) = mpsc::channel();
+    let (started_sender, started) = mpsc::channel();
+    // The stream can't be sent between threads on some platforms, so it lives on the thread that reads it.
+    thread::spawn(move || {
+        let host = cpal::default_host();
+        let device = match device {
+            Some(name) => host
+                .input_devices()
+                .unwrap()
+                .find(|d| d.description().is_ok_and(|desc| desc.name().contains(name)))
+                .unwrap_or_else(|| panic!("No audio input device named {name}")),
+            None => host
+                .default_input_device()
+                .expect("No audio input device found"),
+        };
+        let config = device.default_input_config().unwrap();
+        println!(
+            "Following audio input: {} @ {} Hz",
+            device
+                .description()
+                .map_or("Unknown device".to_string(), |d| d.to_string()),
+            config.sample_rate()
+        );
+
+        let (chunk_sender, chunks) = mpsc::channel();
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config.config(), chunk_sender),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config.config(), chunk_sender),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config.config(), chunk_sender),
+            cpal::SampleFormat::I32 => build_stream::<i32>(&device, &config.config(), chunk_sender),
+            format => panic!("Unsupported sample format: {format}"),
+        };
+        stream.play().unwrap();
+        started_sender.send(()).unwrap();
+
+        let mut detector = OnsetDetector::new(config.sample_rate() as f64);
+        for (samples, end) in chunks {
+            detector.push(&samples, end, &onset_sender);
+        }
+    });
+    started.recv().expect("Failed to start audio input");
+    onsets
+}
+
+/// Starts detecting onsets in the WAV file at `path` on a new thread, reading it in real time from now on.
+pub fn listen_to_recording(path: &str) -> mpsc::Receiver<Onset> {
+    let mut reader =
+        hound::WavReader::open(path).unwrap_or_else(|e| panic!("Failed to open {path}: {e}"));
+    let spec = reader.spec();
+    println!("Following recording: {path} @ {} Hz", spec.sample_rate);
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().map(Result::unwrap).collect(),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.unwrap() as f32 / scale)
+                .collect()
+        }
+    };
+    let channels = spec.channels as usize;
+    let mono: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    let (onset_sender, onsets) = mpsc::channel();
+    let sample_rate = spec.sample_rate as f64;
+    thread::spawn(move || {
+        let mut detector = OnsetDetector::new(sample_rate);
+        let hop = (HOP_LENGTH * sample_rate) as usize;
+        let start = Instant::now();
+        for (i, chunk) in mono.chunks(hop).enumerate() {
+            let end =
+                start + Duration::from_secs_f64(((i * hop + chunk.len()) as f64) / sample_rate);
+            thread::sleep(end.saturating_duration_since(Instant::now()));
+            detector.push(chunk, end, &onset_sender);
+        }
+    });
+    onsets
+}
+
+/// Sends the mono mix of each chunk of input from `device`, and when its last sample was received.
+fn build_stream<T: SizedSample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    chunks: mpsc::Sender<(Vec<f32>, Instant)>,
+) -> cpal::Stream
+where
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    device
+        .build_input_stream(
+            config,
+            move |input: &[T], _| {
+                let end = Instant::now();
+                let mono = input
+                    .chunks(channels)
+                    .map(|frame| {
+                        frame.iter().map(|s| s.to_sample::<f32>()).sum::<f32>() / channels as f32
+                    })
+                    .collect();
+                let _ = chunks.send((mono, end));
+            },
+            |e| println!("WARN: Audio input stream error: {e}"),
+            None,
+        )
+        .unwrap()
+}
+
+/// Detects onsets in a stream of mono audio, see the module docs.
+struct OnsetDetector {
+    sample_rate: f64,
+    /// The last [`FRAME_LENGTH`] seconds of samples.
+    frame: VecDeque<f32>,
+    frame_size: usize,
+    hop_size: usize,
+    /// Samples received since the last frame was measured.
+    since_hop: usize,
+    /// Hann window of the frame.
+    window: Vec<f64>,
+    /// Goertzel coefficient of each key tracked.
+    coeffs: Vec<f64>,
+    /// Log energy of each key in the previous frame.
+    prev_energies: Vec<f64>,
+    /// Flux of the preceding frames.
+    flux_history: VecDeque<f64>,
+    /// Flux & onset of the previous frame, which is an onset if its flux is a peak.
+    candidate: Option<(f64, Onset)>,
+    /// Flux of the frame before the previous one.
+    prev_flux: f64,
+    /// Instant of the last onset detected.
+    last_onset: Option<Instant>,
+}
+
+impl OnsetDetector {
+    fn new(sample_rate: f64) -> Self {
+        let frame_size = (FRAME_LENGTH * sample_rate) as usize;
+        let keys = LOWEST_KEY..=HIGHEST_KEY;
+        OnsetDetector {
+            sample_rate,
+            frame: VecDeque::from(vec![0.0; frame_size]),
+            frame_size,
+            hop_size: (HOP_LENGTH * sample_rate) as usize,
+            since_hop: 0,
+            window: (0..frame_size)
+                .map(|i| 0.5 - 0.5 * (TAU * i as f64 / frame_size as f64).cos())
+                .collect(),
+            coeffs: keys
+                .clone()
+                .map(|key| {
+                    let freq = 440.0 * 2f64.powf((key as f64 - 69.0) / 12.0);
+                    2.0 * (TAU * freq / sample_rate).cos()
+                })
+                .collect(),
+            prev_energies: vec![0.0; keys.len()],
+            flux_history: VecDeque::new(),
+            candidate: None,
+            prev_flux: 0.0,
+            last_onset: None,
+        }
+    }
+
+    /// Pushes `samples` ending at `end`, sending the onsets detected to `onsets`.
+    fn push(&mut self, samples: &[f32], end: Instant, onsets: &mpsc::Sender<Onset>) {
+        for (i, sample) in samples.iter().enumerate() {
+            self.frame.pop_front();
+            self.frame.push_back(*sample);
+            self.since_hop += 1;
+            if self.since_hop == self.hop_size {
+                self.since_hop = 0;
+                // Attacks are measured at their strongest when they are in the middle of the (windowed) frame.
+                let center = (samples.len() - 1 - i) as f64 + self.frame_size as f64 / 2.0;
+                let instant = end - Duration::from_secs_f64(center / self.sample_rate);
+                if let Some(onset) = self.measure(instant) {
+                    let _ = onsets.send(onset);
+                }
+            }
+        }
+    }
+
+    /// Measures the current frame, centered at `instant`. Returns the onset of the previous frame if it was one.
+    fn measure(&mut self, instant: Instant) -> Option<Onset> {
+        let energies: Vec<f64> = self
+            .coeffs
+            .iter()
+            .map(|coeff| {
+                let (mut s1, mut s2) = (0.0, 0.0);
+                for (sample, window) in self.frame.iter().zip(&self.window) {
+                    let s = *sample as f64 * window + coeff * s1 - s2;
+                    s2 = s1;
+                    s1 = s;
+                }
+                let power = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+                (1.0 + power).ln()
+            })
+            .collect();
+
+        let mut chroma = [0.0; 12];
+        for (key, (energy, prev)) in
+            (LOWEST_KEY..=HIGHEST_KEY).zip(energies.iter().zip(&self.prev_energies))
+        {
+            chroma[pitch_class(key)] += (energy - prev).max(0.0);
+        }
+        let flux: f64 = chroma.iter().sum();
+        self.prev_energies = energies;
+
+        let mut median: Vec<f64> = self.flux_history.iter().copied().collect();
+        median.sort_by(f64::total_cmp);
+        let threshold = median.get(median.len() / 2).map_or(MIN_ONSET_FLUX, |m| {
+            (m * ONSET_THRESHOLD).max(MIN_ONSET_FLUX)
+        });
+
+        // The previous frame is an onset if its flux is a peak above the threshold.
+        let onset = self.candidate.take().filter(|(candidate_flux, onset)| {
+            *candidate_flux > self.prev_flux
+                && *candidate_flux >= flux
+                && *candidate_flux > threshold
+                && self.last_onset.is_none_or(|last| {
+                    onset.instant.duration_since(last).as_secs_f64() >= MIN_ONSET_INTERVAL
+                })
+        });
+        if let Some((_, onset)) = &onset {
+            self.last_onset = Some(onset.instant);
+        }
+
+        self.prev_flux = self.flux_history.back().copied().unwrap_or(0.0);
+        self.flux_history.push_back(flux);
+        if self.flux_history.len() > FLUX_HISTORY {
+            self.flux_history.pop_front();
+        }
+        self.candidate = Some((flux, Onset { instant, chroma }));
+        onset.map(|(_, onset)| onset)
+    }
+}
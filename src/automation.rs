@@ -0,0 +1,106 @@
+//! Controller automation lanes (e.g. expression, or half-pedaling) in the tuning file: breakpoints that playback
+//! interpolates linearly, so that dynamics can be shaped alongside the tunings instead of being baked into the MIDI
+//! file.
+//!
+//! Automated controllers are sent on all 12 tuned channels, like the pedals of the MIDI file.
+
+use crate::tuner::Tuner;
+
+/// Time in seconds between the controller values sent while ramping between two breakpoints.
+const AUTOMATION_RESOLUTION: f64 = 0.02;
+
+/// Controllers that can be automated by name, with their CC numbers. Others are given by CC number.
+pub const AUTOMATION_CONTROLLERS: [(&str, u8); 7] = [
+    ("modulation", 1),
+    ("breath", 2),
+    ("volume", 7),
+    ("expression", 11),
+    ("sustain", 64),
+    ("sostenuto", 66),
+    ("soft", 67),
+];
+
+/// Curve of a controller from the time of the tuning it is in.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AutomationLane {
+    pub controller: u8,
+    /// Seconds after the tuning & CC value of each breakpoint, in order of time. The value is held after the last one.
+    pub breakpoints: Vec<(f64, u8)>,
+}
+
+/// A controller value of a lane, see [`events`].
+#[derive(Clone)]
+pub struct AutomationEvent {
+    pub time: f64,
+    pub controller: u8,
+    pub value: u8,
+}
+
+/// Parses `<controller> <seconds>:<value>...`, e.g. `expression 0:40 1.5:100 3:80`. The controller is a CC number or
+/// one of [`AUTOMATION_CONTROLLERS`], and the breakpoints must be in order of time.
+pub fn parse_lane(s: &str) -> Option<AutomationLane> {
+    let mut fields = s.split_whitespace();
+    let controller = fields.next()?;
+    let controller = match AUTOMATION_CONTROLLERS
+        .iter()
+        .find(|(name, _)| *name == controller)
+    {
+        Some((_, cc)) => *cc,
+        None => controller.parse().ok().filter(|cc| *cc < 128)?,
+    };
+    let breakpoints = fields
+        .map(|breakpoint| {
+            let (time, value) = breakpoint.split_once(':')?;
+            let time: f64 = time.parse().ok().filter(|t: &f64| *t >= 0.0)?;
+            let value: u8 = value.parse().ok().filter(|v| *v < 128)?;
+            Some((time, value))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if breakpoints.is_empty() || breakpoints.windows(2).any(|w| w[1].0 < w[0].0) {
+        return None;
+    }
+    Some(AutomationLane {
+        controller,
+        breakpoints,
+    })
+}
+
+/// Returns the controller values of the automation lanes of all tunings of `tuner`, in order of time. Values are sent
+/// every [`AUTOMATION_RESOLUTION`] seconds while ramping, and only when they change.
+pub fn events(tuner: &Tuner) -> Vec<AutomationEvent> {
+    let mut events = vec![];
+    for idx in 0..tuner.len() {
+        for lane in &tuner[idx].automation {
+            let start = tuner[idx].time;
+            let mut prev_value = None;
+            let mut push = |time: f64, value: u8| {
+                if prev_value != Some(value) {
+                    events.push(AutomationEvent {
+                        time,
+                        controller: lane.controller,
+                        value,
+                    });
+                    prev_value = Some(value);
+                }
+            };
+            push(start + lane.breakpoints[0].0, lane.breakpoints[0].1);
+            for pair in lane.breakpoints.windows(2) {
+                let ((from_time, from), (to_time, to)) = (pair[0], pair[1]);
+                let steps = ((to_time - from_time) / AUTOMATION_RESOLUTION)
+                    .ceil()
+                    .max(1.0) as usize;
+                for step in 1..=steps {
+                    let fraction = step as f64 / steps as f64;
+                    let value = from as f64 + (to as f64 - from as f64) * fraction;
+                    push(
+                        start + from_time + (to_time - from_time) * fraction,
+                        value.round() as u8,
+                    );
+                }
+            }
+        }
+    }
+    // Stable, so that a later lane of the same controller wins at the same time.
+    events.sort_by(|a, b| a.time.total_cmp(&b.time));
+    events
+}
@@ -0,0 +1,124 @@
+//! Tuning of the pitch class channels with controllers instead of pitch bends, for synths that can map a controller to
+//! the fine tune of a channel but mishandle pitch bends (e.g. smearing them across voices). See
+//! [`crate::CHANNEL_TUNING`].
+//!
+//! The pitch bends sent to the output are converted into the cents they bend by (given the pitch bend range they are
+//! sent for), which are sent as a 14-bit value spanning +/- the range the synth maps the controller to, or as the
+//! channel coarse & fine tuning registered parameters, which synths that support them apply as a static tuning of the
+//! channel.
+
+use crate::output::MidiSink;
+use crate::tuner::TuningSnapshot;
+
+/// How the cents of a channel's tuning are sent instead of pitch bends.
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum ChannelTuning {
+    /// A pair of controllers: the coarse 7 bits on `msb` & the fine 7 bits on `lsb` (usually `msb + 32`), spanning
+    /// +/- `range` cents.
+    Cc { msb: u8, lsb: u8, range: f64 },
+    /// A non-registered parameter (NRPN), set with data entry (CC 6 & 38), spanning +/- `range` cents.
+    Nrpn { parameter: u16, range: f64 },
+    /// The channel coarse tuning (RPN 2) in semitones & fine tuning (RPN 1) in cents, for synths where a static
+    /// tuning of the channel is more stable than pitch bends.
+    Rpn,
+}
+
+impl ChannelTuning {
+    /// Returns the controller messages that tune `channel` by `cents`, or [`None`] if out of range.
+    pub fn messages(&self, channel: u8, cents: f64) -> Option<Vec<[u8; 3]>> {
+        let status = 0xB0 | (channel & 0x0F);
+        Some(match *self {
+            ChannelTuning::Cc { msb, lsb, range } => {
+                let (coarse, fine) = data_entry(cents, range)?;
+                vec![[status, msb, coarse], [status, lsb, fine]]
+            }
+            ChannelTuning::Nrpn { parameter, range } => {
+                let (coarse, fine) = data_entry(cents, range)?;
+                vec![
+                    [status, 99, (parameter >> 7) as u8 & 0x7F],
+                    [status, 98, parameter as u8 & 0x7F],
+                    [status, 6, coarse],
+                    [status, 38, fine],
+                ]
+            }
+            ChannelTuning::Rpn => {
+                let semitones = cents.round();
+                if semitones.abs() > 63.0 {
+                    return None;
+                }
+                // The fine tuning spans +/- 100 cents, of which +/- 50 are needed.
+                let (coarse, fine) = data_entry(cents - semitones, 100.0)?;
+                vec![
+                    [status, 101, 0],
+                    [status, 100, 2],
+                    [status, 6, (64.0 + semitones) as u8],
+                    [status, 38, 0],
+                    [status, 101, 0],
+                    [status, 100, 1],
+                    [status, 6, coarse],
+                    [status, 38, fine],
+                    // Deselects the parameter, so that stray data entry messages don't retune the channel.
+                    [status, 101, 127],
+                    [status, 100, 127],
+                ]
+            }
+        })
+    }
+}
+
+/// Returns the coarse & fine 7 bits of the 14-bit value of `cents` spanning +/- `range` cents, or [`None`] if out of
+/// range.
+fn data_entry(cents: f64, range: f64) -> Option<(u8, u8)> {
+    if cents.abs() > range {
+        return None;
+    }
+    let value = ((cents / range + 1.0) / 2.0 * 16383.0).round() as u16;
+    Some(((value >> 7) as u8, (value & 0x7F) as u8))
+}
+
+/// Wraps a MIDI output to send the pitch bends sent to it as [`ChannelTuning`] controllers instead.
+pub struct ChannelTuned {
+    output: Box<dyn MidiSink>,
+    tuning: ChannelTuning,
+    /// Pitch bend range in +/- semitones that the pitch bends are sent for.
+    pb_range: u16,
+}
+
+impl ChannelTuned {
+    pub fn new(output: Box<dyn MidiSink>, tuning: ChannelTuning, pb_range: u16) -> Self {
+        ChannelTuned {
+            output,
+            tuning,
+            pb_range,
+        }
+    }
+}
+
+impl MidiSink for ChannelTuned {
+    fn send(&mut self, message: &[u8]) {
+        if message[0] & 0xF0 != 0xE0 || message.len() < 3 {
+            self.output.send(message);
+            return;
+        }
+        let channel = message[0] & 0x0F;
+        let bend = (message[1] as u16 | (message[2] as u16) << 7) as f64 - 8192.0;
+        let cents = bend / 8192.0 * self.pb_range as f64 * 100.0;
+        match self.tuning.messages(channel, cents) {
+            Some(messages) => messages.iter().for_each(|message| self.output.send(message)),
+            None => println!("WARN: Can't tune channel {channel} by {cents:.3}c, it is out of the controller's range"),
+        }
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.output.retune(snapshot);
+    }
+
+    fn lookahead(&self) -> f64 {
+        self.output.lookahead()
+    }
+
+    fn timestamp(&mut self, delay: f64) {
+        self.output.timestamp(delay);
+    }
+}
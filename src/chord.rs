@@ -0,0 +1,197 @@
+//! Lightweight chord recognition of the sounding pitch classes, for captioning the harmony in real time (see
+//! [`crate::server::VisualizerMessage::ChordChanged`]), e.g. `F#9(13), otonal on F#`.
+//!
+//! The chord is named relative to the root of the tuning in effect rather than guessed from the notes, so that the
+//! caption agrees with the ratios shown for the notes: the third, fifth & seventh above the root give the chord's
+//! quality, a ninth is stacked onto a seventh chord's number, and the other tones are listed as extensions. The JI
+//! tuning of the chord is described as otonal when all of its pitch classes are harmonics of the root (ratios over a
+//! power of 2, e.g. 4:5:6:7), or utonal when they are all subharmonics of it (e.g. 1/4:1/5:1/6).
+
+use rational::Rational;
+
+use crate::tuner::{spelled_name, PitchSpec, Spellings};
+
+/// Names the chord of `intervals` (semitones above the root, including 0 if it sounds), e.g. `9(13)`, without the
+/// root.
+fn symbol(intervals: &[usize]) -> String {
+    let has = |i: usize| intervals.contains(&i);
+    let major = has(4);
+    let minor = has(3) && !major;
+    let diminished = minor && has(6) && !has(7);
+    let augmented = major && has(8) && !has(7);
+    let sus = match (major || minor, has(5), has(2)) {
+        (false, true, _) => Some(5),
+        (false, false, true) => Some(2),
+        _ => None,
+    };
+    let seventh = match (diminished && has(9), has(10), has(11)) {
+        (true, false, false) => Some(9),
+        (_, true, _) => Some(10),
+        (_, false, true) => Some(11),
+        _ => None,
+    };
+    let ninth = seventh.is_some() && has(2) && sus != Some(2);
+
+    let number = if ninth { "9" } else { "7" };
+    let mut symbol = match (seventh, diminished, augmented, minor) {
+        (Some(9), ..) => "dim7".to_string(),
+        (Some(10), true, ..) => format!("m{number}b5"),
+        (Some(10), _, true, _) => format!("aug{number}"),
+        (Some(10), _, _, true) => format!("m{number}"),
+        (Some(10), ..) => number.to_string(),
+        (Some(_), true, ..) => format!("dim(maj{number})"),
+        (Some(_), _, true, _) => format!("aug(maj{number})"),
+        (Some(_), _, _, true) => format!("m(maj{number})"),
+        (Some(_), ..) => format!("maj{number}"),
+        (None, true, ..) => "dim".to_string(),
+        (None, _, true, _) => "aug".to_string(),
+        (None, _, _, true) => "m".to_string(),
+        (None, ..) if sus.is_none() && !major && has(7) && intervals.len() == 2 => "5".to_string(),
+        (None, ..) => String::new(),
+    };
+    if seventh.is_none() && has(9) {
+        symbol.push('6');
+    }
+    if seventh.is_none() && has(2) && sus != Some(2) {
+        symbol.push_str("add9");
+    }
+    match sus {
+        Some(5) => symbol.push_str("sus4"),
+        Some(_) => symbol.push_str("sus2"),
+        None => {}
+    }
+
+    let mut extensions = vec![];
+    if has(1) {
+        extensions.push("b9");
+    }
+    if has(3) && major {
+        extensions.push("#9");
+    }
+    if has(5) && sus.is_none() {
+        extensions.push("11");
+    }
+    if has(6) && !diminished {
+        extensions.push(if has(7) { "#11" } else { "b5" });
+    }
+    if has(8) && !augmented {
+        extensions.push("b13");
+    }
+    if has(9) && seventh.is_some_and(|seventh| seventh != 9) {
+        extensions.push("13");
+    }
+    if !extensions.is_empty() {
+        symbol.push_str(&format!("({})", extensions.join(",")));
+    }
+    symbol
+}
+
+/// Returns `otonal` if the pitches of `tones` are all harmonics of the root tuned to `root`, `utonal` if they are all
+/// subharmonics of it, or [`None`] if neither or any of them is tempered.
+fn harmonic_kind(tones: &[(usize, PitchSpec)], root: PitchSpec) -> Option<&'static str> {
+    let PitchSpec::Ratio(root_pitch) = root else {
+        return None;
+    };
+    let ratios = tones
+        .iter()
+        .map(|(_, pitch)| match pitch {
+            PitchSpec::Ratio(pitch) => Some(*pitch / root_pitch),
+            _ => None,
+        })
+        .collect::<Option<Vec<Rational>>>()?;
+    let power_of_2 = |n: i128| n & (n - 1) == 0;
+    if ratios.iter().all(|ratio| power_of_2(ratio.denominator())) {
+        Some("otonal")
+    } else if ratios.iter().all(|ratio| power_of_2(ratio.numerator())) {
+        Some("utonal")
+    } else {
+        None
+    }
+}
+
+/// Captions the chord of the sounding `tones` (pitch classes in any order, repeats allowed, with their tunings) relative
+/// to `root` tuned as in `tuning`, e.g. `F#9(13), otonal on F#`, named as spelt in `spellings`. The tones are tuned
+/// separately so that those of the lower range of a keyboard split can be tuned by its own tuning. Empty if fewer than
+/// 2 pitch classes are sounding.
+pub fn caption(
+    tones: &[(usize, PitchSpec)],
+    tuning: &[PitchSpec; 12],
+    spellings: &Spellings,
+    root: usize,
+) -> String {
+    let mut pitch_classes: Vec<usize> = tones.iter().map(|(pc, _)| *pc).collect();
+    pitch_classes.sort_unstable();
+    pitch_classes.dedup();
+    if pitch_classes.len() < 2 {
+        return String::new();
+    }
+    let intervals: Vec<usize> = pitch_classes
+        .iter()
+        .map(|pc| (pc + 12 - root) % 12)
+        .collect();
+    let root_name = spelled_name(spellings, root);
+    let chord = format!("{root_name}{}", symbol(&intervals));
+    match harmonic_kind(tones, tuning[root]) {
+        Some(kind) => format!("{chord}, {kind} on {root_name}"),
+        None => chord,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seventh_chords() {
+        assert_eq!(symbol(&[0, 3, 6, 9]), "dim7");
+        assert_eq!(symbol(&[0, 3, 6, 10]), "m7b5");
+        assert_eq!(symbol(&[0, 4, 7, 10]), "7");
+        assert_eq!(symbol(&[0, 4, 7, 11]), "maj7");
+    }
+
+    #[test]
+    fn ninth_with_thirteenth() {
+        assert_eq!(symbol(&[0, 2, 4, 7, 9, 10]), "9(13)");
+    }
+
+    #[test]
+    fn suspended_chords() {
+        assert_eq!(symbol(&[0, 5, 7]), "sus4");
+        assert_eq!(symbol(&[0, 2, 7]), "sus2");
+        assert_eq!(symbol(&[0, 5, 7, 10]), "7sus4");
+    }
+
+    #[test]
+    fn added_ninth() {
+        assert_eq!(symbol(&[0, 2, 4, 7]), "add9");
+        assert_eq!(symbol(&[0, 2, 3, 7]), "madd9");
+    }
+
+    #[test]
+    fn power_chord() {
+        assert_eq!(symbol(&[0, 7]), "5");
+    }
+
+    /// Each tone is described by its own tuning, e.g. of the lower range of a keyboard split.
+    #[test]
+    fn caption_tunes_tones_separately() {
+        let ratio = |n, d| PitchSpec::Ratio(Rational::new(n, d));
+        let tuning = [ratio(1, 1); 12];
+        let spellings = [None; 12];
+        let otonal = [(0, ratio(1, 1)), (4, ratio(5, 4)), (7, ratio(3, 2))];
+        assert_eq!(caption(&otonal, &tuning, &spellings, 0), "A, otonal on A");
+        let utonal = [(0, ratio(1, 1)), (5, ratio(4, 3)), (8, ratio(8, 5))];
+        assert!(caption(&utonal, &tuning, &spellings, 0).ends_with(", utonal on A"));
+        let mixed = [(0, ratio(1, 1)), (4, ratio(5, 4)), (7, ratio(4, 3))];
+        assert_eq!(caption(&mixed, &tuning, &spellings, 0), "A");
+        assert_eq!(
+            caption(
+                &[(0, ratio(1, 1)), (0, ratio(2, 1))],
+                &tuning,
+                &spellings,
+                0
+            ),
+            ""
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! Click track generated from the tempo map & time signatures of the MIDI file, for a performer playing along with
+//! playback (e.g. the acoustic part of a hybrid piece) to stay locked to the playback that drives the retunes.
+//!
+//! The click is sent on a channel of the playback output that isn't used by the tuned pitch classes, or to a
+//! separate MIDI output port (e.g. the performer's in-ear monitor mix), as General MIDI percussion notes: an accented
+//! click on each downbeat, a click on each other beat, and a softer one on each subdivision of a beat.
+
+use crate::score::Score;
+
+/// Where the click track is sent, see [`crate::CLICK_TRACK`].
+#[allow(dead_code)]
+pub enum ClickOutput {
+    /// No click track.
+    Off,
+    /// On this channel (12-15, the channels not used by the tuned pitch classes) of the playback output.
+    Channel(u8),
+    /// On the General MIDI percussion channel of the MIDI output port whose name contains this.
+    Port(&'static str),
+}
+
+/// General MIDI percussion channel, which clicks are sent on when sent to a separate port.
+pub const PERCUSSION_CHANNEL: u8 = 9;
+
+/// Key & velocity of a downbeat click (Hi Wood Block).
+const DOWNBEAT: (u8, u8) = (76, 127);
+
+/// Key & velocity of a beat click (Low Wood Block).
+const BEAT: (u8, u8) = (77, 100);
+
+/// Key & velocity of a subdivision click (Low Wood Block, softer).
+const SUBDIVISION: (u8, u8) = (77, 60);
+
+/// A click of the click track.
+#[derive(Clone)]
+pub struct Click {
+    /// Time in seconds.
+    pub time: f64,
+    pub key: u8,
+    pub velocity: u8,
+}
+
+/// Returns the clicks of every beat of `score`, each divided into `subdivision` clicks, in order of time.
+///
+/// Beats are the note values of the time signature's denominator, e.g. eighth notes in 6/8.
+pub fn clicks(score: &Score, subdivision: u32) -> Vec<Click> {
+    let mut clicks = vec![];
+    for bar in &score.bars {
+        let ticks_per_beat = score.ppqn * 4 / bar.beat_unit as u64;
+        for beat in 0..bar.beats as u64 {
+            for sub in 0..subdivision as u64 {
+                let tick = bar.start_tick
+                    + beat * ticks_per_beat
+                    + sub * ticks_per_beat / subdivision as u64;
+                let (key, velocity) = match (beat, sub) {
+                    (0, 0) => DOWNBEAT,
+                    (_, 0) => BEAT,
+                    _ => SUBDIVISION,
+                };
+                clicks.push(Click {
+                    time: score.tick_time(tick),
+                    key,
+                    velocity,
+                });
+            }
+        }
+    }
+    clicks
+}
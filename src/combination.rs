@@ -0,0 +1,177 @@
+//! Combination tones of a sonority: the difference & summation tones heard (or implied) when its notes sound together,
+//! e.g. to check whether the difference tones of a chord reinforce a virtual fundamental.
+
+use std::fmt::{Display, Write as _};
+
+use crate::score::Score;
+use crate::tuner::{key_name, snapshot_at, spelled_key_name, TuningSnapshot};
+
+/// Combination tones outside this range in Hz are ignored (below hearing, or above the highest MIDI note).
+const HZ_RANGE: (f64, f64) = (20.0, 12000.0);
+
+/// Combination tones within this many cents of each other are counted as the same tone.
+const CLUSTER_CENTS: f64 = 3.0;
+
+/// Number of combination tones reported per sonority, those produced by the most pairs of notes first.
+pub const TONES_PER_SONORITY: usize = 6;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CombinationKind {
+    Difference,
+    Summation,
+}
+
+/// A combination tone produced by one or more pairs of notes.
+#[derive(Clone, Copy)]
+pub struct CombinationTone {
+    pub kind: CombinationKind,
+    /// 1 for `f2 - f1` & `f1 + f2`, 2 for `2f1 - f2`, `2f2 - f1`, `2f1 + f2` & `f1 + 2f2`.
+    pub order: u8,
+    pub hz: f64,
+    /// Number of pairs of notes producing this tone (within [`CLUSTER_CENTS`]).
+    pub pairs: usize,
+}
+
+impl CombinationTone {
+    /// Returns the nearest MIDI note and the cents of the tone above its 12edo pitch.
+    pub fn nearest_key(&self, a4_hz: f64) -> (u8, f64) {
+        let cents = 1200.0 * (self.hz / a4_hz).log2();
+        let key = 69.0 + (cents / 100.0).round();
+        (key as u8, cents - 100.0 * (key - 69.0))
+    }
+
+    /// Returns e.g. `d1` for a first order difference tone.
+    pub fn label(&self) -> String {
+        let kind = match self.kind {
+            CombinationKind::Difference => 'd',
+            CombinationKind::Summation => 's',
+        };
+        format!("{kind}{}", self.order)
+    }
+
+    /// Describes the tone, e.g. `d1 C#2 -13.7c 69.30 Hz (3 pairs)`.
+    pub fn describe(&self, a4_hz: f64) -> String {
+        let (key, cents) = self.nearest_key(a4_hz);
+        format!(
+            "{} {} {cents:+.1}c {:.2} Hz ({} pair{})",
+            self.label(),
+            key_name(key),
+            self.hz,
+            self.pairs,
+            if self.pairs == 1 { "" } else { "s" }
+        )
+    }
+}
+
+/// Returns the first & second order combination tones of the notes at `pitches` (in cents relative to A4, where A4 is
+/// `a4_hz`), merging tones within [`CLUSTER_CENTS`] of each other. The tones produced by the most pairs of notes come
+/// first, then lower orders, then lower pitches.
+pub fn combination_tones(pitches: &[f64], a4_hz: f64) -> Vec<CombinationTone> {
+    let mut hz: Vec<f64> = pitches
+        .iter()
+        .map(|cents| a4_hz * 2f64.powf(cents / 1200.0))
+        .collect();
+    hz.sort_by(f64::total_cmp);
+    hz.dedup_by(|a, b| (1200.0 * (*a / *b).log2()).abs() < CLUSTER_CENTS);
+
+    let mut tones = vec![];
+    for (i, f1) in hz.iter().enumerate() {
+        for f2 in &hz[i + 1..] {
+            let tone = |kind, order, hz| CombinationTone {
+                kind,
+                order,
+                hz,
+                pairs: 1,
+            };
+            tones.extend([
+                tone(CombinationKind::Difference, 1, f2 - f1),
+                tone(CombinationKind::Summation, 1, f1 + f2),
+                tone(CombinationKind::Difference, 2, 2.0 * f1 - f2),
+                tone(CombinationKind::Difference, 2, 2.0 * f2 - f1),
+                tone(CombinationKind::Summation, 2, 2.0 * f1 + f2),
+                tone(CombinationKind::Summation, 2, f1 + 2.0 * f2),
+            ]);
+        }
+    }
+    tones.retain(|tone| (HZ_RANGE.0..=HZ_RANGE.1).contains(&tone.hz));
+
+    // Merge tones within CLUSTER_CENTS of the lowest tone of each cluster, keeping the lowest order.
+    tones.sort_by(|a, b| a.hz.total_cmp(&b.hz));
+    let mut clusters: Vec<CombinationTone> = vec![];
+    for tone in tones {
+        match clusters.last_mut() {
+            Some(cluster) if 1200.0 * (tone.hz / cluster.hz).log2() < CLUSTER_CENTS => {
+                cluster.pairs += 1;
+                if tone.order < cluster.order {
+                    cluster.order = tone.order;
+                    cluster.kind = tone.kind;
+                }
+            }
+            _ => clusters.push(tone),
+        }
+    }
+    clusters.sort_by(|a, b| {
+        b.pairs
+            .cmp(&a.pairs)
+            .then(a.order.cmp(&b.order))
+            .then(a.hz.total_cmp(&b.hz))
+    });
+    clusters
+}
+
+/// Reports the strongest combination tones of the notes sounding at each of `times` (e.g. the starts of the segments
+/// of [`crate::analysis::segment`]), tuned as in `snapshots`.
+pub fn combination_report(
+    score: &Score,
+    snapshots: &[TuningSnapshot],
+    times: &[f64],
+    a4_hz: f64,
+) -> String {
+    let mut report = String::new();
+    for (i, time) in times.iter().enumerate() {
+        let Some(snapshot) = snapshot_at(snapshots, *time) else {
+            continue;
+        };
+        let mut keys: Vec<u8> = score
+            .notes
+            .iter()
+            .filter(|n| n.start <= *time && n.release > *time)
+            .map(|n| n.key)
+            .collect();
+        keys.sort();
+        keys.dedup();
+        if keys.len() < 2 {
+            continue;
+        }
+        let pitches: Vec<f64> = keys.iter().map(|key| snapshot.key_cents(*key)).collect();
+        let names: Vec<String> = keys
+            .iter()
+            .map(|key| spelled_key_name(&snapshot.spellings, *key))
+            .collect();
+        writeln!(
+            report,
+            "{:>4}  {:>8.3}s  {:>9}  {}",
+            i + 1,
+            time,
+            score.position(*time),
+            names.join(" ")
+        )
+        .unwrap();
+        for tone in combination_tones(&pitches, a4_hz)
+            .iter()
+            .take(TONES_PER_SONORITY)
+        {
+            writeln!(report, "      {}", tone.describe(a4_hz)).unwrap();
+        }
+    }
+    report
+}
+
+impl Display for CombinationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CombinationKind::Difference => write!(f, "difference"),
+            CombinationKind::Summation => write!(f, "summation"),
+        }
+    }
+}
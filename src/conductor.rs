@@ -0,0 +1,72 @@
+//! Conductor mode, for freely timed concert situations: playback pauses right before each tuning with a `cue` (see
+//! [`crate::tuning_file`]), e.g. at a fermata or between movements, and waits for the conductor (or the performer) to
+//! advance it by pressing enter, tapping a key or pedal of the MIDI input, or sending `advance` to the websocket server
+//! (e.g. from a phone in the hall).
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::server;
+use crate::tap::{TapDetector, TapInput};
+use crate::timer::Interrupt;
+use crate::tuner::TuningData;
+
+/// Websocket message that advances past a cue.
+const ADVANCE_MESSAGE: &str = "advance";
+
+/// Sends an advance for each [`ADVANCE_MESSAGE`] sent to the websocket server, on a new thread.
+pub fn advance_on_websocket(advance: mpsc::Sender<()>) {
+    let messages = server::control_messages();
+    thread::spawn(move || {
+        for message in messages {
+            if message.trim() == ADVANCE_MESSAGE && advance.send(()).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Sends an advance for each tap of `input` in the MIDI `messages` on a new thread. Returns the other messages.
+pub fn advance_on_taps(
+    input: &'static TapInput,
+    messages: mpsc::Receiver<(Instant, Vec<u8>)>,
+    advance: mpsc::Sender<()>,
+) -> mpsc::Receiver<(Instant, Vec<u8>)> {
+    let (others_sender, others) = mpsc::channel();
+    thread::spawn(move || {
+        let mut detector = TapDetector::new(input);
+        for (instant, raw) in messages {
+            if detector.tapped(&raw) {
+                let _ = advance.send(());
+            } else {
+                let _ = others_sender.send((instant, raw));
+            }
+        }
+    });
+    others
+}
+
+/// Waits at the cue of `tuning` until it is advanced. Returns false if `interrupt` was raised meanwhile.
+///
+/// Advances that came before the cue was reached are ignored, so that an accidental press doesn't skip it.
+pub fn wait(tuning: &TuningData, advances: &mpsc::Receiver<()>, interrupt: &Interrupt) -> bool {
+    advances.try_iter().for_each(drop);
+    match tuning.cue.as_deref() {
+        Some("") | None => println!("Cue before {}, waiting to advance...", tuning.describe()),
+        Some(cue) => println!(
+            "Cue before {}: {cue}, waiting to advance...",
+            tuning.describe()
+        ),
+    }
+    loop {
+        match advances.recv_timeout(Duration::from_millis(100)) {
+            Ok(()) => {
+                println!("Advancing");
+                return true;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) if !interrupt.is_raised() => {}
+            Err(_) => return false,
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Settings of a run: the command line (see [`Cli`]) and the project file of `run` (see [`crate::project`]) applied
+//! over the constants of `main.rs`, as a [`Config`] that is passed to playback and the other commands.
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use clap::{Parser, ValueEnum};
+
+use crate::output::{Allocation, StealPolicy};
+use crate::pieces::Piece;
+use crate::project;
+use crate::{
+    ACTIVATE_MIDI, ACTIVATE_VISUALIZER, CHANNEL_ALLOCATION, COMMANDS, DEBUG_PRINT, KEYBOARD_SPLIT,
+    MIDI_FILE, MIDI_PLAYBACK_DEVICE_NAME, PB_RANGE, PLAYBACK_SPEED, START_FROM, TUNING_FILE,
+};
+
+/// The command line. Options can be given anywhere.
+#[derive(Parser)]
+#[command(name = "ji-performer", version, about = "Just intonation playback of MIDI files", after_help = COMMANDS)]
+pub struct Cli {
+    /// The command & its arguments, see below. A FILE.mid given after the command is played or analysed (with the
+    /// tuning file FILE.tuning) instead of MIDI_FILE, and a PROJECT.toml given after it applies that project file
+    #[arg(value_name = "COMMAND")]
+    args: Vec<String>,
+    /// Apply the variant blocks named NAME in the tuning file, e.g. to render A/B versions of a passage. Can be given
+    /// multiple times
+    #[arg(long = "variant", value_name = "NAME")]
+    variants: Vec<String>,
+    /// Also write every message sent to the visualizer (notes, tunings, positions...) to stdout as a line of JSON, e.g.
+    /// to pipe playback into jq or a logger. The rest of the output then goes to stderr. Can't be used with
+    /// --no-visualizer
+    #[arg(long, value_name = "FORMAT")]
+    emit: Option<Emit>,
+    /// Start playing from SECONDS instead of START_FROM
+    #[arg(long, value_name = "SECONDS", value_parser = non_negative)]
+    start: Option<f64>,
+    /// Play at MULTIPLIER times the speed of the MIDI file instead of PLAYBACK_SPEED
+    #[arg(long, value_name = "MULTIPLIER", value_parser = positive)]
+    speed: Option<f64>,
+    /// Send pitch bends for a pitch bend range of +/- SEMITONES instead of PB_RANGE
+    #[arg(long, value_name = "SEMITONES", value_parser = clap::value_parser!(u16).range(1..=96))]
+    pb_range: Option<u16>,
+    /// Don't send messages to the visualizer, as if ACTIVATE_VISUALIZER were false
+    #[arg(long)]
+    no_visualizer: bool,
+    /// Don't send MIDI output, as if ACTIVATE_MIDI were false
+    #[arg(long)]
+    no_midi: bool,
+    /// Print debug output, as if DEBUG_PRINT were true
+    #[arg(long)]
+    debug: bool,
+    /// Play each note on a free channel of its own instead of CHANNEL_ALLOCATION, stealing the channel of the note
+    /// chosen by POLICY when all 12 are sounding
+    #[arg(long, value_name = "POLICY")]
+    steal: Option<StealPolicy>,
+}
+
+/// Formats of `--emit`.
+#[derive(Clone, Copy, ValueEnum)]
+enum Emit {
+    /// A line of JSON per message
+    Jsonl,
+}
+
+/// The settings of a run, see the [module docs](self).
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Names of the variant blocks applied to the tuning files.
+    pub variants: Vec<String>,
+    /// Whether the visualizer messages are also written to stdout as JSON lines (`--emit jsonl`).
+    pub emit_jsonl: bool,
+    /// Position playback starts from, see [`START_FROM`].
+    pub start_from: f64,
+    /// See [`PLAYBACK_SPEED`].
+    pub speed: f64,
+    /// Pitch bend range in +/- semitones that pitch bends are sent for, and that tunings are checked against when
+    /// loaded. See [`PB_RANGE`].
+    pub pb_range: u16,
+    /// See [`ACTIVATE_VISUALIZER`].
+    pub visualizer: bool,
+    /// See [`ACTIVATE_MIDI`].
+    pub midi: bool,
+    /// See [`DEBUG_PRINT`].
+    pub debug: bool,
+    /// Name of the output connected to without asking, see [`MIDI_PLAYBACK_DEVICE_NAME`].
+    pub device: String,
+    /// How notes are assigned to the tuned channels, see [`CHANNEL_ALLOCATION`].
+    pub allocation: Allocation,
+    /// MIDI file played & analysed by the commands, see [`MIDI_FILE`].
+    pub midi_file: String,
+    /// Tuning timeline of [`Config::midi_file`], see [`TUNING_FILE`].
+    pub tuning_file: String,
+    /// Whether the MIDI file was given (after the command, or by a project file), to be played once instead of the
+    /// [`crate::PIECES`].
+    pub file_given: bool,
+}
+
+impl Config {
+    /// The MIDI file & tuning file of the config as a piece, named by the MIDI file.
+    pub fn piece(&self) -> Piece {
+        Piece {
+            name: Cow::Owned(self.midi_file.clone()),
+            midi_file: Cow::Owned(self.midi_file.clone()),
+            tuning_file: Cow::Owned(self.tuning_file.clone()),
+        }
+    }
+}
+
+impl Cli {
+    /// The command & its arguments, without the MIDI file & project file given after the command.
+    pub fn args(&self) -> Vec<String> {
+        let files = [self.file_idx(&[".mid", ".midi"]), self.file_idx(&[".toml"])];
+        (self.args.iter().enumerate())
+            .filter(|(idx, _)| !files.contains(&Some(*idx)))
+            .map(|(_, arg)| arg.clone())
+            .collect()
+    }
+
+    /// Index in the arguments of the first file given after the command with one of `extensions`.
+    fn file_idx(&self, extensions: &[&str]) -> Option<usize> {
+        (1..self.args.len()).find(|&idx| extensions.iter().any(|ext| self.args[idx].ends_with(ext)))
+    }
+
+    /// Returns the config given by the command line, loading the project file given (which `run` requires). The MIDI
+    /// file & options given override the settings of the project file.
+    pub fn config(&self) -> Config {
+        let mut config = Config {
+            variants: self.variants.clone(),
+            emit_jsonl: self.emit.is_some(),
+            start_from: START_FROM,
+            speed: PLAYBACK_SPEED,
+            pb_range: PB_RANGE,
+            visualizer: ACTIVATE_VISUALIZER,
+            midi: ACTIVATE_MIDI,
+            debug: DEBUG_PRINT,
+            device: MIDI_PLAYBACK_DEVICE_NAME.to_string(),
+            allocation: CHANNEL_ALLOCATION,
+            midi_file: MIDI_FILE.to_string(),
+            tuning_file: TUNING_FILE.to_string(),
+            file_given: false,
+        };
+        let args = self.args();
+        let project_file = self.file_idx(&[".toml"]).map(|idx| &self.args[idx]);
+        if args.first().is_some_and(|cmd| cmd == "run") {
+            if project_file.is_none() {
+                println!("Expected a project file\n\n{COMMANDS}");
+                std::process::exit(1);
+            }
+            if args.len() > 1 {
+                println!("Too many arguments\n\n{COMMANDS}");
+                std::process::exit(1);
+            }
+        }
+        if let Some(path) = project_file {
+            let project = project::load(path);
+            config.start_from = project.start.unwrap_or(START_FROM);
+            config.speed = project.speed.unwrap_or(PLAYBACK_SPEED);
+            config.pb_range = project.pb_range.unwrap_or(PB_RANGE);
+            config.emit_jsonl |= project.emit_jsonl;
+            config.variants = [project.variants, config.variants].concat();
+            config.visualizer = project.visualizer.unwrap_or(ACTIVATE_VISUALIZER);
+            config.device = project.device.unwrap_or(config.device);
+            config.midi_file = project.midi_file;
+            config.tuning_file = project.tuning_file;
+            config.file_given = true;
+        }
+        if let Some(idx) = self.file_idx(&[".mid", ".midi"]) {
+            let midi_file = &self.args[idx];
+            config.tuning_file = Path::new(midi_file)
+                .with_extension("tuning")
+                .to_string_lossy()
+                .into_owned();
+            config.midi_file = midi_file.clone();
+            config.file_given = true;
+        }
+        config.start_from = self.start.unwrap_or(config.start_from);
+        config.speed = self.speed.unwrap_or(config.speed);
+        config.pb_range = self.pb_range.unwrap_or(config.pb_range);
+        config.visualizer &= !self.no_visualizer;
+        config.midi &= !self.no_midi;
+        config.debug |= self.debug;
+        if config.emit_jsonl && !config.visualizer {
+            println!(
+                "--emit jsonl writes the messages sent to the visualizer, so it can't be used with the visualizer \
+                 turned off"
+            );
+            std::process::exit(1);
+        }
+        if let Some(steal) = self.steal {
+            if KEYBOARD_SPLIT.is_some() {
+                println!("--steal can't be used with a KEYBOARD_SPLIT, its lower range is tuned by pitch class");
+                std::process::exit(1);
+            }
+            config.allocation = Allocation::PerNote(steal);
+        }
+        config
+    }
+}
+
+fn non_negative(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(value) if value >= 0.0 => Ok(value),
+        _ => Err("expected a number of at least 0".to_string()),
+    }
+}
+
+fn positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(value) if value > 0.0 => Ok(value),
+        _ => Err("expected a number above 0".to_string()),
+    }
+}
@@ -0,0 +1,227 @@
+//! Comparisons of tuning timelines, to check what an edit of a tuning file actually retunes, or how far each tuning
+//! change moves each pitch class, or to follow the tunings of a single pitch class across a piece.
+
+use std::fmt::Write as _;
+
+use rational::Rational;
+
+use crate::score::Score;
+use crate::tuner::{snapshot_at, spelled_name, PitchSpec, Spellings, Tuner, TuningSnapshot};
+
+/// A pitch class tuned differently in two tunings.
+#[derive(PartialEq)]
+pub struct PitchChange {
+    /// Pitch class (0 is A, 1 is Bb, etc...)
+    pub semitone: usize,
+    pub from: PitchSpec,
+    pub to: PitchSpec,
+}
+
+impl PitchChange {
+    pub fn cents(&self) -> f64 {
+        self.to.cents().unwrap() - self.from.cents().unwrap()
+    }
+
+    /// The interval the pitch class is retuned by, e.g. `81/80`, or [`None`] if either tuning is tempered.
+    pub fn comma(&self) -> Option<Rational> {
+        match (self.from, self.to) {
+            (PitchSpec::Ratio(from), PitchSpec::Ratio(to)) => Some(to / from),
+            _ => None,
+        }
+    }
+}
+
+/// A tuning of a pitch class in a tuning timeline, see [`history`].
+pub struct HistoryEntry {
+    /// Index of the tuning change in the timeline.
+    pub idx: usize,
+    pub time: f64,
+    pub pitch: PitchSpec,
+    /// The retune from the previous tuning of the pitch class, [`None`] for its first tuning.
+    pub change: Option<PitchChange>,
+}
+
+/// Returns the pitch classes tuned differently in `from` & `to`.
+pub fn changes(from: &TuningSnapshot, to: &TuningSnapshot) -> Vec<PitchChange> {
+    (0..12)
+        .filter(|i| from.tuning[*i] != to.tuning[*i])
+        .map(|semitone| PitchChange {
+            semitone,
+            from: from.tuning[semitone],
+            to: to.tuning[semitone],
+        })
+        .collect()
+}
+
+/// Returns the first tuning of `semitone` (0 is A) in `tuner` and every tuning change that retunes it, in order of
+/// time.
+pub fn history(tuner: &Tuner, semitone: usize) -> Vec<HistoryEntry> {
+    let snapshots = tuner.snapshots();
+    let first = &snapshots[0];
+    let mut history = vec![HistoryEntry {
+        idx: 0,
+        time: first.time,
+        pitch: first.tuning[semitone],
+        change: None,
+    }];
+    for idx in 1..snapshots.len() {
+        let change = changes(&snapshots[idx - 1], &snapshots[idx])
+            .into_iter()
+            .find(|c| c.semitone == semitone);
+        if let Some(change) = change {
+            history.push(HistoryEntry {
+                idx,
+                time: snapshots[idx].time,
+                pitch: change.to,
+                change: Some(change),
+            });
+        }
+    }
+    history
+}
+
+/// Reports every tuning of `semitone` in `tuner` (see [`history`]) with its position in `score`, its cents above A,
+/// and the comma it is retuned by from the previous tuning.
+pub fn history_report(score: &Score, tuner: &Tuner, semitone: usize) -> String {
+    let history = history(tuner, semitone);
+    let name = spelled_name(&tuner.snapshots()[0].spellings, semitone);
+    let mut report = String::new();
+    writeln!(
+        report,
+        "{name}: {} tunings, retuned {} times",
+        history.len(),
+        history.len() - 1
+    )
+    .unwrap();
+
+    for entry in &history {
+        let comma = match &entry.change {
+            Some(change) => match change.comma() {
+                Some(comma) => format!("{:+.3}c by {comma}", change.cents()),
+                None => format!("{:+.3}c", change.cents()),
+            },
+            None => String::new(),
+        };
+        let line = format!(
+            "{:>4}  {:>8.3}s  {:>9}  {:>14} {:>9.3}c  {comma:<22}  {}",
+            entry.idx,
+            entry.time,
+            score.position(entry.time),
+            entry.pitch.to_string(),
+            entry.pitch.cents().unwrap(),
+            tuner[entry.idx].annotation().unwrap_or_default()
+        );
+        writeln!(report, "{}", line.trim_end()).unwrap();
+    }
+    report
+}
+
+/// Reports how each tuning change of `tuner` retunes each pitch class, marking changes of at least `threshold`
+/// cents with `!`.
+pub fn consecutive_report(tuner: &Tuner, threshold: f64) -> String {
+    let snapshots = tuner.snapshots();
+    let mut report = String::new();
+    let mut total = 0;
+    let mut above = 0;
+
+    for idx in 1..snapshots.len() {
+        let changes = changes(&snapshots[idx - 1], &snapshots[idx]);
+        writeln!(report, "{}", tuner[idx].describe()).unwrap();
+        write_changes(&mut report, &changes, &snapshots[idx].spellings, threshold);
+        total += changes.len();
+        above += changes
+            .iter()
+            .filter(|c| c.cents().abs() >= threshold)
+            .count();
+    }
+
+    writeln!(
+        report,
+        "{total} retunes in {} tuning changes, {above} of at least {threshold}c",
+        snapshots.len().saturating_sub(1)
+    )
+    .unwrap();
+    report
+}
+
+/// Reports the differences between the tunings in effect in `a` & `b`, whenever they change (at any tuning change of
+/// either), marking differences of at least `threshold` cents with `!`.
+pub fn timeline_report(a: &Tuner, b: &Tuner, threshold: f64) -> String {
+    let (a_snapshots, b_snapshots) = (a.snapshots(), b.snapshots());
+    let mut times: Vec<f64> = a_snapshots
+        .iter()
+        .chain(&b_snapshots)
+        .map(|s| s.time)
+        .collect();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+
+    let mut report = String::new();
+    let mut prev_changes: Vec<PitchChange> = vec![];
+    let mut differing = 0;
+    let mut above = 0;
+
+    for time in times {
+        let (Some(a_snapshot), Some(b_snapshot)) = (
+            snapshot_at(&a_snapshots, time),
+            snapshot_at(&b_snapshots, time),
+        ) else {
+            continue;
+        };
+        let changes = changes(a_snapshot, b_snapshot);
+        if changes == prev_changes {
+            continue;
+        }
+
+        // Describe the tuning by the annotations of either timeline at this time.
+        let tuning = (0..b.len())
+            .map(|i| &b[i])
+            .chain((0..a.len()).map(|i| &a[i]))
+            .find(|td| td.time == time)
+            .unwrap();
+        writeln!(report, "{}", tuning.describe()).unwrap();
+        if changes.is_empty() {
+            writeln!(report, "  (same)").unwrap();
+        }
+        write_changes(&mut report, &changes, &b_snapshot.spellings, threshold);
+
+        differing += changes.len();
+        above += changes
+            .iter()
+            .filter(|c| c.cents().abs() >= threshold)
+            .count();
+        prev_changes = changes;
+    }
+
+    writeln!(
+        report,
+        "{differing} differences, {above} of at least {threshold}c"
+    )
+    .unwrap();
+    report
+}
+
+/// Writes a line per change, naming the pitch classes as in `spellings`.
+fn write_changes(
+    report: &mut String,
+    changes: &[PitchChange],
+    spellings: &Spellings,
+    threshold: f64,
+) {
+    for change in changes {
+        writeln!(
+            report,
+            "  {:<3} {:>14} -> {:<14} {:+9.3}c{}",
+            spelled_name(spellings, change.semitone),
+            change.from.to_string(),
+            change.to.to_string(),
+            change.cents(),
+            if change.cents().abs() >= threshold {
+                "  !"
+            } else {
+                ""
+            },
+        )
+        .unwrap();
+    }
+}
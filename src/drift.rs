@@ -0,0 +1,127 @@
+//! Automation of the global reference pitch (see [`crate::REFERENCE_DRIFT`]), e.g. a slow rise of A4 from 440 to 442 Hz
+//! across a piece, or an upward drift after a climax: a curve multiplied into all tunings, kept separate from the
+//! tuning timeline so that the ratios shown for the notes stay those of the tunings.
+//!
+//! While the curve ramps, the pitch bends of all pitch class channels are sent again whenever the drift changed by
+//! [`DRIFT_RESOLUTION_CENTS`], and outputs tuned by other means (e.g. MTS) are retuned with the drifted tuning.
+
+use std::fmt::Write as _;
+
+use crate::score::Score;
+use crate::tuner::{cents_pitch_bend, pitch_bend_message, PitchSpec, TuningSnapshot};
+use crate::A4_FREQUENCY;
+
+/// Time in seconds between the samples of the curve while it ramps.
+const DRIFT_INTERVAL: f64 = 0.05;
+
+/// The drifted tuning is only sent again once the drift changed by at least this many cents.
+const DRIFT_RESOLUTION_CENTS: f64 = 0.1;
+
+/// A change of the drift, see [`steps`].
+#[derive(Clone)]
+pub struct DriftStep {
+    pub time: f64,
+    /// Frequency of A4 in Hz.
+    pub reference: f64,
+    /// Cents of the reference above [`A4_FREQUENCY`].
+    pub cents: f64,
+}
+
+/// Returns the frequency of A4 at `time` on `curve` (breakpoints of seconds & Hz, in order of time), interpolated
+/// linearly and held before the first & after the last breakpoint. [`A4_FREQUENCY`] if there are none.
+pub fn reference_at(curve: &[(f64, f64)], time: f64) -> f64 {
+    let idx = curve.partition_point(|(t, _)| *t <= time);
+    match (
+        idx.checked_sub(1).map(|i| curve[i]),
+        curve.get(idx).copied(),
+    ) {
+        (None, None) => A4_FREQUENCY,
+        (None, Some((_, hz))) | (Some((_, hz)), None) => hz,
+        (Some((t0, hz0)), Some((t1, hz1))) => hz0 + (hz1 - hz0) * (time - t0) / (t1 - t0),
+    }
+}
+
+/// Returns the cents of the reference above [`A4_FREQUENCY`] at `time` on `curve`, see [`reference_at`].
+pub fn cents_at(curve: &[(f64, f64)], time: f64) -> f64 {
+    1200.0 * (reference_at(curve, time) / A4_FREQUENCY).log2()
+}
+
+/// Returns the changes of the drift of `curve` after its start (see [`cents_at`] for the drift before), in order of
+/// time. Ramps are sampled every [`DRIFT_INTERVAL`] seconds, and a step is only made once the drift changed by
+/// [`DRIFT_RESOLUTION_CENTS`] or at a breakpoint.
+pub fn steps(curve: &[(f64, f64)]) -> Vec<DriftStep> {
+    assert!(
+        curve.windows(2).all(|w| w[0].0 < w[1].0),
+        "Reference drift breakpoints must be in order of time"
+    );
+    assert!(
+        curve.iter().all(|(_, hz)| *hz > 0.0),
+        "Reference drift frequencies must be positive"
+    );
+    let mut steps = vec![];
+    let mut prev_cents = curve
+        .first()
+        .map_or(0.0, |(time, _)| cents_at(curve, *time));
+    for w in curve.windows(2) {
+        let (from, to) = (w[0].0, w[1].0);
+        let samples = ((to - from) / DRIFT_INTERVAL).ceil() as usize;
+        for i in 1..=samples {
+            let time = (from + i as f64 * DRIFT_INTERVAL).min(to);
+            let cents = cents_at(curve, time);
+            if (cents - prev_cents).abs() >= DRIFT_RESOLUTION_CENTS
+                || (time == to && cents != prev_cents)
+            {
+                steps.push(DriftStep {
+                    time,
+                    reference: reference_at(curve, time),
+                    cents,
+                });
+                prev_cents = cents;
+            }
+        }
+    }
+    steps
+}
+
+/// Returns `tuning` raised by `cents` of drift, tempered unless there is no drift.
+pub fn drifted(tuning: &[PitchSpec; 12], cents: f64) -> [PitchSpec; 12] {
+    if cents == 0.0 {
+        return *tuning;
+    }
+    tuning.map(|pitch| match pitch.cents() {
+        Some(pitch) => PitchSpec::Cents(pitch + cents),
+        None => PitchSpec::Keep,
+    })
+}
+
+/// Returns the pitch bends of the semitones tuned by `tuning` raised by `cents` of drift, for a pitch bend range of
+/// +/- `pb_range` semitones.
+pub fn pitch_bends(tuning: &[PitchSpec; 12], cents: f64, pb_range: u16) -> Vec<Vec<u8>> {
+    tuning
+        .iter()
+        .enumerate()
+        .filter_map(|(i, pitch)| {
+            Some(pitch_bend_message(
+                i,
+                cents_pitch_bend(i, pitch.cents()? + cents, pb_range),
+            ))
+        })
+        .collect()
+}
+
+/// Reports the reference in effect at each tuning change of `snapshots` on `curve`.
+pub fn drift_report(score: &Score, snapshots: &[TuningSnapshot], curve: &[(f64, f64)]) -> String {
+    let mut report = String::new();
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        writeln!(
+            report,
+            "{i:>4}  {:>8.3}s  {:>9}  A4 = {:.3} Hz ({:+.2}c)",
+            snapshot.time,
+            score.position(snapshot.time),
+            reference_at(curve, snapshot.time),
+            cents_at(curve, snapshot.time)
+        )
+        .unwrap();
+    }
+    report
+}
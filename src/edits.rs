@@ -0,0 +1,88 @@
+//! Live edits of the tuning from websocket clients, so that a collaborator with the lattice UI can try out ratios during
+//! rehearsal. Clients send `set:<pitch class>:<pitch>`, e.g. `set:E:5/4` or `set:Eb:603.9c`, which tunes the pitch
+//! class as a line of the tuning in effect would (relative to its `root` & with its `offset`, see
+//! [`crate::tuning_file`]).
+//!
+//! Playback applies an edit as soon as no note of the pitch class is sounding (held by its key or the pedals), so that
+//! sounding notes aren't bent, and keeps it until a later tuning retunes the pitch class. With
+//! [`crate::SAVE_TUNING_EDITS`], the edits applied are written to the tuning file when playback stops, as lines of the
+//! tunings they were applied in.
+
+use std::sync::mpsc;
+use std::thread;
+
+use crate::score::Note;
+use crate::server;
+use crate::tuner::{parse_pitch_class, pitch_class, PitchSpec};
+use crate::tuning_file;
+
+/// Tunes a pitch class, see the [module docs](self).
+pub struct SetTuning {
+    /// The pitch class as spelt by the client, e.g. `Eb`.
+    pub name: String,
+    pub semitone: usize,
+    /// Tuning relative to the root of the tuning in effect, as given by the client, e.g. `5/4`.
+    pub value: String,
+    pub pitch: PitchSpec,
+}
+
+/// The edits of a performance: the ones waiting for their pitch class to stop sounding, and the ones applied.
+#[derive(Default)]
+pub struct Edits {
+    /// Edits waiting for their pitch class to stop sounding, in the order they were sent.
+    pub pending: Vec<SetTuning>,
+    /// Edits applied, with the index of the tuning in the tuning file they were applied in.
+    pub applied: Vec<(usize, SetTuning)>,
+}
+
+impl Edits {
+    /// Queues `edits`, and returns the queued ones whose pitch class has no note of `notes` sounding at `time`.
+    pub fn ready(
+        &mut self,
+        edits: impl IntoIterator<Item = SetTuning>,
+        notes: &[Note],
+        time: f64,
+    ) -> Vec<SetTuning> {
+        self.pending.extend(edits);
+        let (ready, waiting) = self.pending.drain(..).partition(|edit| {
+            !notes.iter().any(|note| {
+                pitch_class(note.key) == edit.semitone && (note.start..note.release).contains(&time)
+            })
+        });
+        self.pending = waiting;
+        ready
+    }
+}
+
+/// Returns a receiver of the edits that clients send from now on. Invalid ones are warned about and dropped.
+pub fn listen() -> mpsc::Receiver<SetTuning> {
+    let messages = server::control_messages();
+    let (sender, edits) = mpsc::channel();
+    thread::spawn(move || {
+        for message in messages {
+            let Some(args) = message.trim().strip_prefix("set:") else {
+                continue;
+            };
+            let Some(edit) = parse(args) else {
+                println!("WARN: Invalid tuning edit {args}, expected `set:<pitch class>:<ratio>|<cents>c`");
+                continue;
+            };
+            if sender.send(edit).is_err() {
+                break;
+            }
+        }
+    });
+    edits
+}
+
+/// Parses `<pitch class>:<pitch>`.
+fn parse(args: &str) -> Option<SetTuning> {
+    let (name, value) = args.split_once(':')?;
+    let (name, value) = (name.trim(), value.trim());
+    Some(SetTuning {
+        name: name.to_string(),
+        semitone: parse_pitch_class(name)?,
+        value: value.to_string(),
+        pitch: tuning_file::parse_pitch(value)?,
+    })
+}
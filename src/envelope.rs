@@ -0,0 +1,110 @@
+//! Expressive pitch envelopes (e.g. a scoop into a note, or vibrato) attached to notes in the tuning file, on top of
+//! the static tuning.
+//!
+//! Envelopes are rendered as extra pitch bends on the channel of the note's pitch class, so they bend all notes of that
+//! pitch class sounding at the same time. Outputs that are not tuned by pitch bends ignore them.
+
+use crate::analysis::ONSET_TOLERANCE;
+use crate::score::Score;
+use crate::tuner::{pitch_class, Tuner};
+
+/// Time in seconds between the pitch bends of an envelope.
+const ENVELOPE_RESOLUTION: f64 = 0.01;
+
+/// Shape of a pitch envelope, in cents relative to the tuned pitch of the note.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EnvelopeShape {
+    /// Starts `cents` off the tuned pitch, gliding linearly to it in `duration` seconds.
+    Scoop { cents: f64, duration: f64 },
+    /// Sine vibrato of up to `cents` above & below the tuned pitch at `rate` Hz, until the note stops sounding.
+    Vibrato { cents: f64, rate: f64 },
+}
+
+impl EnvelopeShape {
+    /// Returns the pitch offset in cents at `t` seconds after the start of the note.
+    pub fn cents_at(&self, t: f64) -> f64 {
+        match *self {
+            EnvelopeShape::Scoop { cents, duration } => cents * (1.0 - t / duration).max(0.0),
+            EnvelopeShape::Vibrato { cents, rate } => {
+                cents * (std::f64::consts::TAU * rate * t).sin()
+            }
+        }
+    }
+}
+
+/// A pitch envelope applied to the notes of a pitch class (or a single key) that start while a tuning is in effect.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PitchEnvelope {
+    /// Pitch class (0 is A, 1 is Bb, etc...)
+    pub semitone: usize,
+    /// MIDI note number of the only note to apply to, or [`None`] to apply to all octaves.
+    pub key: Option<u8>,
+    pub shape: EnvelopeShape,
+}
+
+/// A pitch bend of an envelope, relative to the tuned pitch of a channel.
+#[derive(Clone)]
+pub struct EnvelopeBend {
+    pub time: f64,
+    /// Pitch class (0 is A, 1 is Bb, etc...), which is also the channel.
+    pub semitone: usize,
+    /// Offset from the tuning of the pitch class in cents, 0 at the end of an envelope.
+    pub cents: f64,
+}
+
+/// Returns the pitch bends of all envelopes of the notes of `score`, in order of time.
+///
+/// Notes get the envelopes of the tuning in effect at their start (allowing for [`ONSET_TOLERANCE`]). Envelopes of
+/// notes of the same pitch class that overlap are added up.
+pub fn bends(score: &Score, tuner: &Tuner) -> Vec<EnvelopeBend> {
+    // (start, end, shape) of the envelopes of each pitch class.
+    let mut spans: [Vec<(f64, f64, EnvelopeShape)>; 12] = Default::default();
+    for note in &score.notes {
+        let idx = (0..tuner.len())
+            .take_while(|&i| tuner[i].time <= note.start + ONSET_TOLERANCE)
+            .last();
+        let Some(idx) = idx else {
+            continue;
+        };
+        let semitone = pitch_class(note.key);
+        for envelope in &tuner[idx].envelopes {
+            if envelope.semitone != semitone || envelope.key.is_some_and(|k| k != note.key) {
+                continue;
+            }
+            let end = match envelope.shape {
+                EnvelopeShape::Scoop { duration, .. } => note.start + duration,
+                EnvelopeShape::Vibrato { .. } => note.release,
+            };
+            spans[semitone].push((note.start, end, envelope.shape));
+        }
+    }
+
+    let mut bends = vec![];
+    for (semitone, spans) in spans.iter().enumerate() {
+        let mut times: Vec<f64> = spans
+            .iter()
+            .flat_map(|&(start, end, _)| {
+                let steps = ((end - start) / ENVELOPE_RESOLUTION).ceil() as usize;
+                (0..steps)
+                    .map(move |i| start + i as f64 * ENVELOPE_RESOLUTION)
+                    .chain([end])
+            })
+            .collect();
+        times.sort_by(f64::total_cmp);
+        times.dedup();
+
+        bends.extend(times.into_iter().map(|time| {
+            EnvelopeBend {
+                time,
+                semitone,
+                cents: spans
+                    .iter()
+                    .filter(|(start, end, _)| (*start..*end).contains(&time))
+                    .map(|(start, _, shape)| shape.cents_at(time - start))
+                    .sum(),
+            }
+        }));
+    }
+    bends.sort_by(|a, b| a.time.total_cmp(&b.time));
+    bends
+}
@@ -0,0 +1,248 @@
+//! Exports of tuning data into file formats used by other software & hardware.
+
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::mts;
+use crate::notation::HejiSpelling;
+use crate::score::Score;
+use crate::tuner::{key_name, pitch_class, snapshot_at, PitchSpec, TuningSnapshot, SEMITONE_NAMES};
+
+/// Returns the file name (without extension) used for exports of the `idx`-th tuning snapshot, e.g.
+/// `tuning_012_bar034_56.789s`, so files sort in order and can be found by bar.
+pub fn snapshot_file_stem(idx: usize, snapshot: &TuningSnapshot, score: &Score) -> String {
+    let bar = score.bar_at(snapshot.time).number;
+    format!("tuning_{idx:03}_bar{bar:03}_{:.3}s", snapshot.time)
+}
+
+/// Returns the frequency (in Hz) of a MIDI note in the given tuning, where A4 (1/1) is `a4_hz`.
+pub fn key_frequency(snapshot: &TuningSnapshot, key: u8, a4_hz: f64) -> f64 {
+    a4_hz * 2f64.powf(snapshot.key_cents(key) / 1200.0)
+}
+
+/// Writes the frequencies of all 128 MIDI keys as semicolon separated values.
+pub fn write_frequency_csv(snapshot: &TuningSnapshot, a4_hz: f64, path: &str) {
+    let mut csv = String::from("key;name;ratio;cents from A4;cents from 12edo;Hz\n");
+    for key in 0..=127u8 {
+        let cents = snapshot.key_cents(key);
+        writeln!(
+            csv,
+            "{key};{};{};{cents:.3};{:.3};{:.4}",
+            key_name(key),
+            snapshot.tuning[pitch_class(key)],
+            cents - 100.0 * (key as f64 - 69.0),
+            key_frequency(snapshot, key, a4_hz),
+        )
+        .unwrap();
+    }
+    fs::write(path, csv).unwrap_or_else(|e| panic!("Failed to write {path}: {e}"));
+}
+
+/// Writes a Scala keyboard mapping (.kbm) file for the tuning snapshot.
+///
+/// The mapping is linear with 12 degrees per octave, with degree 0 on A. The scale it is meant to be paired with has
+/// A as 1/1 (i.e. all ratios divided by the tuning of A), so the reference frequency given here is that of the
+/// tuned A4.
+///
+/// See <https://www.huygens-fokker.org/scala/help.htm#mappings>
+pub fn write_kbm(snapshot: &TuningSnapshot, a4_hz: f64, path: &str) {
+    let mut kbm = String::new();
+    writeln!(kbm, "! {}", path.rsplit('/').next().unwrap()).unwrap();
+    writeln!(kbm, "! Tuning @ {:.3}s", snapshot.time).unwrap();
+    writeln!(kbm, "!").unwrap();
+    writeln!(kbm, "! Frequencies of A4 and the 11 semitones above:").unwrap();
+    for key in 69..81u8 {
+        writeln!(
+            kbm,
+            "! {:<4} {:<12} {:.4} Hz",
+            key_name(key),
+            snapshot.tuning[pitch_class(key)].to_string(),
+            key_frequency(snapshot, key, a4_hz)
+        )
+        .unwrap();
+    }
+    writeln!(kbm, "!").unwrap();
+    writeln!(kbm, "! Size of map:").unwrap();
+    writeln!(kbm, "12").unwrap();
+    writeln!(kbm, "! First MIDI note number to retune:").unwrap();
+    writeln!(kbm, "0").unwrap();
+    writeln!(kbm, "! Last MIDI note number to retune:").unwrap();
+    writeln!(kbm, "127").unwrap();
+    writeln!(
+        kbm,
+        "! Middle note where the first entry of the mapping is mapped to:"
+    )
+    .unwrap();
+    writeln!(kbm, "69").unwrap();
+    writeln!(kbm, "! Reference note for which frequency is given:").unwrap();
+    writeln!(kbm, "69").unwrap();
+    writeln!(kbm, "! Frequency to tune the above note to:").unwrap();
+    writeln!(kbm, "{:.6}", key_frequency(snapshot, 69, a4_hz)).unwrap();
+    writeln!(kbm, "! Scale degree to consider as formal octave:").unwrap();
+    writeln!(kbm, "12").unwrap();
+    writeln!(kbm, "! Mapping:").unwrap();
+    for degree in 0..12 {
+        writeln!(kbm, "{degree}").unwrap();
+    }
+    fs::write(path, kbm).unwrap_or_else(|e| panic!("Failed to write {path}: {e}"));
+}
+
+/// Writes a Scala scale (.scl) file for the tuning snapshot, with A as 1/1, to be paired with the .kbm file written by
+/// [`write_kbm`].
+///
+/// See <https://www.huygens-fokker.org/scala/scl_format.html>
+pub fn write_scl(snapshot: &TuningSnapshot, path: &str) {
+    let mut scl = String::new();
+    writeln!(scl, "! {}", path.rsplit('/').next().unwrap()).unwrap();
+    writeln!(scl, "!").unwrap();
+    writeln!(scl, "Tuning @ {:.3}s, A = 1/1", snapshot.time).unwrap();
+    writeln!(scl, " 12").unwrap();
+    writeln!(scl, "!").unwrap();
+    for (tuning, name) in snapshot.tuning.iter().zip(SEMITONE_NAMES).skip(1) {
+        match (tuning, snapshot.tuning[0]) {
+            (PitchSpec::Ratio(ratio), PitchSpec::Ratio(a)) => {
+                writeln!(scl, " {} ! {name}", *ratio / a).unwrap()
+            }
+            // Scala reads pitches with a period as cents.
+            _ => writeln!(
+                scl,
+                " {:.6} ! {name}",
+                tuning.cents().unwrap() - snapshot.tuning[0].cents().unwrap()
+            )
+            .unwrap(),
+        }
+    }
+    writeln!(scl, " 2/1").unwrap();
+    fs::write(path, scl).unwrap_or_else(|e| panic!("Failed to write {path}: {e}"));
+}
+
+/// Writes a MIDI Tuning Standard bulk tuning dump (.syx) of all 128 keys, stored to tuning program `program`.
+pub fn write_mts_bulk_dump(snapshot: &TuningSnapshot, a4_hz: f64, program: u8, path: &str) {
+    let name = format!("JI @ {:.3}s", snapshot.time);
+    let syx = mts::bulk_dump(snapshot, a4_hz, program, &name);
+    fs::write(path, syx).unwrap_or_else(|e| panic!("Failed to write {path}: {e}"));
+}
+
+/// Writes a LilyPond include file defining `hejiAnnotations`: a voice of spacer rests carrying a markup for every
+/// note onset, listing the HEJI spelling and cent deviation (from 12edo of the spelt note) of each note of the onset,
+/// top to bottom.
+///
+/// The spacers follow the bars & time signatures of the MIDI file, so the annotations line up with an engraving that
+/// uses the same bars, e.g.
+///
+/// ```lilypond
+/// \include "heji.ily"
+/// << \new Staff { ... } \new Dynamics \hejiAnnotations >>
+/// ```
+pub fn write_lilypond_heji(score: &Score, snapshots: &[TuningSnapshot], path: &str) {
+    let whole_note = score.ppqn * 4;
+
+    // Markup of each onset tick, in order.
+    let mut onsets: Vec<(u64, String)> = vec![];
+    for (i, note) in score.notes.iter().enumerate() {
+        if i > 0 && score.notes[i - 1].start_tick == note.start_tick {
+            continue;
+        }
+        let mut keys: Vec<u8> = score.notes[i..]
+            .iter()
+            .take_while(|n| n.start_tick == note.start_tick)
+            .map(|n| n.key)
+            .collect();
+        keys.sort_unstable_by(|a, b| b.cmp(a));
+        keys.dedup();
+
+        let Some(snapshot) = snapshot_at(snapshots, note.start) else {
+            continue;
+        };
+        let lines: Vec<String> = keys
+            .iter()
+            .map(|key| {
+                let pc = pitch_class(*key);
+                let cents = snapshot.tuning[pc].cents().unwrap();
+                match &snapshot.monzos[pc] {
+                    Some(monzo) => {
+                        let spelling = HejiSpelling::new(monzo);
+                        let deviation = spelling.deviation(cents);
+                        format!("\"{spelling} {deviation:+.0}\"")
+                    }
+                    // Tempered notes are spelt as in 12edo.
+                    None => {
+                        let deviation = cents - 100.0 * pc as f64;
+                        format!("\"{} {deviation:+.0}\"", SEMITONE_NAMES[pc])
+                    }
+                }
+            })
+            .collect();
+        onsets.push((
+            note.start_tick,
+            format!("^\\markup \\tiny \\column {{ {} }}", lines.join(" ")),
+        ));
+    }
+
+    let mut ly = String::new();
+    writeln!(
+        ly,
+        "%% HEJI spellings & cent deviations from 12edo of every note, relative to A = 1/1."
+    )
+    .unwrap();
+    writeln!(
+        ly,
+        "%% Generated by JI Performer. Bars follow the time signatures of the MIDI file."
+    )
+    .unwrap();
+    writeln!(ly).unwrap();
+    writeln!(ly, "\\version \"2.24.0\"").unwrap();
+    writeln!(ly).unwrap();
+    writeln!(ly, "hejiAnnotations = {{").unwrap();
+
+    let mut onset_idx = 0;
+    let mut time_sig = (0, 0);
+    for bar in &score.bars {
+        if (bar.beats, bar.beat_unit) != time_sig {
+            time_sig = (bar.beats, bar.beat_unit);
+            writeln!(ly, "  \\time {}/{}", bar.beats, bar.beat_unit).unwrap();
+        }
+        let bar_end = bar.start_tick + whole_note * bar.beats as u64 / bar.beat_unit as u64;
+        write!(ly, "  % Bar {}\n ", bar.number).unwrap();
+
+        // Split the bar into spacers at each onset.
+        let mut tick = bar.start_tick;
+        while tick < bar_end {
+            let markup = match onsets.get(onset_idx) {
+                Some((onset_tick, markup)) if *onset_tick == tick => {
+                    onset_idx += 1;
+                    markup.as_str()
+                }
+                _ => "",
+            };
+            let next = onsets
+                .get(onset_idx)
+                .map_or(bar_end, |(t, _)| *t)
+                .min(bar_end);
+            write!(ly, " {}{markup}", spacer(next - tick, whole_note)).unwrap();
+            tick = next;
+        }
+        writeln!(ly, " |").unwrap();
+    }
+    writeln!(ly, "}}").unwrap();
+
+    fs::write(path, ly).unwrap_or_else(|e| panic!("Failed to write {path}: {e}"));
+}
+
+/// Returns a LilyPond spacer rest lasting `ticks`, e.g. `s1*3/16`.
+fn spacer(ticks: u64, whole_note: u64) -> String {
+    let gcd = gcd(ticks, whole_note);
+    match (ticks / gcd, whole_note / gcd) {
+        (1, 1) => "s1".to_string(),
+        (num, 1) => format!("s1*{num}"),
+        (num, den) => format!("s1*{num}/{den}"),
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
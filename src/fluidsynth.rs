@@ -0,0 +1,97 @@
+//! Output to [FluidSynth](https://www.fluidsynth.org/) through its shell server, so General MIDI SoundFonts can be
+//! played with the per-channel pitch bend scheme without any virtual MIDI ports, e.g.
+//!
+//! ```sh
+//! fluidsynth -s -i FluidR3_GM.sf2
+//! ```
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use midly::live::LiveEvent;
+use midly::MidiMessage;
+
+use crate::output::MidiSink;
+
+pub struct FluidSynth {
+    stream: TcpStream,
+}
+
+impl FluidSynth {
+    /// Connects to the FluidSynth shell server at `addr`, optionally loads `soundfont`, and sets up all 16 channels to
+    /// play General MIDI `program` with a pitch bend range of +/- `pb_range` semitones.
+    pub fn connect(addr: &str, soundfont: Option<&str>, program: u8, pb_range: u16) -> Self {
+        let stream = TcpStream::connect(addr).unwrap_or_else(|e| {
+            panic!("Failed to connect to FluidSynth at {addr} ({e}). Start it with `fluidsynth -s -i <soundfont.sf2>`")
+        });
+        stream.set_nodelay(true).unwrap();
+        let mut fluidsynth = FluidSynth { stream };
+
+        // The SoundFont given on FluidSynth's command line has ID 1.
+        let sfont_id = soundfont.map_or(1, |path| fluidsynth.load(path));
+
+        for channel in 0..16 {
+            // In General MIDI, channel 10 is for drums, but here every channel plays a pitch class. Selecting the
+            // preset directly (rather than by program change) makes it melodic.
+            fluidsynth.command(&format!("select {channel} {sfont_id} 0 {program}"));
+            // RPN 0: pitch bend sensitivity
+            fluidsynth.command(&format!("cc {channel} 101 0"));
+            fluidsynth.command(&format!("cc {channel} 100 0"));
+            fluidsynth.command(&format!("cc {channel} 6 {pb_range}"));
+            fluidsynth.command(&format!("cc {channel} 38 0"));
+        }
+        println!("Connected to FluidSynth at {addr}");
+
+        fluidsynth
+    }
+
+    /// Loads a SoundFont and returns its ID.
+    fn load(&mut self, path: &str) -> u32 {
+        self.command(&format!("load \"{path}\""));
+
+        // FluidSynth replies with "loaded SoundFont has ID <id>".
+        self.stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let mut reader = BufReader::new(self.stream.try_clone().unwrap());
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                panic!("FluidSynth failed to load SoundFont {path}");
+            }
+            if let Some(id) = line.split("has ID").nth(1) {
+                return id
+                    .trim()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Unexpected reply from FluidSynth: {line}"));
+            }
+            if line.contains("fail") || line.contains("error") {
+                panic!("FluidSynth failed to load SoundFont {path}: {line}");
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str) {
+        writeln!(self.stream, "{command}").unwrap();
+    }
+}
+
+impl MidiSink for FluidSynth {
+    fn send(&mut self, message: &[u8]) {
+        let Ok(LiveEvent::Midi { channel, message }) = LiveEvent::parse(message) else {
+            return;
+        };
+        let command = match message {
+            MidiMessage::NoteOn { key, vel } => format!("noteon {channel} {key} {vel}"),
+            MidiMessage::NoteOff { key, .. } => format!("noteoff {channel} {key}"),
+            MidiMessage::PitchBend { bend } => format!("pitch_bend {channel} {}", bend.0),
+            MidiMessage::Controller { controller, value } => {
+                format!("cc {channel} {controller} {value}")
+            }
+            _ => return,
+        };
+        self.command(&command);
+    }
+}
@@ -0,0 +1,308 @@
+//! Live following of a performance of the MIDI file, so that playback (and with it the retunes) follows the rubato of a
+//! human performer instead of a fixed clock, e.g. to retune a layered electronic part along with an acoustic piano.
+//!
+//! Onsets detected in the input (see [`LiveInput`]) are matched against the onsets of the score near the expected
+//! position by the pitch classes played, and by how well the rhythm of the last few seconds of detected onsets fits the
+//! score at the tempo the match implies, so that missed, extra & repeated chords don't derail the follower. Each match
+//! moves the playback transport to the matched onset, at the tempo of the match.
+//!
+//! The notes of a live MIDI performance are followed the same way by the `live` command, which retunes the performance
+//! itself rather than playback (see `live` in `main.rs`).
+
+use std::collections::VecDeque;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use crate::analysis::ONSET_TOLERANCE;
+use crate::score::Score;
+use crate::sync::Transport;
+use crate::tuner::pitch_class;
+
+/// Score onsets up to this many seconds before or after the position expected from the last match (at the estimated
+/// tempo) are matched against, so that the follower can recover from missed onsets or a wrong match.
+const SEARCH_WINDOW: f64 = 1.0;
+
+/// Seconds of recently detected onsets that a match has to fit the rhythm of, at the tempo it implies.
+const FIT_WINDOW: f64 = 6.0;
+
+/// A detected onset fits the rhythm of a match if it is within this many seconds of a score onset at its tempo.
+const FIT_TOLERANCE: f64 = 0.06;
+
+/// Tempos up to this fraction faster or slower than the estimated tempo are tried for each match.
+const TEMPO_RANGE: f64 = 0.1;
+
+/// Step between the tempos tried, as a fraction of the estimated tempo.
+const TEMPO_STEP: f64 = 0.01;
+
+/// Limits of the estimated tempo relative to the playback speed.
+const TEMPO_LIMITS: (f64, f64) = (0.5, 2.0);
+
+/// Subtracted from the fit of a match per second between the score onset & the expected position, so that the right
+/// one of repeated chords is matched.
+const TIMING_PENALTY: f64 = 0.5;
+
+/// Subtracted from the fit of a match per fraction of tempo change it implies, so that the tempo only changes when the
+/// rhythm of the performance calls for it.
+const TEMPO_PENALTY: f64 = 1.0;
+
+/// Minimum similarity (0 to 1, see [`ScoreFollower::new`]) of the pitch classes of a detected onset & a score onset
+/// for them to match.
+const MIN_SIMILARITY: f64 = 0.6;
+
+/// A warning is printed after this many detected onsets in a row that don't match the score.
+const LOST_ONSETS: usize = 8;
+
+/// Input of the performance to follow, see [`crate::LIVE_FOLLOW`].
+#[allow(dead_code)]
+pub enum LiveInput {
+    /// Playback isn't following a performance.
+    Off,
+    /// Pitch tracking of the audio input device whose name contains this, or the default input device if [`None`].
+    /// Requires the `live-audio` feature.
+    Audio(Option<&'static str>),
+    /// Pitch tracking of a recorded take (WAV file), read in real time as if it were being played, to rehearse
+    /// following without a performer. Requires the `live-audio` feature.
+    Recording(&'static str),
+}
+
+/// An onset detected in the input.
+#[derive(Clone, Copy)]
+pub struct Onset {
+    /// When the onset was played.
+    pub instant: Instant,
+    /// Weights of the pitch classes played (0 is A).
+    pub chroma: [f64; 12],
+}
+
+impl Onset {
+    /// Onset of a single MIDI note.
+    pub fn note(instant: Instant, key: u8) -> Self {
+        let mut chroma = [0.0; 12];
+        chroma[pitch_class(key)] = 1.0;
+        Onset { instant, chroma }
+    }
+}
+
+/// Starts detecting onsets in `input` on a new thread. Returns [`None`] for [`LiveInput::Off`].
+pub fn listen(input: &LiveInput) -> Option<mpsc::Receiver<Onset>> {
+    match input {
+        LiveInput::Off => None,
+        #[cfg(feature = "live-audio")]
+        LiveInput::Audio(device) => Some(crate::audio_input::listen(*device)),
+        #[cfg(feature = "live-audio")]
+        LiveInput::Recording(path) => Some(crate::audio_input::listen_to_recording(path)),
+        #[cfg(not(feature = "live-audio"))]
+        LiveInput::Audio(_) | LiveInput::Recording(_) => {
+            panic!("Following audio input requires the `live-audio` feature")
+        }
+    }
+}
+
+/// Waits for the performer to play the first onset of `score` from `from` seconds on, then keeps the returned transport
+/// following the `onsets` of the performance on a new thread, starting at `speed`.
+pub fn follow(onsets: mpsc::Receiver<Onset>, score: &Score, from: f64, speed: f64) -> Transport {
+    let mut follower = ScoreFollower::new(score, from, speed, cosine_similarity);
+    println!("Waiting for the performer to start from {from}s...");
+
+    let (instant, time) = loop {
+        let onset = onsets.recv().expect("Live input stopped");
+        if let Some(time) = follower.matched(&onset) {
+            break (onset.instant, time);
+        }
+    };
+    let transport = Transport::start(time, speed);
+    transport.set_at(instant, time, speed);
+    println!("Following the performer from {time:.3}s");
+
+    let follower_transport = transport.clone();
+    thread::spawn(move || {
+        for onset in onsets {
+            if let Some(time) = follower.matched(&onset) {
+                follower_transport.set_at(onset.instant, time, follower.speed);
+            }
+        }
+        println!("Live input stopped");
+    });
+    transport
+}
+
+/// Matches detected onsets to the onsets of a score, in order.
+pub struct ScoreFollower {
+    /// Time of each onset of the score, and the weights of the pitch classes starting together at it.
+    onsets: Vec<(f64, [f64; 12])>,
+    /// Index of the first score onset followed.
+    first: usize,
+    /// When the last match was played, and the time of the score onset it matched.
+    last_match: Option<(Instant, f64)>,
+    /// Onsets detected in the last [`FIT_WINDOW`] seconds since the first match.
+    recent: VecDeque<Onset>,
+    /// Estimated speed of the performance relative to the score.
+    speed: f64,
+    /// Playback speed that the estimated speed starts at, and is limited relative to (see [`TEMPO_LIMITS`]).
+    base_speed: f64,
+    /// Number of detected onsets in a row that didn't match.
+    unmatched: usize,
+    /// Similarity of the pitch classes of a detected onset & a score onset.
+    similarity: fn(&[f64; 12], &[f64; 12]) -> f64,
+}
+
+impl ScoreFollower {
+    /// Follows `score` from the onset at or after `from` seconds, expecting it to be played at `speed`. `similarity`
+    /// returns how similar (0 to 1) the pitch classes of a detected onset are to those of a score onset, e.g.
+    /// [`cosine_similarity`] for pitch tracked chords or [`played_in`] for single MIDI notes.
+    pub fn new(
+        score: &Score,
+        from: f64,
+        speed: f64,
+        similarity: fn(&[f64; 12], &[f64; 12]) -> f64,
+    ) -> Self {
+        let mut onsets: Vec<(f64, [f64; 12])> = vec![];
+        for note in &score.notes {
+            match onsets.last_mut() {
+                Some((time, chroma)) if note.start - *time <= ONSET_TOLERANCE => {
+                    chroma[pitch_class(note.key)] += 1.0;
+                }
+                _ => {
+                    let mut chroma = [0.0; 12];
+                    chroma[pitch_class(note.key)] = 1.0;
+                    onsets.push((note.start, chroma));
+                }
+            }
+        }
+        ScoreFollower {
+            first: onsets.partition_point(|(time, _)| *time < from - ONSET_TOLERANCE),
+            onsets,
+            last_match: None,
+            recent: VecDeque::new(),
+            speed,
+            base_speed: speed,
+            unmatched: 0,
+            similarity,
+        }
+    }
+
+    /// Matches `onset` to the score, returning the time of the score onset it matched, if any.
+    ///
+    /// Each score onset near the expected position is tried at a range of tempos, scored by how well the recently
+    /// detected onsets fit the score at that tempo if `onset` is that score onset. The tempo of the best match becomes
+    /// the estimated tempo.
+    pub fn matched(&mut self, onset: &Onset) -> Option<f64> {
+        // Position of the performer expected from the last match, or the start position until the first match.
+        let expected = match self.last_match {
+            Some((instant, time)) => {
+                time + onset.instant.duration_since(instant).as_secs_f64() * self.speed
+            }
+            None => self.onsets.get(self.first)?.0,
+        };
+        if self.last_match.is_some() {
+            self.recent.push_back(*onset);
+        }
+        while self.recent.front().is_some_and(|recent| {
+            onset.instant.duration_since(recent.instant).as_secs_f64() > FIT_WINDOW
+        }) {
+            self.recent.pop_front();
+        }
+
+        let start = self
+            .onsets
+            .partition_point(|(time, _)| *time < expected - SEARCH_WINDOW);
+        let end = self
+            .onsets
+            .partition_point(|(time, _)| *time <= expected + SEARCH_WINDOW);
+        let steps = (TEMPO_RANGE / TEMPO_STEP) as i32;
+        let mut best: Option<(f64, usize, f64)> = None;
+        for idx in start.max(self.first)..end {
+            let (time, chroma) = &self.onsets[idx];
+            let onset_similarity = (self.similarity)(&onset.chroma, chroma);
+            if onset_similarity < MIN_SIMILARITY {
+                continue;
+            }
+            for step in -steps..=steps {
+                let speed = (self.speed * (1.0 + step as f64 * TEMPO_STEP)).clamp(
+                    TEMPO_LIMITS.0 * self.base_speed,
+                    TEMPO_LIMITS.1 * self.base_speed,
+                );
+                let fit = self
+                    .fit(onset.instant, *time, speed)
+                    .unwrap_or(onset_similarity)
+                    - (time - expected).abs() * TIMING_PENALTY
+                    - (speed / self.speed - 1.0).abs() * TEMPO_PENALTY;
+                if best.is_none_or(|(best_fit, _, _)| fit > best_fit) {
+                    best = Some((fit, idx, speed));
+                }
+            }
+        }
+
+        let Some((_, idx, speed)) = best else {
+            self.unmatched += 1;
+            if self.unmatched == LOST_ONSETS {
+                println!(
+                    "WARN: Lost the performer after {:.3}s",
+                    self.last_match.map_or(0.0, |m| m.1)
+                );
+            }
+            return None;
+        };
+        self.unmatched = 0;
+        let time = self.onsets[idx].0;
+        if self.last_match.is_none() {
+            self.recent.push_back(*onset);
+        }
+        self.last_match = Some((onset.instant, time));
+        self.speed = speed;
+        Some(time)
+    }
+
+    /// Returns how well the recently detected onsets fit the score (their mean similarity to the score onsets they
+    /// fall on, 0 to 1) if the score is at `time` at `instant` & playing at `speed`. [`None`] if there are none.
+    fn fit(&self, instant: Instant, time: f64, speed: f64) -> Option<f64> {
+        if self.recent.is_empty() {
+            return None;
+        }
+        let total: f64 = self
+            .recent
+            .iter()
+            .map(|onset| {
+                let at = time - instant.duration_since(onset.instant).as_secs_f64() * speed;
+                let start = self
+                    .onsets
+                    .partition_point(|(t, _)| *t < at - FIT_TOLERANCE);
+                self.onsets[start..]
+                    .iter()
+                    .take_while(|(t, _)| *t <= at + FIT_TOLERANCE)
+                    .map(|(_, chroma)| (self.similarity)(&onset.chroma, chroma))
+                    .fold(0.0, f64::max)
+            })
+            .sum();
+        Some(total / self.recent.len() as f64)
+    }
+}
+
+/// Cosine similarity of two sets of pitch class weights.
+pub fn cosine_similarity(a: &[f64; 12], b: &[f64; 12]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm = |x: &[f64; 12]| x.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if dot == 0.0 {
+        0.0
+    } else {
+        dot / (norm(a) * norm(b))
+    }
+}
+
+/// Fraction of the weight of the `played` pitch classes that is on pitch classes of `chord`, e.g. 1 for a note of
+/// the chord.
+pub fn played_in(played: &[f64; 12], chord: &[f64; 12]) -> f64 {
+    let total: f64 = played.iter().sum();
+    let inside: f64 = played
+        .iter()
+        .zip(chord)
+        .filter(|(_, c)| **c > 0.0)
+        .map(|(p, _)| p)
+        .sum();
+    if total == 0.0 {
+        0.0
+    } else {
+        inside / total
+    }
+}
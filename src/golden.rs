@@ -0,0 +1,160 @@
+//! Golden-run regression checks, so that refactors of the scheduler & tuner can be verified not to change the
+//! performance: the complete byte stream sent to the output by an offline performance (see
+//! [`crate::perform_offline`]) is captured with the logical time each message is due at, and compared against a
+//! golden file captured before.
+//!
+//! Golden files have a line per message: its time in seconds (to the microsecond) and its bytes in hex, e.g.
+//! `18.448000 e3 12 45`.
+
+use std::fmt::{Display, Write as _};
+use std::fs;
+
+use crate::output::MidiSink;
+use crate::score::Score;
+use crate::tuner::key_name;
+
+/// A message sent to the output, at the time it is due.
+pub struct Message {
+    pub time: f64,
+    pub bytes: Vec<u8>,
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.6}", self.time)?;
+        for byte in &self.bytes {
+            write!(f, " {byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Message {
+    /// Parses a line of a golden file.
+    fn parse(line: &str) -> Option<Message> {
+        let mut words = line.split_whitespace();
+        Some(Message {
+            time: words.next()?.parse().ok()?,
+            bytes: words
+                .map(|byte| u8::from_str_radix(byte, 16).ok())
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Describes the message, e.g. `note on, channel 3, C#4, velocity 80`.
+    fn describe(&self) -> String {
+        let bytes = &self.bytes;
+        let data = |i: usize| bytes.get(i).copied().unwrap_or(0);
+        let channel = data(0) & 0x0F;
+        match data(0) >> 4 {
+            0x8 => format!("note off, channel {channel}, {}", key_name(data(1))),
+            0x9 => format!(
+                "note on, channel {channel}, {}, velocity {}",
+                key_name(data(1)),
+                data(2)
+            ),
+            0xA => format!(
+                "aftertouch, channel {channel}, {}, {}",
+                key_name(data(1)),
+                data(2)
+            ),
+            0xB => format!("CC {} = {}, channel {channel}", data(1), data(2)),
+            0xC => format!("program change {}, channel {channel}", data(1)),
+            0xD => format!("channel pressure {}, channel {channel}", data(1)),
+            0xE => format!(
+                "pitch bend {:+}, channel {channel}",
+                (data(1) as i32 | (data(2) as i32) << 7) - 8192
+            ),
+            _ => format!("system message of {} bytes", bytes.len()),
+        }
+    }
+}
+
+/// A sink that captures the messages sent to it, at the time set by [`Capture::advance`].
+#[derive(Default)]
+pub struct Capture {
+    time: f64,
+    pub messages: Vec<Message>,
+}
+
+impl Capture {
+    /// Sets the time of the messages sent from now on.
+    pub fn advance(&mut self, time: f64) {
+        self.time = time;
+    }
+}
+
+impl MidiSink for Capture {
+    fn send(&mut self, message: &[u8]) {
+        self.messages.push(Message {
+            time: self.time,
+            bytes: message.to_vec(),
+        });
+    }
+}
+
+/// Writes `messages` as a golden file at `path`.
+pub fn write(path: &str, messages: &[Message]) {
+    let text: String = messages
+        .iter()
+        .map(|message| format!("{message}\n"))
+        .collect();
+    fs::write(path, text).unwrap();
+}
+
+/// Loads the messages of the golden file at `path`.
+pub fn load(path: &str) -> Vec<Message> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            Message::parse(line)
+                .unwrap_or_else(|| panic!("Invalid message at {path}:{}: {line}", i + 1))
+        })
+        .collect()
+}
+
+/// Returns the index of the first message where `actual` differs from `golden` (as written in golden files), or
+/// [`None`] if they are the same.
+pub fn first_divergence(golden: &[Message], actual: &[Message]) -> Option<usize> {
+    let same = |a: &Message, b: &Message| a.to_string() == b.to_string();
+    match golden.iter().zip(actual).position(|(a, b)| !same(a, b)) {
+        Some(idx) => Some(idx),
+        None => (golden.len() != actual.len()).then_some(golden.len().min(actual.len())),
+    }
+}
+
+/// Reports the divergence of `actual` from `golden` at message `idx`, with the bar & beat of the messages in `score`
+/// and the message before, for context.
+pub fn divergence_report(
+    score: &Score,
+    golden: &[Message],
+    actual: &[Message],
+    idx: usize,
+) -> String {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "First divergence at message {} (of {} in the golden run, {} in this run):",
+        idx + 1,
+        golden.len(),
+        actual.len()
+    )
+    .unwrap();
+    let line = |message: Option<&Message>| match message {
+        Some(message) => format!(
+            "{message}  (bar {}: {})",
+            score.position(message.time),
+            message.describe()
+        ),
+        None => "(no more messages)".to_string(),
+    };
+    if let Some(before) = idx.checked_sub(1) {
+        writeln!(report, "  before:   {}", line(golden.get(before))).unwrap();
+    }
+    writeln!(report, "  golden:   {}", line(golden.get(idx))).unwrap();
+    writeln!(report, "  this run: {}", line(actual.get(idx))).unwrap();
+    report
+}
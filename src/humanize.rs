@@ -0,0 +1,117 @@
+//! Humanized timing of the note events of a quantized MIDI file: swing of the off-beat eighth notes and random
+//! micro-timing, so that playback & renders of demos sound less mechanical.
+//!
+//! Only note events are moved, never tuning changes. A note is left in place if moving it would move it across a
+//! tuning change, so that it is still played in the tuning it was written in.
+
+use std::collections::HashMap;
+
+use midly::num::u28;
+use midly::{MidiMessage, TrackEvent, TrackEventKind};
+
+use crate::score::Score;
+
+/// How to humanize the timing of notes, see [`humanize`].
+#[derive(Clone, Copy)]
+pub struct Humanize {
+    /// Largest random shift of a note in ms, earlier or later. Each note is shifted by a uniformly distributed amount.
+    pub jitter_ms: f64,
+    /// Delay of notes on the off-beat eighths as a fraction of an eighth note, e.g. 1/3 for triplet swing. 0 is
+    /// straight.
+    pub swing: f64,
+    /// Seed of the random shifts, so that the same seed plays the same timing every time.
+    pub seed: u64,
+}
+
+/// SplitMix64 pseudorandom number generator, which is plenty for timing jitter.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniformly distributed number in [-1, 1).
+    fn next_signed(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+}
+
+/// Returns the events of `track` (the only track of the file of `score`) with note ons moved as configured by
+/// `humanize`, and each note off moved by the same amount as its note on so that durations are kept.
+///
+/// Notes are not moved across any of `tuning_times` (see the [module docs](self)), nor before the release of the
+/// previous note of the same key, which would cut them short.
+pub fn humanize<'a>(
+    track: &[TrackEvent<'a>],
+    score: &Score,
+    tuning_times: &[f64],
+    humanize: &Humanize,
+) -> Vec<TrackEvent<'a>> {
+    let mut rng = Rng(humanize.seed);
+    let ppqn = score.ppqn;
+    let crosses_tuning = |from: f64, to: f64| {
+        let (low, high) = if from < to { (from, to) } else { (to, from) };
+        tuning_times.iter().any(|t| *t > low && *t <= high)
+    };
+
+    // Shift in ticks of the sounding notes by (channel, key), and the tick of the last note off of each key.
+    let mut shifts: HashMap<(u8, u8), i64> = HashMap::new();
+    let mut released: HashMap<(u8, u8), u64> = HashMap::new();
+    // (new tick, event), in the original order.
+    let mut events: Vec<(u64, TrackEvent<'a>)> = Vec::with_capacity(track.len());
+    let mut tick = 0u64;
+
+    for event in track {
+        tick += event.delta.as_int() as u64;
+        let new_tick = match event.kind {
+            TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn { key, vel },
+            } if vel > 0 => {
+                let note = (channel.as_int(), key.as_int());
+                let secs_per_quarter = score.tick_time(tick + ppqn) - score.tick_time(tick);
+                let mut shift = humanize.jitter_ms / 1000.0 * rng.next_signed() / secs_per_quarter
+                    * ppqn as f64;
+                if tick % ppqn == ppqn / 2 {
+                    shift += humanize.swing * (ppqn / 2) as f64;
+                }
+                let mut new_tick = (tick as i64 + shift.round() as i64).max(0) as u64;
+                if crosses_tuning(score.tick_time(tick), score.tick_time(new_tick)) {
+                    new_tick = tick;
+                }
+                new_tick = new_tick.max(released.get(&note).copied().unwrap_or(0));
+                shifts.insert(note, new_tick as i64 - tick as i64);
+                new_tick
+            }
+            TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. },
+            } => {
+                let note = (channel.as_int(), key.as_int());
+                let shift = shifts.remove(&note).unwrap_or(0);
+                let new_tick = (tick as i64 + shift).max(0) as u64;
+                released.insert(note, new_tick);
+                new_tick
+            }
+            _ => tick,
+        };
+        events.push((new_tick, *event));
+    }
+
+    // Stable, so events that end up at the same tick keep their order.
+    events.sort_by_key(|(tick, _)| *tick);
+    let mut prev_tick = 0;
+    events
+        .into_iter()
+        .map(|(tick, mut event)| {
+            event.delta = u28::new((tick - prev_tick) as u32);
+            prev_tick = tick;
+            event
+        })
+        .collect()
+}
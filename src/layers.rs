@@ -0,0 +1,152 @@
+//! Tuning layers combined with the tuning timeline before playback (see [`crate::TUNING_LAYERS`]), so that adjustments
+//! of the whole performance, such as a global stretch, a correction file edited between passes, or expressive
+//! inflections of some pitch classes, don't need to be written into every tuning of the timeline.
+//!
+//! Each layer adjusts the pitches of the layers below it: JI adjustments of JI pitches multiply their ratios (so the
+//! visualizer shows the combined ratios), otherwise their cents are added. The pitch bends are computed from the
+//! combined pitches.
+//!
+//! Correction files have a line per adjustment: its time in seconds, the pitch class it adjusts (or `all`), and the
+//! adjustment as a ratio or in cents, kept until changed by a later line. Lines starting with `#` are ignored.
+//!
+//! ```text
+//! 0.0    all  -1.5c
+//! 18.448 C#   81/80
+//! 42.0   C#   1/1
+//! ```
+
+use std::fs;
+
+use rational::Rational;
+
+use crate::tuner::{parse_pitch_class, PitchSpec, Tuner};
+use crate::tuning_file::{line_error, parse_pitch, report_errors};
+
+/// A layer of adjustments combined with the tuning timeline.
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum TuningLayer {
+    /// Stretches the intervals above A by `cents` per octave, e.g. to widen every interval slightly as in a stretched
+    /// piano tuning. Octaves themselves can't be stretched, as all octaves of a pitch class share a channel.
+    Stretch { cents: f64 },
+    /// Adjusts each pitch class (starting from A) by the given cents throughout, e.g. to inflect the leading tones of
+    /// a piece.
+    Offsets([f64; 12]),
+    /// Adjusts pitch classes over time as given by the correction file at the path, which is read again whenever
+    /// playback starts, so it can be edited between passes. See the [module docs](self).
+    Corrections(&'static str),
+}
+
+/// A layer ready to be combined with the timeline.
+enum Layer {
+    Stretch(f64),
+    /// The adjustment of each pitch class in effect from each time on, in order of time.
+    Timeline(Vec<(f64, [PitchSpec; 12])>),
+}
+
+impl Layer {
+    fn load(layer: &TuningLayer) -> Layer {
+        match *layer {
+            TuningLayer::Stretch { cents } => Layer::Stretch(cents),
+            TuningLayer::Offsets(offsets) => {
+                Layer::Timeline(vec![(0.0, offsets.map(PitchSpec::Cents))])
+            }
+            TuningLayer::Corrections(path) => Layer::Timeline(load_corrections(path)),
+        }
+    }
+
+    fn times(&self) -> Vec<f64> {
+        match self {
+            Layer::Stretch(_) => vec![],
+            Layer::Timeline(timeline) => timeline.iter().map(|(time, _)| *time).collect(),
+        }
+    }
+
+    /// Adjusts `pitch` of `semitone` applied at `time`.
+    fn adjust(&self, time: f64, semitone: usize, pitch: PitchSpec) -> PitchSpec {
+        let adjustment = match self {
+            Layer::Stretch(cents) => PitchSpec::Cents(pitch.cents().unwrap() / 1200.0 * cents),
+            Layer::Timeline(timeline) => {
+                let idx = timeline.partition_point(|(t, _)| *t <= time);
+                match idx.checked_sub(1) {
+                    Some(idx) => timeline[idx].1[semitone],
+                    None => return pitch,
+                }
+            }
+        };
+        match (pitch, adjustment) {
+            (PitchSpec::Ratio(ratio), PitchSpec::Ratio(adjustment)) => {
+                PitchSpec::Ratio(ratio * adjustment)
+            }
+            (pitch, adjustment) => {
+                PitchSpec::Cents(pitch.cents().unwrap() + adjustment.cents().unwrap())
+            }
+        }
+    }
+}
+
+/// Loads the correction file at `path`. Prints every invalid line and exits if there are any.
+fn load_corrections(path: &str) -> Vec<(f64, [PitchSpec; 12])> {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Failed to read correction file {path}: {e}"));
+    let mut timeline: Vec<(f64, [PitchSpec; 12])> = vec![];
+    let mut errors = vec![];
+    for (line_idx, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() || words[0].starts_with('#') {
+            continue;
+        }
+        let column = line.find(|c: char| !c.is_whitespace()).unwrap() + 1;
+        let error = |msg: &str| line_error(path, line_idx, column, line, msg);
+        let [time, pitch_class, adjustment] = words[..] else {
+            errors.push(error(
+                "Expected a time, a pitch class (or `all`) & an adjustment",
+            ));
+            continue;
+        };
+        let Some(time) = time.parse::<f64>().ok().filter(|time| *time >= 0.0) else {
+            errors.push(error(&format!("Invalid time: {time}")));
+            continue;
+        };
+        let pitch_classes = match pitch_class {
+            "all" => 0..12,
+            name => match parse_pitch_class(name) {
+                Some(pc) => pc..pc + 1,
+                None => {
+                    errors.push(error(&format!("Invalid pitch class: {name}")));
+                    continue;
+                }
+            },
+        };
+        let Some(adjustment) = parse_pitch(adjustment).filter(|pitch| !pitch.is_keep()) else {
+            errors.push(error(&format!("Invalid adjustment: {adjustment}")));
+            continue;
+        };
+        if timeline.last().is_some_and(|(last, _)| time < *last) {
+            errors.push(error("Adjustments must be in order of time"));
+            continue;
+        }
+        if timeline.last().is_none_or(|(last, _)| time > *last) {
+            let adjustments = timeline
+                .last()
+                .map_or([PitchSpec::Ratio(Rational::one()); 12], |(_, a)| *a);
+            timeline.push((time, adjustments));
+        }
+        for pc in pitch_classes {
+            timeline.last_mut().unwrap().1[pc] = adjustment;
+        }
+    }
+    report_errors(&errors, path);
+    timeline
+}
+
+/// Combines `layers` with the timeline of `tuner`, in order, see the [module docs](self).
+pub fn apply(tuner: &mut Tuner, layers: &[TuningLayer]) {
+    let layers: Vec<Layer> = layers.iter().map(Layer::load).collect();
+    let times: Vec<f64> = layers.iter().flat_map(Layer::times).collect();
+    tuner.layer(&times, |time, semitone, pitch| {
+        layers
+            .iter()
+            .fold(pitch, |pitch, layer| layer.adjust(time, semitone, pitch))
+    });
+}
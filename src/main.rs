@@ -1,112 +1,2020 @@
 use broadcaster::BroadcastChannel;
+use clap::Parser;
 use futures::executor;
-use midir::MidiOutput;
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput};
 use midly::live::LiveEvent;
 use midly::num::{u4, u7};
-use midly::{self, MetaMessage, MidiMessage, PitchBend, Smf, TrackEventKind};
-use rational::Rational;
-use spin_sleep::{SpinSleeper, SpinStrategy};
+use midly::{self, MetaMessage, MidiMessage, PitchBend, Smf, TrackEvent, TrackEventKind};
+use std::cell::RefCell;
 use std::fs;
 use std::io::stdin;
 use std::process::exit;
-use std::sync::{Arc, Mutex};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::server::{start_websocket_server, VisualizerMessage};
-use crate::tuner::{JIRatio, Monzo, PRIMES, SEMITONE_NAMES};
+use crate::analysis::ONSET_TOLERANCE;
+use crate::channel_tuning::{ChannelTuned, ChannelTuning};
+use crate::click::ClickOutput;
+use crate::config::{Cli, Config};
+use crate::follow::{LiveInput, Onset};
+use crate::humanize::Humanize;
+use crate::layers::TuningLayer;
+use crate::output::{Allocation, ChannelAllocator, LatencyCompensated, MidiSink, NoteChannels};
+use crate::performance::{Performance, Timeline};
+use crate::pieces::{EndOfTrack, Piece};
+use crate::profile::{Backend, Profiled, Retune, SynthProfile};
+use crate::score::{Score, PEDAL_CCS};
+use crate::server::{start_websocket_server, VisualizerMessage, EXPRESSIVE_CONTROLLERS};
+use crate::setlist::Overrides;
+use crate::split::KeyboardSplit;
+use crate::sync::{SyncRole, Transport};
+use crate::tap::TapInput;
+use crate::tuner::{
+    cents_from_12edo, key_monzo, parse_key_name, parse_pitch_class, pitch_class, ratio_name,
+    snapshot_at, spelled_key_name, CurrentTuning, DeferPolicy, PitchSpec, Tuner, TuningSnapshot,
+    PRIMES,
+};
 
 #[macro_use]
 extern crate lazy_static;
 
-mod ondine;
+/// Whether [`println!`] & [`print!`] write to stderr, so that stdout only has the JSON lines of `--emit jsonl`.
+static LOG_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Shadows [`std::println!`] in the whole crate (the modules are declared below), writing to stderr instead if
+/// [`LOG_TO_STDERR`].
+macro_rules! println {
+    ($($arg:tt)*) => {
+        match $crate::LOG_TO_STDERR.load(std::sync::atomic::Ordering::Relaxed) {
+            true => std::eprintln!($($arg)*),
+            false => std::println!($($arg)*),
+        }
+    };
+}
+
+/// Shadows [`std::print!`] like [`println!`].
+macro_rules! print {
+    ($($arg:tt)*) => {
+        match $crate::LOG_TO_STDERR.load(std::sync::atomic::Ordering::Relaxed) {
+            true => std::eprint!($($arg)*),
+            false => std::print!($($arg)*),
+        }
+    };
+}
+
+mod align;
+mod analysis;
+mod api;
+#[cfg(feature = "live-audio")]
+mod audio_input;
+mod automation;
+mod channel_tuning;
+mod chord;
+mod click;
+mod combination;
+mod conductor;
+mod config;
+mod diff;
+mod drift;
+mod edits;
+mod envelope;
+mod export;
+mod fluidsynth;
+mod follow;
+mod golden;
+mod humanize;
+mod layers;
+mod mdns;
+mod metrics;
+mod mts;
+mod notation;
+mod osc;
+mod output;
+mod performance;
+mod pianoteq;
+mod pieces;
+mod preflight;
+mod preview;
+mod profile;
+mod progression;
+mod project;
+mod rtpmidi;
+#[cfg(any(feature = "preview-synth", feature = "render"))]
+mod sampler;
+mod score;
 mod server;
+mod session;
+mod setlist;
+mod soundcheck;
+mod split;
+mod suggest;
+mod supercollider;
+mod surge;
+mod symmetry;
+mod sync;
+#[cfg(any(feature = "preview-synth", feature = "render"))]
+mod synth;
+mod tap;
+mod temper;
+mod timer;
+mod timestamped;
 mod tuner;
+mod tuning_file;
+mod walk;
 
-/// Pitch bend range in +/- semitones. (Make sure PianoTeq is set to same PB value)
+/// Default pitch bend range in +/- semitones, overridden by `--pb-range`. (Make sure PianoTeq is set to same PB value,
+/// or set [`PIANOTEQ_RPC_URL`] to have it checked automatically)
 pub const PB_RANGE: u16 = 4;
 
+/// Whether pitch classes of the tuning file that can't be tuned (beyond the pitch bend range, or with a prime beyond
+/// [`PRIMES`]) are skipped with a warning, keeping their previous tuning, instead of aborting the load of the file. For
+/// performances that should go on despite a slip in the tuning file.
+const LENIENT_TUNING_FILES: bool = false;
+
+/// Whether ratios of the tuning file beyond the pitch bend range that are in range an octave or more away (e.g.
+/// `D 25/12` for `D 25/24`, as ratios are easily written in the wrong octave) are moved there with a warning, instead
+/// of aborting the load of the file.
+const OCTAVE_REDUCE_TUNINGS: bool = false;
+
 /// Start playing from this time (in seconds).
 ///
 /// Other meta messages (non note/cc) like tempo change, track name, etc. will still be
 /// parsed, but notes will not be played and no waiting will be done until this time is reached.
 const START_FROM: f64 = 0.0;
 
+/// MIDI file played & analysed by the commands, unless another is given on the command line or by a project file (see
+/// [`Config::midi_file`]).
 const MIDI_FILE: &str = "ondine.mid";
 
+/// Tuning timeline of [`MIDI_FILE`]. See [`tuning_file`] for the format.
+const TUNING_FILE: &str = "ondine.tuning";
+
+/// Pieces of a concert, e.g.
+/// `&[Piece { name: Cow::Borrowed("Ondine"), midi_file: Cow::Borrowed("ondine.mid"), ... }, ...]`.
+/// After each performance, playback waits for the next piece loaded from the visualizer desk (starting with the first
+/// one), until stopped with Ctrl-C. See [`pieces`]. `&[]` to only play [`MIDI_FILE`].
+const PIECES: &[Piece] = &[];
+
 /// Playback speed multiplier. 1.0 is normal speed.
 const PLAYBACK_SPEED: f64 = 1.0;
 
+/// Seconds to keep playing after the last MIDI event (the final note off, or e.g. lifting the pedal), so that the last
+/// notes & pedal resonance can decay before everything is reset and the program exits. Silence between the last event
+/// and the end of the track is not waited through.
+const PLAYBACK_TAIL: f64 = 4.0;
+
+/// What happens once playback reaches the end of the track: `EndOfTrack::Stop` resets the output and exits (or waits
+/// for the next piece loaded from the desk if there are [`PIECES`]), `EndOfTrack::Loop` starts over from [`START_FROM`]
+/// after [`PLAYBACK_TAIL`], `EndOfTrack::Advance` loads the next of the [`PIECES`], and `EndOfTrack::Hold` keeps the
+/// final tuning & the servers up for the visualizer until stopped with Ctrl-C.
+const END_OF_TRACK: EndOfTrack = EndOfTrack::Stop;
+
+/// `preview` plays this many seconds before & after each tuning change by default.
+const PREVIEW_SECONDS: f64 = 4.0;
+
+/// Seconds of silence between the tuning changes played by `preview`.
+const PREVIEW_GAP: f64 = 1.5;
+
+/// Seconds ahead of time to send messages to MIDI ports that can schedule them (ALSA sequencer on Linux, CoreMIDI on
+/// macOS), instead of spin-sleeping to the exact instant of each message. Messages are then timed by the OS, which is
+/// more accurate & saves a CPU core, but the visualizer & tuning changes are shown up to this much early. 0 to send
+/// messages when they are due.
+const MIDI_LOOKAHEAD: f64 = 0.05;
+
+/// Seconds before the event a tuning change is applied at to send its pitch bends, so that synths that smooth pitch bend
+/// changes have settled on the new tuning by the attack of its first notes. Also applies to the lower range of a
+/// [`KEYBOARD_SPLIT`] and to the steps of [`REFERENCE_DRIFT`]. Bends are never sent before the previous event (or other
+/// message sent during playback, e.g. an envelope bend), so that they don't retune its notes. 0 to send them together
+/// with the event.
+const PRE_BEND_LEAD: f64 = 0.005;
+
+/// Timer period in ms to request from Windows (with `timeBeginPeriod`) during playback, so that sleeps between events
+/// are accurate to about that much. [`None`] leaves the system timer at its default of 15.6 ms, which saves power but
+/// spins the CPU for that long before each event. The effective timer resolution is reported at startup, see
+/// [`timer`]. Ignored on other platforms.
+const WINDOWS_TIMER_PERIOD: Option<u32> = Some(1);
+
+/// Synchronizes playback with ji-performer instances on other machines, e.g. one driving the synth and one driving the
+/// visualizer in the hall. `SyncRole::Master(&["192.168.1.20:57140"])` sends the playback position to the followers
+/// at the given addresses, `SyncRole::Follower("0.0.0.0:57140")` waits for a master to start instead of for enter, and
+/// stays locked to it. See [`sync`].
+const SYNC: SyncRole = SyncRole::Off;
+
+/// Follows a live performance of [`MIDI_FILE`] instead of playing back on a fixed clock, so that the retunes (and
+/// playback, e.g. of a layered electronic part) follow the performer's rubato. `LiveInput::Audio(None)` pitch tracks the
+/// default audio input device, `LiveInput::Recording("take.wav")` a recorded take. Playback starts when the performer
+/// plays the first notes from [`START_FROM`] on, instead of on enter. Set [`ACTIVATE_MIDI`] to false to only send the
+/// retunes. See [`follow`].
+const LIVE_FOLLOW: LiveInput = LiveInput::Off;
+
+/// Tempo of playback tapped on a key (`TapInput::Key(21)`) or pedal (`TapInput::Pedal(67)`) of
+/// [`LIVE_MIDI_INPUT_NAME`], e.g. by a conductor, instead of the tempo of the MIDI file. Each tap is a beat of the
+/// time signature. Can't be used together with [`SYNC`] following or [`LIVE_FOLLOW`]. See [`tap`].
+const TAP_TEMPO: TapInput = TapInput::Off;
+
+/// Pauses playback right before each tuning with a `cue` (see [`tuning_file`]), e.g. at fermatas or between movements,
+/// until it is advanced by enter, a websocket `advance` message, or [`CONDUCTOR_ADVANCE`]. Can't be used together with
+/// [`SYNC`] following or [`LIVE_FOLLOW`]. See [`conductor`].
+const CONDUCTOR_MODE: bool = false;
+
+/// Key (`TapInput::Key(108)`) or pedal (`TapInput::Pedal(67)`) of [`LIVE_MIDI_INPUT_NAME`] that advances past cues in
+/// conductor mode.
+const CONDUCTOR_ADVANCE: TapInput = TapInput::Off;
+
+/// Longest sleep between checks of a transport that is following a sync master or performer (see [`SYNC`] &
+/// [`LIVE_FOLLOW`]) or tapped (see [`TAP_TEMPO`]), which may be moved or change speed meanwhile.
+const FOLLOW_SLEEP_STEP: Duration = Duration::from_millis(10);
+
+/// Seconds that each `+` or `-` entered during playback nudges the next tuning change by, see [`read_controls`].
+const NUDGE_STEP: f64 = 0.05;
+
+/// Whether the tuning edits of websocket clients (see [`edits`]) are written to the tuning file when playback stops.
+const SAVE_TUNING_EDITS: bool = false;
+
+/// Click track for a performer playing along with playback, generated from the tempo map & time signatures of
+/// [`MIDI_FILE`]. `ClickOutput::Channel(15)` sends it on channel 15 of the playback output, `ClickOutput::Port("name")`
+/// to a separate MIDI output port, e.g. for the performer's headphones. See [`click`].
+const CLICK_TRACK: ClickOutput = ClickOutput::Off;
+
+/// Clicks per beat of the click track, e.g. 2 to click eighth notes in 4/4.
+const CLICK_SUBDIVISION: u32 = 1;
+
+/// Splits the keyboard into a lower range with a tuning file & channel bank of its own, e.g. `Some(KeyboardSplit {
+/// below: "C3", tuning_file: "ondine-bass.tuning", port: "31edo bass" })` plays the keys below C3 on the port whose
+/// name contains `31edo bass`, tuned by `ondine-bass.tuning`. See [`split`].
+const KEYBOARD_SPLIT: Option<KeyboardSplit> = None;
+
+/// Name (or part of the name) of the output connected to without asking, overridden by the `device` of a project file.
 const MIDI_PLAYBACK_DEVICE_NAME: &str = "31edo";
 
-/// Turn off when recording video/midi to save CPU.
+/// MIDI input port of the performer's keyboard for the `live` command. Asks for one if no port's name contains this.
+const LIVE_MIDI_INPUT_NAME: &str = "Digital Piano";
+
+/// Latency in seconds to compensate for on outputs whose name (as listed when selecting one) contains the given text,
+/// by sending their messages that much early. For connections with noticeable latency like Bluetooth LE MIDI
+/// peripherals (typically 10-30 ms), e.g. `&[("iPad", 0.02)]`. A negative latency delays the messages of the output
+/// instead, e.g. `&[("Pianoteq", -0.012)]` to keep a software synth in step with a hardware synth 12 ms behind it. Only
+/// outputs that schedule messages (see [`MIDI_LOOKAHEAD`]) can be delayed, by at most their lookahead.
+const OUTPUT_LATENCY: &[(&str, f64)] = &[];
+
+/// Tune MIDI output ports with MIDI Tuning Standard bulk tuning dumps (to tuning program 0) at every tuning change,
+/// instead of pitch bends. For synths that support MTS, but not per-channel pitch bends.
+const MTS_BULK_DUMPS: bool = false;
+
+/// Tune the pitch class channels of MIDI output ports whose name contains the given text with controllers instead of
+/// pitch bends, for synths that can map a controller to fine tune but mishandle pitch bends, e.g. `&[("Vital",
+/// ChannelTuning::Cc { msb: 16, lsb: 48, range: 100.0 }), ("Kontakt", ChannelTuning::Rpn)]`. See [`channel_tuning`].
+const CHANNEL_TUNING: &[(&str, ChannelTuning)] = &[];
+
+/// Synth profiles of MIDI output ports whose name contains the given text, e.g. `&[("Pianoteq", profile::PIANOTEQ),
+/// ("UM-ONE", profile::GENERAL_MIDI)]`, bundling the pitch bend range, the channels of controllers & pitch classes,
+/// the retuning and the backend of the synth played by the port. A profile overrides [`MTS_BULK_DUMPS`],
+/// [`CHANNEL_TUNING`] & [`MIDI_LOOKAHEAD`] for its port. The click track's port is never profiled, as its percussion
+/// channel isn't a pitch class. See [`profile`].
+const SYNTH_PROFILES: &[(&str, SynthProfile)] = &[];
+
+/// How notes are assigned to the 12 tuned channels. `Allocation::PitchClasses` plays each pitch class on a channel of
+/// its own. `Allocation::PerNote(output::StealPolicy::Oldest)` plays each note on a free channel, bent to its tuning
+/// right before its note on, so that released notes ring out in their tuning rather than being bent by later retunes
+/// of their pitch class. When more than 12 notes sound, the channel of the oldest note (or the quietest with
+/// `StealPolicy::Quietest`, or the oldest of the same pitch class with `StealPolicy::SamePitchClass`) is stolen and
+/// logged. A [`KEYBOARD_SPLIT`] needs `Allocation::PitchClasses`. Can be overridden with `--steal POLICY`. See
+/// [`output::ChannelAllocator`].
+const CHANNEL_ALLOCATION: Allocation = Allocation::PitchClasses;
+
+const _: () = assert!(
+    KEYBOARD_SPLIT.is_none() || CHANNEL_ALLOCATION.by_pitch_class(),
+    "KEYBOARD_SPLIT needs CHANNEL_ALLOCATION = Allocation::PitchClasses, its lower range is tuned by pitch class"
+);
+
+/// Pianoteq's JSON-RPC endpoint (enabled by starting Pianoteq with `--serve ""`). If set, Pianoteq's pitch bend range
+/// is checked against the configured range (see [`PB_RANGE`]) and set if it differs, after connecting to a MIDI output
+/// port.
+const PIANOTEQ_RPC_URL: Option<&str> = None;
+
+/// Pianoteq preset to load after connecting, if [`PIANOTEQ_RPC_URL`] is set.
+const PIANOTEQ_PRESET: Option<&str> = None;
+
+/// Address of the FluidSynth shell server (started with `fluidsynth -s`), listed as an output.
+const FLUIDSYNTH_ADDR: &str = "127.0.0.1:9800";
+
+/// SoundFont for FluidSynth to load on connecting. Not needed if it was already given on FluidSynth's command line.
+const FLUIDSYNTH_SOUNDFONT: Option<&str> = None;
+
+/// General MIDI program (0-127) played by FluidSynth. 0 is Acoustic Grand Piano.
+const FLUIDSYNTH_PROGRAM: u8 = 0;
+
+/// Address of Surge XT's OSC input (53280 is its default port), listed as an output.
+const SURGE_OSC_ADDR: &str = "127.0.0.1:53280";
+
+/// Address of SuperCollider's language (sclang listens on 57120 by default), listed as an output.
+const SUPERCOLLIDER_ADDR: &str = "127.0.0.1:57120";
+
+/// Control port of the RTP-MIDI (AppleMIDI) network session to invite for output to a synth on another machine, see
+/// `rtpmidi.rs`. The data port is the next one.
+const RTP_MIDI_ADDR: &str = "192.168.1.30:5004";
+
+/// Turn off when recording video/midi to save CPU. Also turned on by `--debug`.
 const DEBUG_PRINT: bool = false;
 
-/// Turn off when recording MIDI to save CPU.
+/// Turn off when recording MIDI to save CPU. Also turned off by `--no-visualizer`.
 const ACTIVATE_VISUALIZER: bool = true;
 
-/// Turn off when recording video to save CPU.
+/// How often the lag & jitter of playback and the number of dropped visualizer messages are sent to the visualizer as
+/// `timing` messages. [`None`] to not send them.
+const TIMING_TELEMETRY_INTERVAL: Option<Duration> = Some(Duration::from_secs(1));
+
+/// Path of a Unix domain socket to also serve the visualizer messages on (one per line), for same-machine consumers
+/// like OBS scripts. [`None`] to only serve them on the websocket.
+const LOCAL_SOCKET: Option<&str> = None;
+
+/// Address to serve Prometheus metrics of playback on over HTTP, e.g. `Some("0.0.0.0:9184")`, to monitor long
+/// installation-style runs. See [`metrics`]. [`None`] to not serve them.
+const METRICS_ADDR: Option<&str> = None;
+
+/// SQLite database to record every take of playback into (MIDI messages, tuning changes & their lag, transport actions
+/// and warnings), e.g. `Some("export/sessions.db")`, for comparing takes afterwards. Requires the `session-db` feature.
+/// See [`session`]. [`None`] to not record takes.
+const SESSION_DB: Option<&str> = None;
+
+/// Address to serve the HTTP API of playback on (status, current tuning & position, and play/pause/seek), e.g.
+/// `Some("127.0.0.1:8090")`, for shell scripts, OBS plugins & cue systems that don't speak the websocket protocol. See
+/// [`api`]. [`None`] to not serve it.
+const API_ADDR: Option<&str> = None;
+
+/// Name to advertise the websocket server (and the OSC input of a sync follower & the API) as on the local network via
+/// mDNS, e.g. `Some("JI Performer")`, so that visualizer clients can discover the performer machine instead of
+/// hardcoding its IP. Requires the `mdns` feature, and the endpoints to be bound to a non-loopback address. See
+/// [`mdns`]. [`None`] to not advertise them.
+const MDNS_NAME: Option<&str> = None;
+
+/// Seconds of playback between the playback positions sent to the visualizer.
+const POSITION_INTERVAL: f64 = 0.1;
+
+/// Whether to send the strongest combination tones of the notes sounding (held down or by the pedals) to the
+/// visualizer whenever they change, e.g. to display the difference tones implied by a sonority.
+const COMBINATION_TONE_MESSAGES: bool = false;
+
+/// Turn off when recording video to save CPU. Also turned off by `--no-midi`.
 const ACTIVATE_MIDI: bool = true;
 
+/// Play (and render) every tuning as its nearest approximation in this many equal divisions of the octave above A
+/// instead, e.g. `Some(31)` or `Some(12)` to record "JI vs 31edo vs 12edo" comparisons of the same timeline. [`None`]
+/// to play the tunings as given.
+const QUANTIZE_TO_EDO: Option<u32> = None;
+
+/// Layers of adjustments combined with the tunings of every piece before playback (and rendering), after
+/// [`QUANTIZE_TO_EDO`], each adjusting the layers before it, e.g. `&[TuningLayer::Stretch { cents: 1.5 },
+/// TuningLayer::Corrections("corrections.txt")]`. See [`layers`]. Empty to play the tunings as given.
+const TUNING_LAYERS: &[TuningLayer] = &[];
+
+/// Shorten silences (no notes sounding) longer than this many seconds to this length during playback, e.g. `Some(2.0)`
+/// to audition the tuning changes scattered across a piece without waiting through its rests. The tuning changes & other
+/// events in a silence are still applied. [`None`] to play in real time.
+const COMPRESS_SILENCE: Option<f64> = None;
+
+/// Play (and render) the notes with humanized timing, for demos of quantized MIDI files to sound less mechanical, e.g.
+/// `Some(Humanize { jitter_ms: 8.0, swing: 0.0, seed: 1 })`. Tuning changes are never moved. See [`humanize`].
+/// [`None`] to play the notes as written.
+const HUMANIZE: Option<Humanize> = None;
+
+/// Split tuning changes so that notes still sounding from before the change (held or by the sustain pedal) are
+/// retuned only after they stop sounding, wherever possible. See `cargo run -- sustained` for a report.
+///
+/// [`DeferPolicy::Silence`] delays whole tuning changes until nothing is sounding instead, within
+/// [`RETUNE_ON_SILENCE_MAX_DELAY`].
+///
+/// Can be overridden per tuning with `defer` in [`TUNING_FILE`].
+const DEFER_SUSTAINED_RETUNES: DeferPolicy = DeferPolicy::Never;
+
+/// Longest time in seconds that [`DeferPolicy::Silence`] delays a tuning change while waiting for silence. If no notes
+/// stop sounding with the pedal up in that time, the tuning change is applied at its original time.
+const RETUNE_ON_SILENCE_MAX_DELAY: f64 = 1.0;
+
+/// `analyze` flags simultaneously sounding fifths & fourths that deviate from 3/2 & 4/3 by an amount of cents within
+/// this range as wolves. Larger deviations are assumed to be intentional.
+const WOLF_WINDOW_CENTS: (f64, f64) = (10.0, 40.0);
+
+/// `analyze` flags simultaneously sounding notes of different pitch classes tuned closer than this many cents apart
+/// (modulo octaves) as clashes, e.g. F# tuned as a sharp F## against a flat G.
+const CLASH_THRESHOLD_CENTS: f64 = 30.0;
+
+/// `diff` marks pitch classes retuned by at least this many cents with `!`.
+const DIFF_THRESHOLD_CENTS: f64 = 1.0;
+
+/// `snap` moves the time of each tuning change to the latest note onset or start of a rest at most this many seconds
+/// before it.
+const SNAP_TOLERANCE: f64 = 0.05;
+
+/// EDOs that the `edo` report compares each tuning against.
+const EDO_CANDIDATES: &[u32] = &[12, 19, 22, 31, 41, 53];
+
+/// `temper` shows the simplest ratio within this many cents of each tempered pitch class.
+const TEMPER_RATIO_TOLERANCE_CENTS: f64 = 2.0;
+
+/// Frequency of A4 (1/1) in Hz, used for exports of absolute frequencies.
+///
+/// This does not affect playback, make sure the synth's A4 reference is set to the same value.
+const A4_FREQUENCY: f64 = 440.0;
+
+/// Drift of the reference pitch over the performance, as breakpoints of seconds & the frequency of A4 in Hz,
+/// interpolated linearly and held before the first & after the last one, e.g. `&[(0.0, 440.0), (420.0, 442.0)]` for a
+/// slow rise across the piece. Every tuning is raised by the cents the reference is above [`A4_FREQUENCY`]. See
+/// [`drift`]. Empty for no drift.
+const REFERENCE_DRIFT: &[(f64, f64)] = &[];
+
+/// Velocity of the notes struck by `drone`.
+const DRONE_VELOCITY: u8 = 80;
+
+/// Octave of `drone` notes given without an octave number (4 is the octave from C4 (middle C) to B4).
+const DRONE_OCTAVE: i32 = 4;
+
+/// SFZ instrument to use instead of the built-in synth for the preview synth & `render`.
+///
+/// Notes are pitch shifted to the exact JI ratios rather than by pitch bends. See [`sampler`] for supported opcodes.
+#[cfg(any(feature = "preview-synth", feature = "render"))]
+const SFZ_FILE: Option<&str> = None;
+
+/// Sample rate of WAV files written by `render`.
+#[cfg(feature = "render")]
+const RENDER_SAMPLE_RATE: u32 = 48000;
+
+/// Maximum number of seconds `render` keeps rendering after the end of the MIDI file, while notes are still ringing.
+#[cfg(feature = "render")]
+const RENDER_TAIL: f64 = 10.0;
+
+/// Directory where exports & reports are written to.
+const EXPORT_DIR: &str = "export";
+
+/// The commands, listed after the options by `--help` (see [`config::Cli`]).
+const COMMANDS: &str = "\
+Commands (a FILE.mid given after any of them is used with FILE.tuning instead of MIDI_FILE & TUNING_FILE, and
+a PROJECT.toml given after any of them applies the project file, as with run):
+  play [SETLIST | FILE.mid]
+             Play back MIDI_FILE with JI tuning (default), or the PIECES loaded by websocket clients one after
+             another, or the PIECES listed in SETLIST in order, with their overrides of the speed, start point
+             & variants, or FILE.mid once. Enter a variant name during playback to toggle it from the next
+             tuning change
+  run PROJECT.toml
+             Play back the MIDI file of the project file PROJECT.toml (e.g. performance.toml) with the tuning file,
+             pitch bend range, output, start point, speed, variants & visualizer settings it gives
+  analyze    Segment MIDI_FILE into chords, find wolves & clashes, and write a report and skeleton tuning
+             timeline to EXPORT_DIR
+  frequencies
+             Export the frequencies of all 128 MIDI keys for each tuning to EXPORT_DIR/frequencies, as CSV,
+             Scala .scl & .kbm and MTS bulk tuning dump .syx files named by bar & time
+  heatmap    Export a heat map of prime usage per bar to EXPORT_DIR
+  lilypond   Export HEJI spellings & cent deviations of every note as a LilyPond include file to EXPORT_DIR
+  sustained  Report tuning changes that retune notes which are still sounding
+  edo        Report how closely the pitch classes played in each tuning are approximated by each of
+             EDO_CANDIDATES (largest error, transposing the EDO to fit best), and which EDO fits best
+  render [OUTPUT]
+             Render playback of MIDI_FILE with the built-in synth to a WAV file (default EXPORT_DIR/render.wav).
+             Requires the `render` feature
+  drone [TIME] [NOTE...]
+             Sustain NOTEs (e.g. A C#5 Fb, default A) tuned as at TIME seconds (default START_FROM), to check the
+             synth's tuning & pitch bend range with a tuner
+  soundcheck Play a short sequence exercising the channel of every pitch class, the extremes of the pitch
+             bend range, and the fullest chords struck after tuning changes of MIDI_FILE as tuned in the
+             performance, announcing what each step should sound like, to verify the signal chain
+  live       Retune a live MIDI performance of MIDI_FILE from LIVE_MIDI_INPUT_NAME, following the notes played
+             in the score (from START_FROM) and forwarding them to the output after applying the tuning at
+             their position
+  align [take]
+             Tap enter at each tuning change while MIDI_FILE plays (or while playing a recorded take yourself,
+             starting from START_FROM), then write the tapped times to TUNING_FILE
+  preview [SECONDS] [FIRST[-LAST]]
+             Play SECONDS (default PREVIEW_SECONDS) before & after each tuning change of MIDI_FILE in turn, or
+             of the tuning changes FIRST to LAST (indices of the tuning timeline, as in frequencies)
+  golden [FILE]
+             Capture the messages a performance of MIDI_FILE sends to the output, with the time each is due at,
+             as the golden run FILE (default EXPORT_DIR/golden.txt) if it doesn't exist yet, or compare them
+             against it, reporting the first divergence
+  snap       Move the time of each tuning change in TUNING_FILE to the latest note onset or start of a rest
+             at most SNAP_TOLERANCE seconds before it, so that retunes don't land just after notes start
+  batch DIR  Run analyze, frequencies, heatmap, lilypond, sustained, edo (and render, with the `render` feature)
+             over every MIDI file in DIR with a tuning file of the same name (NAME.mid & NAME.tuning),
+             writing the results to EXPORT_DIR/batch/NAME and a summary to EXPORT_DIR/batch/summary.txt
+  temper FILE
+             Solve for the tuning that best fits the interval targets & exact constraints in FILE (e.g.
+             `B ~ D# * 13/8`, `G# == C# * 3/2`), minimizing the weighted cents error of the targets
+  progression FILE
+             Tune the chord progression in FILE so that its common tones keep their pitch across chord changes
+             with as little comma drift as the root motions allow, and write it as a draft tuning timeline to
+             EXPORT_DIR/progression.tuning
+  diff [FILE [OTHER_FILE]]
+             Print how each tuning change of FILE (default TUNING_FILE) retunes each pitch class, or the
+             differences between the tunings of FILE & OTHER_FILE over time. Pitch classes retuned by at least
+             DIFF_THRESHOLD_CENTS are marked with !
+  history NOTE
+             List every tuning of the pitch class NOTE (e.g. C#) in TUNING_FILE, with its bar, ratio, cents
+             above A and the comma it is retuned by from its previous tuning
+  walk       Step through the tuning changes of MIDI_FILE as played back, printing the chord sounding at each,
+             the tuning of every pitch class, the commas they are retuned by and the comment of the tuning,
+             without any output. Enter n (or nothing) for the next, p for the previous, an index to jump to
+             (of the tuning timeline, as in preview) or q to stop";
+
 fn main() {
+    let cli = Cli::parse();
+    let config = cli.config();
+    LOG_TO_STDERR.store(config.emit_jsonl, Ordering::Relaxed);
     println!("JI Performer v0.1");
     println!("------------");
 
     // Initialize lazy_statics
     println!("Initialized {} primes", PRIMES.len());
+
+    let args = &cli.args();
+    match args.first().map(String::as_str) {
+        None => concert(&[], &config),
+        Some("play") => concert(&args[1..], &config),
+        Some("run") => concert(&[], &config),
+        Some("analyze") => println!(
+            "{}",
+            analyze(
+                &Score::load(&config.midi_file),
+                &load_tuner(&config),
+                EXPORT_DIR
+            )
+        ),
+        Some("frequencies") => export_frequency_tables(
+            &Score::load(&config.midi_file),
+            &load_tuner(&config),
+            EXPORT_DIR,
+        ),
+        Some("heatmap") => export_prime_heat_map(
+            &Score::load(&config.midi_file),
+            &load_tuner(&config),
+            EXPORT_DIR,
+        ),
+        Some("lilypond") => export_lilypond_heji(
+            &Score::load(&config.midi_file),
+            &load_tuner(&config),
+            EXPORT_DIR,
+        ),
+        Some("sustained") => report_sustained_retunes(&config),
+        Some("edo") => {
+            let score = Score::load(&config.midi_file);
+            let approximations =
+                analysis::edo_approximations(&score, &load_tuner(&config), EDO_CANDIDATES);
+            print!("{}", analysis::edo_report(&score, &approximations));
+        }
+        Some("batch") => batch(&args[1..], &config),
+        Some("drone") => drone(&args[1..], &config),
+        Some("soundcheck") => soundcheck(&config),
+        Some("live") => live(&config),
+        Some("align") => align(&args[1..], &config),
+        Some("snap") => snap(&config),
+        Some("preview") => preview(&args[1..], &config),
+        Some("golden") => golden(&args[1..], &config),
+        Some("diff") => diff(&args[1..], &config),
+        Some("walk") => walk(&config),
+        Some("history") => history(&args[1..], &config),
+        Some("temper") => temper(&args[1..]),
+        Some("progression") => progression(&args[1..]),
+        #[cfg(feature = "render")]
+        Some("render") => render(&args[1..], &config),
+        Some(cmd) => {
+            println!("Unknown command: {cmd}\n\n{COMMANDS}");
+            exit(1);
+        }
+    }
+}
+
+/// Loads the tuning file of `config` with its variants & pitch bend range, for the commands on its MIDI file.
+fn load_tuner(config: &Config) -> Tuner {
+    let tuner = tuning_file::load(&config.tuning_file, &config.variants, config.pb_range)
+        .unwrap_or_else(|errors| tuning_file::exit_with_errors(&errors, &config.tuning_file));
+    println!("Loaded {} tunings:", tuner.len());
+    tuner.print_csv();
+    tuner
+}
+
+/// Returns the tuning file loaded (or parsed) for playback from `path`, or prints its errors, so that playback returns
+/// without playing rather than stopping the concert.
+fn playable<T>(result: Result<T, Vec<String>>, path: &str) -> Option<T> {
+    match result {
+        Ok(loaded) => Some(loaded),
+        Err(errors) => {
+            tuning_file::print_errors(&errors, path);
+            println!("Not playing {path}");
+            None
+        }
+    }
+}
+
+/// Segments `score` into chords/regions to help with authoring tunings for a new piece, and finds wolves & clashes in
+/// its tunings. Writes the report & a skeleton tuning timeline to `dir`, and returns the report.
+fn analyze(score: &Score, tuner: &Tuner, dir: &str) -> String {
+    let segments = analysis::segment(score);
+
+    let mut report = analysis::segment_report(score, &segments);
+
+    let snapshots = tuner.snapshots();
+    let clashes = analysis::clashes(score, &snapshots, WOLF_WINDOW_CENTS, CLASH_THRESHOLD_CENTS);
+    report.push_str("\nWolves & clashes:\n");
+    report.push_str(&analysis::clash_report(score, &clashes));
+    report.push_str("\nCombination tones:\n");
+    let times: Vec<f64> = segments
+        .iter()
+        .map(|seg| seg.start + ONSET_TOLERANCE)
+        .collect();
+    report.push_str(&combination::combination_report(
+        score,
+        &snapshots,
+        &times,
+        A4_FREQUENCY,
+    ));
+    report.push_str("\nJI suggestions:\n");
+    report.push_str(&suggest::suggestion_report(score, &segments, &snapshots));
+    report.push_str("\nSymmetries:\n");
+    report.push_str(&symmetry::symmetry_report(score, &segments, &snapshots));
+    if !REFERENCE_DRIFT.is_empty() {
+        report.push_str("\nReference drift:\n");
+        report.push_str(&drift::drift_report(score, &snapshots, REFERENCE_DRIFT));
+    }
+
+    fs::create_dir_all(dir).unwrap();
+    let report_path = format!("{dir}/analysis.txt");
+    let skeleton_path = format!("{dir}/skeleton.tuning");
+    fs::write(&report_path, &report).unwrap();
+    fs::write(
+        &skeleton_path,
+        analysis::skeleton_timeline(score, &segments),
+    )
+    .unwrap();
     println!(
-        "Initialized {} tunings:",
-        ondine::TUNER.lock().unwrap().len()
+        "Wrote {} segments to {report_path} and skeleton timeline to {skeleton_path}",
+        segments.len()
     );
-    ondine::TUNER.lock().unwrap().print_csv();
+    report
+}
 
-    let mut broadcast_channel = start_websocket_server();
+/// Exports the frequencies of all MIDI keys for each tuning snapshot as CSV, Scala .scl & .kbm and MTS .syx files.
+///
+/// The .syx files are stored to consecutive tuning programs, wrapping around after 128.
+fn export_frequency_tables(score: &Score, tuner: &Tuner, dir: &str) {
+    let snapshots = tuner.snapshots();
+    let dir = format!("{dir}/frequencies");
+    fs::create_dir_all(&dir).unwrap();
+    for (idx, snapshot) in snapshots.iter().enumerate() {
+        let stem = export::snapshot_file_stem(idx, snapshot, score);
+        let a4 = drift::reference_at(REFERENCE_DRIFT, snapshot.time);
+        export::write_frequency_csv(snapshot, a4, &format!("{dir}/{stem}.csv"));
+        export::write_scl(snapshot, &format!("{dir}/{stem}.scl"));
+        export::write_kbm(snapshot, a4, &format!("{dir}/{stem}.kbm"));
+        export::write_mts_bulk_dump(
+            snapshot,
+            a4,
+            (idx % 128) as u8,
+            &format!("{dir}/{stem}.syx"),
+        );
+    }
+    println!(
+        "Wrote frequency tables of {} tunings to {dir}",
+        snapshots.len()
+    );
+}
 
-    // -----------------------------------------------------------------------------------------------------------------
+/// Exports the prime heat map of `score` as CSV & SVG to `dir`.
+fn export_prime_heat_map(score: &Score, tuner: &Tuner, dir: &str) {
+    let snapshots = tuner.snapshots();
+    let heat_map = analysis::prime_heat_map(score, &snapshots);
 
-    println!("Select a MIDI output port:");
-    let midi_out = MidiOutput::new("JI Performer").unwrap();
+    fs::create_dir_all(dir).unwrap();
+    let csv_path = format!("{dir}/prime_heatmap.csv");
+    let svg_path = format!("{dir}/prime_heatmap.svg");
+    heat_map.write_csv(&csv_path);
+    heat_map.write_svg(&svg_path);
+    println!(
+        "Wrote prime heat map of {} bars to {csv_path} and {svg_path}",
+        heat_map.rows.len()
+    );
+}
 
-    let mut midi_idx = None;
+/// Exports HEJI annotations of every note of `score` as a LilyPond include file to `dir`.
+fn export_lilypond_heji(score: &Score, tuner: &Tuner, dir: &str) {
+    let snapshots = tuner.snapshots();
 
-    for (idx, port) in midi_out.ports().iter().enumerate() {
-        let port_name = midi_out.port_name(port).unwrap();
-        if port_name.contains(MIDI_PLAYBACK_DEVICE_NAME) {
-            midi_idx = Some(idx);
-            println!("[{idx}] {port_name} <Device Found>");
-        } else {
-            println!("[{idx}] {port_name}");
+    fs::create_dir_all(dir).unwrap();
+    let path = format!("{dir}/heji.ily");
+    export::write_lilypond_heji(score, &snapshots, &path);
+    println!(
+        "Wrote HEJI annotations of {} notes to {path}",
+        score.notes.len()
+    );
+}
+
+/// Prints tuning changes of the MIDI file of `config` that retune notes which are still sounding.
+fn report_sustained_retunes(config: &Config) {
+    let score = Score::load(&config.midi_file);
+    let retunes = analysis::sustained_retunes(&score, &load_tuner(config));
+    print!("{}", analysis::sustained_retune_report(&score, &retunes));
+    println!(
+        "{} sustained retunes found, {} can be deferred, {} of which are only held by the pedal \
+        (set DEFER_SUSTAINED_RETUNES or `defer` in TUNING_FILE to do so automatically)",
+        retunes.len(),
+        retunes.iter().filter(|r| r.defer_to.is_some()).count(),
+        retunes
+            .iter()
+            .filter(|r| r.defer_to.is_some() && r.pedal_only)
+            .count()
+    );
+}
+
+/// Runs the analyses & exports (and `render`, with the `render` feature) over every MIDI file in the directory `args[0]`
+/// that has a tuning file of the same name (e.g. `ondine.mid` & `ondine.tuning`), with the selected variants. The
+/// results of each piece are written to their own directory in `EXPORT_DIR/batch`, followed by a summary of all pieces.
+///
+/// A piece that fails (e.g. on an error in its tuning file) is reported in the summary, and the others still run.
+fn batch(args: &[String], config: &Config) {
+    let [dir] = args else {
+        println!("Expected a directory\n\n{COMMANDS}");
+        exit(1);
+    };
+    let mut midi_files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("mid"))
+        })
+        .collect();
+    midi_files.sort();
+
+    let mut summary = String::new();
+    for midi_path in midi_files {
+        let name = midi_path.file_stem().unwrap().to_string_lossy().to_string();
+        let tuning_path = midi_path.with_extension("tuning");
+        if !tuning_path.exists() {
+            println!("Skipping {}: no tuning file", midi_path.display());
+            summary.push_str(&format!("{name}: skipped, no {}\n", tuning_path.display()));
+            continue;
+        }
+        println!("\n== {name} ==");
+        let (midi_file, tuning_file) = (midi_path.to_str().unwrap(), tuning_path.to_str().unwrap());
+        let out_dir = format!("{EXPORT_DIR}/batch/{name}");
+
+        let tuner = match tuning_file::load(tuning_file, &config.variants, config.pb_range) {
+            Ok(tuner) => tuner,
+            Err(errors) => {
+                tuning_file::print_errors(&errors, tuning_file);
+                summary.push_str(&format!(
+                    "{name}: FAILED, {} errors in {tuning_file}\n",
+                    errors.len()
+                ));
+                continue;
+            }
+        };
+        let result = std::panic::catch_unwind(|| {
+            let score = Score::load(midi_file);
+            analyze(&score, &tuner, &out_dir);
+            export_frequency_tables(&score, &tuner, &out_dir);
+            export_prime_heat_map(&score, &tuner, &out_dir);
+            export_lilypond_heji(&score, &tuner, &out_dir);
+            let retunes = analysis::sustained_retunes(&score, &tuner);
+            let sustained_path = format!("{out_dir}/sustained.txt");
+            fs::write(
+                &sustained_path,
+                analysis::sustained_retune_report(&score, &retunes),
+            )
+            .unwrap();
+            println!(
+                "Wrote {} sustained retunes to {sustained_path}",
+                retunes.len()
+            );
+            let approximations = analysis::edo_approximations(&score, &tuner, EDO_CANDIDATES);
+            let edo_path = format!("{out_dir}/edo.txt");
+            fs::write(&edo_path, analysis::edo_report(&score, &approximations)).unwrap();
+            println!("Wrote EDO approximations to {edo_path}");
+
+            let line = format!(
+                "{name}: {} notes, {} tunings, {} sustained retunes",
+                score.notes.len(),
+                tuner.len(),
+                retunes.len()
+            );
+            // Rendering applies the deferrals of playback to the tuner, so it comes last.
+            #[cfg(feature = "render")]
+            let line = {
+                let duration =
+                    render_file(midi_file, tuner, &format!("{out_dir}/render.wav"), config);
+                format!("{line}, rendered {duration:.1}s")
+            };
+            line
+        });
+        match result {
+            Ok(line) => summary.push_str(&format!("{line}\n")),
+            Err(_) => summary.push_str(&format!("{name}: FAILED, see the error above\n")),
+        }
+    }
+
+    fs::create_dir_all(format!("{EXPORT_DIR}/batch")).unwrap();
+    let summary_path = format!("{EXPORT_DIR}/batch/summary.txt");
+    fs::write(&summary_path, &summary).unwrap();
+    println!("\n{summary}Wrote summary to {summary_path}");
+}
+
+/// Prints the retunes of each tuning change of a tuning file, or the differences between two tuning files.
+///
+/// `args` are the paths of up to two tuning files, the first defaulting to the tuning file of `config`. Both are loaded
+/// with the selected variants.
+fn diff(args: &[String], config: &Config) {
+    let load = |path| {
+        tuning_file::load(path, &config.variants, config.pb_range)
+            .unwrap_or_else(|errors| tuning_file::exit_with_errors(&errors, path))
+    };
+    match args {
+        [] => print!(
+            "{}",
+            diff::consecutive_report(&load_tuner(config), DIFF_THRESHOLD_CENTS)
+        ),
+        [path] => {
+            let tuner = load(path);
+            print!("{}", diff::consecutive_report(&tuner, DIFF_THRESHOLD_CENTS));
+        }
+        [a, b] => {
+            let (a, b) = (load(a), load(b));
+            print!("{}", diff::timeline_report(&a, &b, DIFF_THRESHOLD_CENTS));
+        }
+        _ => {
+            println!("Too many arguments\n\n{COMMANDS}");
+            exit(1);
         }
     }
+}
+
+/// Prints every tuning of the pitch class given in `args` in the tuning file of `config` (see [`diff::history`]).
+fn history(args: &[String], config: &Config) {
+    let [name] = args else {
+        println!("Expected a pitch class, e.g. C#\n\n{COMMANDS}");
+        exit(1);
+    };
+    let Some(semitone) = parse_pitch_class(name) else {
+        println!("Invalid pitch class: {name}\n\n{COMMANDS}");
+        exit(1);
+    };
+    let mut tuner = load_tuner(config);
+    prepare_tuner(&mut tuner, &config.midi_file);
+    print!(
+        "{}",
+        diff::history_report(&Score::load(&config.midi_file), &tuner, semitone)
+    );
+}
 
-    if let None = midi_idx {
+/// Prints the tempered tuning that best fits the targets in the file given in `args`.
+fn temper(args: &[String]) {
+    let [path] = args else {
+        println!("Expected a file of targets\n\n{COMMANDS}");
+        exit(1);
+    };
+    let problem = temper::load(path);
+    let solution = temper::solve(&problem);
+    print!(
+        "{}",
+        temper::report(&problem, &solution, TEMPER_RATIO_TOLERANCE_CENTS)
+    );
+}
+
+/// Prints the tuning of the chord progression in the file given in `args`, and writes it as a draft tuning timeline
+/// to [`EXPORT_DIR`].
+fn progression(args: &[String]) {
+    let [path] = args else {
+        println!("Expected a progression file\n\n{COMMANDS}");
+        exit(1);
+    };
+    let progression = progression::load(path);
+    let tuned = progression::solve(&progression);
+    print!("{}", progression::report(&progression, &tuned));
+    fs::create_dir_all(EXPORT_DIR).unwrap();
+    let draft_path = format!("{EXPORT_DIR}/progression.tuning");
+    fs::write(
+        &draft_path,
+        progression::draft_timeline(&progression, &tuned),
+    )
+    .unwrap();
+    println!("Wrote draft tuning timeline to {draft_path}");
+}
+
+/// Sustains notes tuned according to the tuning in effect at a given time, so that the synth's pitch bend range and
+/// tuning can be verified against a (strobe) tuner before a take.
+///
+/// `args` is an optional time in seconds (defaults to the start point of `config`) followed by the note names to
+/// sustain (defaults to A). Notes without an octave number are in [`DRONE_OCTAVE`].
+fn drone(args: &[String], config: &Config) {
+    let (time, names) = match args.first().and_then(|a| a.parse::<f64>().ok()) {
+        Some(time) => (time, &args[1..]),
+        None => (config.start_from, args),
+    };
+    let keys: Vec<u8> = if names.is_empty() {
+        vec![parse_key_name("A", DRONE_OCTAVE).unwrap()]
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                parse_key_name(name, DRONE_OCTAVE).unwrap_or_else(|| {
+                    println!("Invalid note name: {name}\n\n{COMMANDS}");
+                    exit(1);
+                })
+            })
+            .collect()
+    };
+
+    let mut tuner = load_tuner(config);
+    let snapshots = tuner.snapshots();
+    let snapshot = snapshot_at(&snapshots, time).unwrap_or(&snapshots[0]);
+    let root = tuner
+        .seek(snapshot.time)
+        .and_then(|td| td.root)
+        .unwrap_or(0);
+    println!(
+        "Tuning @ {:.3}s (A4 = {A4_FREQUENCY} Hz, pitch bend range +/- {} semitones):",
+        snapshot.time, config.pb_range
+    );
+    for key in &keys {
+        println!(
+            "  {:<4} {:<12} {:+8.3}c from 12edo  {:10.4} Hz",
+            spelled_key_name(&snapshot.spellings, *key),
+            snapshot.tuning[pitch_class(*key)].to_string(),
+            snapshot.key_cents(*key) - 100.0 * (*key as f64 - 69.0),
+            export::key_frequency(snapshot, *key, A4_FREQUENCY),
+        );
+    }
+
+    let mut broadcast_channel = start_websocket_server(config.emit_jsonl);
+    let mut midi_conn = connect_output(config);
+    reset(midi_conn.as_mut(), &mut broadcast_channel);
+
+    // Notes are played on the channels chosen as in playback.
+    let mut note_channels = NoteChannels::new();
+    let mut allocator = config.allocation.allocator(config.pb_range);
+    midi_conn.retune(snapshot);
+    for pb_raw_msg in allocator.retune(&snapshot.tuning, &note_channels) {
+        midi_conn.send(&pb_raw_msg);
+    }
+
+    loop {
+        for key in &keys {
+            let pc = pitch_class(*key);
+            let channel = allocate_note(
+                midi_conn.as_mut(),
+                &mut note_channels,
+                allocator.as_mut(),
+                *key,
+                DRONE_VELOCITY,
+                &snapshot.tuning,
+            );
+            send_note_on(midi_conn.as_mut(), channel, *key, DRONE_VELOCITY);
+
+            if let (true, Some(monzo)) = (config.visualizer, &snapshot.monzos[pc]) {
+                let res = executor::block_on(broadcast_channel.send(&VisualizerMessage::NoteOn {
+                    edosteps_from_a4: *key as i32 - 69,
+                    velocity: DRONE_VELOCITY.into(),
+                    cents: cents_from_12edo(&snapshot.tuning, pc),
+                    ratio: ratio_name(&snapshot.tuning, &snapshot.spellings, pc, root),
+                    monzo: key_monzo(monzo, *key),
+                    name: spelled_key_name(&snapshot.spellings, *key),
+                }));
+                if let Err(e) = res {
+                    println!(
+                        "WARN: Failed to send message to visualizer broadcast channel: {}",
+                        e
+                    );
+                }
+            }
+        }
+
+        println!("Press enter to re-strike, or enter q to stop...");
         let mut input = String::new();
         stdin().read_line(&mut input).unwrap();
-        midi_idx = Some(input.trim().parse().unwrap());
+
+        for key in &keys {
+            let channel = note_channels.note_off(*key, allocator.as_ref());
+            send_note_off(midi_conn.as_mut(), channel, *key, 0);
+
+            if config.visualizer {
+                let res = executor::block_on(broadcast_channel.send(&VisualizerMessage::NoteOff {
+                    edosteps_from_a4: *key as i32 - 69,
+                    velocity: 0.into(),
+                }));
+                if let Err(e) = res {
+                    println!(
+                        "WARN: Failed to send message to visualizer broadcast channel: {}",
+                        e
+                    );
+                }
+            }
+        }
+        if input.trim() == "q" {
+            break;
+        }
     }
 
-    let out_port = &midi_out.ports()[midi_idx.unwrap()];
-    let mut midi_conn = midi_out.connect(out_port, "JI Performer").unwrap();
+    reset(midi_conn.as_mut(), &mut broadcast_channel);
+}
 
-    let exit_flag = Arc::new(Mutex::new(false));
+/// Plays the soundcheck sequence of the MIDI file of `config` (see [`soundcheck`]) to the output step by step, until
+/// done or stopped with Ctrl-C.
+fn soundcheck(config: &Config) {
+    let mut tuner = load_tuner(config);
+    prepare_tuner(&mut tuner, &config.midi_file);
+    let score = Score::load(&config.midi_file);
+    let mut allocator = config.allocation.allocator(config.pb_range);
+    let steps = soundcheck::sequence(
+        &score,
+        &tuner.snapshots(),
+        allocator.as_mut(),
+        config.pb_range,
+    );
+
+    let mut broadcast_channel = start_websocket_server(config.emit_jsonl);
+    let mut midi_conn = connect_output(config);
+    let interrupt = Arc::new(timer::Interrupt::default());
+    {
+        let interrupt = interrupt.clone();
+        if let Err(e) = ctrlc::set_handler(move || interrupt.raise()) {
+            println!("WARN: Failed to set Ctrl-C interrupt handler: {}", e);
+        }
+    }
+    reset(midi_conn.as_mut(), &mut broadcast_channel);
+
+    for (i, step) in steps.iter().enumerate() {
+        println!("[{}/{}] {}", i + 1, steps.len(), step.description);
+        midi_conn.retune(&step.snapshot);
+        for message in &step.on {
+            midi_conn.send(message);
+        }
+        interrupt.sleep(Duration::from_secs_f64(step.seconds));
+        for message in &step.off {
+            midi_conn.send(message);
+        }
+        if interrupt.is_raised() {
+            break;
+        }
+    }
+
+    reset(midi_conn.as_mut(), &mut broadcast_channel);
+    if interrupt.is_raised() {
+        println!("Soundcheck stopped");
+    } else {
+        println!("Soundcheck done");
+    }
+}
 
+/// Steps through the tuning changes of the MIDI file of `config` on the console (see [`walk`]), until stopped with q.
+fn walk(config: &Config) {
+    let score = Score::load(&config.midi_file);
+    let mut tuner = load_tuner(config);
+    prepare_tuner(&mut tuner, &config.midi_file);
+    let snapshots = tuner.snapshots();
+
+    let mut idx = 0;
+    loop {
+        print!("{}", walk::report(&score, &tuner, &snapshots, idx));
+        println!("Enter n (next), p (previous), an index or q...");
+        let mut input = String::new();
+        if stdin().read_line(&mut input).unwrap() == 0 {
+            break;
+        }
+        idx = match input.trim() {
+            "" | "n" if idx + 1 < snapshots.len() => idx + 1,
+            "" | "n" => {
+                println!("That was the last tuning change");
+                idx
+            }
+            "p" => idx.saturating_sub(1),
+            "q" => break,
+            other => match other.parse::<usize>() {
+                Ok(n) if n < snapshots.len() => n,
+                _ => {
+                    println!(
+                        "Expected n, p, q or an index from 0 to {}",
+                        snapshots.len() - 1
+                    );
+                    idx
+                }
+            },
+        };
+    }
+}
+
+/// Retunes a live MIDI performance of the MIDI file of `config` played on [`LIVE_MIDI_INPUT_NAME`]. Each chord played is
+/// followed in the score (see [`follow`]), and the tuning in effect at its position is applied before its notes are
+/// forwarded to the output, on the channel of their pitch class as in playback. Retunes thus land exactly with the
+/// performer's timing, however much rubato. Notes that can't be followed are played with the current tuning.
+fn live(config: &Config) {
+    let mut broadcast_channel = start_websocket_server(config.emit_jsonl);
+    let mut midi_conn = connect_output(config);
+    let (_input_conn, messages) = connect_input();
+
+    let exit_flag = Arc::new(Mutex::new(false));
     {
         let exit_flag = exit_flag.clone();
-        let res = ctrlc::set_handler(move || {
-            if let Ok(mut exit_flag) = exit_flag.lock() {
-                *exit_flag = true;
+        if let Err(e) = ctrlc::set_handler(move || *exit_flag.lock().unwrap() = true) {
+            println!("WARN: Failed to set Ctrl-C interrupt handler: {}", e);
+        }
+    }
+
+    let score = Score::load(&config.midi_file);
+    let mut tuner = load_tuner(config);
+    preflight_check(&tuner, &config.midi_file);
+    prepare_tuner(&mut tuner, &config.midi_file);
+    let mut follower =
+        follow::ScoreFollower::new(&score, config.start_from, config.speed, follow::played_in);
+
+    reset(midi_conn.as_mut(), &mut broadcast_channel);
+
+    let mut position = config.start_from;
+    let mut curr = CurrentTuning::default();
+    // When the chord being played started. Only its first note is followed, the rest are assumed to be in it.
+    let mut chord_start: Option<Instant> = None;
+    let mut pedals = [u7::from(0); 3];
+    let mut note_channels = NoteChannels::new();
+    let mut allocator = config.allocation.allocator(config.pb_range);
+
+    println!("Play from {}s on, press Ctrl-C to stop", config.start_from);
+    while !*exit_flag.lock().unwrap() {
+        let Ok((instant, raw)) = messages.recv_timeout(Duration::from_millis(100)) else {
+            continue;
+        };
+        let Ok(LiveEvent::Midi { message, .. }) = LiveEvent::parse(&raw) else {
+            continue;
+        };
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel > 0 => {
+                let in_chord = chord_start.is_some_and(|start| {
+                    instant.duration_since(start).as_secs_f64() <= ONSET_TOLERANCE
+                });
+                if !in_chord {
+                    chord_start = Some(instant);
+                    if let Some(time) = follower.matched(&Onset::note(instant, key.as_int())) {
+                        position = time;
+                    }
+                }
+
+                // Apply the tuning at the followed position before playing the note. Seeking merges all the tunings
+                // before it, so only seek once the position has moved to another tuning.
+                let moved = tuner.index_at(position) != tuner.current_index();
+                if let Some(tuning_data) = moved.then(|| tuner.seek(position)).flatten() {
+                    println!("{} (@ {position:.3}s)", tuning_data.describe());
+                    curr.apply(&tuning_data);
+                    let drift = drift::cents_at(REFERENCE_DRIFT, position);
+                    let tuning = drift::drifted(&tuning_data.tuning, drift);
+                    midi_conn.retune(&TuningSnapshot::new(position, tuning));
+                    for pb_raw_msg in allocator.retune(&tuning, &note_channels) {
+                        midi_conn.send(&pb_raw_msg);
+                    }
+                    for message in &tuning_data.extra_messages {
+                        midi_conn.send(message);
+                    }
+                    if let (true, Some(annotation)) = (config.visualizer, tuning_data.annotation())
+                    {
+                        let res = executor::block_on(broadcast_channel.send(
+                            &VisualizerMessage::Tuning {
+                                time: tuning_data.time,
+                                pitches: curr.pitches(),
+                                annotation,
+                            },
+                        ));
+                        if let Err(e) = res {
+                            println!(
+                                "WARN: Failed to send message to visualizer broadcast channel: {}",
+                                e
+                            );
+                        }
+                    }
+                }
+
+                let pc = pitch_class(key.as_int());
+                let vel = offset_velocity(vel, curr.velocity_offsets[pc]);
+                let tuning =
+                    drift::drifted(&curr.tuning, drift::cents_at(REFERENCE_DRIFT, position));
+                let channel = allocate_note(
+                    midi_conn.as_mut(),
+                    &mut note_channels,
+                    allocator.as_mut(),
+                    key.as_int(),
+                    vel.as_int(),
+                    &tuning,
+                );
+                send_note_on(midi_conn.as_mut(), channel, key, vel);
+                if let (true, Some(monzo)) = (config.visualizer, &curr.monzos[pc]) {
+                    let res =
+                        executor::block_on(broadcast_channel.send(&VisualizerMessage::NoteOn {
+                            edosteps_from_a4: key.as_int() as i32 - 69,
+                            velocity: vel,
+                            cents: cents_from_12edo(&curr.tuning, pc),
+                            ratio: ratio_name(&curr.tuning, &curr.spellings, pc, curr.root),
+                            monzo: key_monzo(monzo, key.as_int()),
+                            name: spelled_key_name(&curr.spellings, key.as_int()),
+                        }));
+                    if let Err(e) = res {
+                        println!(
+                            "WARN: Failed to send message to visualizer broadcast channel: {}",
+                            e
+                        );
+                    }
+                }
             }
+            MidiMessage::NoteOn { key, vel } | MidiMessage::NoteOff { key, vel } => {
+                let channel = note_channels.note_off(key.as_int(), allocator.as_ref());
+                send_note_off(midi_conn.as_mut(), channel, key, vel);
+                if config.visualizer {
+                    let res =
+                        executor::block_on(broadcast_channel.send(&VisualizerMessage::NoteOff {
+                            edosteps_from_a4: key.as_int() as i32 - 69,
+                            velocity: vel,
+                        }));
+                    if let Err(e) = res {
+                        println!(
+                            "WARN: Failed to send message to visualizer broadcast channel: {}",
+                            e
+                        );
+                    }
+                }
+            }
+            MidiMessage::Controller { controller, value } => {
+                note_channels.pedal(controller.as_int(), value.as_int());
+                send_controller(midi_conn.as_mut(), controller, value);
+                send_controller_state(&mut broadcast_channel, controller, value);
+                if let Some(idx) = PEDAL_CCS.iter().position(|&cc| cc == controller.as_int()) {
+                    pedals[idx] = value;
+                    send_pedal_state(&mut broadcast_channel, pedals);
+                }
+            }
+            // The keyboard's pitch bends would fight the tuning's.
+            _ => {}
+        }
+    }
+
+    mdns::withdraw();
+    println!("Reset & closing connection...");
+    reset(midi_conn.as_mut(), &mut broadcast_channel);
+}
+
+/// Records the times of tuning changes in the tuning file of `config` by tapping along to playback of its MIDI file (or
+/// a recorded take if `args` is `take`), then writes them back to the file.
+fn align(args: &[String], config: &Config) {
+    let tuning_file = &config.tuning_file;
+    let text = fs::read_to_string(tuning_file).unwrap();
+    // In order of appearance in the file, unlike the tuner.
+    let tunings = tuning_file::parse(&text, tuning_file, &config.variants, config.pb_range)
+        .unwrap_or_else(|errors| tuning_file::exit_with_errors(&errors, tuning_file));
+    let start_from = config.start_from;
+
+    let times = match args.first().map(String::as_str) {
+        Some("take") => {
+            println!("Press enter when the take reaches {start_from}s...");
+            stdin().read_line(&mut String::new()).unwrap();
+            let start = Instant::now();
+            align::tap(&tunings, start_from, || {
+                start_from + start.elapsed().as_secs_f64()
+            })
+        }
+        None => {
+            let mut tapper = None;
+            let piece = config.piece();
+            let stage = Stage::new(&piece, &config.variants, false, config);
+            let overrides = Overrides::new(config);
+            play(
+                &piece,
+                &config.variants,
+                &overrides,
+                &stage,
+                &[],
+                config,
+                |transport| {
+                    let tunings = tunings.clone();
+                    tapper = Some(thread::spawn(move || {
+                        let times = align::tap(&tunings, start_from, || transport.time());
+                        println!("Done tapping, press Ctrl-C to stop playback");
+                        times
+                    }));
+                },
+            );
+            let Some(tapper) = tapper else {
+                return;
+            };
+            if !tapper.is_finished() {
+                println!("Playback finished, enter q to stop tapping");
+            }
+            tapper.join().unwrap()
+        }
+        Some(arg) => {
+            println!("Invalid argument: {arg}\n\n{COMMANDS}");
+            exit(1);
+        }
+    };
+
+    let tapped = times.iter().flatten().count();
+    if tapped == 0 {
+        println!("No tuning changes tapped");
+        return;
+    }
+    println!("Write {tapped} tapped times to {tuning_file}? [y/N]");
+    let mut input = String::new();
+    stdin().read_line(&mut input).unwrap();
+    if input.trim().eq_ignore_ascii_case("y") {
+        fs::write(tuning_file, tuning_file::set_times(&text, &times)).unwrap();
+        println!("Wrote {tuning_file}");
+    }
+}
+
+/// Plays the MIDI file of `config` around each of its tuning changes in turn (see [`preview`]), or the ones in the range
+/// `args[1]`, for `args[0]` seconds (default [`PREVIEW_SECONDS`]) either side.
+fn preview(args: &[String], config: &Config) {
+    let seconds = match args.first() {
+        Some(arg) => arg
+            .parse::<f64>()
+            .ok()
+            .filter(|s| *s > 0.0)
+            .unwrap_or_else(|| {
+                println!("Invalid number of seconds: {arg}\n\n{COMMANDS}");
+                exit(1);
+            }),
+        None => PREVIEW_SECONDS,
+    };
+    let tuner = load_tuner(config);
+    let range = match args.get(1) {
+        Some(arg) => preview::parse_range(arg).unwrap_or_else(|| {
+            println!("Invalid range of tuning changes: {arg}\n\n{COMMANDS}");
+            exit(1);
+        }),
+        None => (0, tuner.len()),
+    };
+    let windows = preview::windows(&tuner, seconds, range);
+    if windows.is_empty() {
+        println!(
+            "No tuning changes to preview in {}-{} of {}",
+            range.0,
+            range.1,
+            tuner.len()
+        );
+        return;
+    }
+    let piece = config.piece();
+    let stage = Stage::new(&piece, &config.variants, false, config);
+    let mut broadcast_channel = stage.broadcast_channel.clone();
+    play(
+        &piece,
+        &config.variants,
+        &Overrides::new(config),
+        &stage,
+        &windows,
+        config,
+        |_| {
+            preview::announce(&mut broadcast_channel, &windows[0], 0, windows.len());
+        },
+    );
+}
+
+/// Captures the messages of a performance of the MIDI file of `config` (see [`golden`]) as the golden run at the path
+/// `args[0]` (default `golden.txt` in [`EXPORT_DIR`]) if there is none, or compares them against it. Exits with an
+/// error on a divergence, e.g. for CI.
+fn golden(args: &[String], config: &Config) {
+    let path = args
+        .first()
+        .cloned()
+        .unwrap_or_else(|| format!("{EXPORT_DIR}/golden.txt"));
+    let mut capture = golden::Capture::default();
+    perform_offline(
+        &config.midi_file,
+        load_tuner(config),
+        &mut capture,
+        config,
+        |capture, time| capture.advance(config.start_from + time),
+    );
+    if !std::path::Path::new(&path).exists() {
+        if let Some(dir) = std::path::Path::new(&path).parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        golden::write(&path, &capture.messages);
+        println!(
+            "Captured {} messages as the golden run {path}",
+            capture.messages.len()
+        );
+        return;
+    }
+    let expected = golden::load(&path);
+    match golden::first_divergence(&expected, &capture.messages) {
+        Some(idx) => {
+            let score = Score::load(&config.midi_file);
+            print!(
+                "{}",
+                golden::divergence_report(&score, &expected, &capture.messages, idx)
+            );
+            println!("Delete {path} to capture this run as the golden run instead");
+            exit(1);
+        }
+        None => println!(
+            "The {} messages match the golden run {path}",
+            expected.len()
+        ),
+    }
+}
+
+/// Snaps the times of tuning changes in the tuning file of `config` to the note onsets & rests of its MIDI file just
+/// before them (see [`SNAP_TOLERANCE`]), then writes them back to the file.
+fn snap(config: &Config) {
+    let tuning_file = &config.tuning_file;
+    let text = fs::read_to_string(tuning_file).unwrap();
+    // In order of appearance in the file, unlike the tuner.
+    let tunings = tuning_file::parse(&text, tuning_file, &config.variants, config.pb_range)
+        .unwrap_or_else(|errors| tuning_file::exit_with_errors(&errors, tuning_file));
+    let score = Score::load(&config.midi_file);
+    let snapped = analysis::snap_times(&score, &tunings, SNAP_TOLERANCE);
+    print!("{}", analysis::snap_report(&score, &snapped));
+
+    let count = snapped.iter().flatten().count();
+    if count == 0 {
+        println!("No tuning changes within {SNAP_TOLERANCE}s after a note onset or rest");
+        return;
+    }
+    println!("Write {count} snapped times to {tuning_file}? [y/N]");
+    let mut input = String::new();
+    stdin().read_line(&mut input).unwrap();
+    if input.trim().eq_ignore_ascii_case("y") {
+        let times: Vec<Option<f64>> = snapped
+            .iter()
+            .map(|snapped| snapped.as_ref().map(|s| s.to))
+            .collect();
+        fs::write(tuning_file, tuning_file::set_times(&text, &times)).unwrap();
+        println!("Wrote {tuning_file}");
+    }
+}
+
+/// Senders of the controls of playback, see [`read_controls`].
+#[derive(Clone)]
+struct PlaybackControls {
+    /// The piece being played & its variants.
+    performing: Arc<Mutex<(Piece, Vec<String>)>>,
+    /// Tuners to switch to at the next tuning change.
+    variant_switches: mpsc::Sender<Tuner>,
+    /// Advances past the cue playback is paused at in conductor mode (see [`CONDUCTOR_MODE`]).
+    advance: mpsc::Sender<()>,
+    /// Seconds to move the next tuning change by.
+    nudges: mpsc::Sender<f64>,
+    /// Pitch bend range that the tuning files are loaded for, see [`Config::pb_range`].
+    pb_range: u16,
+}
+
+/// Reads the controls of playback from stdin:
+/// - A variant name toggles the variant of the piece being played, sending the reloaded tuning file to be switched to
+///   at the next tuning change. The toggle is ignored with a warning if the tuning file is invalid with it.
+/// - An empty line (enter) starts playback, or advances past the cue playback is paused at in conductor mode.
+/// - `+` or `-` nudges the next tuning change [`NUDGE_STEP`] seconds later or earlier (repeat for more, e.g. `--`).
+///   The nudged times are written back to [`TUNING_FILE`] when playback stops.
+fn read_controls(controls: PlaybackControls) {
+    println!("Enter a variant name to toggle it from the next tuning change, or +/- to nudge the next tuning change");
+    for line in stdin().lines() {
+        let name = line.unwrap().trim().to_string();
+        if name.is_empty() {
+            let _ = controls.advance.send(());
+            continue;
+        }
+        if name.chars().all(|c| c == '+') || name.chars().all(|c| c == '-') {
+            let steps = name.len() as f64 * if name.starts_with('+') { 1.0 } else { -1.0 };
+            let _ = controls.nudges.send(steps * NUDGE_STEP);
+            continue;
+        }
+        let mut performing = controls.performing.lock().unwrap();
+        let (piece, variants) = &mut *performing;
+        let mut toggled = variants.clone();
+        match toggled.iter().position(|v| *v == name) {
+            Some(i) => {
+                toggled.remove(i);
+            }
+            None => toggled.push(name),
+        }
+        // The file may be invalid with the toggled variants (or edited since playback started), in which case
+        // playback keeps its current tuner.
+        let mut tuner = match tuning_file::load(&piece.tuning_file, &toggled, controls.pb_range) {
+            Ok(tuner) => tuner,
+            Err(errors) => {
+                tuning_file::print_errors(&errors, &piece.tuning_file);
+                println!("WARN: Keeping the current tuning, with variants {variants:?}");
+                continue;
+            }
+        };
+        *variants = toggled;
+        prepare_tuner(&mut tuner, &piece.midi_file);
+        drop(performing);
+        if controls.variant_switches.send(tuner).is_err() {
+            break;
+        }
+    }
+}
+
+/// Prints the issues of `tuner` as the tuning timeline of `midi_file` (see [`preflight`]), if any.
+fn preflight_check(tuner: &Tuner, midi_file: &str) {
+    let score = Score::load(midi_file);
+    let issues = preflight::check(&score, tuner);
+    if !issues.is_empty() {
+        println!(
+            "WARN: {} pre-flight issue(s) with the tuning timeline of {midi_file}:",
+            issues.len()
+        );
+        print!("{}", preflight::report(&score, tuner, &issues));
+    }
+}
+
+/// Applies playback options that modify the tuning data of `midi_file` before playing it back.
+fn prepare_tuner(tuner: &mut Tuner, midi_file: &str) {
+    if let Some(edo) = QUANTIZE_TO_EDO {
+        tuner.quantize(edo);
+        println!("Quantized all tunings to {edo}edo");
+    }
+    if !TUNING_LAYERS.is_empty() {
+        layers::apply(tuner, TUNING_LAYERS);
+        println!(
+            "Combined {} tuning layers with the timeline",
+            TUNING_LAYERS.len()
+        );
+    }
+    if DEFER_SUSTAINED_RETUNES != DeferPolicy::Never
+        || (0..tuner.len()).any(|i| tuner[i].defer.is_some())
+    {
+        let score = Score::load(midi_file);
+        let delayed = analysis::retune_on_silence(
+            &score,
+            tuner,
+            DEFER_SUSTAINED_RETUNES,
+            RETUNE_ON_SILENCE_MAX_DELAY,
+        );
+        let retunes = analysis::sustained_retunes(&score, tuner);
+        let deferred = analysis::defer_sustained_retunes(tuner, &retunes, DEFER_SUSTAINED_RETUNES);
+        println!(
+            "Delayed {delayed} tuning changes until silence, deferred {deferred} sustained retunes"
+        );
+    }
+}
+
+/// Returns the events of `track` of `midi_file` with the timing of its notes humanized as configured by [`HUMANIZE`],
+/// after the tuning changes of `tuner` have been prepared (see [`prepare_tuner`]) so that no note is moved across one.
+fn humanized_track<'a>(
+    track: &[TrackEvent<'a>],
+    tuner: &Tuner,
+    midi_file: &str,
+) -> Vec<TrackEvent<'a>> {
+    match HUMANIZE {
+        Some(config) => {
+            let tuning_times: Vec<f64> = (0..tuner.len()).map(|i| tuner[i].time).collect();
+            println!(
+                "Humanizing note timing by up to {}ms with {} swing (seed {})",
+                config.jitter_ms, config.swing, config.seed
+            );
+            humanize::humanize(track, &Score::load(midi_file), &tuning_times, &config)
+        }
+        None => track.to_vec(),
+    }
+}
+
+/// Returns `track` with the events at the same tick in the order they should be sent in together: meta events (e.g.
+/// tempo changes), pitch bends, other controllers, note offs, then note ons, so that notes ending & starting at the same
+/// time don't cut each other off. A note off of a key that wasn't sounding before the tick ends a note started at the
+/// tick, so it is kept after the note ons. The end of the track is kept last.
+fn batched_track(track: Vec<TrackEvent>) -> Vec<TrackEvent> {
+    // Whether each key of each channel is sounding before the current tick.
+    let mut sounding = [[false; 128]; 16];
+    let mut batched = Vec::with_capacity(track.len());
+    for group in track.chunk_by(|_, next| next.delta == 0) {
+        let rank = |event: &TrackEvent| match event.kind {
+            TrackEventKind::Meta(MetaMessage::EndOfTrack) => 6,
+            TrackEventKind::Midi {
+                message: MidiMessage::PitchBend { .. },
+                ..
+            } => 1,
+            TrackEventKind::Midi {
+                message: MidiMessage::NoteOn { vel, .. },
+                ..
+            } if vel > 0 => 4,
+            TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. },
+            } => match sounding[channel.as_int() as usize][key.as_int() as usize] {
+                true => 3,
+                false => 5,
+            },
+            TrackEventKind::Midi { .. } => 2,
+            _ => 0,
+        };
+        let mut events = group.to_vec();
+        events.sort_by_key(rank);
+        for event in group {
+            if let TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn { key, vel } | MidiMessage::NoteOff { key, vel },
+            } = event.kind
+            {
+                let is_on = matches!(
+                    event.kind,
+                    TrackEventKind::Midi {
+                        message: MidiMessage::NoteOn { .. },
+                        ..
+                    }
+                );
+                sounding[channel.as_int() as usize][key.as_int() as usize] = is_on && vel > 0;
+            }
+        }
+        for (i, mut event) in events.into_iter().enumerate() {
+            event.delta = if i == 0 { group[0].delta } else { 0.into() };
+            batched.push(event);
+        }
+    }
+    batched
+}
+
+/// Returns the pitch bends of the expressive envelopes in `tuner` (see [`envelope::bends`]), without loading
+/// `midi_file` if there are none.
+fn envelope_bends(tuner: &Tuner, midi_file: &str) -> Vec<envelope::EnvelopeBend> {
+    if (0..tuner.len()).any(|i| !tuner[i].envelopes.is_empty()) {
+        envelope::bends(&Score::load(midi_file), tuner)
+    } else {
+        vec![]
+    }
+}
+
+/// Renders playback of the MIDI file of `config` with the built-in synth to a WAV file, as fast as possible.
+///
+/// `args` is an optional output path, which defaults to `render.wav` in [`EXPORT_DIR`].
+#[cfg(feature = "render")]
+fn render(args: &[String], config: &Config) {
+    let path = args
+        .first()
+        .cloned()
+        .unwrap_or_else(|| format!("{EXPORT_DIR}/render.wav"));
+    render_file(&config.midi_file, load_tuner(config), &path, config);
+}
+
+/// Renders playback of `midi_file` tuned by `tuner` with the built-in synth to a WAV file at `path`, as played with
+/// `config`. Returns the duration of the audio in seconds.
+#[cfg(feature = "render")]
+fn render_file(midi_file: &str, tuner: Tuner, path: &str, config: &Config) -> f64 {
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        fs::create_dir_all(dir).unwrap();
+    }
+
+    let instrument = instrument(RENDER_SAMPLE_RATE as f64, config.pb_range);
+    let mut renderer = synth::WavRenderer::create(path, RENDER_SAMPLE_RATE, instrument);
+    perform_offline(
+        midi_file,
+        tuner,
+        &mut renderer,
+        config,
+        synth::WavRenderer::render_until,
+    );
+    let duration = renderer.finish(RENDER_TAIL);
+    println!("Rendered {duration:.1}s of audio to {path}");
+    duration
+}
+
+/// Performs `midi_file` tuned by `tuner` to `sink` as fast as possible, as playback with `config` would from its start
+/// point. `advance` is called with the time of the messages about to be sent (in seconds from the start point), e.g.
+/// to render the audio up to then.
+fn perform_offline<S: MidiSink>(
+    midi_file: &str,
+    mut tuner: Tuner,
+    sink: &mut S,
+    config: &Config,
+    mut advance: impl FnMut(&mut S, f64),
+) {
+    let midi_file_raw_bytes = fs::read(midi_file).unwrap();
+    let smf = Smf::parse(&midi_file_raw_bytes).unwrap();
+    assert!(
+        smf.tracks.len() == 1,
+        "Only single-track MIDI files are supported at this time"
+    );
+    let ppqn = match smf.header.timing {
+        midly::Timing::Metrical(ppqn) => ppqn.as_int(),
+        midly::Timing::Timecode(_frame_per_second, _subframes) => {
+            panic!("Timecode MIDI files are not supported at this time");
+        }
+    };
+
+    preflight_check(&tuner, midi_file);
+    prepare_tuner(&mut tuner, midi_file);
+    let track = batched_track(humanized_track(&smf.tracks[0], &tuner, midi_file));
+    // Offline performances are rendered, so their notes are always sent, without debug output.
+    let offline = Config {
+        midi: true,
+        debug: false,
+        ..config.clone()
+    };
+    let mut performance = Performance::new(tuner, None, midi_file, &offline, None);
+
+    let mut curr_tick = 0;
+    let mut curr_bpm = 120f64;
+    let mut expected_curr_time = 0f64;
+    let start_from = config.start_from;
+
+    for event in track.iter() {
+        curr_tick += event.delta.as_int();
+        let delta_crochets = (event.delta.as_int() as f64) / (ppqn as f64);
+        expected_curr_time += delta_crochets * (60f64 / curr_bpm);
+
+        while let Some(bend) = performance.bends.next_before(expected_curr_time) {
+            if bend.time >= start_from {
+                advance(sink, bend.time - start_from);
+                performance.bend(sink, &bend);
+            }
+        }
+        // Like the controllers of the MIDI file, automation before the start point is sent right away.
+        while let Some(event) = performance.automation.next_before(expected_curr_time) {
+            advance(sink, (event.time - start_from).max(0.0));
+            performance.automate(sink, &event);
+        }
+        while let Some(step) = performance.drift_steps.next_before(expected_curr_time) {
+            advance(sink, (step.time - start_from).max(0.0));
+            performance.drift_to(sink, &step);
+        }
+        advance(sink, expected_curr_time - start_from);
+
+        let changes = performance.update(expected_curr_time, || None);
+        performance.retune(sink, &changes, expected_curr_time);
+
+        match event.kind {
+            TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                curr_bpm = 60_000_000f64 / (tempo.as_int() as f64);
+            }
+            TrackEventKind::Midi { message, .. } => match message {
+                MidiMessage::NoteOn { key, vel } if vel > 0 && expected_curr_time >= start_from => {
+                    performance.note_on(sink, key, vel, curr_tick, expected_curr_time);
+                }
+                MidiMessage::NoteOn { key, vel } | MidiMessage::NoteOff { key, vel }
+                    if expected_curr_time >= start_from =>
+                {
+                    performance.note_off(sink, key, vel);
+                }
+                MidiMessage::Controller { controller, value } => {
+                    performance.controller(sink, controller, value, true);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Plays the pieces loaded by websocket clients one after another (see [`PIECES`]), or the MIDI file of `config` once
+/// if there are none, until stopped with Ctrl-C. Plays the pieces of the setlist at the path `args[0]` in order
+/// instead, if given (see [`setlist`]). Plays the MIDI file of `config` once instead of the pieces if it was given (see
+/// [`Config::file_given`]), e.g. by a project file (see [`project`]). Each is played with `config`.
+fn concert(args: &[String], config: &Config) {
+    assert!(
+        PIECES.is_empty()
+            || !matches!(SYNC, SyncRole::Follower(_)) && matches!(LIVE_FOLLOW, LiveInput::Off),
+        "Pieces can't be played while following a sync master or performer"
+    );
+    if args.len() > 1 {
+        println!("Too many arguments\n\n{COMMANDS}");
+        exit(1);
+    }
+    let once = |piece: Piece| {
+        let overrides = Overrides::new(config);
+        Some(vec![setlist::Entry {
+            piece,
+            variants: config.variants.clone(),
+            overrides,
+        }])
+    };
+    let setlist = match args.first() {
+        Some(path) => Some(setlist::load(path, PIECES, config)),
+        None if config.file_given => once(config.piece()),
+        None => None,
+    };
+    let first = match &setlist {
+        Some(setlist) => (setlist[0].piece.clone(), setlist[0].variants.clone()),
+        None => (
+            PIECES.first().cloned().unwrap_or_else(|| config.piece()),
+            config.variants.clone(),
+        ),
+    };
+    let stage = Stage::new(&first.0, &first.1, true, config);
+    if let Some(setlist) = setlist {
+        // The setlist decides what is played next rather than the desk, so pieces aren't listened for.
+        for entry in &setlist {
+            play(
+                &entry.piece,
+                &entry.variants,
+                &entry.overrides,
+                &stage,
+                &[],
+                config,
+                |_| {},
+            );
+            if stage.interrupt.is_raised() {
+                break;
+            }
+        }
+        mdns::withdraw();
+        return;
+    }
+    if !PIECES.is_empty() {
+        pieces::listen(PIECES, stage.broadcast_channel.clone(), config.pb_range);
+    }
+    loop {
+        let (piece, variants) = pieces::loaded().unwrap_or_else(|| first.clone());
+        play(
+            &piece,
+            &variants,
+            &Overrides::new(config),
+            &stage,
+            &[],
+            config,
+            |_| {},
+        );
+        if PIECES.is_empty() || stage.interrupt.is_raised() {
+            break;
+        }
+        // Unless a client loaded another piece meanwhile, or playback returned without playing for it.
+        let played = pieces::loaded().is_none_or(|(p, v)| p == piece && v == variants);
+        if let (EndOfTrack::Advance, true) = (END_OF_TRACK, played) {
+            // Pieces with an invalid tuning file are skipped.
+            let loaded_next = PIECES
+                .iter()
+                .skip_while(|p| **p != piece)
+                .skip(1)
+                .any(|next| {
+                    pieces::load(
+                        &mut stage.broadcast_channel.clone(),
+                        next,
+                        vec![],
+                        config.pb_range,
+                    )
+                });
+            if !loaded_next {
+                println!("Played the last piece of the concert");
+                break;
+            }
+        }
+    }
+    mdns::withdraw();
+}
+
+/// Where the messages of a MIDI input are sent to, if anywhere.
+type InputRoute = Arc<Mutex<Option<mpsc::Sender<(Instant, Vec<u8>)>>>>;
+
+/// What stays up across performances: the output, the visualizer & API servers, and the controls of playback.
+struct Stage {
+    output: output::Shared,
+    /// MIDI input of the tap tempo & conductor mode, and where its messages are sent to, see [`Stage::midi_input`].
+    input: Option<(MidiInputConnection<()>, InputRoute)>,
+    broadcast_channel: BroadcastChannel<VisualizerMessage>,
+    /// Raised by Ctrl-C (or a sync master stopping), waking playback up from the sleep until the next event.
+    interrupt: Arc<timer::Interrupt>,
+    api: Option<Arc<api::Playback>>,
+    controls: PlaybackControls,
+    /// Starts playback while it is waiting, and advances past the cues of conductor mode.
+    advances: mpsc::Receiver<()>,
+    variant_switches: mpsc::Receiver<Tuner>,
+    nudges: mpsc::Receiver<f64>,
+    tuning_edits: mpsc::Receiver<edits::SetTuning>,
+    /// Whether the controls are read from stdin, see [`read_controls`].
+    reading_controls: bool,
+}
+
+impl Stage {
+    /// Sets up the stage to play `piece` with `variants` first with `config`, reading the controls of playback from
+    /// stdin on a new thread if `read_controls`.
+    fn new(piece: &Piece, variants: &[String], read_controls: bool, config: &Config) -> Stage {
+        let broadcast_channel = start_websocket_server(config.emit_jsonl);
+        // Connected before the controls are read, as it may ask for the output on stdin.
+        let mut output = connect_output(config);
+        if let Some(addr) = METRICS_ADDR {
+            metrics::serve(addr);
+            output = Box::new(metrics::Metered(output));
+        }
+        let tapping = !matches!(TAP_TEMPO, TapInput::Off);
+        let conducting_by_midi = CONDUCTOR_MODE && !matches!(CONDUCTOR_ADVANCE, TapInput::Off);
+        let input = (tapping || conducting_by_midi).then(|| {
+            let (conn, messages) = connect_input();
+            let route = InputRoute::default();
+            {
+                let route = route.clone();
+                thread::spawn(move || {
+                    for message in messages {
+                        if let Some(sender) = &*route.lock().unwrap() {
+                            let _ = sender.send(message);
+                        }
+                    }
+                });
+            }
+            (conn, route)
         });
-        if let Err(e) = res {
-            println!("WARN: Failed to set Ctrl-C interrupt handler: {}", e);
+
+        let interrupt = Arc::new(timer::Interrupt::default());
+        {
+            let interrupt = interrupt.clone();
+            let res = ctrlc::set_handler(move || interrupt.raise());
+            if let Err(e) = res {
+                println!("WARN: Failed to set Ctrl-C interrupt handler: {}", e);
+            }
+        }
+
+        let (advance_sender, advances) = mpsc::channel();
+        if CONDUCTOR_MODE || !PIECES.is_empty() {
+            conductor::advance_on_websocket(advance_sender.clone());
         }
+        let api = API_ADDR
+            .map(|addr| api::serve(addr, advance_sender.clone(), Score::load(&piece.midi_file)));
+
+        let (variant_switch_sender, variant_switches) = mpsc::channel();
+        let (nudge_sender, nudges) = mpsc::channel();
+        let controls = PlaybackControls {
+            performing: Arc::new(Mutex::new((piece.clone(), variants.to_vec()))),
+            variant_switches: variant_switch_sender,
+            advance: advance_sender,
+            nudges: nudge_sender,
+            pb_range: config.pb_range,
+        };
+        if read_controls {
+            let controls = controls.clone();
+            thread::spawn(move || crate::read_controls(controls));
+        }
+
+        Stage {
+            output: output::Shared(Rc::new(RefCell::new(output))),
+            input,
+            broadcast_channel,
+            interrupt,
+            api,
+            controls,
+            advances,
+            variant_switches,
+            nudges,
+            tuning_edits: edits::listen(),
+            reading_controls: read_controls,
+        }
+    }
+
+    /// Returns the messages of the MIDI input from now on, if connected, ending the ones returned before.
+    fn midi_input(&self) -> Option<mpsc::Receiver<(Instant, Vec<u8>)>> {
+        let (_, route) = self.input.as_ref()?;
+        let (sender, messages) = mpsc::channel();
+        *route.lock().unwrap() = Some(sender);
+        Some(messages)
+    }
+}
+
+/// Realtime playback of `piece` with `variants` to the MIDI output & visualizer as configured by `config`, with the
+/// `overrides` of a setlist.
+///
+/// `on_start` is called with the transport of playback when it reaches the start point (or the master's position, see
+/// [`SYNC`]). Returns without playing if a client loads another piece while waiting to start (see [`pieces`]).
+///
+/// Only the `preview` windows are played, one after another, unless there are none (see [`preview`]).
+fn play(
+    piece: &Piece,
+    variants: &[String],
+    overrides: &Overrides,
+    stage: &Stage,
+    preview: &[preview::Window],
+    config: &Config,
+    on_start: impl FnOnce(Transport),
+) {
+    let mut broadcast_channel = stage.broadcast_channel.clone();
+
+    // -----------------------------------------------------------------------------------------------------------------
+
+    let mut midi_conn: Box<dyn MidiSink> = Box::new(stage.output.clone());
+    let sleeper = timer::Sleeper::new(WINDOWS_TIMER_PERIOD);
+
+    // The tuning file is only parsed once per performance: the tuner plays its tunings, and nudges & tuning edits are
+    // written back to its text.
+    let Some((tuning_text, file_tunings)) = playable(
+        tuning_file::read(&piece.tuning_file, variants, config.pb_range),
+        &piece.tuning_file,
+    ) else {
+        return;
+    };
+    // Prepared ahead of playback, so that the history can be queried with the API while waiting to start.
+    let mut tuner = Tuner::new(file_tunings.clone(), config.pb_range);
+    preflight_check(&tuner, &piece.midi_file);
+    prepare_tuner(&mut tuner, &piece.midi_file);
+
+    let api = stage.api.as_ref();
+    if let Some(api) = api {
+        api.waiting(Score::load(&piece.midi_file));
+        api.set_timeline(&tuner);
     }
+    let interrupt = &stage.interrupt;
+    metrics::set_tuning_index(None);
+    *stage.controls.performing.lock().unwrap() = (piece.clone(), variants.to_vec());
+    // Controls sent between performances were meant for the previous one.
+    stage.variant_switches.try_iter().for_each(drop);
+    stage.nudges.try_iter().for_each(drop);
+    stage.tuning_edits.try_iter().for_each(drop);
+    pieces::announce(&mut broadcast_channel, piece, variants, file_tunings.len());
 
     // -----------------------------------------------------------------------------------------------------------------
 
-    let midi_file_raw_bytes = fs::read(MIDI_FILE).unwrap();
+    let midi_file_raw_bytes = fs::read(&*piece.midi_file).unwrap();
     let smf = Smf::parse(&midi_file_raw_bytes).unwrap();
 
-    println!("Loaded MIDI file: {MIDI_FILE}");
+    println!("Loaded MIDI file: {}", &piece.midi_file);
     println!("smf tracks: {}", smf.tracks.len());
 
     assert!(
@@ -124,13 +2032,130 @@ fn main() {
         }
     };
 
-    println!("Press enter to start playing...");
+    let conducting_by_midi = CONDUCTOR_MODE && !matches!(CONDUCTOR_ADVANCE, TapInput::Off);
+
+    // MIDI input of the tap tempo & conductor mode, read from when playback starts.
+    let tapping = !matches!(TAP_TEMPO, TapInput::Off);
+    let mut midi_input = stage.midi_input();
+
+    // Followers start when the master does, from the master's position. Live following starts when the performer does.
+    let following = match SYNC {
+        SyncRole::Follower(addr) => {
+            let interrupt = interrupt.clone();
+            Some(sync::follow(addr, overrides.speed, move || {
+                interrupt.raise()
+            }))
+        }
+        _ => match follow::listen(&LIVE_FOLLOW) {
+            Some(onsets) => {
+                let score = Score::load(&piece.midi_file);
+                Some(follow::follow(
+                    onsets,
+                    &score,
+                    overrides.start_from,
+                    overrides.speed,
+                ))
+            }
+            None => {
+                match (overrides.gap, API_ADDR) {
+                    (Some(0.0), _) => println!("Playing {} attacca", piece.name),
+                    (Some(gap), _) => println!(
+                        "Starting {} in {gap}s, or press enter to start now...",
+                        piece.name
+                    ),
+                    (None, Some(_)) => {
+                        println!(
+                            "Press enter (or POST /play to the API) to start playing {}...",
+                            piece.name
+                        )
+                    }
+                    (None, None) => println!("Press enter to start playing {}...", piece.name),
+                }
+
+                // Advances sent before now were meant for the previous performance.
+                stage.advances.try_iter().for_each(drop);
+                if !stage.reading_controls {
+                    let advance = stage.controls.advance.clone();
+                    thread::spawn(move || {
+                        let mut _void = String::new();
+                        stdin().read_line(&mut _void).unwrap();
+                        let _ = advance.send(());
+                    });
+                }
+                // Started by itself after the gap of a setlist, see [`setlist`].
+                let gap_end = overrides
+                    .gap
+                    .map(|gap| Instant::now() + Duration::from_secs_f64(gap));
+                while stage
+                    .advances
+                    .recv_timeout(Duration::from_millis(100))
+                    .is_err()
+                {
+                    if gap_end.is_some_and(|end| Instant::now() >= end) {
+                        break;
+                    }
+                    let loaded = pieces::loaded().filter(|(p, v)| p != piece || v != variants);
+                    if interrupt.is_raised() || loaded.is_some() {
+                        return;
+                    }
+                }
+                None
+            }
+        },
+    };
+
+    // The keys below a keyboard split are sent to the bank of the lower range, see [`split`].
+    let mut lower_range = None;
+    if let Some(keyboard_split) = &KEYBOARD_SPLIT {
+        let bank = connect_port(
+            keyboard_split.port,
+            midi_conn.lookahead(),
+            "lower range",
+            true,
+            config,
+        );
+        let Some(mut tuner) = playable(
+            tuning_file::load(keyboard_split.tuning_file, variants, config.pb_range),
+            keyboard_split.tuning_file,
+        ) else {
+            return;
+        };
+        prepare_tuner(&mut tuner, &piece.midi_file);
+        let lower = split::LowerRange::new(keyboard_split, bank, tuner);
+        midi_conn = Box::new(lower.router(midi_conn));
+        lower_range = Some(lower);
+    }
 
-    let mut _void = String::new();
-    stdin().read_line(&mut _void).unwrap();
-    drop(_void);
+    let session = SESSION_DB.map(|path| {
+        session::Session::start(
+            path,
+            &piece.midi_file,
+            &piece.tuning_file,
+            overrides.start_from,
+            variants,
+        )
+    });
+    let recorder = session.as_ref().map(session::Session::recorder);
+    if let Some(recorder) = &recorder {
+        midi_conn = Box::new(session::Recorded::new(midi_conn, recorder.clone()));
+    }
 
-    let track = &smf.tracks[0];
+    assert!(
+        !tapping || following.is_none(),
+        "Tap tempo can't be used while following a sync master or performer"
+    );
+    assert!(
+        !CONDUCTOR_MODE || following.is_none(),
+        "Conductor mode can't be used while following a sync master or performer"
+    );
+    assert!(
+        COMPRESS_SILENCE.is_none() || following.is_none(),
+        "Silences can't be compressed while following a sync master or performer"
+    );
+    assert!(
+        preview.is_empty() || following.is_none(),
+        "Tuning changes can't be previewed while following a sync master or performer"
+    );
 
     let mut curr_tick = 0;
     let mut curr_bpm = 120f64;
@@ -138,37 +2163,111 @@ fn main() {
     // Expected curernt time of the current track event.
     let mut expected_curr_time = 0f64;
 
-    // Instant when the file starts playing back.
-    // If we want to start playing halfway, this value is initialized to the time when the first event
+    // Transport of playback, started when the file starts playing back.
+    // If we want to start playing halfway, this value is initialized when the first event
     // that we want to play back is reached.
-    let mut start: Option<Instant> = None;
-
-    // On windows, these are the default settings for SpinSleeper::default(), which are using.
-    //
-    let spin_sleeper =
-        // This crate requests 1ms native accuracy from Windows using timeBeginPeriod/timeEndPeriod,
-        // which should, by right, have 1ms accuracy. Just to be safe, use 2ms.
-        // reduce cpu % (and accuracy) by reducing the number below to like <= 1e6 or sth.
-        SpinSleeper::new(1_000_000)
-        // use x86 PAUSE instruction to notify the CPU that we are in a spin loop
-        .with_spin_strategy(SpinStrategy::SpinLoopHint);
-
-    // No need to make any custom config as the default already works fine.
+    let mut start: Option<Transport> = None;
+    // Position playback starts from, moved by seeking with the API or to the next preview window.
+    let mut start_point = preview
+        .first()
+        .map_or(overrides.start_from, |window| window.from);
+    // Index of the preview window being played.
+    let mut previewing = 0;
+    // Position to seek to next, e.g. the start of the next preview window once one is over.
+    let mut pending_seek = None;
+    // Transport of playback while it starts over to seek.
+    let mut restarted: Option<Transport> = None;
+    let mut on_start = Some(on_start);
+
+    // Times of the tunings in the tuning file (in order of appearance), as nudged during playback.
+    let mut file_times: Vec<f64> = file_tunings.iter().map(|td| td.time).collect();
+    // Tuning edits of clients, see [`edits`].
+    let mut edits = edits::Edits::default();
+    let notes = Score::load(&piece.midi_file).notes;
+
+    // Outputs that schedule messages are sent them ahead of time, so a plain sleep is accurate enough.
+    let lookahead = midi_conn.lookahead();
+    let sleep = |duration| {
+        if lookahead > 0.0 {
+            interrupt.sleep(duration);
+        } else {
+            sleeper.sleep(duration, interrupt);
+        }
+    };
+    // Whether the API asked to seek, which playback stops waiting for the next event for.
+    let seeking = || api.is_some_and(|api| api.seeking());
+    // Sleeps until `lookahead` before the transport reaches `time`, or until interrupted or seeking. A transport that
+    // is following, tapped or controlled by the API is slept on in short steps, as it may be moved or change speed
+    // meanwhile. Returns how many seconds late it woke up.
+    let sleep_until = |transport: &Transport, time: f64| {
+        while let Some(duration) = transport
+            .until(time - lookahead)
+            .filter(|_| !interrupt.is_raised() && !seeking())
+        {
+            if following.is_some() || tapping || api.is_some() {
+                sleep(duration.min(FOLLOW_SLEEP_STEP));
+            } else {
+                sleep(duration);
+            }
+        }
+        let lag = transport.time() - (time - lookahead);
+        if following.is_none() {
+            metrics::record_lag(lag);
+        }
+        lag
+    };
+    // Lags of the events sent, except while following a transport that may be moved.
+    let mut timing_stats =
+        timer::TimingStats::new(TIMING_TELEMETRY_INTERVAL.filter(|_| following.is_none()));
+    // Playback position last sent to the visualizer.
+    let mut sent_position = f64::NEG_INFINITY;
+    // Time of the last MIDI event so far, after which playback stops with [`PLAYBACK_TAIL`].
+    let mut last_event_time = overrides.start_from;
+    // Time of the last message sent so far, before which the pitch bends of retunes are never sent (see
+    // [`PRE_BEND_LEAD`]), so that they don't retune notes of the previous event.
+    let mut prev_event_time = f64::NEG_INFINITY;
 
     // before starting to play, send all notes off, reset all controllers, and reset pitch bend.
-    reset(&mut midi_conn, &mut broadcast_channel);
-
-    let mut tuner = ondine::TUNER.lock().unwrap();
+    reset(midi_conn.as_mut(), &mut broadcast_channel);
+
+    let track = batched_track(humanized_track(&smf.tracks[0], &tuner, &piece.midi_file));
+    let mut performance = Performance::new(
+        tuner,
+        lower_range,
+        &piece.midi_file,
+        config,
+        config.visualizer.then(|| broadcast_channel.clone()),
+    );
 
-    // Contains the current tuning. We keep track of this for debug purposes (so we can print the curr tuning as
-    // formatted rationals)
-    // Initialized to dummy values of 1/1 first, will be updated according to tuning data.
-    let mut curr_tuning = [Rational::new(1, 1); 12];
+    // Parts of the silences that are skipped (see [`COMPRESS_SILENCE`]), keeping half the length at either end so that
+    // the release of the notes before & the lead-in of the notes after are still heard.
+    let skipped_silences: Vec<(f64, f64)> = match COMPRESS_SILENCE {
+        Some(length) => analysis::silences(&Score::load(&piece.midi_file), length)
+            .into_iter()
+            .map(|(from, to)| (from + length / 2.0, to - length / 2.0))
+            .collect(),
+        None => vec![],
+    };
 
-    // Contains current tuning as monzos. Necessary to memoize monzo() calls to prevent repeated
-    // prime decomposition at the speed of light.
-    // The first element is for A, second Bb, etc...
-    let mut curr_monzos: [Monzo; 12] = curr_tuning.map(|x| x.monzo().unwrap());
+    // Clicks of the click track, sent to their own port, if any.
+    let mut clicks = Timeline::new(match CLICK_TRACK {
+        ClickOutput::Off => vec![],
+        _ => click::clicks(&Score::load(&piece.midi_file), CLICK_SUBDIVISION),
+    });
+    let (mut click_port, click_channel) = match CLICK_TRACK {
+        ClickOutput::Channel(channel) => {
+            assert!(
+                (12..16).contains(&channel),
+                "Click track channel {channel} is used by the tuned pitch classes, use 12-15"
+            );
+            (None, channel)
+        }
+        ClickOutput::Port(name) => (
+            Some(connect_port(name, lookahead, "click track", false, config)),
+            click::PERCUSSION_CHANNEL,
+        ),
+        ClickOutput::Off => (None, click::PERCUSSION_CHANNEL),
+    };
 
     // println!("Using default monzos: {:?}", monzos); should be array of 12 empty arrays, since 1/1 has no prime factors.
 
@@ -176,111 +2275,468 @@ fn main() {
 
     // MAIN PLAYBACK LOOP
 
-    for event in track.iter() {
+    let mut next_event = 0;
+    while let Some(event) = track.get(next_event) {
+        next_event += 1;
+
+        // Seek by starting over from the beginning of the track, applying the events before the position the API asked
+        // for without playing notes, as before [`START_FROM`].
+        if let Some(time) = api.and_then(|api| api.take_seek()).or(pending_seek.take()) {
+            if following.is_some() {
+                println!("WARN: Can't seek while following a sync master or performer");
+            } else {
+                println!("Seeking to {time:.3}s");
+                if let Some(recorder) = &recorder {
+                    recorder.transport(expected_curr_time, format!("seek to {time:.3}"));
+                }
+                midi_conn.timestamp(0.0);
+                reset(midi_conn.as_mut(), &mut broadcast_channel);
+                restarted = start.take().or(restarted);
+                start_point = time;
+                last_event_time = time;
+                prev_event_time = f64::NEG_INFINITY;
+                next_event = 0;
+                curr_tick = 0;
+                curr_bpm = 120.0;
+                expected_curr_time = 0.0;
+                performance.restart();
+                clicks.restart();
+                sent_position = f64::NEG_INFINITY;
+                continue;
+            }
+        }
+
         let delta = event.delta.as_int(); // how many midi ticks after the previous event should this event occur.
         curr_tick += delta;
         let delta_crochets = (delta as f64) / (ppqn as f64); // delta in terms of quarter notes
         expected_curr_time += delta_crochets * (60f64 / curr_bpm); // crochets * (seconds / crochets) = seconds
 
-        let tuning_data = tuner.update(expected_curr_time);
+        // Once playback is past the end of the window previewed, silence it and seek to the next one, if any.
+        if let (Some(window), Some(transport)) = (preview.get(previewing), &start) {
+            if expected_curr_time > window.to {
+                sleep_until(transport, window.to);
+                if interrupt.is_raised() {
+                    break;
+                }
+                previewing += 1;
+                let Some(next) = preview.get(previewing) else {
+                    break;
+                };
+                midi_conn.timestamp(0.0);
+                reset(midi_conn.as_mut(), &mut broadcast_channel);
+                interrupt.sleep(Duration::from_secs_f64(PREVIEW_GAP));
+                preview::announce(&mut broadcast_channel, next, previewing, preview.len());
+                pending_seek = Some(next.from);
+                continue;
+            }
+        }
 
-        // Memoize new tuning data.
-        if let Some(tuning_data) = tuning_data {
-            for (i, ratio) in tuning_data.tuning.iter().enumerate() {
-                if *ratio != Rational::zero() {
-                    curr_tuning[i] = *ratio;
+        // Skip the silence this event is in once playback reaches it, applying the events in it right away.
+        if let Some(transport) = &start {
+            let silence = skipped_silences
+                .iter()
+                .find(|(from, to)| *from < expected_curr_time && transport.time() < *to);
+            if let Some(&(from, to)) = silence {
+                sleep_until(transport, from);
+                transport.set_at(Instant::now(), to, transport.speed());
+                println!("Skipped silence from {from:.3}s to {to:.3}s");
+                if let Some(recorder) = &recorder {
+                    recorder.transport(from, format!("skip to {to:.3}"));
                 }
             }
-            for (i, monzo) in tuning_data.monzos.iter().enumerate() {
-                if let Some(monzo) = monzo {
-                    curr_monzos[i] = monzo.clone();
+        }
+
+        // Send the envelope pitch bends due before this event at their own times, relative to the tuning before it.
+        while let Some(bend) = performance.bends.next_before(expected_curr_time) {
+            let Some(transport) = &start else {
+                continue;
+            };
+            timing_stats.record(sleep_until(transport, bend.time));
+            if seeking() {
+                break;
+            }
+            midi_conn.timestamp((bend.time - transport.time()).max(0.0));
+            performance.bend(midi_conn.as_mut(), &bend);
+            prev_event_time = bend.time;
+        }
+
+        // Send the automated controller values due before this event at their own times.
+        while let Some(event) = performance.automation.next_before(expected_curr_time) {
+            let Some(transport) = &start else {
+                performance.hold_automation(&event);
+                continue;
+            };
+            timing_stats.record(sleep_until(transport, event.time));
+            if seeking() {
+                break;
+            }
+            midi_conn.timestamp((event.time - transport.time()).max(0.0));
+            prev_event_time = event.time;
+            performance.automate(midi_conn.as_mut(), &event);
+        }
+
+        // Send the drift of the reference due before this event at its own times (with the lead of tuning changes),
+        // retuning all pitch classes. Drift before the start point is sent right away, like tuning changes.
+        while let Some(step) = performance.drift_steps.next_before(expected_curr_time) {
+            if let Some(transport) = &start {
+                let send_time = (step.time - PRE_BEND_LEAD).max(prev_event_time);
+                timing_stats.record(sleep_until(transport, send_time));
+                if seeking() {
+                    break;
                 }
+                midi_conn.timestamp((send_time - transport.time()).max(0.0));
+                prev_event_time = send_time;
+                performance.show(VisualizerMessage::Drift {
+                    reference: step.reference,
+                    cents: step.cents,
+                });
             }
+            performance.drift_to(midi_conn.as_mut(), &step);
         }
 
-        if let Ok(exit_flag) = exit_flag.lock() {
-            if *exit_flag {
+        // Send the clicks due before this event at their own times.
+        while let Some(click) = clicks.next_before(expected_curr_time) {
+            let Some(transport) = &start else {
+                continue;
+            };
+            if skipped_silences
+                .iter()
+                .any(|(from, to)| (*from..*to).contains(&click.time))
+            {
+                continue;
+            }
+            let conn = click_port.as_deref_mut().unwrap_or(midi_conn.as_mut());
+            timing_stats.record(sleep_until(transport, click.time));
+            if seeking() {
                 break;
             }
+            conn.timestamp((click.time - transport.time()).max(0.0));
+            prev_event_time = click.time;
+            if let Some(prev) = clicks.previous() {
+                send_note_off(conn, click_channel, prev.key, 0);
+            }
+            send_note_on(conn, click_channel, click.key, click.velocity);
+        }
+
+        // Nudge the next tuning change in the file. Tuning changes that were delayed or deferred during playback (see
+        // [`prepare_tuner`]) are only nudged in the file.
+        for delta in stage.nudges.try_iter() {
+            let next = (0..file_times.len())
+                .filter(|&i| file_times[i] > expected_curr_time)
+                .min_by(|&a, &b| file_times[a].total_cmp(&file_times[b]));
+            let Some(idx) = next else {
+                continue;
+            };
+            let time = file_times[idx];
+            file_times[idx] = performance
+                .tuner
+                .nudge(time, time + delta)
+                .unwrap_or(time + delta);
+            println!(
+                "Nudged {} to {:.3}s ({:+.3}s)",
+                file_tunings[idx].describe(),
+                file_times[idx],
+                file_times[idx] - file_tunings[idx].time
+            );
+            if let Some(recorder) = &recorder {
+                let action = format!(
+                    "nudge {} to {:.3}",
+                    file_tunings[idx].describe(),
+                    file_times[idx]
+                );
+                recorder.transport(expected_curr_time, action);
+            }
+        }
+
+        // Switch to the latest variants selected during playback at tuning changes, see [`Performance::update`].
+        let changes = performance.update(expected_curr_time, || {
+            stage.variant_switches.try_iter().last()
+        });
+        if changes.switched {
+            if let Some(api) = api {
+                api.set_timeline(&performance.tuner);
+            }
+            println!("Switched tuning variants @ {expected_curr_time:.3}s");
+            if let Some(recorder) = &recorder {
+                recorder.transport(expected_curr_time, "switch variants");
+            }
+        }
+        if let Some(tuning_data) = &changes.upper {
+            metrics::set_tuning_index(performance.tuner.current_index());
+            if let Some(api) = api {
+                let pitches = performance.upper.pitches();
+                api.set_tuning(performance.tuner.current_index(), tuning_data, pitches);
+            }
+        }
+
+        if interrupt.is_raised() {
+            break;
         }
 
-        if expected_curr_time >= START_FROM && start.is_none() {
+        let start_from = following.as_ref().map_or(start_point, Transport::time);
+        if expected_curr_time >= start_from && start.is_none() {
             if let TrackEventKind::Midi {
                 channel: _,
                 message: _,
             } = event.kind
             {
+                performance.send_pedals(midi_conn.as_mut());
                 // Start counting time from the first actual midi event (ignore metadata).
-                start = Some(Instant::now());
+                let transport = match restarted.take() {
+                    Some(transport) => {
+                        transport.set(start_point);
+                        transport
+                    }
+                    None => {
+                        let transport = following
+                            .clone()
+                            .unwrap_or_else(|| Transport::start(start_point, overrides.speed));
+                        if let SyncRole::Master(followers) = SYNC {
+                            sync::start_master(followers, transport.clone());
+                        }
+                        transport
+                    }
+                };
+                if let Some(api) = api {
+                    api.started(transport.clone());
+                }
+                start = Some(transport.clone());
+                if let Some(recorder) = &recorder {
+                    recorder.transport(expected_curr_time, "start");
+                }
+                if let Some(mut messages) = midi_input.take() {
+                    if conducting_by_midi {
+                        let advance = stage.controls.advance.clone();
+                        messages =
+                            conductor::advance_on_taps(&CONDUCTOR_ADVANCE, messages, advance);
+                    }
+                    if tapping {
+                        let score = Score::load(&piece.midi_file);
+                        tap::tap_tempo(
+                            &TAP_TEMPO,
+                            messages,
+                            transport.clone(),
+                            score,
+                            overrides.speed,
+                        );
+                    }
+                }
+                if let Some(on_start) = on_start.take() {
+                    on_start(transport);
+                }
+            }
+        }
+
+        // Stop at the end of the track without waiting for it, see [`PLAYBACK_TAIL`], or start over from the start
+        // point once the last notes have rung out, see [`END_OF_TRACK`].
+        if let (Some(transport), TrackEventKind::Meta(MetaMessage::EndOfTrack)) =
+            (&start, event.kind)
+        {
+            println!("End of Track");
+            if END_OF_TRACK != EndOfTrack::Loop || following.is_some() || !preview.is_empty() {
+                break;
+            }
+            sleep_until(transport, last_event_time + overrides.tail);
+            if interrupt.is_raised() {
+                break;
             }
+            println!("Looping back to {}s", overrides.start_from);
+            pending_seek = Some(overrides.start_from);
+            next_event = 0;
+            continue;
+        }
+        if let TrackEventKind::Midi { .. } = event.kind {
+            last_event_time = expected_curr_time;
         }
 
-        if let Some(start_instant) = start {
+        // Events at the same tick as the previous one (see [`batched_track`]) are sent right after it, in the same
+        // scheduling slot, unless a tuning change is applied at them.
+        let mut lag = None;
+        let retuning = changes.any();
+        if let Some(transport) = start.as_ref().filter(|_| delta > 0 || retuning) {
             // only sleep if we have reached where we want to start playing.
-            let curr_time = (start_instant.elapsed().as_secs_f64() * PLAYBACK_SPEED) + START_FROM;
-            let time_diff = expected_curr_time - curr_time;
-            if time_diff > 0f64 {
-                spin_sleeper.sleep(Duration::from_secs_f64(time_diff));
-            } else if time_diff < -0.001f64 {
+            let time_diff = expected_curr_time - transport.time();
+            if time_diff < -0.001f64 && following.is_none() {
                 println!("WARN: Falling behind by {:.3} ms", -time_diff * 1000.0);
+                if let Some(recorder) = &recorder {
+                    recorder.warning(
+                        expected_curr_time,
+                        format!("Falling behind by {:.3} ms", -time_diff * 1000.0),
+                    );
+                }
+            }
+            // The pitch bends of a tuning change (of either range of a keyboard split) are sent ahead of the event, but
+            // not before the previous one, see [`PRE_BEND_LEAD`].
+            let send_time = match retuning {
+                true => (expected_curr_time - PRE_BEND_LEAD).max(prev_event_time),
+                false => expected_curr_time,
+            };
+            lag = Some(sleep_until(transport, send_time));
+            timing_stats.record(lag.unwrap());
+            if interrupt.is_raised() {
+                break;
+            }
+            if seeking() {
+                continue;
+            }
+            // Pause at the cue until it is advanced, then carry on from it. The transport is stopped meanwhile, so that
+            // sync followers wait too.
+            if let (true, Some(tuning_data)) = (
+                CONDUCTOR_MODE,
+                changes.upper.as_ref().filter(|td| td.cue.is_some()),
+            ) {
+                let speed = transport.speed();
+                transport.set_speed(0.0);
+                if let Some(recorder) = &recorder {
+                    recorder.transport(expected_curr_time, "cue");
+                }
+                if !conductor::wait(tuning_data, &stage.advances, interrupt) {
+                    break;
+                }
+                if let Some(recorder) = &recorder {
+                    recorder.transport(expected_curr_time, "advance");
+                }
+                transport.set_at(Instant::now(), send_time, speed);
             }
+            midi_conn.timestamp((send_time - transport.time()).max(0.0));
+        }
+        prev_event_time = expected_curr_time;
+
+        if let Some((lag, jitter)) = timing_stats.report() {
+            performance.show(VisualizerMessage::Timing {
+                lag,
+                jitter,
+                dropped: server::dropped_messages(),
+            });
+        }
+        if start.is_some() && (expected_curr_time - sent_position).abs() >= POSITION_INTERVAL {
+            sent_position = expected_curr_time;
+            performance.show(VisualizerMessage::Position {
+                time: expected_curr_time,
+            });
         }
 
-        // Send new pitch bends if current tuning is to be modified.
-        if let Some(tuning_data) = tuning_data {
-            for pb_raw_msg in &tuning_data.midi_messages {
-                if let Some(pb_raw_msg) = pb_raw_msg {
-                    midi_conn.send(pb_raw_msg).unwrap();
+        // Send the pitch bends of the tuning changes of either range of a keyboard split.
+        if changes.any() {
+            if let (Some(recorder), Some(_)) = (&recorder, &start) {
+                if let Some(lower_tuning) = &changes.lower {
+                    recorder.tuning(
+                        expected_curr_time,
+                        lag,
+                        format!("{} (lower range)", lower_tuning.describe()),
+                    );
+                }
+                if let Some(tuning_data) = &changes.upper {
+                    recorder.tuning(expected_curr_time, lag, tuning_data.describe());
                 }
             }
-            if DEBUG_PRINT {
-                print!("[{curr_tick:>7}, {expected_curr_time:7.3}s] ");
+            performance.retune(midi_conn.as_mut(), &changes, expected_curr_time);
+        }
+        if let (true, Some(tuning_data)) = (config.debug, &changes.upper) {
+            let curr_tuning = &performance.upper.tuning;
+            print!("[{curr_tick:>7}, {expected_curr_time:7.3}s] ");
+            println!(
+                "{}:\n
+                A:  ({:.3}c) {}
+                Bb: ({:.3}c) {}
+                B:  ({:.3}c) {}
+                C:  ({:.3}c) {}
+                C#: ({:.3}c) {}
+                D:  ({:.3}c) {}
+                D#: ({:.3}c) {}
+                E:  ({:.3}c) {}
+                F:  ({:.3}c) {}
+                F#: ({:.3}c) {}
+                G:  ({:.3}c) {}
+                G#: ({:.3}c) {}
+                ",
+                tuning_data.describe(),
+                curr_tuning[0].cents().unwrap(),
+                curr_tuning[0],
+                curr_tuning[1].cents().unwrap() - 100.0,
+                curr_tuning[1],
+                curr_tuning[2].cents().unwrap() - 200.0,
+                curr_tuning[2],
+                curr_tuning[3].cents().unwrap() - 300.0,
+                curr_tuning[3],
+                curr_tuning[4].cents().unwrap() - 400.0,
+                curr_tuning[4],
+                curr_tuning[5].cents().unwrap() - 500.0,
+                curr_tuning[5],
+                curr_tuning[6].cents().unwrap() - 600.0,
+                curr_tuning[6],
+                curr_tuning[7].cents().unwrap() - 700.0,
+                curr_tuning[7],
+                curr_tuning[8].cents().unwrap() - 800.0,
+                curr_tuning[8],
+                curr_tuning[9].cents().unwrap() - 900.0,
+                curr_tuning[9],
+                curr_tuning[10].cents().unwrap() - 1000.0,
+                curr_tuning[10],
+                curr_tuning[11].cents().unwrap() - 1100.0,
+                curr_tuning[11],
+            );
+        }
+
+        // Sleep for the rest of the lead before sending the event itself.
+        if let (Some(transport), true, true) = (&start, PRE_BEND_LEAD > 0.0, changes.any()) {
+            sleep_until(transport, expected_curr_time);
+            midi_conn.timestamp((expected_curr_time - transport.time()).max(0.0));
+        }
+
+        // Apply the tuning edits of clients to the pitch classes that aren't sounding, see [`edits`].
+        let ready = edits.ready(stage.tuning_edits.try_iter(), &notes, expected_curr_time);
+        for edit in ready {
+            let in_effect = (0..file_times.len())
+                .filter(|&i| file_times[i] <= expected_curr_time)
+                .max_by(|&a, &b| file_times[a].total_cmp(&file_times[b]));
+            let Some(idx) = in_effect else {
+                println!("WARN: Can't set {} before the first tuning", edit.name);
+                continue;
+            };
+            let pitch = file_tunings[idx].relative_to_a(edit.semitone, edit.pitch);
+            let bend_cents = pitch.cents().unwrap() - 100.0 * edit.semitone as f64;
+            if bend_cents.abs() > 100.0 * config.pb_range as f64 {
                 println!(
-                    "Tuning:\n
-                    A:  ({:.3}c) {}
-                    Bb: ({:.3}c) {}
-                    B:  ({:.3}c) {}
-                    C:  ({:.3}c) {}
-                    C#: ({:.3}c) {}
-                    D:  ({:.3}c) {}
-                    D#: ({:.3}c) {}
-                    E:  ({:.3}c) {}
-                    F:  ({:.3}c) {}
-                    F#: ({:.3}c) {}
-                    G:  ({:.3}c) {}
-                    G#: ({:.3}c) {}
-                    ",
-                    curr_tuning[0].cents().unwrap(),
-                    curr_tuning[0],
-                    curr_tuning[1].cents().unwrap() - 100.0,
-                    curr_tuning[1],
-                    curr_tuning[2].cents().unwrap() - 200.0,
-                    curr_tuning[2],
-                    curr_tuning[3].cents().unwrap() - 300.0,
-                    curr_tuning[3],
-                    curr_tuning[4].cents().unwrap() - 400.0,
-                    curr_tuning[4],
-                    curr_tuning[5].cents().unwrap() - 500.0,
-                    curr_tuning[5],
-                    curr_tuning[6].cents().unwrap() - 600.0,
-                    curr_tuning[6],
-                    curr_tuning[7].cents().unwrap() - 700.0,
-                    curr_tuning[7],
-                    curr_tuning[8].cents().unwrap() - 800.0,
-                    curr_tuning[8],
-                    curr_tuning[9].cents().unwrap() - 900.0,
-                    curr_tuning[9],
-                    curr_tuning[10].cents().unwrap() - 1000.0,
-                    curr_tuning[10],
-                    curr_tuning[11].cents().unwrap() - 1100.0,
-                    curr_tuning[11],
+                    "WARN: Can't set {} to {}, it is beyond the pitch bend range",
+                    edit.name, edit.value
+                );
+                continue;
+            }
+            performance.set_pitch(
+                midi_conn.as_mut(),
+                edit.semitone,
+                pitch,
+                &edit.name,
+                expected_curr_time,
+            );
+            let tuning = file_tunings[idx].describe();
+            println!(
+                "Set {} to {} @ {expected_curr_time:.3}s in {tuning}",
+                edit.name, edit.value
+            );
+            if let Some(recorder) = &recorder {
+                recorder.transport(
+                    expected_curr_time,
+                    format!("set {} {}", edit.name, edit.value),
                 );
             }
+            let pitches = performance.upper.pitches();
+            if let Some(api) = api {
+                let index = performance.tuner.current_index();
+                api.set_tuning(index, &file_tunings[idx], pitches.clone());
+            }
+            performance.show(VisualizerMessage::Tuning {
+                time: expected_curr_time,
+                pitches,
+                annotation: format!("{tuning} ({} {})", edit.name, edit.value),
+            });
+            edits.applied.push((idx, edit));
         }
 
         let is_midi_event = matches!(event.kind, TrackEventKind::Midi { .. });
 
-        if (is_midi_event && start.is_some()) || !is_midi_event {
+        if start.is_some() || !is_midi_event {
             // print!("[{curr_tick:>7}, {expected_curr_time:7.3}s] ");
         }
 
@@ -293,122 +2749,460 @@ fn main() {
                 println!("End of Track");
             }
             TrackEventKind::Meta(MetaMessage::Text(text)) => {
-                println!("|> {}", std::str::from_utf8(&text).unwrap());
+                println!("|> {}", std::str::from_utf8(text).unwrap());
             }
             TrackEventKind::Meta(MetaMessage::TrackName(text)) => {
-                println!("Track name: {}", std::str::from_utf8(&text).unwrap());
+                println!("Track name: {}", std::str::from_utf8(text).unwrap());
             }
             TrackEventKind::Midi { message, .. } => {
-                if start.is_some() {
-                    // Only send Note on/off messages if we have reached where we want to start playing.
-                    // println!("MIDI Event: Channel: {}, Message: {:?}", channel, message);
+                // Only send Note on/off messages if we have reached where we want to start playing.
+                // FUTURE REMINDER: a NoteOn with 0 velocity is equivalent to a NoteOff, and should be treated as
+                // such. Right now everything is ok as is, as the visualizer handles this as well. But if there's
+                // some specific on/off behaviour within this program itself, make sure to amend this!
+                match message {
+                    MidiMessage::NoteOn { key, vel } if start.is_some() => {
+                        performance.note_on(
+                            midi_conn.as_mut(),
+                            key,
+                            vel,
+                            curr_tick,
+                            expected_curr_time,
+                        );
+                    }
+                    MidiMessage::NoteOff { key, vel } if start.is_some() => {
+                        performance.note_off(midi_conn.as_mut(), key, vel);
+                    }
+                    // Controllers before the start point are sent too, see [`Performance::controller`].
+                    MidiMessage::Controller { controller, value } => {
+                        performance.controller(
+                            midi_conn.as_mut(),
+                            controller,
+                            value,
+                            start.is_some(),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                // TODO: remove unnecessary println once debugging is done.
+                println!("Unhandled event: {:?}", event);
+            }
+        }
 
-                    if let MidiMessage::NoteOn { key, vel } = message {
-                        // FUTURE REMINDER: a NoteOn with 0 velocity is equivalent to a NoteOff, and should
-                        // be treated as such. Right now everything is ok as is, as the visualizer handles
-                        // this as well. But if there's some specific on/off behaviour within this program
-                        // itself, make sure to amend this!
+        if start.is_some() {
+            performance.show_chord(&notes, expected_curr_time);
+        }
+    }
 
-                        let edosteps_from_a4: i32 = key.as_int() as i32 - 69;
-                        let channel = edosteps_from_a4.rem_euclid(12) as u8;
+    // Let the last notes ring for the tail, unless stopped with Ctrl-C. Timed by the clock rather than the transport,
+    // which may stop with a sync master.
+    if let Some(transport) = start.as_ref().filter(|_| !interrupt.is_raised()) {
+        let tail = transport.until(last_event_time).unwrap_or_default();
+        let tail = tail.saturating_add(Duration::from_secs_f64(overrides.tail));
+        if overrides.tail > 0.0 {
+            println!("Letting the last notes ring for {}s...", overrides.tail);
+        }
+        interrupt.sleep(tail);
+        if END_OF_TRACK == EndOfTrack::Hold {
+            println!("Holding the final tuning, stop with Ctrl-C...");
+            while !interrupt.is_raised() {
+                interrupt.sleep(Duration::from_secs(1));
+            }
+        }
+    }
+    if let Some(api) = api {
+        api.stopped();
+    }
+    if let (Some(recorder), Some(transport)) = (&recorder, &start) {
+        let action = if interrupt.is_raised() {
+            "interrupt"
+        } else {
+            "stop"
+        };
+        recorder.transport(transport.time(), action);
+    }
+    // When interrupted, silence everything right away too, rather than only after the messages scheduled ahead.
+    if interrupt.is_raised() {
+        midi_conn.timestamp(0.0);
+        reset(midi_conn.as_mut(), &mut broadcast_channel);
+    }
 
-                        if ACTIVATE_MIDI {
-                            send_note_on(&mut midi_conn, channel, key, vel);
-                        }
+    if let SyncRole::Master(followers) = SYNC {
+        sync::stop_master(followers);
+    }
+    // Let the messages that are scheduled ahead play before resetting.
+    thread::sleep(Duration::from_secs_f64(lookahead));
+    midi_conn.timestamp(0.0);
+    drop(sleeper);
+    println!("Reset & closing connection...");
+    reset(midi_conn.as_mut(), &mut broadcast_channel);
+    if let Some(click_port) = &mut click_port {
+        click_port.timestamp(0.0);
+        send_cc(click_port.as_mut(), click_channel, 123, 0);
+    }
+    if let Some(session) = session {
+        session.finish();
+    }
 
-                        // 0 is A, 1 is Bb, etc...
-                        let semitone_mod12 = (key.as_int() + 3) as usize % 12;
+    let nudged: Vec<Option<f64>> = file_tunings
+        .iter()
+        .zip(&file_times)
+        .map(|(td, &time)| (time != td.time).then_some(time))
+        .collect();
+    let count = nudged.iter().flatten().count();
+    if count > 0 {
+        fs::write(
+            &*piece.tuning_file,
+            tuning_file::set_times(&tuning_text, &nudged),
+        )
+        .unwrap();
+        println!(
+            "Wrote {count} nudged tuning change times to {}",
+            piece.tuning_file
+        );
+    }
+    if !edits.pending.is_empty() {
+        let names: Vec<&str> = edits
+            .pending
+            .iter()
+            .map(|edit| edit.name.as_str())
+            .collect();
+        println!(
+            "WARN: Tuning edits of {} weren't applied, they kept sounding until the end",
+            names.join(", ")
+        );
+    }
+    if SAVE_TUNING_EDITS && !edits.applied.is_empty() {
+        let text = edits.applied.iter().fold(
+            fs::read_to_string(&*piece.tuning_file).unwrap(),
+            |text, (idx, edit)| tuning_file::set_pitch(&text, *idx, &edit.name, &edit.value),
+        );
+        fs::write(&*piece.tuning_file, text).unwrap();
+        println!(
+            "Wrote {} tuning edits to {}",
+            edits.applied.len(),
+            piece.tuning_file
+        );
+    }
+}
 
-                        let mut monzo = curr_monzos[semitone_mod12].clone();
+/// Connects to an output that is not a MIDI port, with the pitch bend range of `config`.
+type OutputConnector = fn(&Config) -> Box<dyn MidiSink>;
 
-                        // Monzos are relative to A4, so we need to shift the octave to match
-                        let octaves_from_a4 = edosteps_from_a4.div_euclid(12);
+/// Lists the MIDI output ports and other outputs, and connects to the one matching the device of `config` (see
+/// [`MIDI_PLAYBACK_DEVICE_NAME`]), or asks for one if none match. The output is sent pitch bends for the pitch bend
+/// range of `config`.
+fn connect_output(config: &Config) -> Box<dyn MidiSink> {
+    println!("Select a MIDI output port:");
+    // Other outputs may still be used if there is no MIDI support (e.g. ALSA sequencer not loaded).
+    let midi_out = MidiOutput::new("JI Performer")
+        .map_err(|e| println!("WARN: MIDI output unavailable: {e}"))
+        .ok();
+    let ports = midi_out.as_ref().map_or(vec![], |m| m.ports());
+
+    // Outputs other than MIDI ports, listed after them.
+    #[allow(unused_mut)]
+    let mut other_outputs: Vec<(String, OutputConnector)> = vec![
+        (format!("FluidSynth @ {FLUIDSYNTH_ADDR}"), |config| {
+            let (sf, program) = (FLUIDSYNTH_SOUNDFONT, FLUIDSYNTH_PROGRAM);
+            Box::new(fluidsynth::FluidSynth::connect(
+                FLUIDSYNTH_ADDR,
+                sf,
+                program,
+                config.pb_range,
+            ))
+        }),
+        (format!("Surge XT (OSC) @ {SURGE_OSC_ADDR}"), |_| {
+            Box::new(surge::SurgeXt::connect(
+                SURGE_OSC_ADDR,
+                &format!("{EXPORT_DIR}/surge"),
+                A4_FREQUENCY,
+            ))
+        }),
+        (
+            format!("SuperCollider (OSC) @ {SUPERCOLLIDER_ADDR}"),
+            |_| {
+                Box::new(supercollider::SuperCollider::connect(
+                    SUPERCOLLIDER_ADDR,
+                    A4_FREQUENCY,
+                ))
+            },
+        ),
+        (format!("RTP-MIDI session @ {RTP_MIDI_ADDR}"), |_| {
+            Box::new(rtpmidi::RtpMidi::connect(RTP_MIDI_ADDR))
+        }),
+    ];
+    #[cfg(feature = "preview-synth")]
+    other_outputs.push(("Built-in preview synth".to_string(), |config| {
+        let pb_range = config.pb_range;
+        Box::new(synth::PreviewSynth::start(move |sample_rate| {
+            instrument(sample_rate, pb_range)
+        }))
+    }));
+
+    let names = ports
+        .iter()
+        .map(|port| midi_out.as_ref().unwrap().port_name(port).unwrap())
+        .chain(other_outputs.iter().map(|(name, _)| name.clone()));
 
-                        if monzo.len() == 0 {
-                            monzo.push(octaves_from_a4);
-                        } else {
-                            monzo[0] += octaves_from_a4;
-                        }
+    let mut midi_idx = None;
 
-                        if DEBUG_PRINT {
-                            print!("[{curr_tick:>7}, {expected_curr_time:7.3}s] ");
-                            let note_name = SEMITONE_NAMES[semitone_mod12];
-                            let octaves = (key.as_int() as i32 / 12) - 1;
-                            println!("Note on: {}{}, vel: {vel}. {:?}", note_name, octaves, monzo);
-                        }
+    for (idx, name) in names.enumerate() {
+        if midi_idx.is_none() && name.contains(&config.device) {
+            midi_idx = Some(idx);
+            println!("[{idx}] {name} <Device Found>");
+        } else {
+            println!("[{idx}] {name}");
+        }
+    }
 
-                        if ACTIVATE_VISUALIZER {
-                            let res = executor::block_on(broadcast_channel.send(
-                                &VisualizerMessage::NoteOn {
-                                    edosteps_from_a4,
-                                    velocity: vel,
-                                    monzo,
-                                },
-                            ));
-
-                            if let Err(e) = res {
-                                println!(
-                                    "WARN: Failed to send message to visualizer broadcast channel: {}",
-                                    e
-                                );
-                            }
-                        }
-                    } else if let MidiMessage::NoteOff { key, vel } = message {
-                        let edosteps_from_a4 = key.as_int() as i32 - 69;
-                        let channel = edosteps_from_a4.rem_euclid(12) as u8;
+    if midi_idx.is_none() {
+        let mut input = String::new();
+        stdin().read_line(&mut input).unwrap();
+        midi_idx = Some(input.trim().parse().unwrap());
+    }
 
-                        if ACTIVATE_MIDI {
-                            send_note_off(&mut midi_conn, channel, key, vel);
-                        }
+    let idx = midi_idx.unwrap();
+    if idx >= ports.len() {
+        let (name, connect) = &other_outputs[idx - ports.len()];
+        return compensate_latency(name, connect(config));
+    }
+    let port_name = midi_out.as_ref().unwrap().port_name(&ports[idx]).unwrap();
+    let profile = synth_profile(&port_name);
+    let lookahead = match profile.map(|profile| profile.backend) {
+        Some(Backend::Direct) => 0.0,
+        Some(Backend::Scheduled) | None => MIDI_LOOKAHEAD,
+    };
+    let scheduled = (lookahead > 0.0)
+        .then(|| timestamped::connect(&port_name, lookahead))
+        .flatten();
+    let conn = scheduled.unwrap_or_else(|| {
+        Box::new(
+            midi_out
+                .unwrap()
+                .connect(&ports[idx], "JI Performer")
+                .unwrap(),
+        )
+    });
+    if let Some(url) = PIANOTEQ_RPC_URL {
+        pianoteq::handshake(url, PIANOTEQ_PRESET, config.pb_range);
+    }
+    if let Some(profile) = profile {
+        return compensate_latency(
+            &port_name,
+            profiled(&port_name, profile, conn, config.pb_range),
+        );
+    }
+    if MTS_BULK_DUMPS {
+        return compensate_latency(
+            &port_name,
+            Box::new(mts::MtsBulkDump::new(conn, A4_FREQUENCY)),
+        );
+    }
+    compensate_latency(&port_name, tune_channels(&port_name, conn, config.pb_range))
+}
 
-                        if ACTIVATE_VISUALIZER {
-                            let res = executor::block_on(broadcast_channel.send(
-                                &VisualizerMessage::NoteOff {
-                                    edosteps_from_a4,
-                                    velocity: vel,
-                                },
-                            ));
-                            if let Err(e) = res {
-                                println!(
-                                    "WARN: Failed to send message to visualizer broadcast channel: {}",
-                                    e
-                                );
-                            }
-                        }
-                    }
-                }
+/// Returns the [`SYNTH_PROFILES`] profile of the MIDI output port `port_name`, if any.
+fn synth_profile(port_name: &str) -> Option<SynthProfile> {
+    SYNTH_PROFILES
+        .iter()
+        .find(|(name, _)| port_name.contains(name))
+        .map(|(_, profile)| *profile)
+}
 
-                // Send all cc messages, that come before the start time, so that existing state
-                // (e.g. sustain pedal) is set correctly for the start point.
-                if let MidiMessage::Controller { controller, value } = message {
-                    // REMINDER: depending on the synth implementation, we may need to duplicate
-                    // CC messages on to all channels. According to Pianoteq, sending
-                    send_cc(&mut midi_conn, 0, controller, value);
+/// Tunes the MIDI output port `port_name` and rewrites the messages sent to it as given by its synth `profile`, from
+/// pitch bends sent for a range of +/- `pb_range` semitones.
+fn profiled(
+    port_name: &str,
+    profile: SynthProfile,
+    conn: Box<dyn MidiSink>,
+    pb_range: u16,
+) -> Box<dyn MidiSink> {
+    println!(
+        "Playing {port_name} with the {} synth profile",
+        profile.name
+    );
+    let conn: Box<dyn MidiSink> = match profile.retune {
+        Retune::PitchBends => conn,
+        Retune::Mts => Box::new(mts::MtsBulkDump::new(conn, A4_FREQUENCY)),
+        Retune::Channels(tuning) => Box::new(ChannelTuned::new(conn, tuning, pb_range)),
+    };
+    Box::new(Profiled::new(conn, profile, pb_range))
+}
 
-                    let res = executor::block_on(
-                        broadcast_channel.send(&VisualizerMessage::CC { controller, value }),
-                    );
-                    if let Err(e) = res {
-                        println!("WARN: Failed to send message to vis1ualizer: {}", e);
-                    }
-                }
-            }
-            _ => {
-                // TODO: remove unnecessary println once debugging is done.
-                println!("Unhandled event: {:?}", event);
+/// Tunes the channels of the MIDI output port `port_name` with controllers, if it has a [`CHANNEL_TUNING`], from pitch
+/// bends sent for a range of +/- `pb_range` semitones.
+fn tune_channels(port_name: &str, conn: Box<dyn MidiSink>, pb_range: u16) -> Box<dyn MidiSink> {
+    match CHANNEL_TUNING
+        .iter()
+        .find(|(name, _)| port_name.contains(name))
+    {
+        Some(&(_, tuning)) => {
+            println!("Tuning the channels of {port_name} with controllers instead of pitch bends");
+            Box::new(ChannelTuned::new(conn, tuning, pb_range))
+        }
+        None => conn,
+    }
+}
+
+/// Turns the note of `key` on at `velocity` at the channel chosen by `allocator`, sending the pitch bends that tune it
+/// to `tuning` first (see [`ChannelAllocator::tune_note`]), after turning off the note stolen to free the channel, if
+/// any. Returns the channel, which the note on is sent to.
+fn allocate_note(
+    midi_conn: &mut dyn MidiSink,
+    note_channels: &mut NoteChannels,
+    allocator: &mut dyn ChannelAllocator,
+    key: u8,
+    velocity: u8,
+    tuning: &[PitchSpec; 12],
+) -> u8 {
+    let (channel, stolen) = note_channels.note_on(key, velocity, allocator);
+    if let Some(stolen) = stolen {
+        send_note_off(midi_conn, channel, stolen, 0);
+    }
+    for message in allocator.tune_note(key, channel, tuning) {
+        midi_conn.send(&message);
+    }
+    channel
+}
+
+/// Returns a tuning that retunes `semitone` to `cents` above A and keeps the others, e.g. for a pitch envelope.
+fn semitone_tuning(semitone: usize, cents: f64) -> [PitchSpec; 12] {
+    let mut tuning = [PitchSpec::Keep; 12];
+    tuning[semitone] = PitchSpec::Cents(cents);
+    tuning
+}
+
+/// Compensates for the [`OUTPUT_LATENCY`] of the output `port_name`, if any.
+fn compensate_latency(port_name: &str, conn: Box<dyn MidiSink>) -> Box<dyn MidiSink> {
+    match OUTPUT_LATENCY
+        .iter()
+        .find(|(name, _)| port_name.contains(name))
+    {
+        Some(&(_, latency)) if latency >= 0.0 => {
+            println!(
+                "Compensating for {:.1} ms of latency on {port_name}",
+                latency * 1000.0
+            );
+            Box::new(LatencyCompensated {
+                sink: conn,
+                latency,
+            })
+        }
+        Some(&(_, latency)) => {
+            println!("Delaying {port_name} by {:.1} ms", -latency * 1000.0);
+            if -latency > conn.lookahead() {
+                println!(
+                    "WARN: {port_name} can only be delayed by its lookahead of {:.1} ms, as it can't schedule messages \
+                     further ahead",
+                    conn.lookahead() * 1000.0
+                );
             }
+            Box::new(LatencyCompensated {
+                sink: conn,
+                latency,
+            })
         }
+        None => conn,
     }
+}
 
-    println!("Reset & closing connection...");
-    reset(&mut midi_conn, &mut broadcast_channel);
-    midi_conn.close();
-    exit(0);
+/// Connects to the MIDI output port whose name contains `name` for `what` (e.g. `click track`), scheduling messages
+/// `lookahead` seconds ahead like the playback output. The port is played with its synth profile if `profiled`, except
+/// for its backend, as its messages are sent ahead like those of the playback output. Pitch bends are sent to it for
+/// the pitch bend range of `config`.
+fn connect_port(
+    name: &str,
+    lookahead: f64,
+    what: &str,
+    profiled: bool,
+    config: &Config,
+) -> Box<dyn MidiSink> {
+    let client = format!("JI Performer {what}");
+    let midi_out =
+        MidiOutput::new(&client).unwrap_or_else(|e| panic!("MIDI output unavailable: {e}"));
+    let ports = midi_out.ports();
+    let port = ports
+        .iter()
+        .find(|port| midi_out.port_name(port).is_ok_and(|n| n.contains(name)))
+        .unwrap_or_else(|| panic!("No MIDI output port named {name} for the {what}"));
+    let port_name = midi_out.port_name(port).unwrap();
+    println!("Output of the {what}: {port_name}");
+    let scheduled = (lookahead > 0.0)
+        .then(|| timestamped::connect(&port_name, lookahead))
+        .flatten();
+    let conn = scheduled.unwrap_or_else(|| {
+        if lookahead > 0.0 {
+            println!("WARN: Messages can't be scheduled on {port_name}, the {what} will be {lookahead}s early");
+        }
+        Box::new(midi_out.connect(port, &client).unwrap())
+    });
+    match synth_profile(&port_name).filter(|_| profiled) {
+        Some(profile) => compensate_latency(
+            &port_name,
+            self::profiled(&port_name, profile, conn, config.pb_range),
+        ),
+        None => compensate_latency(&port_name, tune_channels(&port_name, conn, config.pb_range)),
+    }
+}
+
+/// Lists the MIDI input ports, and connects to the one matching [`LIVE_MIDI_INPUT_NAME`], or asks for one if none
+/// match. Returns the connection, which closes when dropped, and the messages received with when they arrived.
+fn connect_input() -> (MidiInputConnection<()>, mpsc::Receiver<(Instant, Vec<u8>)>) {
+    println!("Select a MIDI input port:");
+    let mut midi_in = MidiInput::new("JI Performer input")
+        .unwrap_or_else(|e| panic!("MIDI input unavailable: {e}"));
+    midi_in.ignore(Ignore::All);
+    let ports = midi_in.ports();
+
+    let mut midi_idx = None;
+    for (idx, port) in ports.iter().enumerate() {
+        let name = midi_in.port_name(port).unwrap();
+        if midi_idx.is_none() && name.contains(LIVE_MIDI_INPUT_NAME) {
+            midi_idx = Some(idx);
+            println!("[{idx}] {name} <Device Found>");
+        } else {
+            println!("[{idx}] {name}");
+        }
+    }
+    if ports.is_empty() {
+        panic!("No MIDI input ports found");
+    }
+
+    if midi_idx.is_none() {
+        let mut input = String::new();
+        stdin().read_line(&mut input).unwrap();
+        midi_idx = Some(input.trim().parse().unwrap());
+    }
+
+    let (sender, messages) = mpsc::channel();
+    let conn = midi_in
+        .connect(
+            &ports[midi_idx.unwrap()],
+            "JI Performer input",
+            move |_, message, _| {
+                let _ = sender.send((Instant::now(), message.to_vec()));
+            },
+            (),
+        )
+        .unwrap();
+    (conn, messages)
+}
+
+/// Creates the instrument played by the preview synth & `render`: an SFZ sampler if [`SFZ_FILE`] is set, otherwise the
+/// built-in synth, bent by pitch bends sent for a range of +/- `pb_range` semitones.
+#[cfg(any(feature = "preview-synth", feature = "render"))]
+fn instrument(sample_rate: f64, pb_range: u16) -> Box<dyn synth::Instrument> {
+    match SFZ_FILE {
+        Some(path) => Box::new(sampler::Sampler::load(path, sample_rate)),
+        None => Box::new(synth::Synth::new(sample_rate, pb_range)),
+    }
 }
 
 /// Resets all controllers, turns off all notes, reset visualizer.
 fn reset(
-    midi_conn: &mut midir::MidiOutputConnection,
+    midi_conn: &mut dyn MidiSink,
     broadcast_channel: &mut BroadcastChannel<VisualizerMessage>,
 ) {
     // before starting to play, send all notes off, reset all controllers, and reset pitch bend.
@@ -433,76 +3227,318 @@ fn reset(
         value: 0.into(),
     }))
     .unwrap();
+    for (_, name, value) in EXPRESSIVE_CONTROLLERS {
+        executor::block_on(broadcast_channel.send(&VisualizerMessage::Controller {
+            name,
+            value: value as f64 / 127.0,
+        }))
+        .unwrap();
+    }
+
+    // Not all synths reset the pedals with CC 121.
+    send_pedals(midi_conn, broadcast_channel, [0.into(); 3]);
 }
 
-fn send_pitch_bend<T: Into<u4>>(
-    midi_conn: &mut midir::MidiOutputConnection,
-    channel: T,
-    bend: PitchBend,
+/// Sends the pedal CC values (in the order of [`PEDAL_CCS`]) to the MIDI output, and their state to the visualizer.
+fn send_pedals(
+    midi_conn: &mut dyn MidiSink,
+    broadcast_channel: &mut BroadcastChannel<VisualizerMessage>,
+    pedals: [u7; 3],
+) {
+    for (&controller, value) in PEDAL_CCS.iter().zip(pedals) {
+        send_controller(midi_conn, controller, value);
+    }
+    send_pedal_state(broadcast_channel, pedals);
+}
+
+/// Sends the state of the pedals (CC values in the order of [`PEDAL_CCS`]) to the visualizer.
+fn send_pedal_state(broadcast_channel: &mut BroadcastChannel<VisualizerMessage>, pedals: [u7; 3]) {
+    let [sustain, sostenuto, soft] = pedals;
+    let res = executor::block_on(broadcast_channel.send(&VisualizerMessage::Pedals {
+        sustain,
+        sostenuto,
+        soft,
+    }));
+    if let Err(e) = res {
+        println!("WARN: Failed to send message to visualizer: {}", e);
+    }
+}
+
+/// Sends the strongest combination tones of the notes of `pitches` (in cents from A4) to the visualizer, if
+/// [`COMBINATION_TONE_MESSAGES`] is enabled.
+fn send_combination_tones(
+    broadcast_channel: &mut BroadcastChannel<VisualizerMessage>,
+    pitches: &[f64],
+) {
+    if !COMBINATION_TONE_MESSAGES {
+        return;
+    }
+    let tones = combination::combination_tones(pitches, A4_FREQUENCY)
+        .into_iter()
+        .take(combination::TONES_PER_SONORITY)
+        .map(|tone| {
+            let (key, cents) = tone.nearest_key(A4_FREQUENCY);
+            (tone, key, cents)
+        })
+        .collect();
+    let res =
+        executor::block_on(broadcast_channel.send(&VisualizerMessage::CombinationTones { tones }));
+    if let Err(e) = res {
+        println!("WARN: Failed to send message to visualizer: {}", e);
+    }
+}
+
+/// Sends `chord` to the visualizer as a [`VisualizerMessage::ChordChanged`] if it differs from `curr_chord`, the one
+/// last sent.
+fn send_chord(
+    broadcast_channel: &mut BroadcastChannel<VisualizerMessage>,
+    curr_chord: &mut String,
+    chord: String,
+) {
+    if chord == *curr_chord {
+        return;
+    }
+    *curr_chord = chord.clone();
+    let res =
+        executor::block_on(broadcast_channel.send(&VisualizerMessage::ChordChanged { chord }));
+    if let Err(e) = res {
+        println!("WARN: Failed to send message to visualizer: {}", e);
+    }
+}
+
+/// Returns `vel` with `offset` (see [`tuner::TuningData::velocity_offsets`]) added, within 1-127. Note ons with
+/// velocity 0 are note offs and are left as they are.
+fn offset_velocity(vel: u7, offset: Option<i32>) -> u7 {
+    match offset {
+        Some(offset) if vel > 0 => u7::from((vel.as_int() as i32 + offset).clamp(1, 127) as u8),
+        _ => vel,
+    }
+}
+
+/// Sends a CC to the visualizer, and by name if it is one of the [`EXPRESSIVE_CONTROLLERS`].
+fn send_controller_state(
+    broadcast_channel: &mut BroadcastChannel<VisualizerMessage>,
+    controller: u7,
+    value: u7,
 ) {
+    let res =
+        executor::block_on(broadcast_channel.send(&VisualizerMessage::CC { controller, value }));
+    if let Err(e) = res {
+        println!("WARN: Failed to send message to visualizer: {}", e);
+    }
+    if let Some((_, name, _)) = EXPRESSIVE_CONTROLLERS
+        .iter()
+        .find(|(cc, _, _)| *cc == controller.as_int())
+    {
+        let res = executor::block_on(broadcast_channel.send(&VisualizerMessage::Controller {
+            name,
+            value: value.as_int() as f64 / 127.0,
+        }));
+        if let Err(e) = res {
+            println!("WARN: Failed to send message to visualizer: {}", e);
+        }
+    }
+}
+
+fn send_pitch_bend<T: Into<u4>>(midi_conn: &mut dyn MidiSink, channel: T, bend: PitchBend) {
     let ev = LiveEvent::Midi {
-        channel: channel.try_into().expect("Channel out of range"),
+        channel: channel.into(),
         message: MidiMessage::PitchBend { bend },
     };
 
     let mut raw = vec![];
     ev.write(&mut raw).unwrap();
-    midi_conn.send(&raw).unwrap();
+    midi_conn.send(&raw);
 }
 
 fn send_note_on<T: Into<u4>, S: Into<u7>, U: Into<u7>>(
-    midi_conn: &mut midir::MidiOutputConnection,
+    midi_conn: &mut dyn MidiSink,
     channel: T,
     note: S,
     velocity: U,
 ) {
     let ev = LiveEvent::Midi {
-        channel: channel.try_into().expect("Channel out of range"),
+        channel: channel.into(),
         message: MidiMessage::NoteOn {
-            key: note.try_into().expect("Note out of range"),
-            vel: velocity.try_into().expect("Velocity out of range"),
+            key: note.into(),
+            vel: velocity.into(),
         },
     };
 
     let mut raw = vec![];
     ev.write(&mut raw).unwrap();
-    midi_conn.send(&raw).unwrap();
+    midi_conn.send(&raw);
 }
 
 fn send_note_off<T: Into<u4>, S: Into<u7>, U: Into<u7>>(
-    midi_conn: &mut midir::MidiOutputConnection,
+    midi_conn: &mut dyn MidiSink,
     channel: T,
     note: S,
     velocity: U,
 ) {
     let ev = LiveEvent::Midi {
-        channel: channel.try_into().expect("Channel out of range"),
+        channel: channel.into(),
         message: MidiMessage::NoteOff {
-            key: note.try_into().expect("Note out of range"),
-            vel: velocity.try_into().expect("Velocity out of range"),
+            key: note.into(),
+            vel: velocity.into(),
         },
     };
 
     let mut raw = vec![];
     ev.write(&mut raw).unwrap();
-    midi_conn.send(&raw).unwrap();
+    midi_conn.send(&raw);
+}
+
+/// Sends a CC message from the MIDI file. Pedals ([`PEDAL_CCS`]) are sent on all 12 tuned channels: synths with
+/// per-channel pedals (e.g. FluidSynth) would otherwise only sustain the pitch class on channel 0, and a sostenuto
+/// pedal only catches the notes of its own channel. Other CCs are only sent on channel 0, which is enough for Pianoteq.
+fn send_controller<S: Into<u7>, U: Into<u7>>(
+    midi_conn: &mut dyn MidiSink,
+    controller: S,
+    value: U,
+) {
+    let (controller, value) = (controller.into(), value.into());
+    if PEDAL_CCS.contains(&controller.as_int()) {
+        for c in 0..12 {
+            send_cc(midi_conn, c, controller, value);
+        }
+    } else {
+        send_cc(midi_conn, 0, controller, value);
+    }
+}
+
+/// Sends a controller value of an automation lane on all 12 tuned channels, see [`automation`].
+fn send_automation(midi_conn: &mut dyn MidiSink, controller: u8, value: u8) {
+    for c in 0..12 {
+        send_cc(midi_conn, c, controller, value);
+    }
 }
 
 fn send_cc<T: Into<u4>, S: Into<u7>, U: Into<u7>>(
-    midi_conn: &mut midir::MidiOutputConnection,
+    midi_conn: &mut dyn MidiSink,
     channel: T,
     controller: S,
     value: U,
 ) {
     let ev = LiveEvent::Midi {
-        channel: channel.try_into().expect("Channel out of range"),
+        channel: channel.into(),
         message: MidiMessage::Controller {
-            controller: controller.try_into().expect("Controller out of range"),
-            value: value.try_into().expect("Value out of range"),
+            controller: controller.into(),
+            value: value.into(),
         },
     };
 
     let mut raw = vec![];
     ev.write(&mut raw).unwrap();
-    midi_conn.send(&raw).unwrap();
+    midi_conn.send(&raw);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midly::num::{u14, u24};
+
+    fn midi(delta: u32, message: MidiMessage) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: delta.into(),
+            kind: TrackEventKind::Midi {
+                channel: 0.into(),
+                message,
+            },
+        }
+    }
+
+    fn on(delta: u32, key: u8, vel: u8) -> TrackEvent<'static> {
+        midi(
+            delta,
+            MidiMessage::NoteOn {
+                key: key.into(),
+                vel: vel.into(),
+            },
+        )
+    }
+
+    fn off(delta: u32, key: u8) -> TrackEvent<'static> {
+        midi(
+            delta,
+            MidiMessage::NoteOff {
+                key: key.into(),
+                vel: 0.into(),
+            },
+        )
+    }
+
+    fn meta(delta: u32, message: MetaMessage<'static>) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: delta.into(),
+            kind: TrackEventKind::Meta(message),
+        }
+    }
+
+    /// A re-struck key is turned off before it is turned on again, and the end of the track stays last.
+    #[test]
+    fn restruck_key_ends_before_it_starts() {
+        let track = vec![
+            on(0, 60, 80),
+            meta(96, MetaMessage::EndOfTrack),
+            on(0, 60, 70),
+            off(0, 60),
+        ];
+        let expected = vec![
+            on(0, 60, 80),
+            off(96, 60),
+            on(0, 60, 70),
+            meta(0, MetaMessage::EndOfTrack),
+        ];
+        assert_eq!(batched_track(track), expected);
+    }
+
+    /// A note off of a key that wasn't sounding ends a note started at the same tick, so it goes after the note ons,
+    /// while those of sounding keys go before them. A note on with velocity 0 is a note off.
+    #[test]
+    fn zero_length_note_ends_after_it_starts() {
+        let track = vec![
+            on(0, 65, 80),
+            on(48, 62, 80),
+            off(0, 62),
+            on(0, 65, 0),
+            on(0, 64, 80),
+        ];
+        let expected = vec![
+            on(0, 65, 80),
+            on(48, 65, 0),
+            on(0, 62, 80),
+            on(0, 64, 80),
+            off(0, 62),
+        ];
+        assert_eq!(batched_track(track), expected);
+    }
+
+    /// Meta events go first, then pitch bends, then other controllers, and the delta of the tick stays on the first.
+    #[test]
+    fn tempo_change_goes_before_bend() {
+        let bend = MidiMessage::PitchBend {
+            bend: PitchBend(u14::from(9000)),
+        };
+        let sustain = MidiMessage::Controller {
+            controller: 64.into(),
+            value: 127.into(),
+        };
+        let tempo = MetaMessage::Tempo(u24::from(400_000));
+        let track = vec![
+            on(10, 60, 80),
+            midi(0, sustain),
+            midi(0, bend),
+            meta(0, tempo),
+            off(24, 60),
+        ];
+        let expected = vec![
+            meta(10, tempo),
+            midi(0, bend),
+            midi(0, sustain),
+            on(0, 60, 80),
+            off(24, 60),
+        ];
+        assert_eq!(batched_track(track), expected);
+    }
 }
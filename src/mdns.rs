@@ -0,0 +1,104 @@
+//! Advertisement of the endpoints of the performer on the local network via mDNS (zeroconf / Bonjour), so that
+//! visualizer clients on the venue network can discover the performer machine instead of hardcoding its IP. Enabled by
+//! [`crate::MDNS_NAME`], which every endpoint is advertised as:
+//!
+//! - `_ji-performer._tcp`: the websocket server of the visualizer messages, see [`crate::server`].
+//! - `_osc._udp`: the OSC input of a sync follower (see [`crate::sync`]), while following.
+//! - `_http._tcp`: the HTTP API of playback (see [`crate::api`]), if served.
+//!
+//! The TXT record of each has the `path` to use, e.g. `/ji/sync`. Endpoints bound to a loopback address only accept
+//! connections from the same machine, so they aren't advertised. Requires the `mdns` feature.
+
+use std::net::{SocketAddr, ToSocketAddrs};
+#[cfg(feature = "mdns")]
+use std::sync::Mutex;
+#[cfg(feature = "mdns")]
+use std::time::Duration;
+
+use crate::MDNS_NAME;
+
+/// The mDNS responder, and the full names of the services advertised by it.
+#[cfg(feature = "mdns")]
+static RESPONDER: Mutex<Option<(mdns_sd::ServiceDaemon, Vec<String>)>> = Mutex::new(None);
+
+/// Advertises the endpoint at `addr` as a service of `service_type` (e.g. `_osc._udp`) with `path` in its TXT record,
+/// if enabled by [`MDNS_NAME`].
+pub fn advertise(service_type: &str, addr: &str, path: &str) {
+    let Some(name) = MDNS_NAME else {
+        return;
+    };
+    let addr = addr
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .unwrap_or_else(|| panic!("Can't advertise invalid address {addr}"));
+    if addr.ip().is_loopback() {
+        println!("WARN: Not advertising {service_type} at {addr} via mDNS, it only accepts local connections");
+        return;
+    }
+    register(name, service_type, addr, path);
+    println!("Advertising {service_type} at {addr} as {name} via mDNS");
+}
+
+/// Stops advertising the services, telling clients that they are gone.
+pub fn withdraw() {
+    #[cfg(feature = "mdns")]
+    if let Some((responder, services)) = RESPONDER.lock().unwrap().take() {
+        for service in services {
+            if let Ok(status) = responder.unregister(&service) {
+                let _ = status.recv_timeout(Duration::from_secs(1));
+            }
+        }
+        let _ = responder.shutdown();
+    }
+}
+
+#[cfg(feature = "mdns")]
+fn register(name: &str, service_type: &str, addr: SocketAddr, path: &str) {
+    use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+    // Named after the instance, as a host name of the machine can't be gotten portably.
+    let host: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    // All addresses of the machine are advertised for endpoints bound to all interfaces.
+    let ip = match addr.ip().is_unspecified() {
+        true => String::new(),
+        false => addr.ip().to_string(),
+    };
+    let mut info = ServiceInfo::new(
+        &format!("{service_type}.local."),
+        name,
+        &format!("{host}.local."),
+        ip,
+        addr.port(),
+        &[("path", path)][..],
+    )
+    .unwrap_or_else(|e| panic!("Can't advertise {service_type} as {name}: {e}"));
+    if addr.ip().is_unspecified() {
+        info = info.enable_addr_auto();
+    }
+
+    let mut responder = RESPONDER.lock().unwrap();
+    let (responder, services) = responder.get_or_insert_with(|| {
+        let daemon =
+            ServiceDaemon::new().unwrap_or_else(|e| panic!("Failed to start mDNS responder: {e}"));
+        (daemon, vec![])
+    });
+    services.push(info.get_fullname().to_string());
+    responder
+        .register(info)
+        .unwrap_or_else(|e| panic!("Failed to advertise {service_type} via mDNS: {e}"));
+}
+
+#[cfg(not(feature = "mdns"))]
+fn register(_name: &str, _service_type: &str, _addr: SocketAddr, _path: &str) {
+    panic!("Advertising via mDNS requires the `mdns` feature")
+}
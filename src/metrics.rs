@@ -0,0 +1,167 @@
+//! Prometheus metrics of playback, so that long installation-style runs can be monitored with standard tooling (e.g. a
+//! Prometheus server scraping the machine, with Grafana alerts). Served over HTTP in the Prometheus text format at
+//! [`crate::METRICS_ADDR`]; every path serves the metrics, so `/metrics` works as usual.
+//!
+//! - `ji_performer_events_sent_total`: MIDI messages sent to the output.
+//! - `ji_performer_scheduling_lag_seconds`: quantiles of how late the last [`LAG_WINDOW`] events were sent after they
+//!   were due (negative if early), except while following a sync master or performer.
+//! - `ji_performer_websocket_clients`: connected visualizer clients (websocket & local socket).
+//! - `ji_performer_dropped_messages_total`: messages that failed to be sent to a visualizer client.
+//! - `ji_performer_tuning_index`: index of the tuning in effect in the tuning timeline, -1 before the first.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::output::MidiSink;
+use crate::server;
+use crate::tuner::TuningSnapshot;
+
+/// Number of most recent events the lag quantiles are computed over.
+const LAG_WINDOW: usize = 1000;
+
+/// Quantiles of the scheduling lag that are reported.
+const LAG_QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 1.0];
+
+static EVENTS_SENT: AtomicU64 = AtomicU64::new(0);
+static TUNING_INDEX: AtomicI64 = AtomicI64::new(-1);
+static LAGS: Mutex<Lags> = Mutex::new(Lags {
+    recent: VecDeque::new(),
+    sum: 0.0,
+    count: 0,
+});
+
+/// Scheduling lags in seconds: the last [`LAG_WINDOW`] ones, and the sum & count of all of them.
+struct Lags {
+    recent: VecDeque<f64>,
+    sum: f64,
+    count: u64,
+}
+
+/// Counts the messages sent to a sink as `ji_performer_events_sent_total`.
+pub struct Metered(pub Box<dyn MidiSink>);
+
+impl MidiSink for Metered {
+    fn send(&mut self, message: &[u8]) {
+        EVENTS_SENT.fetch_add(1, Ordering::Relaxed);
+        self.0.send(message);
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.0.retune(snapshot);
+    }
+
+    fn lookahead(&self) -> f64 {
+        self.0.lookahead()
+    }
+
+    fn timestamp(&mut self, delay: f64) {
+        self.0.timestamp(delay);
+    }
+}
+
+/// Records an event that was sent `lag` seconds after it was due (negative if early).
+pub fn record_lag(lag: f64) {
+    let mut lags = LAGS.lock().unwrap();
+    if lags.recent.len() == LAG_WINDOW {
+        lags.recent.pop_front();
+    }
+    lags.recent.push_back(lag);
+    lags.sum += lag;
+    lags.count += 1;
+}
+
+/// Sets the index of the tuning in effect, [`None`] before the first.
+pub fn set_tuning_index(idx: Option<usize>) {
+    TUNING_INDEX.store(idx.map_or(-1, |idx| idx as i64), Ordering::Relaxed);
+}
+
+/// Serves the metrics over HTTP at `addr`, on a new thread.
+pub fn serve(addr: &str) {
+    let listener = TcpListener::bind(addr)
+        .unwrap_or_else(|e| panic!("Failed to bind metrics endpoint {addr}: {e}"));
+    println!("Serving metrics on http://{addr}/metrics");
+    thread::spawn(move || {
+        for mut stream in listener.incoming().filter_map(Result::ok) {
+            // The request doesn't matter, but is read so that the client doesn't see the connection reset.
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let body = render();
+            let res = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            );
+            if let Err(e) = res {
+                println!("WARN: Failed to serve metrics: {e}");
+            }
+        }
+    });
+}
+
+/// Returns the metrics in the Prometheus text format.
+fn render() -> String {
+    let mut text = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, f64)]| {
+        writeln!(text, "# HELP {name} {help}").unwrap();
+        writeln!(text, "# TYPE {name} {kind}").unwrap();
+        for (suffix, value) in samples {
+            writeln!(text, "{name}{suffix} {value}").unwrap();
+        }
+    };
+
+    metric(
+        "ji_performer_events_sent_total",
+        "counter",
+        "MIDI messages sent to the output.",
+        &[(String::new(), EVENTS_SENT.load(Ordering::Relaxed) as f64)],
+    );
+
+    let lags = LAGS.lock().unwrap();
+    let mut recent: Vec<f64> = lags.recent.iter().copied().collect();
+    recent.sort_by(f64::total_cmp);
+    let mut samples: Vec<(String, f64)> = LAG_QUANTILES
+        .iter()
+        .map(|q| {
+            let value = match recent.len() {
+                0 => f64::NAN,
+                len => recent[(q * (len - 1) as f64).round() as usize],
+            };
+            (format!("{{quantile=\"{q}\"}}"), value)
+        })
+        .collect();
+    samples.push(("_sum".to_string(), lags.sum));
+    samples.push(("_count".to_string(), lags.count as f64));
+    drop(lags);
+    metric(
+        "ji_performer_scheduling_lag_seconds",
+        "summary",
+        "How late events were sent after they were due (negative if early).",
+        &samples,
+    );
+
+    metric(
+        "ji_performer_websocket_clients",
+        "gauge",
+        "Connected visualizer clients.",
+        &[(String::new(), server::clients() as f64)],
+    );
+    metric(
+        "ji_performer_dropped_messages_total",
+        "counter",
+        "Messages that failed to be sent to a visualizer client.",
+        &[(String::new(), server::dropped_messages() as f64)],
+    );
+    metric(
+        "ji_performer_tuning_index",
+        "gauge",
+        "Index of the tuning in effect in the tuning timeline, -1 before the first.",
+        &[(String::new(), TUNING_INDEX.load(Ordering::Relaxed) as f64)],
+    );
+    text
+}
@@ -0,0 +1,88 @@
+//! [MIDI Tuning Standard](https://midi.org/midi-tuning-updated-specification) bulk tuning dumps, for synths that
+//! support MTS but not real-time single note tuning changes (or per-channel pitch bends).
+
+use crate::output::MidiSink;
+use crate::tuner::TuningSnapshot;
+
+/// Returns a non-real-time bulk tuning dump SysEx message (including `F0` & `F7`) of all 128 keys, stored to tuning
+/// program `program` with the given name (truncated/padded to 16 ASCII characters).
+///
+/// MTS frequencies are absolute, with A4 = 440 Hz, so `a4_hz` is the frequency of A4 (1/1).
+pub fn bulk_dump(snapshot: &TuningSnapshot, a4_hz: f64, program: u8, name: &str) -> Vec<u8> {
+    // Device ID 7F: all devices
+    let mut data = vec![0x7E, 0x7F, 0x08, 0x01, program & 0x7F];
+    let name = name
+        .chars()
+        .filter(char::is_ascii)
+        .chain(std::iter::repeat(' '));
+    data.extend(name.take(16).map(|c| c as u8 & 0x7F));
+
+    let a4_offset = 12.0 * (a4_hz / 440.0).log2();
+    for key in 0..=127u8 {
+        let semitones = 69.0 + snapshot.key_cents(key) / 100.0 + a4_offset;
+        data.extend(frequency_data(semitones));
+    }
+
+    let checksum = data.iter().fold(0, |acc, byte| acc ^ byte) & 0x7F;
+    let mut message = vec![0xF0];
+    message.extend(data);
+    message.push(checksum);
+    message.push(0xF7);
+    message
+}
+
+/// Encodes a pitch (in semitones, 69 being 440 Hz) as the 3 byte MTS frequency data format: the 12edo semitone below
+/// and the fraction of a semitone above it in 14 bits.
+fn frequency_data(semitones: f64) -> [u8; 3] {
+    // 7F 7F 7F is reserved for "no change".
+    let semitones = semitones.clamp(0.0, 127.0 + 16382.0 / 16384.0);
+    let mut semitone = semitones.floor() as u32;
+    let mut fraction = ((semitones - semitone as f64) * 16384.0).round() as u32;
+    if fraction == 16384 {
+        semitone += 1;
+        fraction = 0;
+    }
+    [
+        semitone as u8,
+        (fraction >> 7) as u8,
+        (fraction & 0x7F) as u8,
+    ]
+}
+
+/// Wraps a MIDI output to tune it with a bulk tuning dump at every tuning change instead of pitch bends, which are
+/// not sent.
+///
+/// Dumps are stored to tuning program 0, which the synth must have selected. A dump is 408 bytes, which takes ~130ms
+/// to send over a 5-pin DIN MIDI cable.
+pub struct MtsBulkDump {
+    output: Box<dyn MidiSink>,
+    a4_hz: f64,
+}
+
+impl MtsBulkDump {
+    pub fn new(output: Box<dyn MidiSink>, a4_hz: f64) -> Self {
+        MtsBulkDump { output, a4_hz }
+    }
+}
+
+impl MidiSink for MtsBulkDump {
+    fn send(&mut self, message: &[u8]) {
+        if message[0] & 0xF0 != 0xE0 {
+            self.output.send(message);
+        }
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        let name = format!("JI @ {:.3}s", snapshot.time);
+        self.output.send(&bulk_dump(snapshot, self.a4_hz, 0, &name));
+        self.output.retune(snapshot);
+    }
+
+    fn lookahead(&self) -> f64 {
+        self.output.lookahead()
+    }
+
+    fn timestamp(&mut self, delay: f64) {
+        self.output.timestamp(delay);
+    }
+}
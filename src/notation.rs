@@ -0,0 +1,138 @@
+//! Spelling of JI pitches in Helmholtz-Ellis JI Pitch Notation (HEJI).
+//!
+//! In HEJI, each pitch is written as a Pythagorean (3-limit) nominal & accidental, plus one comma accidental for
+//! every higher prime in the ratio. E.g. relative to A, 5/4 is a Pythagorean C♯ (81/64) lowered by a syntonic comma
+//! (81/80), and 7/4 is a Pythagorean G (16/9) lowered by a septimal comma (64/63).
+
+use std::fmt;
+
+use crate::tuner::{Monzo, PRIMES_BY_INDEX};
+
+/// Nominals in order of the chain of fifths.
+const NOMINALS: [char; 7] = ['F', 'C', 'G', 'D', 'A', 'E', 'B'];
+
+/// Index of A in [`NOMINALS`], since all ratios are relative to A.
+const A_NOMINAL: i32 = 4;
+
+/// The Pythagorean interval (as a power of 3) that each prime's HEJI comma is applied to.
+///
+/// E.g. 5 is spelt as 3^4 = 81/64 (Pythagorean major third) altered by the syntonic comma.
+///
+/// Primes not listed here use the closest Pythagorean interval within 6 fifths.
+const HEJI_PYTHAGOREAN_BASES: [(u32, i32); 13] = [
+    (5, 4),   // 81/80
+    (7, -2),  // 64/63
+    (11, -1), // 33/32
+    (13, -4), // 1053/1024
+    (17, 7),  // 2187/2176
+    (19, -3), // 513/512
+    (23, 6),  // 736/729
+    (29, -2), // 261/256
+    (31, 0),  // 32/31
+    (37, 2),  // 37/36
+    (41, 4),  // 82/81
+    (43, -1), // 129/128
+    (47, 6),  // 752/729
+];
+
+/// A pitch spelt in HEJI.
+pub struct HejiSpelling {
+    /// Power of 3 of the Pythagorean part of the spelling, i.e. the position along the chain of fifths relative to A.
+    pub fifths: i32,
+    /// Comma accidentals as `(prime, count)`. A positive count raises, a negative count lowers.
+    pub commas: Vec<(u32, i32)>,
+}
+
+impl HejiSpelling {
+    /// Spells a pitch class monzo (relative to A) in HEJI. Powers of 2 are ignored.
+    pub fn new(monzo: &Monzo) -> Self {
+        let mut fifths = monzo.get(1).copied().unwrap_or(0);
+        let mut commas = vec![];
+        for (i, exp) in monzo.iter().enumerate().skip(2) {
+            if *exp == 0 {
+                continue;
+            }
+            let prime = PRIMES_BY_INDEX[i];
+            let base = pythagorean_base(prime);
+            fifths += base * exp;
+            let direction = if cents_from(prime, base) > 0.0 { 1 } else { -1 };
+            commas.push((prime, exp * direction));
+        }
+        HejiSpelling { fifths, commas }
+    }
+
+    /// Returns the nominal (letter name) of the spelling.
+    pub fn nominal(&self) -> char {
+        NOMINALS[(A_NOMINAL + self.fifths).rem_euclid(7) as usize]
+    }
+
+    /// Returns the number of sharps (positive) or flats (negative) of the Pythagorean accidental.
+    pub fn sharps(&self) -> i32 {
+        (A_NOMINAL + self.fifths).div_euclid(7)
+    }
+
+    /// Returns the number of 12edo semitones above A of the Pythagorean part of the spelling, in `0..12`.
+    pub fn semitones(&self) -> i32 {
+        (7 * self.fifths).rem_euclid(12)
+    }
+
+    /// Returns the deviation in cents of a pitch (given in cents above A, octave reduced) from the 12edo pitch of its
+    /// spelt nominal & accidental.
+    pub fn deviation(&self, cents: f64) -> f64 {
+        let dev = cents - 100.0 * self.semitones() as f64;
+        dev - 1200.0 * (dev / 1200.0).round()
+    }
+}
+
+impl fmt::Display for HejiSpelling {
+    /// Formats the spelling as text, e.g. `C♯↓` for 5/4 or `G 7↓` for 7/4 (relative to A).
+    ///
+    /// Syntonic commas are written as arrows attached to the accidental, other commas as the prime followed by an
+    /// arrow, with the count if it is more than 1.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.nominal())?;
+        // Beyond double sharps/flats, a single accidental is followed by double ones.
+        let sharps = self.sharps();
+        let (single, double) = if sharps > 0 {
+            ("♯", "𝄪")
+        } else {
+            ("♭", "𝄫")
+        };
+        if sharps % 2 != 0 {
+            write!(f, "{single}")?;
+        }
+        write!(f, "{}", double.repeat((sharps.unsigned_abs() / 2) as usize))?;
+
+        for (prime, count) in &self.commas {
+            let arrow = if *count > 0 { "↑" } else { "↓" };
+            if *prime == 5 {
+                write!(f, "{}", arrow.repeat(count.unsigned_abs() as usize))?;
+            } else if count.abs() == 1 {
+                write!(f, " {prime}{arrow}")?;
+            } else {
+                write!(f, " {prime}{arrow}×{}", count.abs())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the power of 3 of the Pythagorean interval that `prime` is spelt relative to.
+fn pythagorean_base(prime: u32) -> i32 {
+    if let Some((_, base)) = HEJI_PYTHAGOREAN_BASES.iter().find(|(p, _)| *p == prime) {
+        return *base;
+    }
+    (-6..=6)
+        .min_by(|a, b| {
+            cents_from(prime, *a)
+                .abs()
+                .total_cmp(&cents_from(prime, *b).abs())
+        })
+        .unwrap()
+}
+
+/// Returns the size in cents of `prime` relative to `3^fifths`, octave reduced to `-600..600`.
+fn cents_from(prime: u32, fifths: i32) -> f64 {
+    let cents = 1200.0 * ((prime as f64).log2() - fifths as f64 * 3f64.log2());
+    cents - 1200.0 * (cents / 1200.0).round()
+}
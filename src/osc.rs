@@ -0,0 +1,96 @@
+//! Minimal [OSC 1.0](https://opensoundcontrol.stanford.edu/spec-1_0.html) message encoding & sending over UDP.
+
+use std::net::UdpSocket;
+
+/// An OSC argument.
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+}
+
+/// Encodes an OSC message with the given address pattern & arguments.
+pub fn encode(address: &str, args: &[OscArg]) -> Vec<u8> {
+    let mut packet = vec![];
+    write_string(&mut packet, address);
+
+    let mut type_tags = String::from(",");
+    for arg in args {
+        type_tags.push(match arg {
+            OscArg::Int(_) => 'i',
+            OscArg::Float(_) => 'f',
+            OscArg::String(_) => 's',
+        });
+    }
+    write_string(&mut packet, &type_tags);
+
+    for arg in args {
+        match arg {
+            OscArg::Int(i) => packet.extend(i.to_be_bytes()),
+            OscArg::Float(f) => packet.extend(f.to_be_bytes()),
+            OscArg::String(s) => write_string(&mut packet, s),
+        }
+    }
+    packet
+}
+
+/// Decodes an OSC message (not a bundle) into its address pattern & arguments. Returns [`None`] if it is malformed or has
+/// arguments of types other than int, float & string.
+pub fn decode(packet: &[u8]) -> Option<(String, Vec<OscArg>)> {
+    let mut pos = 0;
+    let address = read_string(packet, &mut pos)?;
+    let type_tags = read_string(packet, &mut pos)?;
+    let mut args = vec![];
+    for tag in type_tags.strip_prefix(',')?.chars() {
+        let arg = match tag {
+            'i' => OscArg::Int(i32::from_be_bytes(
+                packet.get(pos..pos + 4)?.try_into().ok()?,
+            )),
+            'f' => OscArg::Float(f32::from_be_bytes(
+                packet.get(pos..pos + 4)?.try_into().ok()?,
+            )),
+            's' => OscArg::String(read_string(packet, &mut pos)?),
+            _ => return None,
+        };
+        if tag != 's' {
+            pos += 4;
+        }
+        args.push(arg);
+    }
+    Some((address, args))
+}
+
+/// Reads a null terminated string padded to a multiple of 4 bytes at `pos`, advancing it past the padding.
+fn read_string(packet: &[u8], pos: &mut usize) -> Option<String> {
+    let len = packet.get(*pos..)?.iter().position(|&b| b == 0)?;
+    let s = String::from_utf8(packet[*pos..*pos + len].to_vec()).ok()?;
+    *pos += (len / 4 + 1) * 4;
+    Some(s)
+}
+
+/// Writes a null terminated string, padded to a multiple of 4 bytes.
+fn write_string(packet: &mut Vec<u8>, s: &str) {
+    packet.extend(s.as_bytes());
+    packet.extend(std::iter::repeat_n(0, 4 - s.len() % 4));
+}
+
+/// Sends OSC messages over UDP to a fixed address.
+pub struct OscSender {
+    socket: UdpSocket,
+}
+
+impl OscSender {
+    pub fn connect(addr: &str) -> Self {
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket
+            .connect(addr)
+            .unwrap_or_else(|e| panic!("Failed to resolve OSC address {addr}: {e}"));
+        OscSender { socket }
+    }
+
+    pub fn send(&self, address: &str, args: &[OscArg]) {
+        if let Err(e) = self.socket.send(&encode(address, args)) {
+            println!("WARN: Failed to send OSC message {address}: {e}");
+        }
+    }
+}
@@ -0,0 +1,525 @@
+//! Destinations for the MIDI messages of the performance.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use clap::ValueEnum;
+
+use crate::drift;
+use crate::score::{CC_SOSTENUTO, CC_SUSTAIN};
+use crate::tuner::{
+    cents_pitch_bend, key_name, pitch_bend_message, pitch_class, PitchSpec, TuningSnapshot,
+};
+
+/// Something that raw MIDI messages can be sent to, e.g. a MIDI output port.
+pub trait MidiSink {
+    /// Sends a single raw MIDI message.
+    fn send(&mut self, message: &[u8]);
+
+    /// Called with the complete tuning whenever it changes, right before the pitch bends that apply it are sent.
+    ///
+    /// Sinks that can tune notes directly can use this to tune exactly, instead of relying on the 14-bit pitch bends.
+    fn retune(&mut self, _snapshot: &TuningSnapshot) {}
+
+    /// How many seconds ahead of time messages can be sent, to be played after the delay set by
+    /// [`MidiSink::timestamp`]. 0 for sinks that play messages as soon as they are sent.
+    fn lookahead(&self) -> f64 {
+        0.0
+    }
+
+    /// Delays the messages sent from now on by `delay` seconds (at most [`MidiSink::lookahead`]) from when they are sent.
+    fn timestamp(&mut self, _delay: f64) {}
+}
+
+impl MidiSink for midir::MidiOutputConnection {
+    fn send(&mut self, message: &[u8]) {
+        midir::MidiOutputConnection::send(self, message).unwrap();
+    }
+}
+
+/// Sends the messages of a sink `latency` seconds early, to compensate for the latency of its connection, e.g. of a
+/// Bluetooth LE MIDI peripheral. Playback sleeps until that much earlier, and scheduled messages are delayed by that
+/// much less.
+///
+/// A negative `latency` delays the messages instead, e.g. to keep a software synth in step with a slower hardware one.
+/// Only sinks that schedule messages can be delayed, by at most their [`MidiSink::lookahead`].
+pub struct LatencyCompensated {
+    pub sink: Box<dyn MidiSink>,
+    pub latency: f64,
+}
+
+impl MidiSink for LatencyCompensated {
+    fn send(&mut self, message: &[u8]) {
+        self.sink.send(message);
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.sink.retune(snapshot);
+    }
+
+    fn lookahead(&self) -> f64 {
+        (self.sink.lookahead() + self.latency).max(0.0)
+    }
+
+    fn timestamp(&mut self, delay: f64) {
+        self.sink
+            .timestamp((delay - self.latency).clamp(0.0, self.sink.lookahead()));
+    }
+}
+
+/// A sink shared by several owners, e.g. the output that the performances of a concert are played to in turn.
+#[derive(Clone)]
+pub struct Shared(pub Rc<RefCell<Box<dyn MidiSink>>>);
+
+impl MidiSink for Shared {
+    fn send(&mut self, message: &[u8]) {
+        self.0.borrow_mut().send(message);
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.0.borrow_mut().retune(snapshot);
+    }
+
+    fn lookahead(&self) -> f64 {
+        self.0.borrow().lookahead()
+    }
+
+    fn timestamp(&mut self, delay: f64) {
+        self.0.borrow_mut().timestamp(delay);
+    }
+}
+
+/// A sounding note, see [`NoteChannels`].
+#[derive(Clone, Copy)]
+pub struct Voice {
+    pub channel: u8,
+    pub velocity: u8,
+    /// Number of notes turned on before it, to tell which notes are older.
+    pub onset: u64,
+    /// Whether its key was released, so that only the pedals hold it.
+    pub released: bool,
+    /// Whether the sostenuto pedal caught it.
+    pub sostenuto: bool,
+}
+
+/// The sounding notes by key, with the channels they were turned on at, so that each note off is sent to the channel
+/// its note on was sent to, rather than the channel its key would be sent to now.
+///
+/// A released note held by the sustain or sostenuto pedal keeps sounding, so it keeps its channel until the pedal is
+/// lifted, rather than having the channel given to another note, which would retune it.
+pub struct NoteChannels {
+    voices: [Option<Voice>; 128],
+    onsets: u64,
+    /// Whether the sustain pedal is down.
+    sustain: bool,
+    /// Whether the sostenuto pedal is down.
+    sostenuto: bool,
+}
+
+impl NoteChannels {
+    pub fn new() -> Self {
+        NoteChannels {
+            voices: [None; 128],
+            onsets: 0,
+            sustain: false,
+            sostenuto: false,
+        }
+    }
+
+    /// Turns the note of `key` on at `velocity` at the channel chosen by `allocator`. Returns the channel, and the key
+    /// of the note stolen to free it, if any, which is forgotten and has to be turned off at the channel first.
+    pub fn note_on(
+        &mut self,
+        key: u8,
+        velocity: u8,
+        allocator: &mut dyn ChannelAllocator,
+    ) -> (u8, Option<u8>) {
+        let (channel, stolen) = allocator.allocate(key, self);
+        if let Some(stolen) = stolen {
+            self.voices[stolen as usize] = None;
+        }
+        // A re-struck key caught by the sostenuto pedal stays caught.
+        let sostenuto = self.voices[key as usize].is_some_and(|voice| voice.sostenuto);
+        self.voices[key as usize] = Some(Voice {
+            channel,
+            velocity,
+            onset: self.onsets,
+            released: false,
+            sostenuto,
+        });
+        self.onsets += 1;
+        (channel, stolen)
+    }
+
+    /// Returns the channel the note of `key` was turned on at, or the one `allocator` falls back to if it isn't
+    /// sounding, and forgets it unless a pedal holds it.
+    pub fn note_off(&mut self, key: u8, allocator: &dyn ChannelAllocator) -> u8 {
+        let Some(voice) = &mut self.voices[key as usize] else {
+            return allocator.fallback(key);
+        };
+        voice.released = true;
+        let channel = voice.channel;
+        self.free_released();
+        channel
+    }
+
+    /// Sets the pedal of `controller` (see [`crate::score::PEDAL_CCS`]) to `value`, down from 64 on. Pressing the
+    /// sostenuto pedal catches the notes sounding, and lifting a pedal forgets the released notes it held.
+    pub fn pedal(&mut self, controller: u8, value: u8) {
+        let down = value >= 64;
+        match controller {
+            CC_SUSTAIN => self.sustain = down,
+            CC_SOSTENUTO if down != self.sostenuto => {
+                self.sostenuto = down;
+                for voice in self.voices.iter_mut().flatten() {
+                    voice.sostenuto = down;
+                }
+            }
+            _ => return,
+        }
+        self.free_released();
+    }
+
+    /// Forgets the released notes that no pedal holds.
+    fn free_released(&mut self) {
+        let sustain = self.sustain;
+        for voice in &mut self.voices {
+            if voice.is_some_and(|voice| voice.released && !sustain && !voice.sostenuto) {
+                *voice = None;
+            }
+        }
+    }
+
+    /// Returns the sounding notes with their keys, from the lowest key up.
+    pub fn voices(&self) -> impl Iterator<Item = (u8, Voice)> + '_ {
+        (0..128).filter_map(|key| Some((key, self.voices[key as usize]?)))
+    }
+}
+
+/// Chooses the channels that notes are played on, given the notes sounding (see [`NoteChannels`]), and the pitch bends
+/// that tune them. Playback sends the pitch bends of tuning changes, drift steps, pitch envelopes and live edits as
+/// returned by [`ChannelAllocator::retune`], and those of [`ChannelAllocator::tune_note`] before each note on, so that
+/// a new allocator doesn't need changes to the playback loop.
+///
+/// Keyboard splits (see [`crate::split`]) are deliberately not an allocator: an allocator chooses channels of one bank
+/// for the one tuning it is given, while the lower range of a split is a second bank on another port, tuned by a
+/// timeline of its own. The split is a [`MidiSink`] instead, routing the notes below it to that bank after the
+/// allocator has chosen their channels. The bank is tuned by pitch class, so a split needs [`PitchClassChannels`] for
+/// the notes routed to it to land on the channel of their pitch class.
+pub trait ChannelAllocator {
+    /// Returns the channel to turn the note of `key` on at, and the key of the sounding note to steal it from if the
+    /// channel has to be freed for it.
+    fn allocate(&mut self, key: u8, sounding: &NoteChannels) -> (u8, Option<u8>);
+
+    /// Returns the channel to turn the note of `key` off at if it isn't sounding, e.g. as it was turned on before the
+    /// start point.
+    fn fallback(&self, key: u8) -> u8;
+
+    /// Returns the pitch bends that retune the notes of the semitones that `tuning` doesn't keep (see
+    /// [`PitchSpec::Keep`]).
+    fn retune(&self, tuning: &[PitchSpec; 12], sounding: &NoteChannels) -> Vec<Vec<u8>>;
+
+    /// Returns the pitch bends to send before the note of `key` is turned on at `channel`, to tune it to `tuning`.
+    fn tune_note(&self, key: u8, channel: u8, tuning: &[PitchSpec; 12]) -> Vec<Vec<u8>>;
+}
+
+/// How notes are assigned to channels, see [`crate::CHANNEL_ALLOCATION`] & [`crate::config::Config::allocation`].
+#[derive(Clone, Copy, Debug)]
+pub enum Allocation {
+    /// See [`PitchClassChannels`].
+    PitchClasses,
+    /// See [`PerNoteChannels`].
+    PerNote(StealPolicy),
+}
+
+impl Allocation {
+    /// Whether each note is played on the channel of its pitch class, as the lower range of a keyboard split is.
+    pub const fn by_pitch_class(self) -> bool {
+        matches!(self, Allocation::PitchClasses)
+    }
+
+    /// Returns the allocator, tuning with pitch bends for a range of +/- `pb_range` semitones.
+    pub fn allocator(self, pb_range: u16) -> Box<dyn ChannelAllocator> {
+        match self {
+            Allocation::PitchClasses => Box::new(PitchClassChannels { pb_range }),
+            Allocation::PerNote(steal) => Box::new(PerNoteChannels::new(steal, pb_range)),
+        }
+    }
+}
+
+/// Plays each note on the channel of its pitch class (0 for A, 1 for Bb...), which the pitch bends of its tuning are
+/// sent on for a pitch bend range of +/- `pb_range` semitones, so notes are already tuned when turned on. Never runs
+/// out of channels, as the notes sharing a channel share its tuning.
+pub struct PitchClassChannels {
+    pub pb_range: u16,
+}
+
+impl ChannelAllocator for PitchClassChannels {
+    fn allocate(&mut self, key: u8, _sounding: &NoteChannels) -> (u8, Option<u8>) {
+        (pitch_class(key) as u8, None)
+    }
+
+    fn fallback(&self, key: u8) -> u8 {
+        pitch_class(key) as u8
+    }
+
+    fn retune(&self, tuning: &[PitchSpec; 12], _sounding: &NoteChannels) -> Vec<Vec<u8>> {
+        drift::pitch_bends(tuning, 0.0, self.pb_range)
+    }
+
+    fn tune_note(&self, _key: u8, _channel: u8, _tuning: &[PitchSpec; 12]) -> Vec<Vec<u8>> {
+        vec![]
+    }
+}
+
+/// Which sounding note [`PerNoteChannels`] takes the channel of when all channels are sounding.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum StealPolicy {
+    /// The note turned on first.
+    Oldest,
+    /// The note turned on at the lowest velocity, the oldest of those.
+    Quietest,
+    /// The oldest note of the same pitch class as the new note (e.g. its octave), or else the oldest note.
+    SamePitchClass,
+}
+
+/// Plays each note on a channel of its own out of the 12 tuned channels, bent to its tuning right before its note on.
+/// The free channel that was used longest ago is taken, so that a released note keeps ringing in its tuning rather
+/// than being bent by later retunes of its pitch class. When all channels are sounding, the channel of a sounding note
+/// is stolen by the [`StealPolicy`] (the note is turned off first), and logged, rather than playing two differently
+/// tuned notes on one channel.
+pub struct PerNoteChannels {
+    steal: StealPolicy,
+    pb_range: u16,
+    /// Number of notes allocated before the last note allocated to each channel, 0 for unused channels.
+    last_used: [u64; 12],
+    allocated: u64,
+}
+
+impl PerNoteChannels {
+    pub fn new(steal: StealPolicy, pb_range: u16) -> Self {
+        PerNoteChannels {
+            steal,
+            pb_range,
+            last_used: [0; 12],
+            allocated: 0,
+        }
+    }
+
+    /// Returns the key of the note whose channel is stolen for the note of `key`.
+    fn victim(&self, key: u8, sounding: &NoteChannels) -> u8 {
+        let onset = |(_, voice): &(u8, Voice)| voice.onset;
+        let victim = match self.steal {
+            StealPolicy::Oldest => sounding.voices().min_by_key(onset),
+            StealPolicy::Quietest => sounding
+                .voices()
+                .min_by_key(|(_, voice)| (voice.velocity, voice.onset)),
+            StealPolicy::SamePitchClass => sounding
+                .voices()
+                .filter(|(other, _)| pitch_class(*other) == pitch_class(key))
+                .min_by_key(onset)
+                .or_else(|| sounding.voices().min_by_key(onset)),
+        };
+        victim.expect("All channels are sounding").0
+    }
+}
+
+impl ChannelAllocator for PerNoteChannels {
+    fn allocate(&mut self, key: u8, sounding: &NoteChannels) -> (u8, Option<u8>) {
+        self.allocated += 1;
+        // A re-struck key takes over the channel of the note it restarts.
+        if let Some((_, voice)) = sounding.voices().find(|(other, _)| *other == key) {
+            self.last_used[voice.channel as usize] = self.allocated;
+            return (voice.channel, None);
+        }
+        let busy: Vec<u8> = sounding.voices().map(|(_, voice)| voice.channel).collect();
+        let free = (0..12u8)
+            .filter(|channel| !busy.contains(channel))
+            .min_by_key(|channel| self.last_used[*channel as usize]);
+        let (channel, stolen) = match free {
+            Some(channel) => (channel, None),
+            None => {
+                let victim = self.victim(key, sounding);
+                let voice = sounding
+                    .voices()
+                    .find(|(other, _)| *other == victim)
+                    .unwrap()
+                    .1;
+                let channel = voice.channel;
+                let policy = match self.steal {
+                    StealPolicy::Oldest => "oldest note",
+                    StealPolicy::Quietest => "quietest note",
+                    StealPolicy::SamePitchClass => "oldest note of the same pitch class",
+                };
+                let held = if voice.released {
+                    ", held by a pedal"
+                } else {
+                    ""
+                };
+                println!(
+                    "WARN: All channels are sounding, stealing channel {channel} from {} (the {policy}{held}) for {}",
+                    key_name(victim),
+                    key_name(key)
+                );
+                (channel, Some(victim))
+            }
+        };
+        self.last_used[channel as usize] = self.allocated;
+        (channel, stolen)
+    }
+
+    fn fallback(&self, key: u8) -> u8 {
+        pitch_class(key) as u8
+    }
+
+    fn retune(&self, tuning: &[PitchSpec; 12], sounding: &NoteChannels) -> Vec<Vec<u8>> {
+        sounding
+            .voices()
+            .flat_map(|(key, voice)| self.tune_note(key, voice.channel, tuning))
+            .collect()
+    }
+
+    fn tune_note(&self, key: u8, channel: u8, tuning: &[PitchSpec; 12]) -> Vec<Vec<u8>> {
+        let semitone = pitch_class(key);
+        let Some(cents) = tuning[semitone].cents() else {
+            return vec![];
+        };
+        vec![pitch_bend_message(
+            channel as usize,
+            cents_pitch_bend(semitone, cents, self.pb_range),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Turns the notes of `keys` on at `velocity` in order, returning their channels.
+    fn play(
+        notes: &mut NoteChannels,
+        allocator: &mut dyn ChannelAllocator,
+        keys: &[u8],
+        velocity: u8,
+    ) -> Vec<u8> {
+        keys.iter()
+            .map(|&key| notes.note_on(key, velocity, allocator).0)
+            .collect()
+    }
+
+    /// Fills all 12 channels with the notes of keys 60 to 71, the note of key 64 being the quietest. Returns the key &
+    /// the channel stolen for a note of key 49, of the pitch class of key 61, under `steal`.
+    fn steal(steal: StealPolicy) -> (u8, u8) {
+        let mut notes = NoteChannels::new();
+        let mut allocator = PerNoteChannels::new(steal, 2);
+        for key in 60..72 {
+            let velocity = if key == 64 { 20 } else { 80 };
+            notes.note_on(key, velocity, &mut allocator);
+        }
+        let channel_of = |key| notes.voices().find(|(k, _)| *k == key).unwrap().1.channel;
+        let channels: Vec<(u8, u8)> = (60..72).map(|key| (key, channel_of(key))).collect();
+        let (channel, stolen) = notes.note_on(49, 80, &mut allocator);
+        let stolen = stolen.expect("All channels are sounding");
+        let (_, stolen_channel) = channels.iter().find(|(key, _)| *key == stolen).unwrap();
+        assert_eq!(channel, *stolen_channel);
+        assert!(notes.voices().all(|(key, _)| key != stolen));
+        (stolen, channel)
+    }
+
+    #[test]
+    fn restruck_key_keeps_its_channel() {
+        let mut notes = NoteChannels::new();
+        let mut allocator = PerNoteChannels::new(StealPolicy::Oldest, 2);
+        let channels = play(&mut notes, &mut allocator, &[60, 64, 60], 80);
+        assert_eq!(channels, [0, 1, 0]);
+        assert_eq!(notes.voices().count(), 2);
+        assert_eq!(notes.note_off(60, &allocator), 0);
+        assert_eq!(notes.voices().count(), 1);
+    }
+
+    #[test]
+    fn free_channel_used_longest_ago_is_reused() {
+        let mut notes = NoteChannels::new();
+        let mut allocator = PerNoteChannels::new(StealPolicy::Oldest, 2);
+        let channels = play(&mut notes, &mut allocator, &[60, 61, 62], 80);
+        assert_eq!(channels, [0, 1, 2]);
+        // The channels that were never used are free for longer than the released ones.
+        notes.note_off(61, &allocator);
+        notes.note_off(60, &allocator);
+        let channels = play(
+            &mut notes,
+            &mut allocator,
+            &(63..72).collect::<Vec<_>>(),
+            80,
+        );
+        assert_eq!(channels, (3..12).collect::<Vec<_>>());
+        // Then the channel of key 60, released after key 61's but used before it.
+        assert_eq!(play(&mut notes, &mut allocator, &[72, 73], 80), [0, 1]);
+    }
+
+    #[test]
+    fn note_off_of_silent_key_falls_back() {
+        let mut notes = NoteChannels::new();
+        let allocator = PerNoteChannels::new(StealPolicy::Oldest, 2);
+        assert_eq!(notes.note_off(62, &allocator), pitch_class(62) as u8);
+    }
+
+    #[test]
+    fn oldest_note_is_stolen() {
+        assert_eq!(steal(StealPolicy::Oldest), (60, 0));
+    }
+
+    #[test]
+    fn quietest_note_is_stolen() {
+        assert_eq!(steal(StealPolicy::Quietest), (64, 4));
+    }
+
+    #[test]
+    fn note_of_same_pitch_class_is_stolen() {
+        assert_eq!(steal(StealPolicy::SamePitchClass), (61, 1));
+    }
+
+    #[test]
+    fn sustain_pedal_holds_released_notes() {
+        let mut notes = NoteChannels::new();
+        let mut allocator = PerNoteChannels::new(StealPolicy::Oldest, 2);
+        play(&mut notes, &mut allocator, &[60], 80);
+        notes.pedal(CC_SUSTAIN, 127);
+        assert_eq!(notes.note_off(60, &allocator), 0);
+        // The held note keeps its channel.
+        assert_eq!(play(&mut notes, &mut allocator, &[62], 80), [1]);
+        assert!(notes
+            .voices()
+            .any(|(key, voice)| key == 60 && voice.released));
+        notes.note_off(62, &allocator);
+        notes.pedal(CC_SUSTAIN, 0);
+        assert_eq!(notes.voices().count(), 0);
+    }
+
+    #[test]
+    fn sostenuto_pedal_holds_the_notes_it_caught() {
+        let mut notes = NoteChannels::new();
+        let mut allocator = PerNoteChannels::new(StealPolicy::Oldest, 2);
+        play(&mut notes, &mut allocator, &[60], 80);
+        notes.pedal(CC_SOSTENUTO, 127);
+        play(&mut notes, &mut allocator, &[62], 80);
+        notes.note_off(60, &allocator);
+        notes.note_off(62, &allocator);
+        let held: Vec<u8> = notes.voices().map(|(key, _)| key).collect();
+        assert_eq!(held, [60]);
+        notes.pedal(CC_SOSTENUTO, 0);
+        assert_eq!(notes.voices().count(), 0);
+    }
+
+    #[test]
+    fn notes_held_by_pedal_are_stolen() {
+        let mut notes = NoteChannels::new();
+        let mut allocator = PerNoteChannels::new(StealPolicy::Oldest, 2);
+        notes.pedal(CC_SUSTAIN, 127);
+        for key in 60..72 {
+            notes.note_on(key, 80, &mut allocator);
+            notes.note_off(key, &allocator);
+        }
+        assert_eq!(notes.note_on(72, 80, &mut allocator), (0, Some(60)));
+    }
+}
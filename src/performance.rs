@@ -0,0 +1,543 @@
+//! State of a performance that the events of the MIDI file update as they are played, shared by realtime playback &
+//! offline performances: the tuning in effect of each range of a keyboard split (see [`crate::split`]), the channels
+//! the notes sound on & the pedals, and the timelines of the features sent between the events of the MIDI file (pitch
+//! envelopes, automation & the drift of the reference).
+//!
+//! Each kind of event has a hook (e.g. [`Performance::note_on`]) that sends it to the output & the visualizer, so that
+//! the playback loop only has to time them.
+
+use broadcaster::BroadcastChannel;
+use futures::executor;
+use midly::num::u7;
+
+use crate::automation::{self, AutomationEvent};
+use crate::click::Click;
+use crate::config::Config;
+use crate::drift::{self, DriftStep};
+use crate::envelope::EnvelopeBend;
+use crate::output::{ChannelAllocator, MidiSink, NoteChannels};
+use crate::score::{Note, PEDAL_CCS};
+use crate::server::VisualizerMessage;
+use crate::split::LowerRange;
+use crate::tuner::{
+    cents_from_12edo, key_monzo, pitch_class, ratio_name, spelled_key_name, CurrentTuning,
+    PitchSpec, Spelling, Tuner, TuningData, TuningSnapshot,
+};
+use crate::{
+    allocate_note, chord, envelope_bends, offset_velocity, semitone_tuning, send_automation,
+    send_chord, send_combination_tones, send_controller, send_controller_state, send_note_off,
+    send_note_on, send_pedal_state, REFERENCE_DRIFT,
+};
+
+/// An event of a [`Timeline`].
+pub trait Timed {
+    /// Time of the event in seconds.
+    fn time(&self) -> f64;
+}
+
+impl Timed for EnvelopeBend {
+    fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+impl Timed for AutomationEvent {
+    fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+impl Timed for DriftStep {
+    fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+impl Timed for Click {
+    fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+/// The events of a feature in order of time, and the next one to send.
+pub struct Timeline<T> {
+    events: Vec<T>,
+    next: usize,
+}
+
+impl<T: Timed + Clone> Timeline<T> {
+    pub fn new(events: Vec<T>) -> Self {
+        Timeline { events, next: 0 }
+    }
+
+    /// Returns the next event if it is due before `time`, moving on to the one after it.
+    pub fn next_before(&mut self, time: f64) -> Option<T> {
+        let event = self
+            .events
+            .get(self.next)
+            .filter(|e| e.time() < time)?
+            .clone();
+        self.next += 1;
+        Some(event)
+    }
+
+    /// Returns the event before the one last returned by [`Timeline::next_before`], if any.
+    pub fn previous(&self) -> Option<&T> {
+        self.next.checked_sub(2).map(|i| &self.events[i])
+    }
+
+    /// Starts over from the first event.
+    pub fn restart(&mut self) {
+        self.next = 0;
+    }
+
+    /// Replaces the events, carrying on from the first one due at `time` or after.
+    pub fn replace(&mut self, events: Vec<T>, time: f64) {
+        self.next = events.partition_point(|e| e.time() < time);
+        self.events = events;
+    }
+}
+
+/// The tuning changes due at an event, see [`Performance::update`].
+pub struct TuningChanges {
+    /// Tuning change of the upper range, which is the whole keyboard without a keyboard split.
+    pub upper: Option<TuningData>,
+    /// Tuning change of the lower range of the keyboard split.
+    pub lower: Option<TuningData>,
+    /// Whether the tuner of the upper range was switched for another one.
+    pub switched: bool,
+}
+
+impl TuningChanges {
+    /// Whether either range changes tuning.
+    pub fn any(&self) -> bool {
+        self.upper.is_some() || self.lower.is_some()
+    }
+}
+
+/// The state of a performance, see the [module docs](self).
+pub struct Performance {
+    /// Tuning timeline of the upper range, which is the whole keyboard without a keyboard split.
+    pub tuner: Tuner,
+    /// Tuning in effect of the upper range.
+    pub upper: CurrentTuning,
+    /// The lower range of the keyboard split, if any.
+    pub lower: Option<LowerRange>,
+    /// Drift of the reference in cents, see [`crate::drift`].
+    pub drift: f64,
+    note_channels: NoteChannels,
+    allocator: Box<dyn ChannelAllocator>,
+    /// Pedal CC values in the order of [`PEDAL_CCS`].
+    pedals: [u7; 3],
+    /// Caption of the chord last sent to the visualizer, see [`chord`].
+    chord: String,
+    /// Keys of the notes of the MIDI file sounding, with the times they stop sounding, see
+    /// [`Performance::show_chord`].
+    sounding: Vec<(u8, f64)>,
+    /// Index of the next note of the MIDI file to start sounding.
+    next_note: usize,
+    /// Whether a range was retuned since the chord was last captioned.
+    retuned: bool,
+    /// Pitch bends of the expressive envelopes of notes, see [`crate::envelope`].
+    pub bends: Timeline<EnvelopeBend>,
+    /// Controller values of the automation lanes, see [`crate::automation`].
+    pub automation: Timeline<AutomationEvent>,
+    /// Automated controller values due before the start point, see [`Performance::hold_automation`].
+    automated_before_start: Vec<(u8, u8)>,
+    /// Changes of the drift of the reference.
+    pub drift_steps: Timeline<DriftStep>,
+    midi_file: String,
+    /// Where the events are shown, [`None`] if they aren't.
+    visualizer: Option<BroadcastChannel<VisualizerMessage>>,
+    /// Whether notes are sent, see [`Config::midi`].
+    midi: bool,
+    /// See [`Config::debug`].
+    debug: bool,
+}
+
+impl Performance {
+    /// Plays `midi_file` tuned by `tuner`, and by `lower` below a keyboard split, as configured by `config`. The
+    /// events are shown on `visualizer`, if any.
+    pub fn new(
+        tuner: Tuner,
+        lower: Option<LowerRange>,
+        midi_file: &str,
+        config: &Config,
+        visualizer: Option<BroadcastChannel<VisualizerMessage>>,
+    ) -> Self {
+        Performance {
+            bends: Timeline::new(envelope_bends(&tuner, midi_file)),
+            automation: Timeline::new(automation::events(&tuner)),
+            tuner,
+            upper: CurrentTuning::default(),
+            lower,
+            drift: drift::cents_at(REFERENCE_DRIFT, 0.0),
+            note_channels: NoteChannels::new(),
+            allocator: config.allocation.allocator(config.pb_range),
+            pedals: [u7::from(0); 3],
+            chord: String::new(),
+            sounding: vec![],
+            next_note: 0,
+            retuned: true,
+            automated_before_start: vec![],
+            drift_steps: Timeline::new(drift::steps(REFERENCE_DRIFT)),
+            midi_file: midi_file.to_string(),
+            visualizer,
+            midi: config.midi,
+            debug: config.debug,
+        }
+    }
+
+    /// Starts over from the beginning of the MIDI file, once the output has been reset, e.g. to seek.
+    pub fn restart(&mut self) {
+        self.tuner.seek(f64::NEG_INFINITY);
+        self.upper = CurrentTuning::default();
+        if let Some(lower) = &mut self.lower {
+            lower.restart();
+        }
+        self.drift = drift::cents_at(REFERENCE_DRIFT, 0.0);
+        self.note_channels = NoteChannels::new();
+        self.pedals = [u7::from(0); 3];
+        self.chord.clear();
+        self.sounding.clear();
+        self.next_note = 0;
+        self.retuned = true;
+        self.bends.restart();
+        self.automation.restart();
+        self.automated_before_start.clear();
+        self.drift_steps.restart();
+    }
+
+    /// Returns the tuning in effect of the range of `key`.
+    pub fn tuning_of(&self, key: u8) -> &CurrentTuning {
+        match &self.lower {
+            Some(lower) if lower.contains(key) => &lower.tuning,
+            _ => &self.upper,
+        }
+    }
+
+    /// Moves the tuning timelines of both ranges to `time`, applying the tuning changes due. Their pitch bends are
+    /// sent by [`Performance::retune`].
+    ///
+    /// At a tuning change of the upper range, `switch` may return a tuner to carry on with instead (e.g. with other
+    /// variants), whose complete tuning in effect at `time` is applied, so that differences in its earlier tunings are
+    /// applied too.
+    pub fn update(&mut self, time: f64, switch: impl FnOnce() -> Option<Tuner>) -> TuningChanges {
+        let mut upper = self.tuner.update(time).cloned();
+        let mut switched = false;
+        if upper.is_some() {
+            if let Some(tuner) = switch() {
+                self.tuner = tuner;
+                upper = self.tuner.seek(time);
+                self.bends
+                    .replace(envelope_bends(&self.tuner, &self.midi_file), time);
+                self.automation
+                    .replace(automation::events(&self.tuner), time);
+                switched = true;
+            }
+        }
+        if let Some(tuning_data) = &upper {
+            self.upper.apply(tuning_data);
+        }
+        let lower = self.lower.as_mut().and_then(|lower| lower.update(time));
+        TuningChanges {
+            upper,
+            lower,
+            switched,
+        }
+    }
+
+    /// Sends the pitch bends of the tuning changes of both ranges at `time` raised by the drift, then the extra
+    /// messages of the upper range's, and shows it.
+    pub fn retune(&mut self, sink: &mut dyn MidiSink, changes: &TuningChanges, time: f64) {
+        self.retuned |= changes.any();
+        if let (Some(lower), Some(tuning_data)) = (&mut self.lower, &changes.lower) {
+            lower.retune(tuning_data, time, self.drift);
+        }
+        let Some(tuning_data) = &changes.upper else {
+            return;
+        };
+        sink.retune(&TuningSnapshot::new(
+            time,
+            drift::drifted(&self.upper.tuning, self.drift),
+        ));
+        let tuning = drift::drifted(&tuning_data.tuning, self.drift);
+        for message in self.allocator.retune(&tuning, &self.note_channels) {
+            sink.send(&message);
+        }
+        for message in &tuning_data.extra_messages {
+            sink.send(message);
+        }
+        if let (true, Some(annotation)) = (self.visualizer.is_some(), tuning_data.annotation()) {
+            self.show(VisualizerMessage::Tuning {
+                time: tuning_data.time,
+                pitches: self.upper.pitches(),
+                annotation,
+            });
+        }
+    }
+
+    /// Retunes `semitone` of the upper range to `pitch` spelt as `name` at `time`, e.g. for a tuning edit of a client.
+    pub fn set_pitch(
+        &mut self,
+        sink: &mut dyn MidiSink,
+        semitone: usize,
+        pitch: PitchSpec,
+        name: &str,
+        time: f64,
+    ) {
+        self.upper.tuning[semitone] = pitch;
+        self.upper.monzos[semitone] = pitch.monzo();
+        self.upper.spellings[semitone] = Spelling::parse(name);
+        self.retuned = true;
+        sink.retune(&TuningSnapshot::new(
+            time,
+            drift::drifted(&self.upper.tuning, self.drift),
+        ));
+        let cents = pitch.cents().unwrap() + self.drift;
+        for message in self
+            .allocator
+            .retune(&semitone_tuning(semitone, cents), &self.note_channels)
+        {
+            sink.send(&message);
+        }
+    }
+
+    /// Sends the pitch bend of an expressive envelope, relative to the tuning in effect.
+    pub fn bend(&mut self, sink: &mut dyn MidiSink, bend: &EnvelopeBend) {
+        let cents = self.upper.tuning[bend.semitone].cents().unwrap() + bend.cents + self.drift;
+        for message in self
+            .allocator
+            .retune(&semitone_tuning(bend.semitone, cents), &self.note_channels)
+        {
+            sink.send(&message);
+        }
+    }
+
+    /// Moves the reference by the drift of `step`, retuning all pitch classes of both ranges.
+    pub fn drift_to(&mut self, sink: &mut dyn MidiSink, step: &DriftStep) {
+        self.drift = step.cents;
+        let tuning = drift::drifted(&self.upper.tuning, self.drift);
+        sink.retune(&TuningSnapshot::new(step.time, tuning));
+        for message in self.allocator.retune(&tuning, &self.note_channels) {
+            sink.send(&message);
+        }
+        // The router only sends the pitch bends of the upper range, see [`crate::split::Router`].
+        if let Some(lower) = &mut self.lower {
+            lower.drift(step.time, self.drift);
+        }
+    }
+
+    /// Holds an automated controller value due before the start point, to be sent before the next one.
+    pub fn hold_automation(&mut self, event: &AutomationEvent) {
+        self.automated_before_start
+            .retain(|(controller, _)| *controller != event.controller);
+        self.automated_before_start
+            .push((event.controller, event.value));
+    }
+
+    /// Sends an automated controller value, after the ones held before the start point.
+    pub fn automate(&mut self, sink: &mut dyn MidiSink, event: &AutomationEvent) {
+        let held = std::mem::take(&mut self.automated_before_start);
+        for (controller, value) in held.into_iter().chain([(event.controller, event.value)]) {
+            send_automation(sink, controller, value);
+            let pedal = PEDAL_CCS.iter().position(|&cc| cc == controller);
+            if let Some(idx) = pedal {
+                self.pedals[idx] = value.into();
+                self.note_channels.pedal(controller, value);
+            }
+            if let Some(channel) = &mut self.visualizer {
+                send_controller_state(channel, controller.into(), value.into());
+                if pedal.is_some() {
+                    send_pedal_state(channel, self.pedals);
+                }
+            }
+            if pedal.is_some() {
+                self.show_combination_tones();
+            }
+        }
+    }
+
+    /// Plays a note on of `key` at `vel`, tuned by the range of the key and with the velocity offset of its pitch
+    /// class. A note on with velocity 0 ends the note, on the channel it was started on. `tick` & `time` are the
+    /// position of the event, for debug output.
+    pub fn note_on(&mut self, sink: &mut dyn MidiSink, key: u7, vel: u7, tick: u32, time: f64) {
+        let pc = pitch_class(key.as_int());
+        let range = self.tuning_of(key.as_int());
+        let vel = offset_velocity(vel, range.velocity_offsets[pc]);
+        let tuning = drift::drifted(&range.tuning, self.drift);
+        let channel = match vel > 0 {
+            true => allocate_note(
+                sink,
+                &mut self.note_channels,
+                self.allocator.as_mut(),
+                key.as_int(),
+                vel.as_int(),
+                &tuning,
+            ),
+            false => self
+                .note_channels
+                .note_off(key.as_int(), self.allocator.as_ref()),
+        };
+        if self.midi {
+            send_note_on(sink, channel, key, vel);
+        }
+
+        let range = self.tuning_of(key.as_int());
+        let monzo = range.monzos[pc]
+            .as_ref()
+            .map(|m| key_monzo(m, key.as_int()));
+        if self.debug {
+            print!("[{tick:>7}, {time:7.3}s] ");
+            println!(
+                "Note on: {}, vel: {vel}. {:?}",
+                spelled_key_name(&range.spellings, key.as_int()),
+                monzo
+            );
+        }
+        // Tempered notes have no monzo to show.
+        let message = monzo.map(|monzo| VisualizerMessage::NoteOn {
+            edosteps_from_a4: key.as_int() as i32 - 69,
+            velocity: vel,
+            cents: cents_from_12edo(&range.tuning, pc),
+            ratio: ratio_name(&range.tuning, &range.spellings, pc, range.root),
+            monzo,
+            name: spelled_key_name(&range.spellings, key.as_int()),
+        });
+
+        self.show_combination_tones();
+        if let Some(message) = message {
+            self.show(message);
+        }
+    }
+
+    /// Plays a note off of `key` at `vel`, on the channel its note was started on.
+    pub fn note_off(&mut self, sink: &mut dyn MidiSink, key: u7, vel: u7) {
+        let channel = self
+            .note_channels
+            .note_off(key.as_int(), self.allocator.as_ref());
+        if self.midi {
+            send_note_off(sink, channel, key, vel);
+        }
+        self.show_combination_tones();
+        self.show(VisualizerMessage::NoteOff {
+            edosteps_from_a4: key.as_int() as i32 - 69,
+            velocity: vel,
+        });
+    }
+
+    /// Sends a controller of the MIDI file, unless it is a pedal and playback hasn't `started` yet: pedals before the
+    /// start point are only sent once playback reaches it (see [`Performance::send_pedals`]), so that the sostenuto
+    /// pedal doesn't catch notes that were never played. Other controllers are sent right away, so that their state is
+    /// set for the start point.
+    pub fn controller(
+        &mut self,
+        sink: &mut dyn MidiSink,
+        controller: u7,
+        value: u7,
+        started: bool,
+    ) {
+        let pedal = PEDAL_CCS.iter().position(|&cc| cc == controller.as_int());
+        if let Some(idx) = pedal {
+            self.pedals[idx] = value;
+        }
+        if !started && pedal.is_some() {
+            return;
+        }
+        self.note_channels
+            .pedal(controller.as_int(), value.as_int());
+        send_controller(sink, controller, value);
+        if let Some(channel) = &mut self.visualizer {
+            send_controller_state(channel, controller, value);
+            if pedal.is_some() {
+                send_pedal_state(channel, self.pedals);
+            }
+        }
+        if pedal.is_some() {
+            self.show_combination_tones();
+        }
+    }
+
+    /// Sends the pedals as they are, once playback reaches the start point.
+    pub fn send_pedals(&mut self, sink: &mut dyn MidiSink) {
+        for (&controller, value) in PEDAL_CCS.iter().zip(self.pedals) {
+            self.note_channels.pedal(controller, value.as_int());
+            send_controller(sink, controller, value);
+        }
+        if let Some(channel) = &mut self.visualizer {
+            send_pedal_state(channel, self.pedals);
+        }
+    }
+
+    /// Shows the chord of the `notes` of the MIDI file sounding at `time` if it changed, see [`chord`]. The notes are
+    /// in order of their start, and `time` only moves forward until [`Performance::restart`]. Each note is tuned by
+    /// the range of its key, and the chord is named relative to the root of the upper range.
+    pub fn show_chord(&mut self, notes: &[Note], time: f64) {
+        if self.visualizer.is_none() {
+            return;
+        }
+        let sounding = self.sounding.len();
+        self.sounding.retain(|(_, release)| *release > time);
+        let mut changed = self.sounding.len() != sounding;
+        while let Some(note) = notes.get(self.next_note).filter(|note| note.start <= time) {
+            self.next_note += 1;
+            if note.release > time {
+                self.sounding.push((note.key, note.release));
+                changed = true;
+            }
+        }
+        if !changed && !self.retuned {
+            return;
+        }
+        self.retuned = false;
+        let tones: Vec<(usize, PitchSpec)> = self
+            .sounding
+            .iter()
+            .map(|(key, _)| {
+                let pc = pitch_class(*key);
+                (pc, self.tuning_of(*key).tuning[pc])
+            })
+            .collect();
+        let chord = chord::caption(
+            &tones,
+            &self.upper.tuning,
+            &self.upper.spellings,
+            self.upper.root,
+        );
+        if let Some(channel) = &mut self.visualizer {
+            send_chord(channel, &mut self.chord, chord);
+        }
+    }
+
+    /// Shows the combination tones of the notes sounding, including those held by the pedals, each tuned by the
+    /// range of its key.
+    fn show_combination_tones(&mut self) {
+        if self.visualizer.is_none() {
+            return;
+        }
+        let pitches: Vec<f64> = self
+            .note_channels
+            .voices()
+            .map(|(key, _)| {
+                let tuning = drift::drifted(&self.tuning_of(key).tuning, self.drift);
+                TuningSnapshot::new(0.0, tuning).key_cents(key)
+            })
+            .collect();
+        if let Some(channel) = &mut self.visualizer {
+            send_combination_tones(channel, &pitches);
+        }
+    }
+
+    /// Sends `message` to the visualizer, if the events are shown.
+    pub fn show(&mut self, message: VisualizerMessage) {
+        let Some(channel) = &mut self.visualizer else {
+            return;
+        };
+        if let Err(e) = executor::block_on(channel.send(&message)) {
+            println!(
+                "WARN: Failed to send message to visualizer broadcast channel: {}",
+                e
+            );
+        }
+    }
+}
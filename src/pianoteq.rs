@@ -0,0 +1,84 @@
+//! Handshake with [Pianoteq](https://www.modartt.com/pianoteq) over its JSON-RPC API before playing to it over MIDI,
+//! so a mismatched pitch bend range or preset is caught before the performance instead of heard during it.
+//!
+//! Pianoteq's JSON-RPC server has to be enabled by starting it with `--serve ""` (or `--serve <port>`).
+
+use serde_json::{json, Value};
+
+/// Loads `preset` (if any), then checks that Pianoteq's pitch bend range is +/- `pb_range` semitones, setting it if
+/// not.
+pub fn handshake(url: &str, preset: Option<&str>, pb_range: u16) {
+    if let Some(preset) = preset {
+        call(url, "loadPreset", json!({ "name": preset }));
+    }
+    let info = call(url, "getInfo", Value::Null);
+    println!(
+        "Pianoteq {} @ {url}, preset: {}",
+        info[0]["version"].as_str().unwrap_or("?"),
+        info[0]["current_preset"]["name"].as_str().unwrap_or("?")
+    );
+
+    let Some((id, range)) = pitch_bend_range(url) else {
+        println!("WARN: Pianoteq has no pitch bend range parameter, make sure it is set to +/-{pb_range} semitones");
+        return;
+    };
+    if range == pb_range as f64 {
+        return;
+    }
+    println!("Pianoteq's pitch bend range is +/-{range} semitones, setting it to +/-{pb_range}");
+    call(
+        url,
+        "setParameters",
+        json!({ "list": [{ "id": id, "text": pb_range.to_string() }] }),
+    );
+    match pitch_bend_range(url) {
+        Some((_, range)) if range == pb_range as f64 => {}
+        _ => panic!(
+            "Failed to set Pianoteq's pitch bend range to +/-{pb_range} semitones, set it manually"
+        ),
+    }
+}
+
+/// Returns the ID & value (in semitones) of the pitch bend range parameter of the current preset.
+fn pitch_bend_range(url: &str) -> Option<(String, f64)> {
+    let parameters = call(url, "getParameters", Value::Null);
+    let parameter = parameters.as_array()?.iter().find(|p| {
+        ["id", "name"].iter().any(|field| {
+            p[field]
+                .as_str()
+                .is_some_and(|s| s.to_lowercase().contains("pitch bend"))
+        })
+    })?;
+    // The text is formatted for display, e.g. "4.00"
+    let range = parameter["text"]
+        .as_str()?
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .find(|s| !s.is_empty())?
+        .parse()
+        .ok()?;
+    Some((parameter["id"].as_str()?.to_string(), range))
+}
+
+/// Calls a JSON-RPC method & returns its result. `params` is omitted if null.
+fn call(url: &str, method: &str, params: Value) -> Value {
+    let mut request = json!({ "jsonrpc": "2.0", "id": 0, "method": method });
+    if !params.is_null() {
+        request["params"] = params;
+    }
+    let response: Value = ureq::post(url)
+        .send_json(request)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Pianoteq JSON-RPC call to {url} failed ({e}). Start Pianoteq with `--serve \"\"`"
+            )
+        })
+        .into_json()
+        .unwrap_or_else(|e| panic!("Invalid JSON-RPC response from Pianoteq: {e}"));
+    if !response["error"].is_null() {
+        panic!(
+            "Pianoteq JSON-RPC call {method} failed: {}",
+            response["error"]
+        );
+    }
+    response["result"].clone()
+}
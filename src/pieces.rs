@@ -0,0 +1,169 @@
+//! Concerts of several pieces (see [`crate::PIECES`]), run from the visualizer desk: websocket clients can list the
+//! pieces & their variants, and load the one to play next between performances.
+//!
+//! Clients send:
+//!
+//! - `pieces`: a `piece:<name>:<variant>,<variant>...` message is broadcast for each piece in order, with the variants
+//!   declared in its tuning file.
+//! - `load:<name>` or `load:<name>:<variant>,<variant>...`: plays the piece with the variants next. Playback waiting
+//!   to start waits for the loaded piece instead, and a performance in progress is played to the end first.
+//! - `advance`: starts playback of the piece waiting to start, like pressing enter (see [`crate::conductor`]).
+//!
+//! Whenever a piece is loaded, and when playback of a piece starts waiting, its metadata is broadcast as a `loaded`
+//! message, see [`VisualizerMessage::PieceChanged`]. A piece whose tuning file is invalid isn't loaded, its errors are
+//! broadcast as an `invalid` message instead, see [`VisualizerMessage::PieceInvalid`].
+
+use std::borrow::Cow;
+use std::fs;
+use std::sync::Mutex;
+use std::thread;
+
+use broadcaster::BroadcastChannel;
+use futures::executor;
+
+use crate::score::Score;
+use crate::server::{self, VisualizerMessage};
+use crate::tuning_file;
+
+/// A MIDI file with its tuning timeline.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Piece {
+    /// Name that clients load the piece by, e.g. `Ondine`.
+    pub name: Cow<'static, str>,
+    pub midi_file: Cow<'static, str>,
+    pub tuning_file: Cow<'static, str>,
+}
+
+/// What happens once playback of a piece reaches the end of its track, see [`crate::END_OF_TRACK`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(dead_code)]
+pub enum EndOfTrack {
+    /// Stop playback, resetting the output. Exits, unless there are pieces to load from the desk.
+    Stop,
+    /// Start over from the start point once the last notes have rung out, until stopped with Ctrl-C.
+    Loop,
+    /// Load the next piece of the concert to play next, like a `load` message without variants. Exits after the last
+    /// one.
+    Advance,
+    /// Keep the final tuning sounding and the servers up for the visualizer until stopped with Ctrl-C.
+    Hold,
+}
+
+/// The piece & variants last loaded by a client.
+static LOADED: Mutex<Option<(Piece, Vec<String>)>> = Mutex::new(None);
+
+/// Returns the piece & variants last loaded by a client, if any.
+pub fn loaded() -> Option<(Piece, Vec<String>)> {
+    LOADED.lock().unwrap().clone()
+}
+
+/// Broadcasts the metadata of `piece` with `variants`, whose tuning timeline has `tunings` tunings, as a
+/// [`VisualizerMessage::PieceChanged`].
+pub fn announce(
+    broadcast_channel: &mut BroadcastChannel<VisualizerMessage>,
+    piece: &Piece,
+    variants: &[String],
+    tunings: usize,
+) {
+    let score = Score::load(&piece.midi_file);
+    let duration = score
+        .notes
+        .iter()
+        .map(|note| note.release)
+        .fold(0.0, f64::max);
+    send(
+        broadcast_channel,
+        VisualizerMessage::PieceChanged {
+            name: piece.name.to_string(),
+            variants: variants.to_vec(),
+            duration,
+            bars: score.bars.len(),
+            tunings,
+            midi_file: piece.midi_file.to_string(),
+            tuning_file: piece.tuning_file.to_string(),
+        },
+    );
+}
+
+/// Answers the `pieces` & `load` messages of clients (see the [module docs](self)) on a new thread, loading the pieces
+/// for a pitch bend range of +/- `pb_range` semitones.
+pub fn listen(
+    pieces: &'static [Piece],
+    mut broadcast_channel: BroadcastChannel<VisualizerMessage>,
+    pb_range: u16,
+) {
+    let messages = server::control_messages();
+    thread::spawn(move || {
+        for message in messages {
+            let message = message.trim();
+            if message == "pieces" {
+                for piece in pieces {
+                    let text = fs::read_to_string(&*piece.tuning_file).unwrap_or_default();
+                    let variants = tuning_file::variants(&text);
+                    send(
+                        &mut broadcast_channel,
+                        VisualizerMessage::Piece {
+                            name: piece.name.to_string(),
+                            variants,
+                        },
+                    );
+                }
+            } else if let Some(args) = message.strip_prefix("load:") {
+                let (name, variants) = args.split_once(':').unwrap_or((args, ""));
+                let Some(piece) = pieces.iter().find(|piece| piece.name == name) else {
+                    println!("WARN: Can't load {name}, no such piece");
+                    continue;
+                };
+                let variants: Vec<String> = variants
+                    .split(',')
+                    .filter(|variant| !variant.is_empty())
+                    .map(String::from)
+                    .collect();
+                load(&mut broadcast_channel, piece, variants, pb_range);
+            }
+        }
+    });
+}
+
+/// Loads `piece` with `variants` for a pitch bend range of +/- `pb_range` semitones to play next, announcing it.
+/// Returns false if its tuning file is invalid, which is reported to clients as a
+/// [`VisualizerMessage::PieceInvalid`] instead, leaving the piece to play next as it was.
+pub fn load(
+    broadcast_channel: &mut BroadcastChannel<VisualizerMessage>,
+    piece: &Piece,
+    variants: Vec<String>,
+    pb_range: u16,
+) -> bool {
+    let tuner = match tuning_file::load(&piece.tuning_file, &variants, pb_range) {
+        Ok(tuner) => tuner,
+        Err(errors) => {
+            tuning_file::print_errors(&errors, &piece.tuning_file);
+            println!("WARN: Can't load {} with variants {variants:?}", piece.name);
+            send(
+                broadcast_channel,
+                VisualizerMessage::PieceInvalid {
+                    name: piece.name.to_string(),
+                    variants,
+                    errors,
+                },
+            );
+            return false;
+        }
+    };
+    println!(
+        "Loaded {} with variants {variants:?} to play next",
+        piece.name
+    );
+    announce(broadcast_channel, piece, &variants, tuner.len());
+    *LOADED.lock().unwrap() = Some((piece.clone(), variants));
+    true
+}
+
+fn send(broadcast_channel: &mut BroadcastChannel<VisualizerMessage>, message: VisualizerMessage) {
+    if let Err(e) = executor::block_on(broadcast_channel.send(&message)) {
+        println!(
+            "WARN: Failed to send message to visualizer broadcast channel: {}",
+            e
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Pre-flight checks of a tuning timeline against its MIDI file, to catch a tuning file written for a different MIDI
+//! file (or another take or edit of it) before playback rather than by ear.
+
+use std::fmt::Write as _;
+
+use crate::analysis::ONSET_TOLERANCE;
+use crate::diff::changes;
+use crate::score::Score;
+use crate::tuner::{pitch_class, Tuner, SEMITONE_NAMES};
+
+/// The timeline is reported as too short for the MIDI file if its last tuning change comes before this fraction of the
+/// MIDI file's duration.
+const MIN_TIMELINE_COVERAGE: f64 = 0.5;
+
+/// Something in a tuning timeline that doesn't fit its MIDI file.
+pub enum Issue {
+    /// Tuning change at index `idx` starts after the last note of the MIDI file stops sounding at `end`.
+    TuningAfterEnd { idx: usize, end: f64 },
+    /// Pitch classes retuned by the tuning change at index `idx` that don't sound until they are retuned again.
+    UnheardRetunes { idx: usize, semitones: Vec<usize> },
+    /// Notes that start before the first tuning change, so they are played untuned.
+    NotesBeforeFirstTuning { count: usize, first: f64 },
+    /// The last tuning change is early in the MIDI file, which lasts until `end`.
+    DurationMismatch { last: f64, end: f64 },
+}
+
+/// Returns the issues of `tuner` as a tuning timeline of `score`, in order of time.
+pub fn check(score: &Score, tuner: &Tuner) -> Vec<Issue> {
+    let mut issues = vec![];
+    if tuner.len() == 0 {
+        return issues;
+    }
+    let end = score.notes.iter().map(|n| n.release).fold(0.0, f64::max);
+
+    let before: Vec<_> = score
+        .notes
+        .iter()
+        .filter(|n| n.start + ONSET_TOLERANCE < tuner[0].time)
+        .collect();
+    if let Some(first) = before.first() {
+        issues.push(Issue::NotesBeforeFirstTuning {
+            count: before.len(),
+            first: first.start,
+        });
+    }
+
+    let snapshots = tuner.snapshots();
+    for idx in 1..tuner.len() {
+        if tuner[idx].time >= end {
+            issues.push(Issue::TuningAfterEnd { idx, end });
+            continue;
+        }
+        let semitones: Vec<usize> = changes(&snapshots[idx - 1], &snapshots[idx])
+            .iter()
+            .map(|c| c.semitone)
+            .filter(|&semitone| {
+                // Until the pitch class is retuned again, or the end.
+                let until = (idx + 1..tuner.len())
+                    .find(|&i| !tuner[i].tuning[semitone].is_keep())
+                    .map_or(end, |i| tuner[i].time);
+                !score.notes.iter().any(|n| {
+                    pitch_class(n.key) == semitone && n.start < until && n.release > tuner[idx].time
+                })
+            })
+            .collect();
+        if !semitones.is_empty() {
+            issues.push(Issue::UnheardRetunes { idx, semitones });
+        }
+    }
+
+    let last = tuner[tuner.len() - 1].time;
+    if tuner.len() > 1 && last < end * MIN_TIMELINE_COVERAGE {
+        issues.push(Issue::DurationMismatch { last, end });
+    }
+    issues
+}
+
+/// Returns a human readable report of `issues` (one line per issue) with the bar positions in `score`.
+pub fn report(score: &Score, tuner: &Tuner, issues: &[Issue]) -> String {
+    let mut report = String::new();
+    for issue in issues {
+        match issue {
+            Issue::TuningAfterEnd { idx, end } => writeln!(
+                report,
+                "{}: starts after the end of the MIDI file at {end:.3}s (bar {})",
+                tuner[*idx].describe(),
+                score.position(*end),
+            ),
+            Issue::UnheardRetunes { idx, semitones } => writeln!(
+                report,
+                "{} (bar {}): retunes {} which never sound before being retuned again",
+                tuner[*idx].describe(),
+                score.position(tuner[*idx].time),
+                semitones
+                    .iter()
+                    .map(|s| SEMITONE_NAMES[*s])
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Issue::NotesBeforeFirstTuning { count, first } => writeln!(
+                report,
+                "{count} notes start before the first tuning at {}s, from {first:.3}s (bar {})",
+                tuner[0].time,
+                score.position(*first),
+            ),
+            Issue::DurationMismatch { last, end } => writeln!(
+                report,
+                "The last tuning change is at {last}s (bar {}), but the MIDI file lasts until {end:.3}s (bar {}). \
+                Is the tuning file for a shorter version?",
+                score.position(*last),
+                score.position(*end),
+            ),
+        }
+        .unwrap();
+    }
+    report
+}
@@ -0,0 +1,77 @@
+//! Previews of the tuning changes of a timeline, to audit every transition of a new timeline one after another rather
+//! than listening through whole passes. Playback plays a few seconds either side of each tuning change in turn,
+//! announcing which change is previewed on the console & to the visualizer (see [`VisualizerMessage::Preview`]), and
+//! moves on to the next one after a moment of silence.
+
+use broadcaster::BroadcastChannel;
+use futures::executor;
+
+use crate::server::VisualizerMessage;
+use crate::tuner::Tuner;
+
+/// The stretch of the piece played around a tuning change.
+pub struct Window {
+    /// Index of the tuning change in the tuning timeline.
+    pub index: usize,
+    /// Time of the tuning change.
+    pub change: f64,
+    pub from: f64,
+    pub to: f64,
+    /// See [`crate::tuner::TuningData::describe`].
+    pub description: String,
+    pub annotation: String,
+}
+
+/// Returns the windows of `seconds` before & after each of the tuning changes of `tuner` numbered in `range` (indices
+/// of the tuning timeline, as in the `frequencies` export), in order of time.
+pub fn windows(tuner: &Tuner, seconds: f64, range: (usize, usize)) -> Vec<Window> {
+    (range.0..=range.1.min(tuner.len().saturating_sub(1)))
+        .map(|index| Window {
+            index,
+            change: tuner[index].time,
+            from: (tuner[index].time - seconds).max(0.0),
+            to: tuner[index].time + seconds,
+            description: tuner[index].describe(),
+            annotation: tuner[index].annotation().unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Parses a range of tuning changes, `FIRST-LAST` or `FIRST` (inclusive).
+pub fn parse_range(arg: &str) -> Option<(usize, usize)> {
+    match arg.split_once('-') {
+        Some((first, last)) => Some((first.trim().parse().ok()?, last.trim().parse().ok()?)),
+        None => {
+            let index = arg.trim().parse().ok()?;
+            Some((index, index))
+        }
+    }
+}
+
+/// Announces that the `n`th of `count` windows is being previewed.
+pub fn announce(
+    broadcast_channel: &mut BroadcastChannel<VisualizerMessage>,
+    window: &Window,
+    n: usize,
+    count: usize,
+) {
+    println!(
+        "[{}/{count}] Previewing tuning change #{}: {} ({:.3}s to {:.3}s)",
+        n + 1,
+        window.index,
+        window.description,
+        window.from,
+        window.to
+    );
+    let message = VisualizerMessage::Preview {
+        index: window.index,
+        time: window.change,
+        annotation: window.annotation.clone(),
+    };
+    if let Err(e) = executor::block_on(broadcast_channel.send(&message)) {
+        println!(
+            "WARN: Failed to send message to visualizer broadcast channel: {}",
+            e
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Synth profiles, bundling the settings that depend on the synth played: its pitch bend range, which channels
+//! controllers are sent on, which channels the pitch classes are played on, how it is retuned, and how messages are
+//! sent to it. A profile is selected per MIDI output port (see [`crate::SYNTH_PROFILES`]), so that switching synths is a
+//! change of profile rather than of many settings.
+//!
+//! Playback sends the same messages whatever the synth, as if its pitch bend range were the configured one (see
+//! [`crate::PB_RANGE`]) and it played the pitch classes on channels 0-11. A profile rewrites them for the synth on
+//! their way to the port.
+
+use midly::PitchBend;
+
+use crate::channel_tuning::ChannelTuning;
+use crate::output::MidiSink;
+use crate::tuner::TuningSnapshot;
+
+/// The settings of a synth, see the [module docs](self).
+#[derive(Clone, Copy)]
+pub struct SynthProfile {
+    pub name: &'static str,
+    /// Pitch bend range of the synth in +/- semitones, set with RPN 0 on the channels of the pitch classes when
+    /// connecting. Pitch bends are rescaled from the configured range to it. [`None`] is the configured range. Only
+    /// used when retuned with pitch bends.
+    pub pb_range: Option<u16>,
+    pub controllers: Controllers,
+    /// Channels of the synth that each channel sent by playback is played on, e.g. to keep the pitch classes off the
+    /// General MIDI percussion channel.
+    pub channels: [u8; 16],
+    pub retune: Retune,
+    pub backend: Backend,
+}
+
+/// Which channels the controllers (e.g. the pedals) are sent on.
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Controllers {
+    /// On every channel they are sent on by playback, e.g. the pedals on the channels of all pitch classes.
+    Duplicated,
+    /// Once, on this channel of the synth (e.g. the master channel of an MPE zone): controllers sent on the channel of
+    /// A are sent there, and those sent on the channels of the other pitch classes are dropped.
+    Single(u8),
+}
+
+/// How the pitch classes are tuned.
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Retune {
+    /// With pitch bends on their channels.
+    PitchBends,
+    /// With MIDI Tuning Standard bulk dumps at every tuning change, see [`crate::MTS_BULK_DUMPS`].
+    Mts,
+    /// With controllers on their channels, see [`crate::channel_tuning`].
+    Channels(ChannelTuning),
+}
+
+/// How messages are sent to the port.
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Backend {
+    /// Scheduled by the OS [`crate::MIDI_LOOKAHEAD`] seconds ahead of time, where supported.
+    Scheduled,
+    /// When they are due, e.g. for hardware whose drivers are unreliable with scheduled messages.
+    Direct,
+}
+
+/// Channels as sent by playback.
+const SAME_CHANNELS: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// Pianoteq, whose pitch bend range can be checked with [`crate::PIANOTEQ_RPC_URL`].
+#[allow(dead_code)]
+pub const PIANOTEQ: SynthProfile = SynthProfile {
+    name: "Pianoteq",
+    pb_range: None,
+    controllers: Controllers::Duplicated,
+    channels: SAME_CHANNELS,
+    retune: Retune::PitchBends,
+    backend: Backend::Scheduled,
+};
+
+/// Kontakt instruments, which are tuned with the channel fine tuning as many of their scripts mishandle pitch bends.
+#[allow(dead_code)]
+pub const KONTAKT: SynthProfile = SynthProfile {
+    name: "Kontakt",
+    pb_range: None,
+    controllers: Controllers::Duplicated,
+    channels: SAME_CHANNELS,
+    retune: Retune::Channels(ChannelTuning::Rpn),
+    backend: Backend::Scheduled,
+};
+
+/// General MIDI hardware, at the default pitch bend range of +/- 2 semitones, with F# (channel 9) swapped with channel
+/// 15 to keep it off the percussion channel, so that a click track on channel 15 is played as percussion.
+#[allow(dead_code)]
+pub const GENERAL_MIDI: SynthProfile = SynthProfile {
+    name: "General MIDI",
+    pb_range: Some(2),
+    controllers: Controllers::Duplicated,
+    channels: [0, 1, 2, 3, 4, 5, 6, 7, 8, 15, 10, 11, 12, 13, 14, 9],
+    retune: Retune::PitchBends,
+    backend: Backend::Direct,
+};
+
+/// MPE synths, as the lower zone: the pitch classes on member channels 1-12 at the default member pitch bend range of
+/// +/- 48 semitones, and the controllers on the master channel 0.
+#[allow(dead_code)]
+pub const MPE: SynthProfile = SynthProfile {
+    name: "MPE",
+    pb_range: Some(48),
+    controllers: Controllers::Single(0),
+    channels: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 0],
+    retune: Retune::PitchBends,
+    backend: Backend::Scheduled,
+};
+
+/// Wraps a MIDI output to rewrite the messages sent to it for a [`SynthProfile`]. The output should already be tuned
+/// as given by [`SynthProfile::retune`].
+pub struct Profiled {
+    output: Box<dyn MidiSink>,
+    profile: SynthProfile,
+    /// Pitch bend range in +/- semitones that the pitch bends are sent for.
+    sent_range: u16,
+    /// Pitch bend range of the synth.
+    pb_range: u16,
+}
+
+impl Profiled {
+    /// Wraps `output`, which is sent pitch bends for a range of +/- `pb_range` semitones, setting the pitch bend range
+    /// of the channels of the pitch classes if retuned with pitch bends.
+    pub fn new(mut output: Box<dyn MidiSink>, profile: SynthProfile, pb_range: u16) -> Self {
+        let sent_range = pb_range;
+        let pb_range = profile.pb_range.unwrap_or(sent_range);
+        if let Retune::PitchBends = profile.retune {
+            for channel in &profile.channels[..12] {
+                let status = 0xB0 | (channel & 0x0F);
+                // RPN 0 (pitch bend sensitivity) in semitones, then deselects it.
+                let rpn = [
+                    [101, 0],
+                    [100, 0],
+                    [6, pb_range as u8],
+                    [38, 0],
+                    [101, 127],
+                    [100, 127],
+                ];
+                for [controller, value] in rpn {
+                    output.send(&[status, controller, value]);
+                }
+            }
+        }
+        Profiled {
+            output,
+            profile,
+            sent_range,
+            pb_range,
+        }
+    }
+}
+
+impl MidiSink for Profiled {
+    fn send(&mut self, message: &[u8]) {
+        if message.is_empty() || message[0] >= 0xF0 {
+            self.output.send(message);
+            return;
+        }
+        let kind = message[0] & 0xF0;
+        let channel = (message[0] & 0x0F) as usize;
+        let mut message = message.to_vec();
+        message[0] = kind | self.profile.channels[channel];
+        match (kind, self.profile.controllers) {
+            (0xB0, Controllers::Single(_)) if (1..12).contains(&channel) => return,
+            (0xB0, Controllers::Single(single)) if channel == 0 => {
+                message[0] = kind | (single & 0x0F)
+            }
+            (0xE0, _)
+                if message.len() >= 3 && matches!(self.profile.retune, Retune::PitchBends) =>
+            {
+                let bend = (message[1] as u16 | (message[2] as u16) << 7) as f64 - 8192.0;
+                let range = bend / 8192.0 * self.sent_range as f64 / self.pb_range as f64;
+                if range.abs() > 1.0 {
+                    println!(
+                        "WARN: Can't bend channel {channel} by {:.1}c, beyond the pitch bend range of the {} profile",
+                        range * self.pb_range as f64 * 100.0,
+                        self.profile.name
+                    );
+                }
+                let bend = PitchBend::from_f64(range).0.as_int();
+                message[1] = (bend & 0x7F) as u8;
+                message[2] = (bend >> 7) as u8;
+            }
+            _ => {}
+        }
+        self.output.send(&message);
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.output.retune(snapshot);
+    }
+
+    fn lookahead(&self) -> f64 {
+        self.output.lookahead()
+    }
+
+    fn timestamp(&mut self, delay: f64) {
+        self.output.timestamp(delay);
+    }
+}
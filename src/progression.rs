@@ -0,0 +1,381 @@
+//! Drafts a tuning timeline for a chord progression: each chord is tuned in JI above its root, and the roots are
+//! placed so that annotated common tones keep their pitch across chord changes, while the comma drift of roots that
+//! come back later in the progression is kept as small as the root motions allow.
+//!
+//! The progression is read from a line based file, with `#` comments:
+//!
+//! ```text
+//! offset 5/4                       Pitch of the root of the first chord relative to A (default: its 5-limit
+//!                                  placeholder, as in skeleton timelines).
+//!
+//! chord 54.2 C# 1/1 5/4 3/2 7/4    Chord at the given time in seconds: its root and tones as ratios above it.
+//! label C#7                        Optional label of the chord, as in tuning files.
+//! chord 56.0 F# 1/1 5/4 3/2
+//! motion 4/3                       Root motion from the previous chord, modulo octaves.
+//! common C#                        Pitch classes held at the same pitch from the previous chord.
+//! ```
+//!
+//! Every chord after the first needs a `motion`, `common` tones, or both. Common tones are exact constraints, while
+//! root motions and returns of a root to its pitch in an earlier chord are weighed against each other by least squares
+//! (see [`crate::temper::least_squares`]) where the constraints leave room to, e.g. when a chain of motions without
+//! common tones would drift by a comma.
+
+use std::fmt::Write as _;
+use std::fs;
+
+use rational::Rational;
+
+use crate::analysis::DEFAULT_5_LIMIT;
+use crate::temper::{least_squares, nearest_octave, Equation, EXACT_WEIGHT};
+use crate::tuner::{parse_pitch_class, JIRatio, SEMITONE_NAMES};
+use crate::tuning_file::{line_error, parse_ratio, report_errors};
+
+/// Weight of keeping a root at its pitch in the last chord with the same root, relative to the root motions.
+const RETURN_WEIGHT: f64 = 4.0;
+
+/// Chords tuned this many cents or less away from their JI root motion are not reported as tempered.
+const TEMPERED_TOLERANCE_CENTS: f64 = 0.001;
+
+/// `chord <time> <root> <ratio>...`
+pub struct Chord {
+    pub time: f64,
+    pub root: usize,
+    /// Tones of the chord as ratios above the root, octave reduced, and their pitch classes.
+    pub tones: Vec<(usize, Rational)>,
+    pub label: Option<String>,
+    /// Root motion from the previous chord.
+    pub motion: Option<Rational>,
+    /// Pitch classes held from the previous chord.
+    pub common: Vec<usize>,
+}
+
+impl Chord {
+    fn tone(&self, pc: usize) -> Option<Rational> {
+        self.tones
+            .iter()
+            .find(|(tone_pc, _)| *tone_pc == pc)
+            .map(|(_, ratio)| *ratio)
+    }
+}
+
+pub struct Progression {
+    /// Pitch of the root of the first chord relative to A, see [`Progression::offset`].
+    pub offset: Option<Rational>,
+    pub chords: Vec<Chord>,
+}
+
+/// Tuning of a chord of a [`Progression`].
+pub struct TunedChord {
+    /// Pitch of the root relative to A along the chain of common tones (or root motions, where there are none) from
+    /// the first chord, within an octave of its 12edo pitch.
+    pub root_ratio: Rational,
+    /// Cents that the root is tuned away from `root_ratio` to reduce drift.
+    pub tempering: f64,
+    /// Error of the root motion from the previous chord in cents.
+    pub motion_error: Option<f64>,
+}
+
+impl Progression {
+    /// Returns the pitch of the root of the first chord relative to A.
+    pub fn offset(&self) -> Rational {
+        self.offset.unwrap_or_else(|| {
+            let (n, d) = DEFAULT_5_LIMIT[self.chords[0].root];
+            Rational::new(n as i128, d as i128)
+        })
+    }
+}
+
+impl TunedChord {
+    /// Returns the pitch of the root in cents above A.
+    pub fn cents(&self) -> f64 {
+        self.root_ratio.cents().unwrap() + self.tempering
+    }
+}
+
+/// Loads the progression in the file at `path` (see the [module docs](self)). Prints every invalid line and exits if
+/// there are any.
+pub fn load(path: &str) -> Progression {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Failed to read progression {path}: {e}"));
+    parse(&text, path)
+}
+
+/// Parses the contents of a progression file. `path` is only used in error messages.
+pub fn parse(text: &str, path: &str) -> Progression {
+    let mut progression = Progression {
+        offset: None,
+        chords: vec![],
+    };
+    let mut errors = vec![];
+
+    for (line_idx, raw_line) in text.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (field, value) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(field, value)| (field, value.trim()));
+        let column = raw_line.len() - raw_line.trim_start().len() + 1;
+        let error = |msg: &str| line_error(path, line_idx, column, raw_line, msg);
+
+        let result = (|| -> Result<(), String> {
+            if field == "offset" {
+                progression.offset = Some(
+                    parse_ratio(value)
+                        .ok_or_else(|| error("Invalid offset, expected `offset <ratio>`"))?,
+                );
+                return Ok(());
+            }
+            if field == "chord" {
+                let chord = parse_chord(value).ok_or_else(|| {
+                    error("Invalid chord, expected `chord <seconds> <root> <ratio>...` with one ratio per pitch class")
+                })?;
+                progression.chords.push(chord);
+                return Ok(());
+            }
+            let Some(chord) = progression.chords.last_mut() else {
+                return Err(error(&format!("{field} before the first chord")));
+            };
+            match field {
+                "label" => chord.label = Some(value.to_string()),
+                "motion" => {
+                    chord.motion = Some(
+                        parse_ratio(value)
+                            .ok_or_else(|| error("Invalid motion, expected `motion <ratio>`"))?,
+                    )
+                }
+                "common" => {
+                    for name in value.split_whitespace() {
+                        let pc = parse_pitch_class(name).ok_or_else(|| {
+                            error("Invalid common tones, expected `common <pitch class>...`")
+                        })?;
+                        chord.common.push(pc);
+                    }
+                }
+                _ => return Err(error(&format!("Unknown field `{field}`"))),
+            }
+            Ok(())
+        })();
+        if let Err(error) = result {
+            errors.push(error);
+        }
+    }
+    report_errors(&errors, path);
+
+    for (i, chord) in progression.chords.iter().enumerate() {
+        let at = format!("{path}: Chord at {:.3}s", chord.time);
+        let Some(previous) = i.checked_sub(1).map(|i| &progression.chords[i]) else {
+            if chord.motion.is_some() || !chord.common.is_empty() {
+                errors.push(format!(
+                    "{at} is the first chord, it can't have a motion or common tones"
+                ));
+            }
+            continue;
+        };
+        if chord.motion.is_none() && chord.common.is_empty() {
+            errors.push(format!("{at} needs a motion or common tones"));
+        }
+        for pc in &chord.common {
+            if chord.tone(*pc).is_none() || previous.tone(*pc).is_none() {
+                errors.push(format!(
+                    "{at}: Common tone {} is not in both chords",
+                    SEMITONE_NAMES[*pc]
+                ));
+            }
+        }
+    }
+    if progression.chords.is_empty() {
+        errors.push(format!("{path}: No chords"));
+    }
+    report_errors(&errors, path);
+    progression
+}
+
+fn parse_chord(s: &str) -> Option<Chord> {
+    let mut words = s.split_whitespace();
+    let time = words.next()?.parse().ok().filter(|t: &f64| *t >= 0.0)?;
+    let root = parse_pitch_class(words.next()?)?;
+    let mut tones: Vec<(usize, Rational)> = vec![];
+    for ratio in words {
+        let ratio = parse_ratio(ratio)?;
+        let octaves = ratio.cents().unwrap().div_euclid(1200.0) as i32;
+        let ratio = ratio / Rational::new(2, 1).pow(octaves);
+        let pc = (root + (ratio.cents().unwrap() / 100.0).round() as usize) % 12;
+        if tones.iter().any(|(tone_pc, _)| *tone_pc == pc) {
+            return None;
+        }
+        tones.push((pc, ratio));
+    }
+    (!tones.is_empty()).then_some(Chord {
+        time,
+        root,
+        tones,
+        label: None,
+        motion: None,
+        common: vec![],
+    })
+}
+
+/// Returns `ratio` moved by octaves to within 600 cents of the 12edo pitch of `pc` above A.
+fn near_pitch_class(ratio: Rational, pc: usize) -> Rational {
+    let octaves = ((ratio.cents().unwrap() - 100.0 * pc as f64) / 1200.0).round() as i32;
+    ratio / Rational::new(2, 1).pow(octaves)
+}
+
+/// Tunes the roots of the chords of `progression`.
+///
+/// The unknowns are the deviations of the roots from 12edo, relative to A (the last unknown, fixed at 0).
+pub fn solve(progression: &Progression) -> Vec<TunedChord> {
+    let chords = &progression.chords;
+    let a = chords.len();
+    // Interval from the 12edo pitch of the root of `from` to that of `to`, which is removed from the targets.
+    let edo_motion = |from: &Chord, to: &Chord| 100.0 * to.root as f64 - 100.0 * from.root as f64;
+
+    let mut equations = vec![Equation {
+        a: 0,
+        b: a,
+        value: nearest_octave(
+            progression.offset().cents().unwrap() - 100.0 * chords[0].root as f64,
+        ),
+        weight: EXACT_WEIGHT,
+    }];
+    let mut root_ratios = vec![near_pitch_class(progression.offset(), chords[0].root)];
+    for (i, pair) in chords.windows(2).enumerate() {
+        let [previous, chord] = pair else {
+            unreachable!()
+        };
+        let common_motions: Vec<Rational> = chord
+            .common
+            .iter()
+            .map(|pc| previous.tone(*pc).unwrap() / chord.tone(*pc).unwrap())
+            .collect();
+        for motion in &common_motions {
+            equations.push(Equation {
+                a: i + 1,
+                b: i,
+                value: nearest_octave(motion.cents().unwrap() - edo_motion(previous, chord)),
+                weight: EXACT_WEIGHT,
+            });
+        }
+        if let Some(motion) = chord.motion {
+            equations.push(Equation {
+                a: i + 1,
+                b: i,
+                value: nearest_octave(motion.cents().unwrap() - edo_motion(previous, chord)),
+                weight: 1.0,
+            });
+        }
+        if let Some(j) = (0..=i).rev().find(|j| chords[*j].root == chord.root) {
+            equations.push(Equation {
+                a: i + 1,
+                b: j,
+                value: 0.0,
+                weight: RETURN_WEIGHT,
+            });
+        }
+
+        let motion = common_motions.first().copied().or(chord.motion).unwrap();
+        root_ratios.push(near_pitch_class(root_ratios[i] * motion, chord.root));
+    }
+    let deviations = least_squares(a + 1, a, &equations);
+
+    chords
+        .iter()
+        .enumerate()
+        .map(|(i, chord)| {
+            let cents = 100.0 * chord.root as f64 + deviations[i];
+            let motion_error = chord.motion.filter(|_| i > 0).map(|motion| {
+                let previous = &chords[i - 1];
+                nearest_octave(
+                    deviations[i] - deviations[i - 1] + edo_motion(previous, chord)
+                        - motion.cents().unwrap(),
+                )
+            });
+            TunedChord {
+                root_ratio: root_ratios[i],
+                tempering: cents - root_ratios[i].cents().unwrap(),
+                motion_error,
+            }
+        })
+        .collect()
+}
+
+/// Reports the tuning of each chord of `progression`, and how far each root that comes back drifted from its pitch in
+/// the last chord with the same root.
+pub fn report(progression: &Progression, tuned: &[TunedChord]) -> String {
+    let mut report = String::new();
+    for (i, (chord, tuning)) in progression.chords.iter().zip(tuned).enumerate() {
+        write!(
+            report,
+            "{:.3}s: root {} = {}",
+            chord.time, SEMITONE_NAMES[chord.root], tuning.root_ratio
+        )
+        .unwrap();
+        if let Some(label) = &chord.label {
+            write!(report, " ({label})").unwrap();
+        }
+        if tuning.tempering.abs() > TEMPERED_TOLERANCE_CENTS {
+            write!(report, " tempered by {:+.3}c", tuning.tempering).unwrap();
+        }
+        if let Some(error) = tuning.motion_error {
+            write!(report, ", motion off by {error:+.3}c").unwrap();
+        }
+        report.push('\n');
+
+        if let Some(j) = (0..i)
+            .rev()
+            .find(|j| progression.chords[*j].root == chord.root)
+        {
+            let drift = nearest_octave(tuning.cents() - tuned[j].cents());
+            if drift.abs() > TEMPERED_TOLERANCE_CENTS {
+                writeln!(
+                    report,
+                    "  {} drifted by {drift:+.3}c since {:.3}s",
+                    SEMITONE_NAMES[chord.root], progression.chords[j].time
+                )
+                .unwrap();
+            }
+        }
+    }
+    report
+}
+
+/// Returns a draft tuning timeline of the chords of `progression` tuned as in `tuned`, in the format of tuning files.
+/// Chords that are tempered are tuned in cents. Pitch classes not in the first chord are tuned to 5-limit placeholders.
+pub fn draft_timeline(progression: &Progression, tuned: &[TunedChord]) -> String {
+    let mut timeline = String::new();
+    for (i, (chord, tuned)) in progression.chords.iter().zip(tuned).enumerate() {
+        writeln!(timeline, "tuning {:.3}", chord.time).unwrap();
+        if let Some(label) = &chord.label {
+            writeln!(timeline, "label {label}").unwrap();
+        }
+        let tempered = tuned.tempering.abs() > TEMPERED_TOLERANCE_CENTS;
+        if tempered {
+            writeln!(
+                timeline,
+                "comment Tempered by {:+.3}c to reduce drift",
+                tuned.tempering
+            )
+            .unwrap();
+        }
+        writeln!(timeline, "root {}", SEMITONE_NAMES[chord.root]).unwrap();
+        writeln!(timeline, "offset {}", tuned.root_ratio).unwrap();
+        for (interval, (n, d)) in DEFAULT_5_LIMIT.iter().enumerate() {
+            let pc = (chord.root + interval) % 12;
+            let ratio = match chord.tone(pc) {
+                Some(ratio) => ratio,
+                None if i == 0 => Rational::new(*n as i128, *d as i128),
+                None => continue,
+            };
+            if tempered {
+                let cents = ratio.cents().unwrap() + tuned.tempering;
+                writeln!(timeline, "{} {cents:.3}c", SEMITONE_NAMES[pc]).unwrap();
+            } else {
+                writeln!(timeline, "{} {ratio}", SEMITONE_NAMES[pc]).unwrap();
+            }
+        }
+        timeline.push('\n');
+    }
+    timeline
+}
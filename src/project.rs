@@ -0,0 +1,107 @@
+//! Project files, to describe a performance as data rather than code (`run performance.toml`): the MIDI file & tuning
+//! timeline played, the pitch bend range & output port of the synth, where & how fast playback starts, the variants,
+//! and the visualizer. Keys that aren't given keep the constants of `main.rs`, and command line options override them.
+//! Relative paths are relative to the directory of the project file, so a project can be run from anywhere. A project
+//! file given after any other command applies to it too, e.g. `analyze performance.toml` analyses its MIDI file.
+//!
+//! ```toml
+//! midi_file = "ondine.mid"
+//! tuning_file = "ondine.tuning"   # Default: the MIDI file with the extension .tuning
+//! pb_range = 4                    # See PB_RANGE
+//! device = "Pianoteq"             # See MIDI_PLAYBACK_DEVICE_NAME
+//! start = 45.2                    # See START_FROM
+//! speed = 0.8                     # See PLAYBACK_SPEED
+//! variants = ["bar29-C-5/4"]
+//! visualizer = true               # See ACTIVATE_VISUALIZER
+//! emit_jsonl = false              # See --emit jsonl
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::tuning_file::report_errors;
+
+/// The settings of a project file, see the [module docs](self).
+#[derive(Clone, Debug)]
+pub struct Project {
+    /// Overrides [`crate::MIDI_FILE`].
+    pub midi_file: String,
+    /// Overrides [`crate::TUNING_FILE`].
+    pub tuning_file: String,
+    /// Overrides [`crate::PB_RANGE`].
+    pub pb_range: Option<u16>,
+    /// Overrides [`crate::MIDI_PLAYBACK_DEVICE_NAME`].
+    pub device: Option<String>,
+    pub start: Option<f64>,
+    pub speed: Option<f64>,
+    pub variants: Vec<String>,
+    pub visualizer: Option<bool>,
+    pub emit_jsonl: bool,
+}
+
+/// The keys of a project file as written.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    midi_file: String,
+    tuning_file: Option<String>,
+    pb_range: Option<u16>,
+    device: Option<String>,
+    start: Option<f64>,
+    speed: Option<f64>,
+    #[serde(default)]
+    variants: Vec<String>,
+    visualizer: Option<bool>,
+    #[serde(default)]
+    emit_jsonl: bool,
+}
+
+/// Loads the project file at `path`. Exits if it isn't valid TOML with the keys above, printing every invalid value
+/// first.
+pub fn load(path: &str) -> Project {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Failed to read project file {path}: {e}"));
+    let file: File =
+        toml::from_str(&text).unwrap_or_else(|e| panic!("Invalid project file {path}: {e}"));
+
+    let mut errors = vec![];
+    if file
+        .pb_range
+        .is_some_and(|range| !(1..=96).contains(&range))
+    {
+        errors.push(format!(
+            "{path}: Invalid pb_range, expected 1 to 96 semitones"
+        ));
+    }
+    if file.start.is_some_and(|start| start < 0.0) {
+        errors.push(format!(
+            "{path}: Invalid start, expected at least 0 seconds"
+        ));
+    }
+    if file.speed.is_some_and(|speed| speed <= 0.0) {
+        errors.push(format!(
+            "{path}: Invalid speed, expected a multiplier above 0"
+        ));
+    }
+    report_errors(&errors, path);
+
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let midi_file = dir.join(&file.midi_file);
+    let tuning_file = match &file.tuning_file {
+        Some(tuning_file) => dir.join(tuning_file),
+        None => midi_file.with_extension("tuning"),
+    };
+    Project {
+        midi_file: midi_file.to_string_lossy().into_owned(),
+        tuning_file: tuning_file.to_string_lossy().into_owned(),
+        pb_range: file.pb_range,
+        device: file.device,
+        start: file.start,
+        speed: file.speed,
+        variants: file.variants,
+        visualizer: file.visualizer,
+        emit_jsonl: file.emit_jsonl,
+    }
+}
@@ -0,0 +1,209 @@
+//! Output over the network with [RTP-MIDI](https://www.rfc-editor.org/rfc/rfc6295) (AppleMIDI), so the retuned MIDI
+//! stream can be sent to a synth machine in another room without a physical MIDI/USB run.
+//!
+//! ji-performer is the session initiator: it invites the session listening at [`crate::RTP_MIDI_ADDR`] (e.g. a network
+//! session of Audio MIDI Setup on macOS, or rtpMIDI on Windows), which has to accept it (or accept anyone). MIDI
+//! messages are sent as soon as they are due, without a recovery journal, which is fine on a wired LAN.
+
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::output::MidiSink;
+
+/// Name of the session shown on the receiving end.
+const SESSION_NAME: &str = "JI Performer";
+
+/// AppleMIDI protocol version.
+const PROTOCOL_VERSION: u32 = 2;
+
+/// Number of invitations sent to each port before giving up.
+const INVITATION_ATTEMPTS: u32 = 5;
+
+/// Time between clock synchronizations, which keep the session alive.
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// RTP payload type of RTP-MIDI.
+const PAYLOAD_TYPE: u8 = 0x61;
+
+pub struct RtpMidi {
+    data: UdpSocket,
+    control: UdpSocket,
+    token: u32,
+    ssrc: u32,
+    sequence: u16,
+    start: Instant,
+}
+
+impl RtpMidi {
+    /// Invites the RTP-MIDI session at `addr` (its control port, the data port being the next one), then keeps its
+    /// clock synchronized on a new thread.
+    pub fn connect(addr: &str) -> Self {
+        let control_addr = addr
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .unwrap_or_else(|| panic!("Invalid RTP-MIDI session address: {addr}"));
+        let data_addr = SocketAddr::new(control_addr.ip(), control_addr.port() + 1);
+
+        // Receivers expect the data port of the initiator to be right after its control port too.
+        let (control, data) = (0..100)
+            .find_map(|_| {
+                let control = UdpSocket::bind("0.0.0.0:0").ok()?;
+                let port = control.local_addr().ok()?.port().checked_add(1)?;
+                let data = UdpSocket::bind(("0.0.0.0", port)).ok()?;
+                Some((control, data))
+            })
+            .expect("Failed to bind consecutive UDP ports for RTP-MIDI");
+        control.connect(control_addr).unwrap();
+        data.connect(data_addr).unwrap();
+
+        // Identifiers of the session & of this end of it, which only have to be unique on the network.
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let token = nanos ^ process::id();
+        let ssrc = nanos.rotate_left(16) ^ process::id();
+
+        println!("Inviting RTP-MIDI session at {addr}...");
+        invite(&control, token, ssrc, addr);
+        invite(&data, token, ssrc, addr);
+        println!("Joined RTP-MIDI session at {addr}");
+
+        let start = Instant::now();
+        let sync = data.try_clone().unwrap();
+        thread::spawn(move || synchronize(sync, ssrc, start));
+
+        RtpMidi {
+            data,
+            control,
+            token,
+            ssrc,
+            sequence: 0,
+            start,
+        }
+    }
+}
+
+impl MidiSink for RtpMidi {
+    fn send(&mut self, message: &[u8]) {
+        let mut packet = vec![0x80, PAYLOAD_TYPE];
+        packet.extend(self.sequence.to_be_bytes());
+        packet.extend(timestamp(self.start).to_be_bytes());
+        packet.extend(self.ssrc.to_be_bytes());
+
+        // MIDI command section header without journal, delta times or running status: a 4 bit length, or a 12 bit
+        // length with the B flag set for longer messages (e.g. MTS bulk dumps).
+        if message.len() < 16 {
+            packet.push(message.len() as u8);
+        } else {
+            packet.push(0x80 | (message.len() >> 8) as u8);
+            packet.push(message.len() as u8);
+        }
+        packet.extend(message);
+
+        self.sequence = self.sequence.wrapping_add(1);
+        if let Err(e) = self.data.send(&packet) {
+            println!("WARN: Failed to send RTP-MIDI message: {e}");
+        }
+    }
+}
+
+impl Drop for RtpMidi {
+    fn drop(&mut self) {
+        let _ = self.control.send(&command(b"BY", self.token, self.ssrc));
+    }
+}
+
+/// Returns a session command packet (invitation, or end of session) with the initiator's token & SSRC.
+fn command(command: &[u8; 2], token: u32, ssrc: u32) -> Vec<u8> {
+    let mut packet = vec![0xff, 0xff];
+    packet.extend(command);
+    packet.extend(PROTOCOL_VERSION.to_be_bytes());
+    packet.extend(token.to_be_bytes());
+    packet.extend(ssrc.to_be_bytes());
+    packet
+}
+
+/// Invites the session on the port `socket` is connected to, panicking if it is rejected or there is no reply.
+fn invite(socket: &UdpSocket, token: u32, ssrc: u32, addr: &str) {
+    let mut invitation = command(b"IN", token, ssrc);
+    invitation.extend(SESSION_NAME.as_bytes());
+    invitation.push(0);
+
+    socket
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+    let mut reply = [0; 1024];
+    for _ in 0..INVITATION_ATTEMPTS {
+        socket.send(&invitation).unwrap();
+        let Ok(len) = socket.recv(&mut reply) else {
+            continue;
+        };
+        match reply.get(..4.min(len)) {
+            Some([0xff, 0xff, b'O', b'K']) => return,
+            Some([0xff, 0xff, b'N', b'O']) => {
+                panic!("RTP-MIDI session at {addr} rejected the invitation")
+            }
+            _ => {}
+        }
+    }
+    panic!("No reply from RTP-MIDI session at {addr}, is it listening there?");
+}
+
+/// Returns the time since `start` in units of 100 µs, the clock of the session.
+fn timestamp(start: Instant) -> u32 {
+    (start.elapsed().as_micros() / 100) as u32
+}
+
+/// Synchronizes the clocks with the session every [`SYNC_INTERVAL`] as the initiator, until the socket fails.
+///
+/// Each synchronization is an exchange of three `CK` packets: the initiator sends its time, the receiver adds its own,
+/// and the initiator adds its time again so that the receiver can work out the latency.
+fn synchronize(socket: UdpSocket, ssrc: u32, start: Instant) {
+    let clock_sync = |count: u8, timestamps: [u64; 3]| {
+        let mut packet = vec![0xff, 0xff, b'C', b'K'];
+        packet.extend(ssrc.to_be_bytes());
+        packet.extend([count, 0, 0, 0]);
+        for timestamp in timestamps {
+            packet.extend(timestamp.to_be_bytes());
+        }
+        packet
+    };
+
+    socket.set_read_timeout(Some(SYNC_INTERVAL)).unwrap();
+    let mut reply = [0; 1024];
+    loop {
+        let sent = timestamp(start) as u64;
+        if socket.send(&clock_sync(0, [sent, 0, 0])).is_err() {
+            return;
+        }
+        let deadline = Instant::now() + SYNC_INTERVAL;
+        while Instant::now() < deadline {
+            let len = match socket.recv(&mut reply) {
+                Ok(len) => len,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    continue
+                }
+                Err(e) => {
+                    println!("WARN: Lost the RTP-MIDI session: {e}");
+                    return;
+                }
+            };
+            match reply.get(..len) {
+                Some([0xff, 0xff, b'C', b'K', _, _, _, _, 1, ..]) if len >= 36 => {
+                    let received = u64::from_be_bytes(reply[20..28].try_into().unwrap());
+                    let _ = socket.send(&clock_sync(2, [sent, received, timestamp(start) as u64]));
+                }
+                Some([0xff, 0xff, b'B', b'Y', ..]) => {
+                    println!("WARN: RTP-MIDI session ended by the receiver");
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,464 @@
+//! SFZ sample player [`Instrument`].
+//!
+//! Unlike the pitch bend path, each voice is pitch shifted (resampled) directly to the exact ratio of the current
+//! tuning, so there is no 14-bit pitch bend quantization at all. Pitch bend messages are ignored.
+//!
+//! Only a small subset of SFZ is supported: the `<control>`, `<global>`, `<master>`, `<group>` & `<region>` headers,
+//! and the `sample`, `default_path`, `key`, `lokey`, `hikey`, `pitch_keycenter`, `lovel`, `hivel`, `tune`,
+//! `transpose`, `volume` & `ampeg_release` opcodes. Samples must be WAV files and are mixed down to mono.
+//!
+//! See <https://sfzformat.com/>
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use midly::live::LiveEvent;
+use midly::MidiMessage;
+
+use crate::score::{CC_SOFT, CC_SOSTENUTO, CC_SUSTAIN};
+use crate::synth::{Instrument, SOFT_PEDAL_GAIN};
+use crate::tuner::{pitch_class, TuningSnapshot};
+
+/// Maximum number of simultaneous voices. The oldest voice is stolen when exceeded.
+const MAX_VOICES: usize = 128;
+
+/// Shortest release time in seconds, to avoid clicks for regions without `ampeg_release`.
+const MIN_RELEASE: f64 = 0.005;
+
+/// Voices quieter than this are removed.
+const SILENCE: f64 = 1e-4;
+
+/// Master volume.
+const GAIN: f64 = 0.5;
+
+/// A mono sample loaded into memory.
+struct Sample {
+    frames: Vec<f32>,
+    sample_rate: f64,
+}
+
+struct Region {
+    sample: Arc<Sample>,
+    lokey: u8,
+    hikey: u8,
+    lovel: u8,
+    hivel: u8,
+    /// Pitch of the unshifted sample in cents relative to A4, from `pitch_keycenter`, `tune` & `transpose`.
+    cents: f64,
+    /// Linear gain from `volume`.
+    gain: f64,
+    /// Release time in seconds.
+    release: f64,
+}
+
+struct Voice {
+    region: usize,
+    channel: u8,
+    key: u8,
+    /// Amplitude from velocity & region volume.
+    amplitude: f64,
+    /// Position in the sample, in (fractional) frames.
+    position: f64,
+    /// Frames of the sample to advance per output frame.
+    step: f64,
+    /// Whether the key is still held down.
+    held: bool,
+    /// Whether the sostenuto pedal caught the note, see [`crate::synth::Synth`].
+    sostenuto: bool,
+    /// Gain of the release envelope, 1 until the note is released.
+    release_gain: f64,
+}
+
+pub struct Sampler {
+    sample_rate: f64,
+    regions: Vec<Region>,
+    voices: Vec<Voice>,
+    /// Tuning of each pitch class in cents relative to the next lowest A, updated by [`Instrument::retune`].
+    tuning_cents: [f64; 12],
+    /// Whether the sustain pedal is down. Pedals are shared by all channels, see [`crate::synth::Synth`].
+    sustain: bool,
+    /// Whether the sostenuto pedal is down.
+    sostenuto: bool,
+    /// Whether the soft (una corda) pedal is down.
+    soft: bool,
+}
+
+impl Sampler {
+    /// Loads an SFZ file and all its samples.
+    pub fn load(path: &str, sample_rate: f64) -> Self {
+        let sfz = fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Failed to read SFZ file {path}: {e}"));
+        let sfz_dir = Path::new(path).parent().unwrap_or(Path::new("."));
+
+        let mut samples: HashMap<String, Arc<Sample>> = HashMap::new();
+        let mut regions = vec![];
+
+        // Opcodes of the headers currently in effect, inherited by regions in this order.
+        let mut control: HashMap<String, String> = HashMap::new();
+        let mut global: HashMap<String, String> = HashMap::new();
+        let mut master: HashMap<String, String> = HashMap::new();
+        let mut group: HashMap<String, String> = HashMap::new();
+        let mut region: Option<HashMap<String, String>> = None;
+        let mut header = String::new();
+
+        let mut finish_region =
+            |region: HashMap<String, String>, control: &HashMap<String, String>| {
+                let Some(sample_path) = region.get("sample") else {
+                    return;
+                };
+                let default_path = control.get("default_path").map_or("", String::as_str);
+                let sample_path =
+                    sfz_dir.join(format!("{default_path}{sample_path}").replace('\\', "/"));
+                let sample_path = sample_path.to_string_lossy().to_string();
+                let sample = samples
+                    .entry(sample_path.clone())
+                    .or_insert_with(|| Arc::new(load_sample(&sample_path)))
+                    .clone();
+
+                let key = |opcode: &str, default: u8| {
+                    region.get(opcode).map_or(default, |v| {
+                        parse_sfz_key(v)
+                            .unwrap_or_else(|| panic!("Invalid {opcode} in {path}: {v}"))
+                    })
+                };
+                let num = |opcode: &str, default: f64| {
+                    region.get(opcode).map_or(default, |v| {
+                        v.parse()
+                            .unwrap_or_else(|_| panic!("Invalid {opcode} in {path}: {v}"))
+                    })
+                };
+
+                let (lokey, hikey, keycenter) = if region.contains_key("key") {
+                    let k = key("key", 60);
+                    (k, k, key("pitch_keycenter", k))
+                } else {
+                    (
+                        key("lokey", 0),
+                        key("hikey", 127),
+                        key("pitch_keycenter", 60),
+                    )
+                };
+
+                regions.push(Region {
+                    sample,
+                    lokey,
+                    hikey,
+                    lovel: num("lovel", 1.0) as u8,
+                    hivel: num("hivel", 127.0) as u8,
+                    cents: 100.0 * (keycenter as f64 - 69.0 - num("transpose", 0.0))
+                        - num("tune", 0.0),
+                    gain: 10f64.powf(num("volume", 0.0) / 20.0),
+                    release: num("ampeg_release", 0.0).max(MIN_RELEASE),
+                });
+            };
+
+        // Strip block comments, which may span multiple lines.
+        let mut sfz = sfz.as_str();
+        let mut uncommented = String::new();
+        while let Some(start) = sfz.find("/*") {
+            uncommented.push_str(&sfz[..start]);
+            sfz = sfz[start..]
+                .find("*/")
+                .map_or("", |end| &sfz[start + end + 2..]);
+        }
+        uncommented.push_str(sfz);
+
+        for line in uncommented.lines() {
+            let line = line.split("//").next().unwrap();
+            for token in tokenize(line) {
+                match token {
+                    Token::Header(name) => {
+                        if let Some(region) = region.take() {
+                            finish_region(region, &control);
+                        }
+                        match name.as_str() {
+                            "global" => {
+                                global.clear();
+                                master.clear();
+                                group.clear();
+                            }
+                            "master" => {
+                                master.clear();
+                                group.clear();
+                            }
+                            "group" => group.clear(),
+                            "region" => {
+                                let mut inherited = global.clone();
+                                inherited.extend(master.clone());
+                                inherited.extend(group.clone());
+                                region = Some(inherited);
+                            }
+                            _ => {}
+                        }
+                        header = name;
+                    }
+                    Token::Opcode(name, value) => {
+                        let opcodes = match header.as_str() {
+                            "control" => &mut control,
+                            "global" => &mut global,
+                            "master" => &mut master,
+                            "group" => &mut group,
+                            "region" => region.as_mut().unwrap(),
+                            // Unsupported headers, e.g. <curve> or <effect>
+                            _ => continue,
+                        };
+                        opcodes.insert(name, value);
+                    }
+                }
+            }
+        }
+        if let Some(region) = region.take() {
+            finish_region(region, &control);
+        }
+
+        assert!(
+            !regions.is_empty(),
+            "No regions with samples found in {path}"
+        );
+        println!(
+            "Loaded {} regions ({} samples) from {path}",
+            regions.len(),
+            samples.len()
+        );
+
+        Sampler {
+            sample_rate,
+            regions,
+            voices: vec![],
+            tuning_cents: std::array::from_fn(|i| 100.0 * i as f64),
+            sustain: false,
+            sostenuto: false,
+            soft: false,
+        }
+    }
+
+    /// Presses or lifts the sostenuto pedal. Pressing it catches the voices whose keys are held down.
+    fn set_sostenuto(&mut self, down: bool) {
+        if down == self.sostenuto {
+            return;
+        }
+        self.sostenuto = down;
+        for voice in &mut self.voices {
+            voice.sostenuto = down && voice.held;
+        }
+    }
+
+    /// Returns the resampling step of a voice of `region` playing `key` in the current tuning.
+    fn step(&self, region: usize, key: u8) -> f64 {
+        let region = &self.regions[region];
+        let octaves_from_a4 = (key as i32 - 69).div_euclid(12);
+        let cents = self.tuning_cents[pitch_class(key)] + 1200.0 * octaves_from_a4 as f64;
+        2f64.powf((cents - region.cents) / 1200.0) * region.sample.sample_rate / self.sample_rate
+    }
+}
+
+impl Instrument for Sampler {
+    fn handle(&mut self, message: &[u8]) {
+        let Ok(LiveEvent::Midi { channel, message }) = LiveEvent::parse(message) else {
+            return;
+        };
+        let channel = channel.as_int();
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                let (key, vel) = (key.as_int(), vel.as_int());
+                for idx in 0..self.regions.len() {
+                    let region = &self.regions[idx];
+                    if !(region.lokey..=region.hikey).contains(&key)
+                        || !(region.lovel..=region.hivel).contains(&vel)
+                    {
+                        continue;
+                    }
+                    if self.voices.len() >= MAX_VOICES {
+                        self.voices.remove(0);
+                    }
+                    // Default velocity curve of SFZ (amp_veltrack=100).
+                    let mut amplitude = (vel as f64 / 127.0).powi(2) * region.gain;
+                    if self.soft {
+                        amplitude *= SOFT_PEDAL_GAIN;
+                    }
+                    let step = self.step(idx, key);
+                    self.voices.push(Voice {
+                        region: idx,
+                        channel,
+                        key,
+                        amplitude,
+                        position: 0.0,
+                        step,
+                        held: true,
+                        sostenuto: false,
+                        release_gain: 1.0,
+                    });
+                }
+            }
+            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                for voice in &mut self.voices {
+                    if voice.channel == channel && voice.key == key.as_int() {
+                        voice.held = false;
+                    }
+                }
+            }
+            MidiMessage::Controller { controller, value } => match controller.as_int() {
+                CC_SUSTAIN => self.sustain = value.as_int() >= 64,
+                CC_SOSTENUTO => self.set_sostenuto(value.as_int() >= 64),
+                CC_SOFT => self.soft = value.as_int() >= 64,
+                // Reset all controllers
+                121 => {
+                    self.sustain = false;
+                    self.set_sostenuto(false);
+                    self.soft = false;
+                }
+                // All notes off
+                123 => {
+                    for voice in &mut self.voices {
+                        if voice.channel == channel {
+                            voice.held = false;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Retunes all sounding voices too, the same as pitch bends would.
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.tuning_cents = snapshot.tuning.map(|r| r.cents().unwrap());
+        for i in 0..self.voices.len() {
+            self.voices[i].step = self.step(self.voices[i].region, self.voices[i].key);
+        }
+    }
+
+    #[cfg(feature = "render")]
+    fn is_silent(&self) -> bool {
+        self.voices.is_empty()
+    }
+
+    fn render(&mut self, output: &mut [f32], channels: usize) {
+        output.fill(0.0);
+
+        for voice in &mut self.voices {
+            let region = &self.regions[voice.region];
+            let frames = &region.sample.frames;
+            let released = !voice.held && !self.sustain && !voice.sostenuto;
+            let release_coef = (-1.0 / (region.release * self.sample_rate)).exp();
+
+            for frame in output.chunks_mut(channels) {
+                let idx = voice.position as usize;
+                if idx + 1 >= frames.len() {
+                    voice.release_gain = 0.0;
+                    break;
+                }
+                // Linear interpolation
+                let t = voice.position.fract() as f32;
+                let sample = frames[idx] * (1.0 - t) + frames[idx + 1] * t;
+                let sample = sample * (voice.amplitude * voice.release_gain * GAIN) as f32;
+                for s in frame.iter_mut() {
+                    *s += sample;
+                }
+
+                voice.position += voice.step;
+                if released {
+                    voice.release_gain *= release_coef;
+                }
+            }
+        }
+
+        self.voices
+            .retain(|v| v.amplitude * v.release_gain > SILENCE);
+    }
+}
+
+enum Token {
+    Header(String),
+    Opcode(String, String),
+}
+
+/// Splits a line of SFZ (without comments) into headers & opcodes.
+///
+/// Opcode values may contain spaces (e.g. sample paths), so a value extends up to the next header or opcode.
+fn tokenize(line: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        if let Some(header) = rest.strip_prefix('<') {
+            let end = header.find('>').unwrap_or(header.len());
+            tokens.push(Token::Header(header[..end].trim().to_string()));
+            rest = header[(end + 1).min(header.len())..].trim_start();
+            continue;
+        }
+        let Some(eq) = rest.find('=') else {
+            break;
+        };
+        let name = rest[..eq].trim().to_string();
+        let value = &rest[eq + 1..];
+        // The value ends where the next `<header>` or ` opcode=` starts.
+        let end = value
+            .find('<')
+            .into_iter()
+            .chain(
+                value
+                    .find('=')
+                    .and_then(|next_eq| value[..next_eq].rfind(char::is_whitespace)),
+            )
+            .min()
+            .unwrap_or(value.len());
+        tokens.push(Token::Opcode(name, value[..end].trim().to_string()));
+        rest = value[end..].trim_start();
+    }
+    tokens
+}
+
+/// Parses an SFZ key, which is either a MIDI note number or a note name like `c#4` or `eb3`, where `c4` is 60.
+fn parse_sfz_key(value: &str) -> Option<u8> {
+    if let Ok(key) = value.parse() {
+        return Some(key);
+    }
+    let value = value.to_ascii_lowercase();
+    let mut chars = value.chars();
+    let semitone = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (accidental, octave) = match rest.chars().next()? {
+        '#' => (1, &rest[1..]),
+        'b' => (-1, &rest[1..]),
+        _ => (0, rest),
+    };
+    let key = 12 * (octave.parse::<i32>().ok()? + 1) + semitone + accidental;
+    u8::try_from(key).ok().filter(|k| *k <= 127)
+}
+
+/// Loads a WAV file, mixed down to mono.
+fn load_sample(path: &str) -> Sample {
+    let mut reader = hound::WavReader::open(path)
+        .unwrap_or_else(|e| panic!("Failed to open sample {path}: {e}"));
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().map(Result::unwrap).collect(),
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.unwrap() as f32 / scale)
+                .collect()
+        }
+    };
+    let channels = spec.channels as usize;
+    Sample {
+        frames: interleaved
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect(),
+        sample_rate: spec.sample_rate as f64,
+    }
+}
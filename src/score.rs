@@ -0,0 +1,324 @@
+//! Pre-parsed representation of a MIDI file for offline analysis & exports.
+//!
+//! Unlike the realtime playback loop (which walks the raw track events as they come), this converts the whole file
+//! into notes with absolute start/end times (in seconds) up front, so that analysis code can ask questions like
+//! "which notes are sounding at this time" or "which bar is this".
+
+use std::fs;
+
+use midly::{MetaMessage, MidiMessage, Smf, TrackEventKind};
+
+/// MIDI CC number of the sustain (damper) pedal.
+pub const CC_SUSTAIN: u8 = 64;
+
+/// MIDI CC number of the sostenuto pedal, which only sustains the notes held down when it is pressed.
+pub const CC_SOSTENUTO: u8 = 66;
+
+/// MIDI CC number of the soft (una corda) pedal.
+pub const CC_SOFT: u8 = 67;
+
+/// MIDI CC numbers of all piano pedals.
+pub const PEDAL_CCS: [u8; 3] = [CC_SUSTAIN, CC_SOSTENUTO, CC_SOFT];
+
+/// MIDI CC number of the channel volume.
+pub const CC_VOLUME: u8 = 7;
+
+/// MIDI CC number of expression, a fraction of the channel volume.
+pub const CC_EXPRESSION: u8 = 11;
+
+/// A single note, paired from its note on & note off messages.
+#[derive(Clone, Debug)]
+pub struct Note {
+    /// MIDI note number (69 = A4).
+    pub key: u8,
+    /// Time of note on in seconds.
+    pub start: f64,
+    /// Absolute tick of note on.
+    pub start_tick: u64,
+    /// Time the note stops sounding, taking the sustain & sostenuto pedals into account.
+    ///
+    /// If the sustain pedal is down when the key is released, the note keeps sounding until the pedal is lifted. The
+    /// same goes for the sostenuto pedal, if the key was already held down when the sostenuto pedal was pressed.
+    pub release: f64,
+    /// Time the key is released (note off) in seconds, regardless of the pedals.
+    pub key_release: f64,
+}
+
+/// A bar (measure) according to the time signature & tempo map of the MIDI file.
+#[derive(Clone, Debug)]
+pub struct Bar {
+    /// Bar number, starting from 1.
+    pub number: usize,
+    /// Absolute tick the bar starts at.
+    pub start_tick: u64,
+    /// Start time in seconds.
+    pub start: f64,
+    /// End time in seconds.
+    pub end: f64,
+    /// Number of beats in this bar (numerator of the time signature).
+    pub beats: u8,
+    /// Note value of one beat (denominator of the time signature).
+    pub beat_unit: u8,
+}
+
+pub struct Score {
+    /// Ticks per quarter note.
+    pub ppqn: u64,
+    /// All notes, sorted by start time.
+    pub notes: Vec<Note>,
+    /// Bars according to the MIDI file's time signatures, sorted by start time.
+    ///
+    /// NOTE: For MIDI files recorded in realtime without a tempo map (e.g. `ondine.mid`), these are just the default
+    /// 4/4 @ 120bpm grid and will not line up with the bar numbers of the printed score.
+    pub bars: Vec<Bar>,
+    /// Intervals `(down, up)` in seconds during which the sustain pedal is down, sorted by `down`.
+    pub sustain_pedal: Vec<(f64, f64)>,
+    /// Tempo changes, sorted by tick. The first is at tick 0.
+    tempo_map: Vec<TempoChange>,
+}
+
+/// Tempo change at an absolute tick.
+struct TempoChange {
+    tick: u64,
+    /// Time in seconds this tempo change occurs at.
+    time: f64,
+    /// Microseconds per quarter note.
+    tempo: u32,
+}
+
+impl Score {
+    /// Loads & parses a MIDI file. Events from all tracks are merged.
+    pub fn load(path: &str) -> Score {
+        let raw_bytes =
+            fs::read(path).unwrap_or_else(|e| panic!("Failed to read MIDI file {path}: {e}"));
+        let smf = Smf::parse(&raw_bytes)
+            .unwrap_or_else(|e| panic!("Failed to parse MIDI file {path}: {e}"));
+
+        let ppqn = match smf.header.timing {
+            midly::Timing::Metrical(ppqn) => ppqn.as_int() as u64,
+            midly::Timing::Timecode(_frame_per_second, _subframes) => {
+                panic!("Timecode MIDI files are not supported at this time");
+            }
+        };
+
+        // (absolute tick, track index, event kind)
+        let mut events = vec![];
+        for (track_idx, track) in smf.tracks.iter().enumerate() {
+            let mut tick = 0u64;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                events.push((tick, track_idx, event.kind));
+            }
+        }
+        // stable sort keeps the order of same-tick events within a track.
+        events.sort_by_key(|(tick, _, _)| *tick);
+
+        // First pass: tempo map & time signatures.
+        let mut tempo_map = vec![TempoChange {
+            tick: 0,
+            time: 0.0,
+            tempo: 500_000,
+        }];
+        // (tick, numerator, denominator)
+        let mut time_sigs: Vec<(u64, u8, u8)> = vec![(0, 4, 4)];
+        let mut end_tick = 0;
+
+        for (tick, _, kind) in &events {
+            end_tick = end_tick.max(*tick);
+            match kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    let last = tempo_map.last().unwrap();
+                    let time = last.time + ticks_to_secs(tick - last.tick, last.tempo, ppqn);
+                    if last.tick == *tick {
+                        tempo_map.pop();
+                    }
+                    tempo_map.push(TempoChange {
+                        tick: *tick,
+                        time,
+                        tempo: tempo.as_int(),
+                    });
+                }
+                TrackEventKind::Meta(MetaMessage::TimeSignature(num, den_pow, _, _)) => {
+                    if time_sigs.last().unwrap().0 == *tick {
+                        time_sigs.pop();
+                    }
+                    time_sigs.push((*tick, *num, 2u8.pow(*den_pow as u32)));
+                }
+                _ => {}
+            }
+        }
+
+        let tick_to_time = |tick: u64| tick_time(&tempo_map, ppqn, tick);
+
+        // Bars
+        let mut bars = vec![];
+        let mut bar_tick = 0;
+        while bar_tick <= end_tick {
+            let ts_idx = time_sigs.partition_point(|(t, _, _)| *t <= bar_tick) - 1;
+            let (_, num, den) = time_sigs[ts_idx];
+            let next_bar_tick = bar_tick + ppqn * 4 * num as u64 / den as u64;
+            bars.push(Bar {
+                number: bars.len() + 1,
+                start_tick: bar_tick,
+                start: tick_to_time(bar_tick),
+                end: tick_to_time(next_bar_tick),
+                beats: num,
+                beat_unit: den,
+            });
+            bar_tick = next_bar_tick;
+        }
+
+        // Second pass: pair up notes, taking the sustain & sostenuto pedals into account.
+        let mut notes: Vec<Note> = vec![];
+        // Indices into `notes` of notes currently held down, keyed by (channel, key).
+        let mut held: Vec<Option<usize>> = vec![None; 16 * 128];
+        // Indices into `notes` of notes released while the pedal is down, per channel.
+        let mut pedal_held: Vec<Vec<usize>> = vec![vec![]; 16];
+        let mut pedal_down = [false; 16];
+        let mut pedal_since = [0.0; 16];
+        let mut sustain_pedal = vec![];
+        // Indices into `notes` of notes caught by the sostenuto pedal (held down when it was pressed), per channel.
+        let mut sostenuto_held: Vec<Vec<usize>> = vec![vec![]; 16];
+        let mut sostenuto_down = [false; 16];
+
+        for (tick, _, kind) in &events {
+            let time = tick_to_time(*tick);
+            if let TrackEventKind::Midi { channel, message } = kind {
+                let c = channel.as_int() as usize;
+                match *message {
+                    MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                        let slot = c * 128 + key.as_int() as usize;
+                        if let Some(prev) = held[slot].take() {
+                            // Retriggered without note off, end the previous note here.
+                            notes[prev].release = time;
+                            notes[prev].key_release = time;
+                        }
+                        // Re-striking a key that is only held by a pedal restarts the string.
+                        for held_by_pedal in [&mut pedal_held[c], &mut sostenuto_held[c]] {
+                            held_by_pedal.retain(|&idx| {
+                                if notes[idx].key == key.as_int() {
+                                    notes[idx].release = notes[idx].release.min(time);
+                                    false
+                                } else {
+                                    true
+                                }
+                            });
+                        }
+                        held[slot] = Some(notes.len());
+                        notes.push(Note {
+                            key: key.as_int(),
+                            start: time,
+                            start_tick: *tick,
+                            release: f64::INFINITY,
+                            key_release: f64::INFINITY,
+                        });
+                    }
+                    MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                        let slot = c * 128 + key.as_int() as usize;
+                        if let Some(idx) = held[slot].take() {
+                            notes[idx].key_release = time;
+                            // Notes caught by the sostenuto pedal are released when it is lifted.
+                            if sostenuto_held[c].contains(&idx) {
+                            } else if pedal_down[c] {
+                                pedal_held[c].push(idx);
+                            } else {
+                                notes[idx].release = time;
+                            }
+                        }
+                    }
+                    MidiMessage::Controller { controller, value }
+                        if controller.as_int() == CC_SUSTAIN =>
+                    {
+                        let down = value.as_int() >= 64;
+                        if pedal_down[c] && !down {
+                            for idx in pedal_held[c].drain(..) {
+                                // A note may have been retriggered (and ended) while the pedal was down.
+                                notes[idx].release = notes[idx].release.min(time);
+                            }
+                            sustain_pedal.push((pedal_since[c], time));
+                        } else if !pedal_down[c] && down {
+                            pedal_since[c] = time;
+                        }
+                        pedal_down[c] = down;
+                    }
+                    MidiMessage::Controller { controller, value }
+                        if controller.as_int() == CC_SOSTENUTO =>
+                    {
+                        let down = value.as_int() >= 64;
+                        if !sostenuto_down[c] && down {
+                            sostenuto_held[c] =
+                                (0..128).filter_map(|k| held[c * 128 + k]).collect();
+                        } else if sostenuto_down[c] && !down {
+                            // Notes still held down are released at their note off as usual.
+                            for idx in sostenuto_held[c].drain(..) {
+                                if notes[idx].key_release > time {
+                                } else if pedal_down[c] {
+                                    pedal_held[c].push(idx);
+                                } else {
+                                    notes[idx].release = notes[idx].release.min(time);
+                                }
+                            }
+                        }
+                        sostenuto_down[c] = down;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let duration = tick_to_time(end_tick);
+
+        // Close off notes that never got a note off or pedal release, and pedals that were never lifted.
+        for note in &mut notes {
+            note.release = note.release.min(duration);
+            note.key_release = note.key_release.min(note.release);
+        }
+        for (down, since) in pedal_down.iter().zip(pedal_since) {
+            if *down {
+                sustain_pedal.push((since, duration));
+            }
+        }
+        sustain_pedal.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Score {
+            ppqn,
+            notes,
+            bars,
+            sustain_pedal,
+            tempo_map,
+        }
+    }
+
+    /// Returns the time in seconds of the absolute `tick`.
+    pub fn tick_time(&self, tick: u64) -> f64 {
+        tick_time(&self.tempo_map, self.ppqn, tick)
+    }
+
+    /// Returns the bar that contains `time`.
+    pub fn bar_at(&self, time: f64) -> &Bar {
+        let idx = self.bars.partition_point(|b| b.start <= time).max(1) - 1;
+        &self.bars[idx]
+    }
+
+    /// Returns the beat (starting from 1, rounded to 2 decimals) of `time` within its bar.
+    pub fn beat_at(&self, time: f64) -> f64 {
+        let bar = self.bar_at(time);
+        let beat = 1.0 + (time - bar.start).max(0.0) / (bar.end - bar.start) * bar.beats as f64;
+        (beat * 100.0).round() / 100.0
+    }
+
+    /// Formats `time` as a `bar:beat` position, e.g. `12:3.5` is the "and" of beat 3 of bar 12.
+    pub fn position(&self, time: f64) -> String {
+        format!("{}:{}", self.bar_at(time).number, self.beat_at(time))
+    }
+}
+
+fn tick_time(tempo_map: &[TempoChange], ppqn: u64, tick: u64) -> f64 {
+    let idx = tempo_map.partition_point(|t| t.tick <= tick) - 1;
+    let t = &tempo_map[idx];
+    t.time + ticks_to_secs(tick - t.tick, t.tempo, ppqn)
+}
+
+fn ticks_to_secs(ticks: u64, tempo: u32, ppqn: u64) -> f64 {
+    (ticks as f64 / ppqn as f64) * (tempo as f64 / 1_000_000.0)
+}
@@ -1,16 +1,177 @@
 //! Websocket server
+//!
+//! Clients on another machine can schedule their animations by the server's clock instead of reacting to messages as
+//! they arrive, with a delay that depends on the network jitter. A client sends `sync:<client time>`, which is
+//! answered right away with `sync:<client time>:<server time>` (in ms since the server started), and estimates the
+//! offset of the server's clock as `server time - (client time + arrival time) / 2`, keeping the estimate of the
+//! handshake with the shortest round trip of a few. From its first handshake on, every message the client receives is
+//! prefixed with the server time it was sent at, e.g. `@1234.567:on:...`.
 
-use std::{thread, fmt::Display};
 use futures::executor;
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Instant,
+};
 
 use broadcaster::BroadcastChannel;
 use midly::num::u7;
+use serde_json::{json, Value};
 use websocket::{sync::Server, OwnedMessage};
 
-use crate::tuner::Monzo;
+use crate::combination::CombinationTone;
+use crate::mdns;
+use crate::tuner::{key_name, Monzo};
+use crate::LOCAL_SOCKET;
 
 const WEBSOCKET_ADDR: &str = "127.0.0.1:8765";
 
+/// Number of messages that failed to be sent to a client, see [`dropped_messages`].
+static DROPPED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+/// Number of connected clients, see [`clients`].
+static CLIENTS: AtomicU64 = AtomicU64::new(0);
+
+/// Expressive controllers that are also sent to the visualizer by name (see [`VisualizerMessage::Controller`]): CC
+/// number, name, and value after a reset of all controllers (CC 121).
+pub const EXPRESSIVE_CONTROLLERS: [(u8, &str, u8); 4] = [
+    (1, "modulation", 0),
+    (2, "breath", 0),
+    (4, "foot", 0),
+    (11, "expression", 127),
+];
+
+lazy_static! {
+    /// Senders of the text messages received from clients, see [`control_messages`].
+    static ref CONTROL_SENDERS: Mutex<Vec<mpsc::Sender<String>>> = Mutex::new(vec![]);
+    /// Start of the clock that messages are timestamped with, see [`clock`].
+    static ref CLOCK_START: Instant = Instant::now();
+}
+
+/// Returns the time of the server's clock in ms, see the [module docs](self).
+fn clock() -> f64 {
+    CLOCK_START.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Topics that the websocket stream is partitioned into, so that specialized clients (e.g. the lattice, supertitles,
+/// a telemetry dashboard) only receive the messages they need.
+///
+/// Clients receive all topics until they send `subscribe:<topic>,<topic>...`, after which they only receive the
+/// topics subscribed to. `unsubscribe:<topic>,<topic>...` stops receiving topics.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Topic {
+    /// Notes, CCs, pedals & expressive controllers.
+    Notes,
+    /// Tuning changes, chord names & the drift of the reference pitch.
+    Tuning,
+    /// Playback position & previews.
+    Transport,
+    /// Timing health of playback.
+    Telemetry,
+    /// Information about the pieces of a concert, see [`crate::pieces`].
+    Metadata,
+}
+
+impl Topic {
+    fn parse(name: &str) -> Option<Topic> {
+        match name.trim() {
+            "notes" => Some(Topic::Notes),
+            "tuning" => Some(Topic::Tuning),
+            "transport" => Some(Topic::Transport),
+            "telemetry" => Some(Topic::Telemetry),
+            "metadata" => Some(Topic::Metadata),
+            _ => None,
+        }
+    }
+}
+
+/// Topics a client receives, [`None`] for all of them.
+type Subscriptions = Arc<Mutex<Option<Vec<Topic>>>>;
+
+/// Handles a text message from `client`: `sync:` messages are answered with the reply returned, and timestamp the
+/// client's messages from then on (see the [module docs](self)), `subscribe:` & `unsubscribe:` messages update its
+/// subscriptions, others (e.g. from a conductor's remote) are passed on to the control receivers.
+fn handle_text(
+    subscriptions: &Subscriptions,
+    timestamped: &AtomicBool,
+    text: String,
+    client: &str,
+) -> Option<String> {
+    if let Some(client_time) = text.strip_prefix("sync:") {
+        timestamped.store(true, Ordering::Relaxed);
+        return Some(format!("sync:{client_time}:{:.3}", clock()));
+    }
+    if !update_subscriptions(subscriptions, &text, client) {
+        CONTROL_SENDERS
+            .lock()
+            .unwrap()
+            .retain(|s| s.send(text.clone()).is_ok());
+    }
+    None
+}
+
+/// Returns the text form of `message`, prefixed with the time it is sent at if the client is `timestamped`.
+fn stamped(message: &VisualizerMessage, timestamped: &AtomicBool) -> String {
+    match timestamped.load(Ordering::Relaxed) {
+        true => format!("@{:.3}:{message}", clock()),
+        false => message.to_string(),
+    }
+}
+
+/// Returns whether a client with `subscriptions` receives `message`.
+fn subscribed(subscriptions: &Subscriptions, message: &VisualizerMessage) -> bool {
+    subscriptions
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_none_or(|topics| topics.contains(&message.topic()))
+}
+
+/// Handles a `subscribe:` or `unsubscribe:` message from a client, returning false if `text` is neither.
+fn update_subscriptions(subscriptions: &Subscriptions, text: &str, client: &str) -> bool {
+    let (subscribe, names) = if let Some(names) = text.strip_prefix("subscribe:") {
+        (true, names)
+    } else if let Some(names) = text.strip_prefix("unsubscribe:") {
+        (false, names)
+    } else {
+        return false;
+    };
+    let mut subscriptions = subscriptions.lock().unwrap();
+    for name in names.split(',') {
+        let Some(topic) = Topic::parse(name) else {
+            println!("WARN: {client} tried to (un)subscribe to unknown topic: {name}");
+            continue;
+        };
+        let topics = subscriptions.get_or_insert_with(|| {
+            // Unsubscribing from a topic while receiving all of them keeps the others.
+            if subscribe {
+                vec![]
+            } else {
+                vec![
+                    Topic::Notes,
+                    Topic::Tuning,
+                    Topic::Transport,
+                    Topic::Telemetry,
+                    Topic::Metadata,
+                ]
+            }
+        });
+        topics.retain(|t| *t != topic);
+        if subscribe {
+            topics.push(topic);
+        }
+    }
+    println!(
+        "{client} subscribed to {:?}",
+        subscriptions.as_deref().unwrap_or(&[])
+    );
+    true
+}
+
 /// This is the message that gets sent to the JI lattice visualizer.
 #[derive(Clone)]
 pub enum VisualizerMessage {
@@ -19,7 +180,14 @@ pub enum VisualizerMessage {
         edosteps_from_a4: i32,
         /// Note velocity.
         velocity: u7,
+        /// Cents above the 12 edo pitch of the note.
+        cents: f64,
+        /// Tuning relative to the current root, e.g. `7/4 of D#`, see [`crate::tuner::ratio_name`].
+        ratio: String,
         monzo: Monzo,
+        /// Name of the note as spelt by the tuning, e.g. `Fx4`, see [`crate::tuner::spelled_key_name`]. Only in the
+        /// JSON form, to keep the websocket message format.
+        name: String,
     },
     NoteOff {
         edosteps_from_a4: i32,
@@ -28,38 +196,439 @@ pub enum VisualizerMessage {
     CC {
         controller: u7,
         value: u7,
+    },
+    /// State of the piano pedals as CC values, sent whenever one of them changes. Values in between 0 & 127 are
+    /// partial depths (e.g. half pedaling), a pedal is down from 64.
+    Pedals {
+        sustain: u7,
+        sostenuto: u7,
+        soft: u7,
+    },
+    /// An expressive controller (see [`EXPRESSIVE_CONTROLLERS`]) by name, so that visualizers don't need to know its
+    /// CC number. Sent along with its [`VisualizerMessage::CC`].
+    Controller {
+        name: &'static str,
+        /// From 0 to 1.
+        value: f64,
+    },
+    /// A tuning change annotated with a bar and/or label, for displaying where in the piece playback is.
+    Tuning {
+        /// Time of the tuning change in seconds.
+        time: f64,
+        /// Tuning of each semitone starting from A relative to the current root, with its cents above 12 edo, as in
+        /// [`VisualizerMessage::NoteOn`].
+        pitches: Vec<(String, f64)>,
+        /// E.g. `Bar 23: D#9sus4`, see [`crate::tuner::TuningData::annotation`].
+        annotation: String,
+    },
+    /// The chord of the sounding pitch classes relative to the root of the tuning in effect changed, e.g.
+    /// `F#9(13), otonal on F#`, see [`crate::chord`]. Empty if fewer than 2 pitch classes are sounding.
+    ChordChanged {
+        chord: String,
+    },
+    /// The reference pitch drifted, see [`crate::drift`].
+    Drift {
+        /// Frequency of A4 in Hz.
+        reference: f64,
+        /// Cents of the reference above [`crate::A4_FREQUENCY`], which all tunings are raised by.
+        cents: f64,
+    },
+    /// Scheduling statistics of playback since the previous one, sent periodically so that whoever is watching the
+    /// visuals can see the performance machine struggling before it becomes audible. See [`crate::timer::TimingStats`].
+    Timing {
+        /// Average lag behind schedule of the events in ms.
+        lag: f64,
+        /// Worst-case jitter (spread between the least & most lag) of the events in ms.
+        jitter: f64,
+        /// Number of messages that failed to be sent to a client so far, see [`dropped_messages`].
+        dropped: u64,
+    },
+    /// Playback position, sent periodically during playback (see [`crate::POSITION_INTERVAL`]).
+    Position {
+        /// In seconds.
+        time: f64,
+    },
+    /// A preview of a tuning change started, see [`crate::preview`].
+    Preview {
+        /// Index of the tuning change in the tuning timeline.
+        index: usize,
+        /// Time of the tuning change in seconds.
+        time: f64,
+        /// See [`crate::tuner::TuningData::annotation`], empty if none.
+        annotation: String,
+    },
+    /// Strongest combination tones of the notes sounding, including those held by the pedals, sent whenever they
+    /// change if [`crate::COMBINATION_TONE_MESSAGES`] is enabled. Empty if less than 2 notes are sounding.
+    CombinationTones {
+        /// Each tone with its nearest MIDI note and the cents above its 12 edo pitch.
+        tones: Vec<(CombinationTone, u8, f64)>,
+    },
+    /// A piece of the concert that can be loaded, sent for each piece when a client asks for the `pieces`. See
+    /// [`crate::pieces`].
+    Piece {
+        name: String,
+        /// Variants declared in the piece's tuning file.
+        variants: Vec<String>,
+    },
+    /// The piece to play next changed, or playback of it is waiting to start.
+    PieceChanged {
+        name: String,
+        /// Variants selected.
+        variants: Vec<String>,
+        /// Seconds until the last note stops sounding.
+        duration: f64,
+        /// Number of bars of the MIDI file.
+        bars: usize,
+        /// Number of tunings in the tuning timeline.
+        tunings: usize,
+        midi_file: String,
+        tuning_file: String,
+    },
+    /// A client loaded a piece whose tuning file is invalid with the variants, so it wasn't loaded. See
+    /// [`crate::pieces`].
+    PieceInvalid {
+        name: String,
+        variants: Vec<String>,
+        /// Every error in the tuning file, see [`crate::tuning_file::parse`].
+        errors: Vec<String>,
+    },
+}
+
+impl VisualizerMessage {
+    fn topic(&self) -> Topic {
+        match self {
+            VisualizerMessage::NoteOn { .. }
+            | VisualizerMessage::NoteOff { .. }
+            | VisualizerMessage::CC { .. }
+            | VisualizerMessage::Pedals { .. }
+            | VisualizerMessage::Controller { .. }
+            | VisualizerMessage::CombinationTones { .. } => Topic::Notes,
+            VisualizerMessage::Tuning { .. }
+            | VisualizerMessage::ChordChanged { .. }
+            | VisualizerMessage::Drift { .. } => Topic::Tuning,
+            VisualizerMessage::Timing { .. } => Topic::Telemetry,
+            VisualizerMessage::Position { .. } | VisualizerMessage::Preview { .. } => {
+                Topic::Transport
+            }
+            VisualizerMessage::Piece { .. }
+            | VisualizerMessage::PieceChanged { .. }
+            | VisualizerMessage::PieceInvalid { .. } => Topic::Metadata,
+        }
+    }
+
+    /// Returns the message as a JSON object with its `type` (the prefix of the websocket message), for `--emit jsonl`.
+    fn to_json(&self) -> Value {
+        match self {
+            VisualizerMessage::NoteOn {
+                edosteps_from_a4,
+                velocity,
+                cents,
+                ratio,
+                monzo,
+                name,
+            } => json!({
+                "type": "on",
+                "edosteps_from_a4": edosteps_from_a4,
+                "velocity": velocity.as_int(),
+                "cents": cents,
+                "ratio": ratio,
+                "monzo": monzo,
+                "name": name,
+            }),
+            VisualizerMessage::NoteOff {
+                edosteps_from_a4,
+                velocity,
+            } => json!({
+                "type": "off",
+                "edosteps_from_a4": edosteps_from_a4,
+                "velocity": velocity.as_int(),
+            }),
+            VisualizerMessage::CC { controller, value } => json!({
+                "type": "cc",
+                "controller": controller.as_int(),
+                "value": value.as_int(),
+            }),
+            VisualizerMessage::Pedals {
+                sustain,
+                sostenuto,
+                soft,
+            } => json!({
+                "type": "pedals",
+                "sustain": sustain.as_int(),
+                "sostenuto": sostenuto.as_int(),
+                "soft": soft.as_int(),
+            }),
+            VisualizerMessage::Controller { name, value } => json!({
+                "type": "controller",
+                "name": name,
+                "value": value,
+            }),
+            VisualizerMessage::Tuning {
+                time,
+                pitches,
+                annotation,
+            } => json!({
+                "type": "tuning",
+                "time": time,
+                "pitches": pitches
+                    .iter()
+                    .map(|(ratio, cents)| json!({ "ratio": ratio, "cents": cents }))
+                    .collect::<Vec<Value>>(),
+                "annotation": annotation,
+            }),
+            VisualizerMessage::ChordChanged { chord } => json!({
+                "type": "chord",
+                "chord": chord,
+            }),
+            VisualizerMessage::Drift { reference, cents } => json!({
+                "type": "drift",
+                "reference": reference,
+                "cents": cents,
+            }),
+            VisualizerMessage::Timing {
+                lag,
+                jitter,
+                dropped,
+            } => json!({
+                "type": "timing",
+                "lag": lag,
+                "jitter": jitter,
+                "dropped": dropped,
+            }),
+            VisualizerMessage::Position { time } => json!({
+                "type": "position",
+                "time": time,
+            }),
+            VisualizerMessage::Preview {
+                index,
+                time,
+                annotation,
+            } => json!({
+                "type": "preview",
+                "index": index,
+                "time": time,
+                "annotation": annotation,
+            }),
+            VisualizerMessage::CombinationTones { tones } => json!({
+                "type": "combination",
+                "tones": tones
+                    .iter()
+                    .map(|(tone, key, cents)| json!({
+                        "kind": tone.kind.to_string(),
+                        "order": tone.order,
+                        "hz": tone.hz,
+                        "pairs": tone.pairs,
+                        "name": key_name(*key),
+                        "cents": cents,
+                    }))
+                    .collect::<Vec<Value>>(),
+            }),
+            VisualizerMessage::Piece { name, variants } => json!({
+                "type": "piece",
+                "name": name,
+                "variants": variants,
+            }),
+            VisualizerMessage::PieceChanged {
+                name,
+                variants,
+                duration,
+                bars,
+                tunings,
+                midi_file,
+                tuning_file,
+            } => {
+                json!({
+                    "type": "loaded",
+                    "name": name,
+                    "variants": variants,
+                    "duration": duration,
+                    "bars": bars,
+                    "tunings": tunings,
+                    "midi_file": midi_file,
+                    "tuning_file": tuning_file,
+                })
+            }
+            VisualizerMessage::PieceInvalid {
+                name,
+                variants,
+                errors,
+            } => json!({
+                "type": "invalid",
+                "name": name,
+                "variants": variants,
+                "errors": errors,
+            }),
+        }
     }
 }
 
 impl Display for VisualizerMessage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            VisualizerMessage::NoteOn { edosteps_from_a4, velocity, monzo } => {
-                let monzo_str = monzo.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(":");
-                write!(f, "on:{}:{}:{}", edosteps_from_a4, velocity, monzo_str)
-            },
-            VisualizerMessage::NoteOff { edosteps_from_a4, velocity } => {
+            VisualizerMessage::NoteOn {
+                edosteps_from_a4,
+                velocity,
+                cents,
+                ratio,
+                monzo,
+                ..
+            } => {
+                let monzo_str = monzo
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join(":");
+                write!(
+                    f,
+                    "on:{}:{}:{:+.1}:{}:{}",
+                    edosteps_from_a4, velocity, cents, ratio, monzo_str
+                )
+            }
+            VisualizerMessage::NoteOff {
+                edosteps_from_a4,
+                velocity,
+            } => {
                 write!(f, "off:{}:{}", edosteps_from_a4, velocity)
-            },
+            }
             VisualizerMessage::CC { controller, value } => {
                 write!(f, "cc:{}:{}", controller, value)
             }
+            VisualizerMessage::Pedals {
+                sustain,
+                sostenuto,
+                soft,
+            } => {
+                write!(f, "pedals:{}:{}:{}", sustain, sostenuto, soft)
+            }
+            VisualizerMessage::Controller { name, value } => {
+                write!(f, "controller:{}:{:.3}", name, value)
+            }
+            VisualizerMessage::Tuning {
+                time,
+                pitches,
+                annotation,
+            } => {
+                let pitches_str = pitches
+                    .iter()
+                    .map(|(ratio, cents)| format!("{} ({:+.1}c)", ratio, cents))
+                    .collect::<Vec<String>>()
+                    .join(",");
+                // The annotation is last as it may contain colons.
+                write!(f, "tuning:{}:{}:{}", time, pitches_str, annotation)
+            }
+            VisualizerMessage::ChordChanged { chord } => {
+                write!(f, "chord:{}", chord)
+            }
+            VisualizerMessage::Drift { reference, cents } => {
+                write!(f, "drift:{:.3}:{:+.2}", reference, cents)
+            }
+            VisualizerMessage::Timing {
+                lag,
+                jitter,
+                dropped,
+            } => {
+                write!(f, "timing:{:.3}:{:.3}:{}", lag, jitter, dropped)
+            }
+            VisualizerMessage::Position { time } => {
+                write!(f, "position:{:.3}", time)
+            }
+            VisualizerMessage::Preview {
+                index,
+                time,
+                annotation,
+            } => {
+                // The annotation is last as it may contain colons.
+                write!(f, "preview:{}:{}:{}", index, time, annotation)
+            }
+            VisualizerMessage::CombinationTones { tones } => {
+                let tones_str = tones
+                    .iter()
+                    .map(|(tone, key, cents)| {
+                        format!(
+                            "{} {} {:+.1}c {:.2}Hz x{}",
+                            tone.label(),
+                            key_name(*key),
+                            cents,
+                            tone.hz,
+                            tone.pairs
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join(",");
+                write!(f, "combination:{}", tones_str)
+            }
+            VisualizerMessage::Piece { name, variants } => {
+                write!(f, "piece:{}:{}", name, variants.join(","))
+            }
+            VisualizerMessage::PieceChanged {
+                name,
+                variants,
+                duration,
+                bars,
+                tunings,
+                midi_file,
+                tuning_file,
+            } => {
+                // The file paths are last as they may contain colons.
+                write!(
+                    f,
+                    "loaded:{}:{}:{:.3}:{}:{}:{}:{}",
+                    name,
+                    variants.join(","),
+                    duration,
+                    bars,
+                    tunings,
+                    midi_file,
+                    tuning_file
+                )
+            }
+            VisualizerMessage::PieceInvalid {
+                name,
+                variants,
+                errors,
+            } => {
+                // One error per line, last as they contain colons.
+                write!(
+                    f,
+                    "invalid:{}:{}:{}",
+                    name,
+                    variants.join(","),
+                    errors.join("\n")
+                )
+            }
         }
     }
 }
 
-/// Starts the websocket server at [`WEBSOCKET_ADDR`]
+/// Starts the websocket server at [`WEBSOCKET_ADDR`], and writes every message to stdout as a JSON line too if
+/// `emit_jsonl` (see `--emit jsonl`), which is then all that is written to stdout.
 ///
 /// Returns a clonable broadcast channel that can be used to send messages to all connected clients.
 ///
 /// (It can also receive the messages it sends, but that's not necessary)
-pub fn start_websocket_server() -> BroadcastChannel<VisualizerMessage> {
+pub fn start_websocket_server(emit_jsonl: bool) -> BroadcastChannel<VisualizerMessage> {
     println!("Starting websocket server...");
 
     // clonable broadcast channel (messages sent by one end received by all ends, any channel can send messages)
     let chan: BroadcastChannel<VisualizerMessage> = BroadcastChannel::new();
 
+    lazy_static::initialize(&CLOCK_START);
     let server = Server::bind(WEBSOCKET_ADDR).expect("Failed to bind websocket server");
+    mdns::advertise("_ji-performer._tcp", WEBSOCKET_ADDR, "/");
+    if let Some(path) = LOCAL_SOCKET {
+        start_local_socket(path, &chan);
+    }
+    if emit_jsonl {
+        let mut chan_recv = chan.clone();
+        thread::spawn(move || {
+            while let Some(msg) = executor::block_on(chan_recv.recv()) {
+                let mut json = msg.to_json();
+                json["timestamp"] = json!((clock() * 1000.0).round() / 1000.0);
+                // The rest of the output goes to stderr, see [`crate::LOG_TO_STDERR`].
+                std::println!("{json}");
+            }
+        });
+    }
 
     let chan_recv = chan.clone();
     thread::spawn(move || {
@@ -69,22 +638,65 @@ pub fn start_websocket_server() -> BroadcastChannel<VisualizerMessage> {
             let mut chan_recv = chan_recv.clone(); // clone chan_recv for each connection.
             // Spawn a new thread for each connection.
             thread::spawn(move || {
-                let mut client = request.accept().unwrap();
+                let client = request.accept().unwrap();
 
                 let ip = client.peer_addr().unwrap();
 
                 println!("Connection from {}", ip);
+                CLIENTS.fetch_add(1, Ordering::Relaxed);
+
+                let (mut receiver, sender) = client.split().unwrap();
+                // Shared with the thread answering the client's sync handshakes.
+                let sender = Arc::new(Mutex::new(sender));
+                let subscriptions: Subscriptions = Arc::new(Mutex::new(None));
+                let timestamped = Arc::new(AtomicBool::new(false));
+
+                let client_subscriptions = subscriptions.clone();
+                let client_timestamped = timestamped.clone();
+                let reply_sender = sender.clone();
+                let client = ip.to_string();
+                thread::spawn(move || {
+                    for message in receiver.incoming_messages() {
+                        match message {
+                            Ok(OwnedMessage::Text(text)) => {
+                                let reply = handle_text(
+                                    &client_subscriptions,
+                                    &client_timestamped,
+                                    text,
+                                    &client,
+                                );
+                                if let Some(reply) = reply {
+                                    let _ = reply_sender
+                                        .lock()
+                                        .unwrap()
+                                        .send_message(&OwnedMessage::Text(reply));
+                                }
+                            }
+                            Ok(OwnedMessage::Close(_)) | Err(_) => break,
+                            Ok(_) => {}
+                        }
+                    }
+                });
 
                 while let Some(msg) = executor::block_on(chan_recv.recv()) {
-                    let msg_str = msg.to_string();
-                    let res = client.send_message(&OwnedMessage::Text(msg_str));
+                    if !subscribed(&subscriptions, &msg) {
+                        continue;
+                    }
+                    let msg_str = stamped(&msg, &timestamped);
+                    let res = sender
+                        .lock()
+                        .unwrap()
+                        .send_message(&OwnedMessage::Text(msg_str));
                     if let Err(e) = res {
+                        DROPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
                         println!("Closing connection to {ip}: {e}");
                         break;
                     }
                 }
 
-                if let Err(e) = client.shutdown() {
+                CLIENTS.fetch_sub(1, Ordering::Relaxed);
+                let res = sender.lock().unwrap().shutdown_all();
+                if let Err(e) = res {
                     println!("WARN: Failed to close connection to {ip}: {e}");
                 }
             });
@@ -93,3 +705,90 @@ pub fn start_websocket_server() -> BroadcastChannel<VisualizerMessage> {
 
     chan
 }
+
+/// Serves the same messages as the websocket server to clients of the Unix domain socket at `path`, one per line, for
+/// same-machine consumers (e.g. OBS scripts) that shouldn't go through TCP. Clients can send the same text messages as
+/// to the websocket server, one per line.
+#[cfg(unix)]
+fn start_local_socket(path: &'static str, chan: &BroadcastChannel<VisualizerMessage>) {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    // The socket file of a previous run is left behind, and would fail the bind.
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path)
+        .unwrap_or_else(|e| panic!("Failed to bind local socket {path}: {e}"));
+    println!("Serving visualizer messages on local socket {path}");
+
+    let chan_recv = chan.clone();
+    thread::spawn(move || {
+        for (id, stream) in listener.incoming().filter_map(Result::ok).enumerate() {
+            let mut chan_recv = chan_recv.clone();
+            let client = format!("{path} client {id}");
+            println!("Connection from {client}");
+            CLIENTS.fetch_add(1, Ordering::Relaxed);
+
+            let subscriptions: Subscriptions = Arc::new(Mutex::new(None));
+            let timestamped = Arc::new(AtomicBool::new(false));
+            let client_subscriptions = subscriptions.clone();
+            let client_timestamped = timestamped.clone();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            let reader_client = client.clone();
+            // Shared with the thread answering the client's sync handshakes, so that lines aren't interleaved.
+            let stream = Arc::new(Mutex::new(stream));
+            let reply_stream = stream.clone();
+            thread::spawn(move || {
+                for line in reader.lines().map_while(Result::ok) {
+                    if let Some(reply) = handle_text(
+                        &client_subscriptions,
+                        &client_timestamped,
+                        line,
+                        &reader_client,
+                    ) {
+                        let _ = writeln!(reply_stream.lock().unwrap(), "{reply}");
+                    }
+                }
+            });
+
+            thread::spawn(move || {
+                while let Some(msg) = executor::block_on(chan_recv.recv()) {
+                    if !subscribed(&subscriptions, &msg) {
+                        continue;
+                    }
+                    if let Err(e) =
+                        writeln!(stream.lock().unwrap(), "{}", stamped(&msg, &timestamped))
+                    {
+                        DROPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
+                        println!("Closing connection to {client}: {e}");
+                        break;
+                    }
+                }
+                CLIENTS.fetch_sub(1, Ordering::Relaxed);
+            });
+        }
+    });
+}
+
+/// Named pipes aren't supported (yet), Windows consumers have to use the websocket server.
+#[cfg(not(unix))]
+fn start_local_socket(path: &'static str, _chan: &BroadcastChannel<VisualizerMessage>) {
+    println!("WARN: Local sockets are only supported on Unix, not serving on {path}");
+}
+
+/// Returns a receiver of the text messages that clients send to the websocket server from now on, e.g. `advance` to
+/// advance past a cue in conductor mode.
+pub fn control_messages() -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    CONTROL_SENDERS.lock().unwrap().push(sender);
+    receiver
+}
+
+/// Returns the number of clients connected to the websocket server & local socket.
+pub fn clients() -> u64 {
+    CLIENTS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of messages that failed to be sent to a client so far.
+pub fn dropped_messages() -> u64 {
+    DROPPED_MESSAGES.load(Ordering::Relaxed)
+}
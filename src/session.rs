@@ -0,0 +1,277 @@
+//! Recording of every take of playback into a SQLite session database (see [`crate::SESSION_DB`]), so that takes can
+//! be compared afterwards, e.g. how late the retune at bar 66 was in take 3:
+//!
+//! ```sql
+//! SELECT time, lag, data FROM events WHERE take = 3 AND kind = 'tuning' AND bar = 66;
+//! ```
+//!
+//! Each playback is a row of the `takes` table (`id`, `started_at`, `midi_file`, `tuning_file`, `start_from` &
+//! `variants`), and everything performed in it a row of the `events` table:
+//!
+//! - `take`: id of the take.
+//! - `wall`: seconds since the take was started when the event was played.
+//! - `time`, `bar` & `beat`: position of the event in the MIDI file, NULL for MIDI messages.
+//! - `lag`: seconds the event was sent after it was due (negative if early), for tuning changes.
+//! - `kind`: `midi` (`data` is the message in hex, e.g. `90 45 64`), `tuning` (its description), `transport` (e.g.
+//!   `start`, `cue`, `advance`, `stop`) or `warning`.
+//!
+//! Rows are written in batches on their own thread, so that recording doesn't hold up playback. Requires the
+//! `session-db` feature.
+
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::output::MidiSink;
+use crate::tuner::TuningSnapshot;
+
+/// A row of the `events` table, see the [module docs](self).
+#[cfg_attr(not(feature = "session-db"), allow(dead_code))]
+struct Event {
+    wall: f64,
+    time: Option<f64>,
+    lag: Option<f64>,
+    kind: &'static str,
+    data: String,
+}
+
+#[cfg_attr(not(feature = "session-db"), allow(dead_code))]
+enum Message {
+    Event(Event),
+    /// Writes the remaining events and stops the writer.
+    Finish,
+}
+
+/// A take being recorded.
+pub struct Session {
+    recorder: Recorder,
+    take: i64,
+    writer: JoinHandle<usize>,
+}
+
+/// Records the events of a take, see [`Session::recorder`].
+#[derive(Clone)]
+pub struct Recorder {
+    sender: mpsc::Sender<Message>,
+    started: Instant,
+}
+
+impl Session {
+    /// Starts recording a new take of playing `midi_file` with `tuning_file` (with `variants`) from `start_from`
+    /// seconds into the database at `path`, creating it if needed.
+    pub fn start(
+        path: &str,
+        midi_file: &str,
+        tuning_file: &str,
+        start_from: f64,
+        variants: &[String],
+    ) -> Session {
+        let (sender, receiver) = mpsc::channel();
+        let (take, writer) = open(path, midi_file, tuning_file, start_from, variants, receiver);
+        println!("Recording take {take} to {path}");
+        Session {
+            recorder: Recorder {
+                sender,
+                started: Instant::now(),
+            },
+            take,
+            writer,
+        }
+    }
+
+    pub fn recorder(&self) -> Recorder {
+        self.recorder.clone()
+    }
+
+    /// Writes the events recorded so far and finishes the take. Events recorded afterwards are dropped.
+    pub fn finish(self) {
+        let _ = self.recorder.sender.send(Message::Finish);
+        let events = self.writer.join().unwrap();
+        println!("Recorded {events} events of take {}", self.take);
+    }
+}
+
+impl Recorder {
+    fn record(
+        &self,
+        delay: f64,
+        time: Option<f64>,
+        lag: Option<f64>,
+        kind: &'static str,
+        data: String,
+    ) {
+        let wall = self.started.elapsed().as_secs_f64() + delay;
+        // The writer only stops once the session is finished, after which events are dropped.
+        let _ = self.sender.send(Message::Event(Event {
+            wall,
+            time,
+            lag,
+            kind,
+            data,
+        }));
+    }
+
+    /// Records a tuning change at `time` in the MIDI file, sent `lag` seconds after it was due.
+    pub fn tuning(&self, time: f64, lag: Option<f64>, description: String) {
+        self.record(0.0, Some(time), lag, "tuning", description);
+    }
+
+    /// Records a transport action at `time` in the MIDI file, e.g. `start`.
+    pub fn transport(&self, time: f64, action: impl Into<String>) {
+        self.record(0.0, Some(time), None, "transport", action.into());
+    }
+
+    pub fn warning(&self, time: f64, message: String) {
+        self.record(0.0, Some(time), None, "warning", message);
+    }
+}
+
+/// Records the MIDI messages sent to a sink, at the time they are played.
+pub struct Recorded {
+    sink: Box<dyn MidiSink>,
+    recorder: Recorder,
+    /// Delay of the messages sent from now on, see [`MidiSink::timestamp`].
+    delay: f64,
+}
+
+impl Recorded {
+    pub fn new(sink: Box<dyn MidiSink>, recorder: Recorder) -> Self {
+        Recorded {
+            sink,
+            recorder,
+            delay: 0.0,
+        }
+    }
+}
+
+impl MidiSink for Recorded {
+    fn send(&mut self, message: &[u8]) {
+        let hex: Vec<String> = message.iter().map(|byte| format!("{byte:02X}")).collect();
+        self.recorder
+            .record(self.delay, None, None, "midi", hex.join(" "));
+        self.sink.send(message);
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.sink.retune(snapshot);
+    }
+
+    fn lookahead(&self) -> f64 {
+        self.sink.lookahead()
+    }
+
+    fn timestamp(&mut self, delay: f64) {
+        self.delay = delay;
+        self.sink.timestamp(delay);
+    }
+}
+
+/// Longest time events wait to be written, so that little is lost if the program is killed.
+#[cfg_attr(not(feature = "session-db"), allow(dead_code))]
+const WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Adds a take to the database at `path` and writes the events received from `events` to it on a new thread, until
+/// [`Message::Finish`]. Returns the id of the take, and the writer which returns the number of events written.
+#[cfg(feature = "session-db")]
+fn open(
+    path: &str,
+    midi_file: &str,
+    tuning_file: &str,
+    start_from: f64,
+    variants: &[String],
+    events: mpsc::Receiver<Message>,
+) -> (i64, JoinHandle<usize>) {
+    use rusqlite::{params, Connection};
+
+    use crate::score::Score;
+
+    let mut conn = Connection::open(path)
+        .unwrap_or_else(|e| panic!("Failed to open session database {path}: {e}"));
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS takes (
+            id INTEGER PRIMARY KEY,
+            started_at TEXT NOT NULL DEFAULT (datetime('now')),
+            midi_file TEXT NOT NULL,
+            tuning_file TEXT NOT NULL,
+            start_from REAL NOT NULL,
+            variants TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS events (
+            take INTEGER NOT NULL REFERENCES takes (id),
+            wall REAL NOT NULL,
+            time REAL,
+            bar INTEGER,
+            beat REAL,
+            lag REAL,
+            kind TEXT NOT NULL,
+            data TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS events_by_take ON events (take, kind, bar);",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO takes (midi_file, tuning_file, start_from, variants) VALUES (?1, ?2, ?3, ?4)",
+        params![midi_file, tuning_file, start_from, variants.join(" ")],
+    )
+    .unwrap();
+    let take = conn.last_insert_rowid();
+    let score = Score::load(midi_file);
+
+    let writer = std::thread::spawn(move || {
+        let mut written = 0;
+        let mut finished = false;
+        while !finished {
+            let Ok(first) = events.recv() else {
+                break;
+            };
+            // Everything received within the write interval is written in one transaction.
+            let deadline = Instant::now() + WRITE_INTERVAL;
+            let tx = conn.transaction().unwrap();
+            {
+                let mut insert = tx
+                    .prepare_cached(
+                        "INSERT INTO events (take, wall, time, bar, beat, lag, kind, data)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    )
+                    .unwrap();
+                let mut message = Some(first);
+                while let Some(received) = message.take() {
+                    match received {
+                        Message::Event(event) => {
+                            let bar = event.time.map(|time| score.bar_at(time).number as i64);
+                            let beat = event.time.map(|time| score.beat_at(time));
+                            insert
+                                .execute(params![
+                                    take, event.wall, event.time, bar, beat, event.lag, event.kind,
+                                    event.data
+                                ])
+                                .unwrap();
+                            written += 1;
+                        }
+                        Message::Finish => {
+                            finished = true;
+                            break;
+                        }
+                    }
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    message = events.recv_timeout(timeout).ok();
+                }
+            }
+            tx.commit().unwrap();
+        }
+        written
+    });
+    (take, writer)
+}
+
+#[cfg(not(feature = "session-db"))]
+fn open(
+    _path: &str,
+    _midi_file: &str,
+    _tuning_file: &str,
+    _start_from: f64,
+    _variants: &[String],
+    _events: mpsc::Receiver<Message>,
+) -> (i64, JoinHandle<usize>) {
+    panic!("Recording sessions requires the `session-db` feature")
+}
@@ -0,0 +1,168 @@
+//! Setlists, to run a whole concert program from one invocation (`play SETLIST`): the [`crate::PIECES`] to play in
+//! order, each with overrides of the speed, the start point & the variants, and what happens between them.
+//!
+//! Setlist files have a line per piece: its name followed by any of `speed=<multiplier>`, `from=<seconds>` &
+//! `variants=<variant>,<variant>...`. Between pieces, playback waits for enter (or an `advance` from the desk) as in a
+//! concert, unless the line before the piece is `gap <seconds>`, to start it that many seconds after the last one
+//! stopped, or `attacca`, to start it as soon as the last notes of the previous one are played, without letting them
+//! ring for [`crate::PLAYBACK_TAIL`]. Lines starting with `#` are ignored.
+//!
+//! ```text
+//! Ondine      variants=dry
+//! attacca
+//! Le Gibet    speed=0.95
+//! gap 30
+//! Scarbo      from=12.5
+//! ```
+
+use std::fs;
+
+use crate::config::Config;
+use crate::pieces::Piece;
+use crate::tuning_file::{line_error, report_errors};
+use crate::PLAYBACK_TAIL;
+
+/// Settings of a performance that an entry of a setlist overrides.
+#[derive(Clone, Debug)]
+pub struct Overrides {
+    /// Position playback starts from, see [`Config::start_from`].
+    pub start_from: f64,
+    /// See [`Config::speed`].
+    pub speed: f64,
+    /// Seconds to wait before starting by itself, or [`None`] to wait for enter.
+    pub gap: Option<f64>,
+    /// Seconds the last notes ring for, see [`PLAYBACK_TAIL`].
+    pub tail: f64,
+}
+
+impl Overrides {
+    /// The settings of `config`, before any are overridden.
+    pub fn new(config: &Config) -> Self {
+        Overrides {
+            start_from: config.start_from,
+            speed: config.speed,
+            gap: None,
+            tail: PLAYBACK_TAIL,
+        }
+    }
+}
+
+/// A piece of a setlist, with its variants & overrides.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub piece: Piece,
+    pub variants: Vec<String>,
+    pub overrides: Overrides,
+}
+
+/// How a piece follows the one before it.
+#[derive(Clone, Copy, PartialEq)]
+enum Transition {
+    Wait,
+    Gap(f64),
+    Attacca,
+}
+
+/// Loads the setlist at `path` of `pieces`, whose entries override the settings of `config`. Prints every invalid line
+/// and exits if there are any.
+pub fn load(path: &str, pieces: &[Piece], config: &Config) -> Vec<Entry> {
+    let text =
+        fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read setlist {path}: {e}"));
+    let mut entries: Vec<Entry> = vec![];
+    let mut transition = Transition::Wait;
+    let mut errors = vec![];
+    for (line_idx, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() || words[0].starts_with('#') {
+            continue;
+        }
+        let column = line.find(|c: char| !c.is_whitespace()).unwrap() + 1;
+        let error = |msg: &str| line_error(path, line_idx, column, line, msg);
+        match words[..] {
+            ["attacca"] => {
+                transition = Transition::Attacca;
+                continue;
+            }
+            ["gap", seconds] => {
+                match seconds
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|seconds| *seconds >= 0.0)
+                {
+                    Some(seconds) => transition = Transition::Gap(seconds),
+                    None => errors.push(error(&format!("Invalid gap: {seconds}"))),
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let name = words
+            .iter()
+            .filter(|word| !word.contains('='))
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let Some(piece) = pieces.iter().find(|piece| piece.name == name) else {
+            errors.push(error(&format!("No such piece: {name}")));
+            continue;
+        };
+        let mut entry = Entry {
+            piece: piece.clone(),
+            variants: vec![],
+            overrides: Overrides::new(config),
+        };
+        for word in words.iter().filter(|word| word.contains('=')) {
+            let (key, value) = word.split_once('=').unwrap();
+            let error =
+                |msg: &str| line_error(path, line_idx, line.find(word).unwrap() + 1, line, msg);
+            let valid = match key {
+                "speed" => value
+                    .parse()
+                    .ok()
+                    .filter(|speed| *speed > 0.0)
+                    .map(|speed| entry.overrides.speed = speed),
+                "from" => value
+                    .parse()
+                    .ok()
+                    .filter(|from| *from >= 0.0)
+                    .map(|from| entry.overrides.start_from = from),
+                "variants" => {
+                    entry.variants = value
+                        .split(',')
+                        .filter(|v| !v.is_empty())
+                        .map(String::from)
+                        .collect();
+                    Some(())
+                }
+                _ => {
+                    errors.push(error(&format!("Unknown override: {key}")));
+                    continue;
+                }
+            };
+            if valid.is_none() {
+                errors.push(error(&format!("Invalid {key}: {value}")));
+            }
+        }
+        match transition {
+            Transition::Wait => {}
+            Transition::Gap(seconds) => entry.overrides.gap = Some(seconds),
+            Transition::Attacca => {
+                entry.overrides.gap = Some(0.0);
+                if let Some(previous) = entries.last_mut() {
+                    previous.overrides.tail = 0.0;
+                }
+            }
+        }
+        transition = Transition::Wait;
+        entries.push(entry);
+    }
+    if transition != Transition::Wait {
+        errors.push(format!(
+            "{path}: Expected a piece after the last gap or attacca"
+        ));
+    }
+    report_errors(&errors, path);
+    assert!(!entries.is_empty(), "No pieces in setlist {path}");
+    entries
+}
@@ -0,0 +1,175 @@
+//! Soundcheck sequences, to verify the whole signal chain before doors open: a short sequence that plays a note on the
+//! channel of every pitch class, bends a note to the extremes of the pitch bend range, and plays a few sonorities of
+//! the tuning timeline as tuned in the performance. Each step is announced with what it should sound like, so that a
+//! silent channel, a wrong pitch bend range or a mistuned synth is noticed by ear (or with a tuner) right away.
+
+use midly::PitchBend;
+
+use crate::analysis::ONSET_TOLERANCE;
+use crate::drift;
+use crate::output::{ChannelAllocator, NoteChannels};
+use crate::score::Score;
+use crate::tuner::{key_name, pitch_bend_message, pitch_class, PitchSpec, TuningSnapshot};
+use crate::{DRONE_VELOCITY, REFERENCE_DRIFT};
+
+/// Seconds each channel is played for.
+const CHANNEL_SECONDS: f64 = 0.6;
+
+/// Seconds each extreme of the pitch bend range is held for.
+const BEND_SECONDS: f64 = 1.5;
+
+/// Number of sonorities of the timeline played, the fullest chords struck right after a tuning change.
+const SONORITIES: usize = 3;
+
+/// Seconds each sonority is held for.
+const SONORITY_SECONDS: f64 = 3.0;
+
+/// A step of the sequence: its notes are struck at its start and released at its end.
+pub struct Step {
+    /// What the step should sound like.
+    pub description: String,
+    /// Tuning the output is retuned to before the step (see [`crate::output::MidiSink::retune`]).
+    pub snapshot: TuningSnapshot,
+    /// Pitch bends & note ons sent at the start of the step.
+    pub on: Vec<Vec<u8>>,
+    /// Note offs sent at the end of the step.
+    pub off: Vec<Vec<u8>>,
+    pub seconds: f64,
+}
+
+/// Returns the soundcheck sequence of `score` tuned as by `snapshots`, with notes on the channels chosen by
+/// `allocator` and pitch bends for a range of +/- `pb_range` semitones, see the [module docs](self).
+pub fn sequence(
+    score: &Score,
+    snapshots: &[TuningSnapshot],
+    allocator: &mut dyn ChannelAllocator,
+    pb_range: u16,
+) -> Vec<Step> {
+    let edo12 = TuningSnapshot::new(
+        0.0,
+        std::array::from_fn(|i| PitchSpec::Cents(100.0 * i as f64)),
+    );
+    let mut steps = vec![];
+
+    // A4 to G#5, a pitch class each, untuned.
+    for key in 69..81 {
+        let mut step = Step::new(String::new(), edo12.clone(), CHANNEL_SECONDS);
+        let channel = step.strike(&[key], allocator)[0];
+        step.description = format!("Channel {channel}: {} in 12edo", key_name(key));
+        step.on.insert(
+            0,
+            pitch_bend_message(channel as usize, PitchBend::from_f64(0.0)),
+        );
+        steps.push(step);
+    }
+
+    // A4 bent to either extreme of the pitch bend range, then back.
+    let range = pb_range as u8;
+    let bends = [
+        (-1.0, format!("down {range} semitones"), 69 - range),
+        (1.0, format!("up {range} semitones"), 69 + range),
+        (0.0, "back".to_string(), 69),
+    ];
+    for (bend, bent, sounds_as) in bends {
+        let description = format!("A4 bent {bent}, should sound as {}", key_name(sounds_as));
+        let mut step = Step::new(description, edo12.clone(), BEND_SECONDS);
+        let channel = step.strike(&[69], allocator)[0];
+        step.on.insert(
+            0,
+            pitch_bend_message(channel as usize, PitchBend::from_f64(bend)),
+        );
+        steps.push(step);
+    }
+
+    for (snapshot, keys) in sonorities(score, snapshots) {
+        let drift = drift::cents_at(REFERENCE_DRIFT, snapshot.time);
+        let names: Vec<String> = keys
+            .iter()
+            .map(|key| format!("{} {}", key_name(*key), snapshot.tuning[pitch_class(*key)]))
+            .collect();
+        let position = score.position(snapshot.time);
+        let description = format!(
+            "Bar {position} @ {:.3}s: {}",
+            snapshot.time,
+            names.join(", ")
+        );
+        let tuning = drift::drifted(&snapshot.tuning, drift);
+        let mut step = Step::new(
+            description,
+            TuningSnapshot::new(snapshot.time, tuning),
+            SONORITY_SECONDS,
+        );
+        step.on = allocator.retune(&tuning, &NoteChannels::new());
+        let channels = step.strike(&keys, allocator);
+        let tune_notes: Vec<Vec<u8>> = keys
+            .iter()
+            .zip(channels)
+            .flat_map(|(key, channel)| allocator.tune_note(*key, channel, &tuning))
+            .collect();
+        step.on.splice(0..0, tune_notes);
+        steps.push(step);
+    }
+    steps
+}
+
+impl Step {
+    fn new(description: String, snapshot: TuningSnapshot, seconds: f64) -> Step {
+        Step {
+            description,
+            snapshot,
+            on: vec![],
+            off: vec![],
+            seconds,
+        }
+    }
+
+    /// Strikes `keys` for the step, returning their channels.
+    fn strike(&mut self, keys: &[u8], allocator: &mut dyn ChannelAllocator) -> Vec<u8> {
+        let mut channels = NoteChannels::new();
+        let on: Vec<u8> = keys
+            .iter()
+            .map(|key| channels.note_on(*key, DRONE_VELOCITY, allocator).0)
+            .collect();
+        for (key, channel) in keys.iter().zip(&on) {
+            self.on.push(vec![0x90 | channel, *key, DRONE_VELOCITY]);
+            self.off
+                .push(vec![0x80 | channels.note_off(*key, allocator), *key, 0]);
+        }
+        on
+    }
+}
+
+/// Returns the [`SONORITIES`] fullest of the chords of `score` struck first after each tuning change of `snapshots`
+/// (before the next one), with the tuning they are struck in, in order of time.
+fn sonorities<'a>(
+    score: &Score,
+    snapshots: &'a [TuningSnapshot],
+) -> Vec<(&'a TuningSnapshot, Vec<u8>)> {
+    let mut chords: Vec<(&TuningSnapshot, Vec<u8>)> = snapshots
+        .iter()
+        .enumerate()
+        .filter_map(|(i, snapshot)| {
+            let first = score
+                .notes
+                .iter()
+                .find(|note| note.start >= snapshot.time)?
+                .start;
+            if snapshots.get(i + 1).is_some_and(|next| first >= next.time) {
+                return None;
+            }
+            let mut keys: Vec<u8> = score
+                .notes
+                .iter()
+                .filter(|note| note.start >= first && note.start <= first + ONSET_TOLERANCE)
+                .map(|note| note.key)
+                .collect();
+            keys.sort();
+            keys.dedup();
+            Some((snapshot, keys))
+        })
+        .collect();
+    chords.sort_by_key(|(_, keys)| std::cmp::Reverse(keys.len()));
+    chords.truncate(SONORITIES);
+    chords.sort_by(|a, b| a.0.time.total_cmp(&b.0.time));
+    chords
+}
@@ -0,0 +1,151 @@
+//! Keyboard splits (see [`crate::KEYBOARD_SPLIT`]), for pieces where the bass & treble need different tunings of the
+//! same pitch class at once, e.g. a low D pedal point at 9/8 under a melody spelling it as 10/9.
+//!
+//! The keys below the split are the lower range, which has a tuning file of its own: an independent timeline of
+//! tunings, changing at its own times. Its notes are sent on a channel bank of its own, the 12 pitch class channels of
+//! another MIDI output port (e.g. a second instance of the synth), so that its pitch bends don't bend the notes above.
+//! Pedals & controllers are sent to both.
+//!
+//! Nudges, variant switches, live edits & pitch envelopes only apply to the upper range, and the tuning messages of
+//! the visualizer are of the upper range. The drift of the reference (see [`crate::REFERENCE_DRIFT`]) applies to both.
+//! The notes of the lower range are shown with its tuning, and get the velocity offsets of its tuning file.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::drift;
+use crate::output::{MidiSink, Shared};
+use crate::tuner::{parse_key_name, CurrentTuning, Tuner, TuningData, TuningSnapshot};
+
+/// Splits the keyboard in two ranges, see the [module docs](self).
+pub struct KeyboardSplit {
+    /// Key that the upper range starts from, e.g. `C3`.
+    pub below: &'static str,
+    /// Tuning file of the lower range.
+    pub tuning_file: &'static str,
+    /// The lower range is sent to the MIDI output port whose name contains this.
+    pub port: &'static str,
+}
+
+impl KeyboardSplit {
+    /// Returns the key that the upper range starts from.
+    pub fn key(&self) -> u8 {
+        parse_key_name(self.below, 4)
+            .unwrap_or_else(|| panic!("Invalid keyboard split point {}", self.below))
+    }
+}
+
+/// The lower range of a keyboard split during playback, with its tuning in effect.
+pub struct LowerRange {
+    below: u8,
+    bank: Shared,
+    tuner: Tuner,
+    pub tuning: CurrentTuning,
+}
+
+impl LowerRange {
+    /// Plays the keys below the split of `split` on `bank`, tuned by `tuner`.
+    pub fn new(split: &KeyboardSplit, bank: Box<dyn MidiSink>, tuner: Tuner) -> Self {
+        LowerRange {
+            below: split.key(),
+            bank: Shared(Rc::new(RefCell::new(bank))),
+            tuner,
+            tuning: CurrentTuning::default(),
+        }
+    }
+
+    /// Whether `key` is in the lower range.
+    pub fn contains(&self, key: u8) -> bool {
+        key < self.below
+    }
+
+    /// Returns a sink that sends the notes of the lower range to its bank, and the rest to `upper`.
+    pub fn router(&self, upper: Box<dyn MidiSink>) -> Router {
+        Router {
+            upper,
+            lower: self.bank.clone(),
+            below: self.below,
+        }
+    }
+
+    /// Moves the tuning timeline of the range to `time`, returning the tuning change due, if any. Its pitch bends are
+    /// sent by [`LowerRange::retune`].
+    pub fn update(&mut self, time: f64) -> Option<TuningData> {
+        let tuning_data = self.tuner.update(time).cloned()?;
+        self.tuning.apply(&tuning_data);
+        Some(tuning_data)
+    }
+
+    /// Sends a tuning change returned by [`LowerRange::update`] at `time` to the bank, raised by `cents` of drift.
+    pub fn retune(&mut self, tuning_data: &TuningData, time: f64, cents: f64) {
+        self.bank.retune(&TuningSnapshot::new(
+            time,
+            drift::drifted(&self.tuning.tuning, cents),
+        ));
+        for message in drift::pitch_bends(&tuning_data.tuning, cents, self.tuner.pb_range()) {
+            self.bank.send(&message);
+        }
+    }
+
+    /// Retunes all semitones of the bank to the tuning in effect raised by `cents` of drift, for a drift step at
+    /// `time`.
+    pub fn drift(&mut self, time: f64, cents: f64) {
+        self.bank.retune(&TuningSnapshot::new(
+            time,
+            drift::drifted(&self.tuning.tuning, cents),
+        ));
+        for message in drift::pitch_bends(&self.tuning.tuning, cents, self.tuner.pb_range()) {
+            self.bank.send(&message);
+        }
+    }
+
+    /// Starts the tuning timeline over, for playback starting over from the beginning.
+    pub fn restart(&mut self) {
+        self.tuner.seek(f64::NEG_INFINITY);
+        self.tuning = CurrentTuning::default();
+    }
+}
+
+/// Sends the notes of the keys below the split to the bank of the lower range, and everything else sent to the pitch
+/// class channels to both banks, except the pitch bends of the upper range. Other channels (e.g. the click track) are
+/// only sent to the upper range.
+pub struct Router {
+    upper: Box<dyn MidiSink>,
+    lower: Shared,
+    below: u8,
+}
+
+impl MidiSink for Router {
+    fn send(&mut self, message: &[u8]) {
+        let (status, channel) = (message[0] >> 4, message[0] & 0x0F);
+        if !(0x8..0xF).contains(&status) || channel >= 12 {
+            self.upper.send(message);
+            return;
+        }
+        match status {
+            // Note off, note on & polyphonic aftertouch.
+            0x8..=0xA if message.len() > 1 => match message[1] < self.below {
+                true => self.lower.send(message),
+                false => self.upper.send(message),
+            },
+            0xE => self.upper.send(message),
+            _ => {
+                self.upper.send(message);
+                self.lower.send(message);
+            }
+        }
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.upper.retune(snapshot);
+    }
+
+    fn lookahead(&self) -> f64 {
+        self.upper.lookahead().min(self.lower.lookahead())
+    }
+
+    fn timestamp(&mut self, delay: f64) {
+        self.upper.timestamp(delay);
+        self.lower.timestamp(delay);
+    }
+}
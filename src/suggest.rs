@@ -0,0 +1,273 @@
+//! Candidate JI spellings of the chords found by [`crate::analysis::segment`], to start authoring the tunings of a new
+//! piece from a few sensible options rather than from scratch.
+//!
+//! Each segment's pitch classes are spelt relative to its suggested root in several families of tunings:
+//!
+//! - otonal: harmonics of the root, e.g. 4:5:6:7.
+//! - primodal/N: harmonics over the prime N, e.g. [5, 6, 7, 9, 11, 13]/3, the root being N/N.
+//! - utonal: subharmonics of the root, e.g. 1/4:1/5:1/6.
+//! - N-limit: the simplest N-limit ratio near each 12edo interval above the root.
+//!
+//! Candidates are scored by their complexity and by how far they would move the notes held over from the previous
+//! segment (their drift).
+
+use std::fmt::Write as _;
+
+use rational::Rational;
+
+use crate::analysis::Segment;
+use crate::score::Score;
+use crate::tuner::{snapshot_at, spelled_name, JIRatio, TuningSnapshot};
+
+/// Highest harmonic (or subharmonic) used by the otonal, primodal & utonal candidates.
+const HARMONIC_LIMIT: i128 = 31;
+
+/// Primes that primodal candidates are over.
+const PRIMODAL_PRIMES: &[i128] = &[3, 5, 7, 11, 13];
+
+/// Prime limits of the low complexity candidates.
+const PRIME_LIMITS: &[i128] = &[5, 7, 11, 13];
+
+/// Largest numerator & denominator of the ratios of prime limit candidates.
+const LIMIT_MAX_TERM: i128 = 64;
+
+/// Prime limit candidates prefer the simplest ratio within this many cents of the 12edo interval above the root.
+const LIMIT_TOLERANCE_CENTS: f64 = 30.0;
+
+/// A candidate JI spelling of a chord.
+pub struct Suggestion {
+    /// Family of the candidate, e.g. `otonal`, `primodal/3` or `7-limit`.
+    pub family: String,
+    /// Tunings of the pitch classes of the segment relative to its root, octave reduced, in the same order.
+    pub ratios: Vec<Rational>,
+    /// Base 2 logarithm of the largest term of the chord written as integers in lowest terms (e.g. 6 for 4:5:6),
+    /// which favours chords that are part of a simple harmonic series.
+    pub complexity: f64,
+    /// Largest shift in cents of the pitch classes held over from the previous segment, if the root is kept at its
+    /// tuning in effect before the segment.
+    pub drift: f64,
+}
+
+/// Returns the pitch classes of `segment` including its root, in order of their interval above the root.
+fn chord_tones(segment: &Segment) -> Vec<usize> {
+    let mut pitch_classes = segment.pitch_classes.clone();
+    if !pitch_classes.contains(&segment.root) {
+        pitch_classes.push(segment.root);
+    }
+    pitch_classes.sort_by_key(|pc| (pc + 12 - segment.root) % 12);
+    pitch_classes
+}
+
+/// Returns the ratios of each of [`PRIME_LIMITS`] with terms up to [`LIMIT_MAX_TERM`], octave reduced.
+fn limit_pools() -> Vec<(i128, Vec<Rational>)> {
+    PRIME_LIMITS
+        .iter()
+        .map(|limit| {
+            let pool = (1..=LIMIT_MAX_TERM)
+                .flat_map(|n| (1..=LIMIT_MAX_TERM).map(move |d| (n, d)))
+                .filter(|(n, d)| gcd(*n, *d) == 1 && largest_prime(n * d) <= *limit)
+                .map(|(n, d)| octave_reduce(Rational::new(n, d)))
+                .collect();
+            (*limit, pool)
+        })
+        .collect()
+}
+
+/// Returns `ratio` moved by octaves into [1/1, 2/1).
+fn octave_reduce(ratio: Rational) -> Rational {
+    let octaves = ratio.cents().unwrap().div_euclid(1200.0) as i32;
+    ratio / Rational::new(2, 1).pow(octaves)
+}
+
+/// Returns the ratio among `ratios` (octave reduced) nearest to `semitones` 12edo semitones, preferring the first of
+/// equally near ones.
+fn nearest(ratios: impl Iterator<Item = Rational>, semitones: usize) -> Rational {
+    ratios
+        .map(octave_reduce)
+        .min_by(|a, b| {
+            let error = |r: &Rational| (r.cents().unwrap() - 100.0 * semitones as f64).abs();
+            error(a).total_cmp(&error(b))
+        })
+        .unwrap()
+}
+
+/// Returns the largest prime factor of `n`.
+fn largest_prime(mut n: i128) -> i128 {
+    let mut largest = 1;
+    let mut factor = 2;
+    while n > 1 {
+        while n % factor == 0 {
+            n /= factor;
+            largest = factor;
+        }
+        factor += 1;
+    }
+    largest
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Returns the complexity of a chord of `ratios`, see [`Suggestion::complexity`].
+fn complexity(ratios: &[Rational]) -> f64 {
+    let lcm = ratios.iter().fold(1, |lcm, r| {
+        lcm / gcd(lcm, r.denominator()) * r.denominator()
+    });
+    let terms: Vec<i128> = ratios
+        .iter()
+        .map(|r| r.numerator() * (lcm / r.denominator()))
+        .collect();
+    let divisor = terms.iter().fold(0, |divisor, term| gcd(divisor, *term));
+    let largest = terms.iter().max().unwrap() / divisor;
+    (largest as f64).log2()
+}
+
+/// Returns the candidate tunings of pitch classes `intervals` semitones above a root, by family. `limit_pools` are the
+/// ratios of the prime limit candidates, see [`limit_pools`].
+fn candidates(
+    intervals: &[usize],
+    limit_pools: &[(i128, Vec<Rational>)],
+) -> Vec<(String, Vec<Rational>)> {
+    let harmonics = || (1..=HARMONIC_LIMIT).map(|h| Rational::new(h, 1));
+    let mut candidates = vec![(
+        "otonal".to_string(),
+        intervals.iter().map(|i| nearest(harmonics(), *i)).collect(),
+    )];
+    for prime in PRIMODAL_PRIMES {
+        let ratios = intervals
+            .iter()
+            .map(|i| match i {
+                0 => Rational::one(),
+                _ => nearest(harmonics().map(|h| h / Rational::new(*prime, 1)), *i),
+            })
+            .collect();
+        candidates.push((format!("primodal/{prime}"), ratios));
+    }
+    candidates.push((
+        "utonal".to_string(),
+        intervals
+            .iter()
+            .map(|i| nearest(harmonics().map(|h| Rational::one() / h), *i))
+            .collect(),
+    ));
+    for (limit, pool) in limit_pools {
+        let ratios = intervals
+            .iter()
+            .map(|i| {
+                let target = 100.0 * *i as f64;
+                let simplest = pool
+                    .iter()
+                    .filter(|r| (r.cents().unwrap() - target).abs() <= LIMIT_TOLERANCE_CENTS)
+                    .min_by_key(|r| r.numerator() * r.denominator());
+                simplest
+                    .copied()
+                    .unwrap_or_else(|| nearest(pool.iter().copied(), *i))
+            })
+            .collect();
+        candidates.push((format!("{limit}-limit"), ratios));
+    }
+    candidates
+}
+
+/// Suggests the simplest candidate JI spelling of `segment` (of its [`chord_tones`]) of each kind (otonal, primodal,
+/// utonal & prime limit), simplest first, with their drift from the tuning in effect before it in `snapshots`.
+fn suggest(
+    segment: &Segment,
+    snapshots: &[TuningSnapshot],
+    limit_pools: &[(i128, Vec<Rational>)],
+) -> Vec<Suggestion> {
+    let root = segment.root;
+    let pitch_classes = chord_tones(segment);
+    let intervals: Vec<usize> = pitch_classes
+        .iter()
+        .map(|pc| (pc + 12 - root) % 12)
+        .collect();
+    let before = snapshot_at(snapshots, segment.start - 0.001);
+
+    let mut suggestions: Vec<Suggestion> = vec![];
+    for (family, ratios) in candidates(&intervals, limit_pools) {
+        if suggestions.iter().any(|s| s.ratios == ratios) {
+            continue;
+        }
+        let drift = before.map_or(0.0, |before| {
+            let root_cents = before.tuning[root].cents().unwrap();
+            segment
+                .held_over
+                .iter()
+                .filter_map(|pc| {
+                    let ratio = ratios[pitch_classes.iter().position(|p| p == pc)?];
+                    let shift =
+                        root_cents + ratio.cents().unwrap() - before.tuning[*pc].cents().unwrap();
+                    Some((shift - 1200.0 * (shift / 1200.0).round()).abs())
+                })
+                .fold(0.0, f64::max)
+        });
+        suggestions.push(Suggestion {
+            family,
+            complexity: complexity(&ratios),
+            ratios,
+            drift,
+        });
+    }
+    suggestions.sort_by(|a, b| a.complexity.total_cmp(&b.complexity));
+    // Only the simplest candidate of each kind, e.g. the primodal one over the best fitting prime.
+    let mut kinds = vec![];
+    suggestions.retain(|s| {
+        let kind = match s.family.as_str() {
+            family if family.starts_with("primodal") => "primodal",
+            family if family.ends_with("-limit") => "limit",
+            "utonal" => "utonal",
+            _ => "otonal",
+        };
+        let first = !kinds.contains(&kind);
+        kinds.push(kind);
+        first
+    });
+    suggestions
+}
+
+/// Reports the suggestions for each segment, with the tunings of its pitch classes relative to its root.
+pub fn suggestion_report(
+    score: &Score,
+    segments: &[Segment],
+    snapshots: &[TuningSnapshot],
+) -> String {
+    let limit_pools = limit_pools();
+    let mut report = String::new();
+    for (i, seg) in segments.iter().enumerate() {
+        // Pitch classes are named as spelt by the tuning in effect, if any.
+        let spellings = snapshot_at(snapshots, seg.start).map_or([None; 12], |s| s.spellings);
+        writeln!(
+            report,
+            "{:>4}  {:>8.3}s  {:>9}  root {}",
+            i + 1,
+            seg.start,
+            score.position(seg.start),
+            spelled_name(&spellings, seg.root)
+        )
+        .unwrap();
+        let pitch_classes = chord_tones(seg);
+        for suggestion in suggest(seg, snapshots, &limit_pools) {
+            let tones: Vec<String> = pitch_classes
+                .iter()
+                .zip(&suggestion.ratios)
+                .map(|(pc, ratio)| format!("{} {ratio}", spelled_name(&spellings, *pc)))
+                .collect();
+            writeln!(
+                report,
+                "      {:<12}  complexity {:>5.2}  drift {:>6.2}c  {}",
+                suggestion.family,
+                suggestion.complexity,
+                suggestion.drift,
+                tones.join(", ")
+            )
+            .unwrap();
+        }
+    }
+    report
+}
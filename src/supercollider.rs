@@ -0,0 +1,179 @@
+//! Output to [SuperCollider](https://supercollider.github.io/) as OSC messages with the exact frequency of every note,
+//! so the tuning timeline can be realized with any SynthDef or pattern, without MIDI.
+//!
+//! Messages sent to sclang (port 57120 by default, see [`crate::SUPERCOLLIDER_ADDR`]):
+//!
+//! - `/ji/note_on id key freq vel`: a note starts. `id` (int) identifies the note in later messages, `key` (int) is the
+//!   MIDI note number, `freq` (float) is in Hz and `vel` (int) is the MIDI velocity, 1-127.
+//! - `/ji/freq id freq`: a sounding note is retuned, e.g. while held by the sustain pedal.
+//! - `/ji/note_off id`: the note is released. The sustain & sostenuto pedals are already accounted for.
+//! - `/ji/cc num value`: any control change (both ints), e.g. for una corda. Pedals are sent on all tuned channels, but
+//!   only forwarded once.
+//!
+//! See the README for an example `OSCdef` playing the `\default` SynthDef.
+
+use midly::live::LiveEvent;
+use midly::MidiMessage;
+
+use crate::export::key_frequency;
+use crate::osc::{OscArg, OscSender};
+use crate::output::MidiSink;
+use crate::score::{CC_SOSTENUTO, CC_SUSTAIN};
+use crate::tuner::TuningSnapshot;
+
+struct Note {
+    id: i32,
+    channel: u8,
+    key: u8,
+    freq: f64,
+    /// Whether the key is still held down.
+    held: bool,
+    /// Whether the sostenuto pedal was pressed while the key was held down.
+    sostenuto: bool,
+}
+
+pub struct SuperCollider {
+    osc: OscSender,
+    a4_hz: f64,
+    /// [`None`] (12edo) until the first retune.
+    snapshot: Option<TuningSnapshot>,
+    /// Notes that have not been released yet.
+    notes: Vec<Note>,
+    next_id: i32,
+    sustain: bool,
+    sostenuto: bool,
+}
+
+impl SuperCollider {
+    /// Sends notes to sclang at `addr`, with A4 (1/1) tuned to `a4_hz`.
+    pub fn connect(addr: &str, a4_hz: f64) -> Self {
+        println!("Sending OSC to SuperCollider at {addr}");
+        SuperCollider {
+            osc: OscSender::connect(addr),
+            a4_hz,
+            snapshot: None,
+            notes: vec![],
+            next_id: 0,
+            sustain: false,
+            sostenuto: false,
+        }
+    }
+
+    fn frequency(&self, key: u8) -> f64 {
+        match &self.snapshot {
+            Some(snapshot) => key_frequency(snapshot, key, self.a4_hz),
+            None => self.a4_hz * 2f64.powf((key as f64 - 69.0) / 12.0),
+        }
+    }
+
+    /// Presses or lifts the sostenuto pedal. Pressing it catches the notes whose keys are held down.
+    fn set_sostenuto(&mut self, down: bool) {
+        if down == self.sostenuto {
+            return;
+        }
+        self.sostenuto = down;
+        for note in &mut self.notes {
+            note.sostenuto = down && note.held;
+        }
+    }
+
+    /// Sends note offs for all notes that are neither held nor sustained.
+    fn release_notes(&mut self) {
+        let sustain = self.sustain;
+        let osc = &self.osc;
+        self.notes.retain(|note| {
+            let sounding = note.held || sustain || note.sostenuto;
+            if !sounding {
+                osc.send("/ji/note_off", &[OscArg::Int(note.id)]);
+            }
+            sounding
+        });
+    }
+}
+
+impl MidiSink for SuperCollider {
+    fn send(&mut self, message: &[u8]) {
+        let Ok(LiveEvent::Midi { channel, message }) = LiveEvent::parse(message) else {
+            return;
+        };
+        let channel = channel.as_int();
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                let note = Note {
+                    id: self.next_id,
+                    channel,
+                    key: key.as_int(),
+                    freq: self.frequency(key.as_int()),
+                    held: true,
+                    sostenuto: false,
+                };
+                self.next_id = self.next_id.wrapping_add(1);
+                self.osc.send(
+                    "/ji/note_on",
+                    &[
+                        OscArg::Int(note.id),
+                        OscArg::Int(note.key as i32),
+                        OscArg::Float(note.freq as f32),
+                        OscArg::Int(vel.as_int() as i32),
+                    ],
+                );
+                self.notes.push(note);
+            }
+            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                for note in &mut self.notes {
+                    if note.channel == channel && note.key == key.as_int() {
+                        note.held = false;
+                    }
+                }
+                self.release_notes();
+            }
+            MidiMessage::Controller { controller, value } => {
+                match controller.as_int() {
+                    CC_SUSTAIN => self.sustain = value.as_int() >= 64,
+                    CC_SOSTENUTO => self.set_sostenuto(value.as_int() >= 64),
+                    // Reset all controllers
+                    121 => {
+                        self.sustain = false;
+                        self.set_sostenuto(false);
+                    }
+                    // All notes off
+                    123 => {
+                        for note in &mut self.notes {
+                            if note.channel == channel {
+                                note.held = false;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                self.release_notes();
+                if channel != 0 {
+                    return;
+                }
+                self.osc.send(
+                    "/ji/cc",
+                    &[
+                        OscArg::Int(controller.as_int() as i32),
+                        OscArg::Int(value.as_int() as i32),
+                    ],
+                );
+            }
+            // Frequencies are already exact.
+            _ => {}
+        }
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.snapshot = Some(snapshot.clone());
+        for i in 0..self.notes.len() {
+            let freq = self.frequency(self.notes[i].key);
+            if freq != self.notes[i].freq {
+                self.notes[i].freq = freq;
+                self.osc.send(
+                    "/ji/freq",
+                    &[OscArg::Int(self.notes[i].id), OscArg::Float(freq as f32)],
+                );
+            }
+        }
+    }
+}
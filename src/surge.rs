@@ -0,0 +1,92 @@
+//! Output to [Surge XT](https://surge-synthesizer.github.io/) over its OSC interface. Instead of pitch bends, Surge XT
+//! is tuned directly by loading a Scala .scl & .kbm file pair for every tuning change.
+//!
+//! OSC input has to be enabled in Surge XT's settings, listening on the port of [`crate::SURGE_OSC_ADDR`]. Other
+//! synths implementing the same `/mnote`, `/cc` & `/tuning` messages can be used too.
+
+use std::fs;
+use std::path::Path;
+
+use midly::live::LiveEvent;
+use midly::MidiMessage;
+
+use crate::export;
+use crate::osc::{OscArg, OscSender};
+use crate::output::MidiSink;
+use crate::tuner::TuningSnapshot;
+
+pub struct SurgeXt {
+    osc: OscSender,
+    /// Where the tuning files are written for Surge XT to load.
+    tuning_dir: String,
+    a4_hz: f64,
+    /// Number of tunings sent so far, to give each tuning unique file names.
+    tunings_sent: usize,
+}
+
+impl SurgeXt {
+    /// Connects to Surge XT's OSC input at `addr`. Tuning files are written to `tuning_dir`, with A4 (1/1) tuned to
+    /// `a4_hz`.
+    pub fn connect(addr: &str, tuning_dir: &str, a4_hz: f64) -> Self {
+        fs::create_dir_all(tuning_dir).unwrap();
+        // Surge XT runs as a separate process (possibly in a DAW with a different working directory).
+        let tuning_dir = Path::new(tuning_dir)
+            .canonicalize()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+        println!("Sending OSC to Surge XT at {addr}");
+
+        SurgeXt {
+            osc: OscSender::connect(addr),
+            tuning_dir,
+            a4_hz,
+            tunings_sent: 0,
+        }
+    }
+}
+
+impl MidiSink for SurgeXt {
+    fn send(&mut self, message: &[u8]) {
+        let Ok(LiveEvent::Midi { channel, message }) = LiveEvent::parse(message) else {
+            return;
+        };
+        match message {
+            MidiMessage::NoteOn { key, vel } => self.osc.send(
+                "/mnote",
+                &[
+                    OscArg::Float(key.as_int() as f32),
+                    OscArg::Float(vel.as_int() as f32),
+                ],
+            ),
+            // Velocity 0 releases the note.
+            MidiMessage::NoteOff { key, .. } => self.osc.send(
+                "/mnote",
+                &[OscArg::Float(key.as_int() as f32), OscArg::Float(0.0)],
+            ),
+            // Pedals are sent on all tuned channels, but Surge has a single set of controllers.
+            MidiMessage::Controller { controller, value } if channel == 0 => self.osc.send(
+                "/cc",
+                &[
+                    OscArg::Float(controller.as_int() as f32),
+                    OscArg::Float(value.as_int() as f32),
+                ],
+            ),
+            // The tuning files already tune every note exactly.
+            _ => {}
+        }
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        // Surge XT loads files asynchronously, so each tuning is written to new files rather than overwriting the
+        // ones that may not have been loaded yet.
+        let stem = format!("{}/tuning_{:03}", self.tuning_dir, self.tunings_sent % 1000);
+        self.tunings_sent += 1;
+        let scl_path = format!("{stem}.scl");
+        let kbm_path = format!("{stem}.kbm");
+        export::write_scl(snapshot, &scl_path);
+        export::write_kbm(snapshot, self.a4_hz, &kbm_path);
+        self.osc.send("/tuning/scl", &[OscArg::String(scl_path)]);
+        self.osc.send("/tuning/kbm", &[OscArg::String(kbm_path)]);
+    }
+}
@@ -0,0 +1,175 @@
+//! Cyclic symmetries of the chords found by [`crate::analysis::segment`], and whether their JI tunings keep them.
+//!
+//! In 12edo, transposing by a tritone, a major third or a minor third divides the octave into 2, 3 or 4 equal steps, so
+//! chords built from these cycles (tritone pairs, augmented triads & diminished sevenths) are invariant under the
+//! cyclic groups Z/2Z, Z/3Z & Z/4Z. A chord has such a symmetry approximately when most of its pitch classes are
+//! mapped onto pitch classes of the chord by the transposition, e.g. a dominant seventh flat ninth is a diminished
+//! seventh plus a root.
+//!
+//! JI can't divide the octave equally, so a full cycle always breaks somewhere by a comma (e.g. 5/4, 5/4, 32/25 of an
+//! augmented triad) unless it is tempered, while a partial cycle (e.g. two stacked 6/5s) may keep its steps equal. For
+//! each symmetry found, the report lists the steps of the cycle in the tuning in effect and how much the largest and
+//! smallest step differ.
+
+use std::fmt::Write as _;
+
+use rational::Rational;
+
+use crate::analysis::{Segment, ONSET_TOLERANCE};
+use crate::score::Score;
+use crate::tuner::{snapshot_at, spelled_name, PitchSpec, TuningSnapshot};
+
+/// The cyclic symmetries looked for: the number of steps they divide the octave into, with their name.
+const SYMMETRIES: &[(usize, &str)] = &[(2, "tritone"), (3, "augmented"), (4, "diminished")];
+
+/// Smallest fraction of a chord's pitch classes that a transposition must map onto the chord for it to have the
+/// symmetry approximately.
+const MIN_INVARIANCE: f64 = 0.75;
+
+/// Steps of a cycle differing by less than this many cents keep the symmetry.
+const PRESERVED_TOLERANCE_CENTS: f64 = 1.0;
+
+/// A cyclic symmetry of a chord.
+pub struct Symmetry {
+    /// The number of equal steps of the octave that the symmetry transposes by, e.g. 4 for Z/4Z.
+    pub order: usize,
+    /// Fraction of the pitch classes of the chord that are mapped onto the chord by the transposition.
+    pub invariance: f64,
+    /// Whether the chord contains a whole cycle of the transposition.
+    pub complete: bool,
+    /// Steps of the transposition between pitch classes of the chord, as the pitch class they start from.
+    pub steps: Vec<usize>,
+}
+
+/// Returns the cyclic symmetries of `pitch_classes`, see the [module docs](self).
+fn symmetries(pitch_classes: &[usize]) -> Vec<Symmetry> {
+    let mut symmetries: Vec<Symmetry> = vec![];
+    for (order, _) in SYMMETRIES {
+        let interval = 12 / order;
+        let steps: Vec<usize> = pitch_classes
+            .iter()
+            .copied()
+            .filter(|pc| pitch_classes.contains(&((pc + interval) % 12)))
+            .collect();
+        let complete = pitch_classes
+            .iter()
+            .any(|pc| (1..*order).all(|i| pitch_classes.contains(&((pc + i * interval) % 12))));
+        let invariance = steps.len() as f64 / pitch_classes.len() as f64;
+        if !complete && invariance < MIN_INVARIANCE {
+            continue;
+        }
+        symmetries.push(Symmetry {
+            order: *order,
+            invariance,
+            complete,
+            steps,
+        });
+    }
+    // A diminished seventh contains two tritone pairs, which aren't reported separately.
+    let diminished = symmetries
+        .iter()
+        .find(|s| s.order == 4)
+        .map(|s| s.invariance);
+    symmetries
+        .retain(|s| s.order != 2 || diminished.is_none_or(|invariance| invariance < s.invariance));
+    symmetries
+}
+
+/// Returns the step from pitch class `from` to `to` above it in `snapshot`, as a ratio if both are JI.
+fn step(snapshot: &TuningSnapshot, from: usize, to: usize) -> (PitchSpec, f64) {
+    let octave = if to < from { 2 } else { 1 };
+    let pitch = match (snapshot.tuning[from], snapshot.tuning[to]) {
+        (PitchSpec::Ratio(from), PitchSpec::Ratio(to)) => {
+            PitchSpec::Ratio(to / from * Rational::new(octave, 1))
+        }
+        (from, to) => PitchSpec::Cents(
+            to.cents().unwrap() - from.cents().unwrap() + 1200.0 * (octave - 1) as f64,
+        ),
+    };
+    (pitch, pitch.cents().unwrap())
+}
+
+/// Reports the symmetries of each segment, with the steps of their cycles in the tuning in effect at its start in
+/// `snapshots` and whether the tuning keeps them equal.
+pub fn symmetry_report(
+    score: &Score,
+    segments: &[Segment],
+    snapshots: &[TuningSnapshot],
+) -> String {
+    let mut report = String::new();
+    for (i, seg) in segments.iter().enumerate() {
+        let symmetries = symmetries(&seg.pitch_classes);
+        if symmetries.is_empty() {
+            continue;
+        }
+        let snapshot = snapshot_at(snapshots, seg.start + ONSET_TOLERANCE);
+        let spellings = snapshot.map_or([None; 12], |s| s.spellings);
+        let names: Vec<String> = seg
+            .pitch_classes
+            .iter()
+            .map(|pc| spelled_name(&spellings, *pc))
+            .collect();
+        writeln!(
+            report,
+            "{:>4}  {:>8.3}s  {:>9}  {}",
+            i + 1,
+            seg.start,
+            score.position(seg.start),
+            names.join(" ")
+        )
+        .unwrap();
+        for symmetry in symmetries {
+            let name = SYMMETRIES
+                .iter()
+                .find(|(order, _)| *order == symmetry.order)
+                .unwrap()
+                .1;
+            let kind = if symmetry.complete {
+                "complete"
+            } else {
+                "partial"
+            };
+            let mut line = format!(
+                "      Z/{}Z {:<10}  {:<8}  invariance {:>3.0}%",
+                symmetry.order,
+                name,
+                kind,
+                symmetry.invariance * 100.0
+            );
+            if let Some(snapshot) = snapshot {
+                let interval = 12 / symmetry.order;
+                let steps: Vec<(String, f64)> = symmetry
+                    .steps
+                    .iter()
+                    .map(|from| {
+                        let to = (from + interval) % 12;
+                        let (pitch, cents) = step(snapshot, *from, to);
+                        let names = format!(
+                            "{}-{}",
+                            spelled_name(&spellings, *from),
+                            spelled_name(&spellings, to)
+                        );
+                        (format!("{names} {pitch}"), cents)
+                    })
+                    .collect();
+                let largest = steps
+                    .iter()
+                    .map(|(_, cents)| *cents)
+                    .fold(f64::MIN, f64::max);
+                let smallest = steps
+                    .iter()
+                    .map(|(_, cents)| *cents)
+                    .fold(f64::MAX, f64::min);
+                let spread = largest - smallest;
+                let verdict = match spread < PRESERVED_TOLERANCE_CENTS {
+                    true => "preserved".to_string(),
+                    false => format!("breaks by {spread:.2}c"),
+                };
+                let steps: Vec<String> = steps.into_iter().map(|(step, _)| step).collect();
+                write!(line, "  {verdict:<17}  {}", steps.join(", ")).unwrap();
+            }
+            writeln!(report, "{line}").unwrap();
+        }
+    }
+    report
+}
@@ -0,0 +1,156 @@
+//! Synchronization of ji-performer instances on different machines, e.g. one driving the synth and one driving the
+//! visualizer in the hall, so that they start together and stay locked to the same transport.
+//!
+//! The master sends its playback position to every follower as an OSC message over UDP, `/ji/sync time` (float, in
+//! seconds of the MIDI file) every [`SYNC_INTERVAL`] seconds, and `/ji/sync/stop` when playback stops. Followers start
+//! playing from the position of the first message, and move their clock to each following one.
+
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::mdns;
+use crate::osc::{self, OscArg, OscSender};
+
+/// Seconds between the position messages of the master.
+const SYNC_INTERVAL: f64 = 0.1;
+
+/// Followers only move their clock when it is off from the master's by more than this many seconds, so that network
+/// jitter doesn't make the clock jump back & forth.
+const SYNC_TOLERANCE: f64 = 0.002;
+
+/// Role of this instance in synchronized playback, see [`crate::SYNC`].
+#[allow(dead_code)]
+pub enum SyncRole {
+    /// Not synchronized.
+    Off,
+    /// Sends the playback position to the followers at these addresses.
+    Master(&'static [&'static str]),
+    /// Waits for a master to start playback, receiving its position at this address.
+    Follower(&'static str),
+}
+
+/// Clock of the playback position (time in the MIDI file) advancing at the playback speed, shared between threads.
+/// The clock can be moved & sped up or slowed down by another thread, e.g. to follow a sync master or a live
+/// performance.
+#[derive(Clone)]
+pub struct Transport {
+    /// An instant, the position at that instant, & the speed of the clock from then on.
+    origin: Arc<Mutex<(Instant, f64, f64)>>,
+}
+
+impl Transport {
+    /// Starts the clock now at position `time`, advancing at `speed`.
+    pub fn start(time: f64, speed: f64) -> Self {
+        Transport {
+            origin: Arc::new(Mutex::new((Instant::now(), time, speed))),
+        }
+    }
+
+    /// Returns the current position.
+    pub fn time(&self) -> f64 {
+        let (instant, time, speed) = *self.origin.lock().unwrap();
+        time + instant.elapsed().as_secs_f64() * speed
+    }
+
+    /// Returns how long it takes until the position reaches `time` at the current speed, or [`None`] if it already
+    /// has. Stopped, it takes [`Duration::MAX`].
+    pub fn until(&self, time: f64) -> Option<Duration> {
+        let (instant, origin, speed) = *self.origin.lock().unwrap();
+        let secs = (time - origin) / speed - instant.elapsed().as_secs_f64();
+        (secs > 0.0).then(|| Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX))
+    }
+
+    /// Moves the clock so that the current position is `time`.
+    pub fn set(&self, time: f64) {
+        let mut origin = self.origin.lock().unwrap();
+        *origin = (Instant::now(), time, origin.2);
+    }
+
+    /// Returns the current speed.
+    pub fn speed(&self) -> f64 {
+        self.origin.lock().unwrap().2
+    }
+
+    /// Changes the speed of the clock from the current position on.
+    pub fn set_speed(&self, speed: f64) {
+        let mut origin = self.origin.lock().unwrap();
+        let (instant, time, old_speed) = *origin;
+        let now = Instant::now();
+        *origin = (
+            now,
+            time + now.duration_since(instant).as_secs_f64() * old_speed,
+            speed,
+        );
+    }
+
+    /// Moves the clock so that the position at `instant` is `time`, advancing at `speed` from then on.
+    pub fn set_at(&self, instant: Instant, time: f64, speed: f64) {
+        *self.origin.lock().unwrap() = (instant, time, speed);
+    }
+}
+
+/// Sends the position of `transport` to the followers at `addrs` every [`SYNC_INTERVAL`] seconds, on a new thread.
+pub fn start_master(addrs: &[&str], transport: Transport) {
+    let followers: Vec<OscSender> = addrs.iter().map(|addr| OscSender::connect(addr)).collect();
+    println!("Sending sync to {} followers", followers.len());
+    thread::spawn(move || loop {
+        let time = transport.time();
+        for follower in &followers {
+            follower.send("/ji/sync", &[OscArg::Float(time as f32)]);
+        }
+        thread::sleep(Duration::from_secs_f64(SYNC_INTERVAL));
+    });
+}
+
+/// Tells the followers at `addrs` that playback stopped.
+pub fn stop_master(addrs: &[&str]) {
+    for addr in addrs {
+        OscSender::connect(addr).send("/ji/sync/stop", &[]);
+    }
+}
+
+/// Waits for the first position message of a master at `addr`, then keeps the returned transport locked to the
+/// master's position on a new thread, advancing at `speed` in between. `on_stop` is called when the master stops.
+pub fn follow(addr: &str, speed: f64, on_stop: impl Fn() + Send + 'static) -> Transport {
+    let socket = UdpSocket::bind(addr)
+        .unwrap_or_else(|e| panic!("Failed to listen for sync at {addr}: {e}"));
+    mdns::advertise("_osc._udp", addr, "/ji/sync");
+    println!("Waiting for the sync master at {addr}...");
+
+    let receive = move || -> Option<f64> {
+        let mut buf = [0; 1024];
+        loop {
+            let len = socket.recv(&mut buf).unwrap();
+            match osc::decode(&buf[..len]) {
+                Some((address, args)) if address == "/ji/sync" => match args[..] {
+                    [OscArg::Float(time)] => return Some(time as f64),
+                    _ => println!("WARN: Invalid sync message"),
+                },
+                Some((address, _)) if address == "/ji/sync/stop" => return None,
+                _ => println!("WARN: Invalid sync message"),
+            }
+        }
+    };
+
+    // Messages of a previous run of the master are ignored by waiting for the first position.
+    let mut first = None;
+    while first.is_none() {
+        first = receive();
+    }
+    let transport = Transport::start(first.unwrap(), speed);
+    println!("Following the sync master from {:.3}s", transport.time());
+
+    let follower = transport.clone();
+    thread::spawn(move || {
+        while let Some(time) = receive() {
+            if (follower.time() - time).abs() > SYNC_TOLERANCE {
+                follower.set(time);
+            }
+        }
+        println!("Sync master stopped");
+        on_stop();
+    });
+    transport
+}
@@ -0,0 +1,406 @@
+//! Built-in polyphonic synth, for auditioning the retuned playback on machines without Pianoteq or a loopback MIDI
+//! device. Plays in realtime with the `preview-synth` feature, and renders to WAV with the `render` feature.
+//!
+//! Like Pianoteq, each MIDI channel has its own pitch bend, so the per-pitch class channel retuning works the same.
+//! Voices are simple sine or saw waves with a piano-like decay.
+
+use std::f64::consts::TAU;
+#[cfg(feature = "render")]
+use std::fs::File;
+#[cfg(feature = "render")]
+use std::io::BufWriter;
+#[cfg(feature = "preview-synth")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "preview-synth")]
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+#[cfg(feature = "preview-synth")]
+use cpal::{FromSample, SizedSample};
+use midly::live::LiveEvent;
+use midly::MidiMessage;
+
+use crate::output::MidiSink;
+use crate::score::{CC_SOFT, CC_SOSTENUTO, CC_SUSTAIN};
+use crate::tuner::TuningSnapshot;
+
+/// A sound source that can be played by [`PreviewSynth`] or rendered by [`WavRenderer`].
+pub trait Instrument: Send {
+    /// Handles a raw MIDI message.
+    fn handle(&mut self, message: &[u8]);
+
+    /// See [`MidiSink::retune`]. Instruments tuned by pitch bends ignore this.
+    fn retune(&mut self, _snapshot: &TuningSnapshot) {}
+
+    /// Renders the next `output.len() / channels` frames, writing the same signal to all channels.
+    fn render(&mut self, output: &mut [f32], channels: usize);
+
+    /// Returns true if no voices are sounding.
+    #[cfg(feature = "render")]
+    fn is_silent(&self) -> bool;
+}
+
+#[allow(dead_code)]
+enum Waveform {
+    Sine,
+    /// Band-limited (PolyBLEP) sawtooth. Its harmonics make beating & mistunings much easier to hear than with sines.
+    Saw,
+}
+
+const WAVEFORM: Waveform = Waveform::Saw;
+
+/// Maximum number of simultaneous voices. The oldest voice is stolen when exceeded.
+const MAX_VOICES: usize = 64;
+
+/// Attack time in seconds.
+const ATTACK: f64 = 0.005;
+
+/// Time in seconds for a held note to decay to 1/e of its amplitude.
+const DECAY: f64 = 2.5;
+
+/// Time in seconds for a released note to decay to 1/e of its amplitude.
+const RELEASE: f64 = 0.1;
+
+/// Voices quieter than this are removed.
+const SILENCE: f64 = 1e-4;
+
+/// Master volume.
+const GAIN: f64 = 0.1;
+
+/// Gain of notes played with the soft (una corda) pedal down.
+pub const SOFT_PEDAL_GAIN: f64 = 0.6;
+
+struct Voice {
+    channel: u8,
+    key: u8,
+    /// Amplitude from velocity, 0 to 1.
+    amplitude: f64,
+    /// Phase from 0 to 1.
+    phase: f64,
+    /// Seconds since note on.
+    age: f64,
+    /// Whether the key is still held down.
+    held: bool,
+    /// Whether the sostenuto pedal was pressed while the key was held down, sustaining it until the pedal is lifted.
+    sostenuto: bool,
+    /// Gain of the release envelope, 1 until the note is released.
+    release_gain: f64,
+}
+
+/// The built-in synth's state & voices, independent of the audio output.
+pub struct Synth {
+    sample_rate: f64,
+    voices: Vec<Voice>,
+    /// Pitch bend range in +/- semitones that pitch bends are sent for.
+    pb_range: u16,
+    /// Pitch bend of each channel in semitones.
+    bends: [f64; 16],
+    /// Whether the sustain pedal is down.
+    ///
+    /// Playback sends pedals on all tuned channels (and other CCs on channel 0 only), so the pedals are shared by all
+    /// channels.
+    sustain: bool,
+    /// Whether the sostenuto pedal is down.
+    sostenuto: bool,
+    /// Whether the soft (una corda) pedal is down.
+    soft: bool,
+}
+
+impl Synth {
+    pub fn new(sample_rate: f64, pb_range: u16) -> Self {
+        Synth {
+            sample_rate,
+            voices: vec![],
+            pb_range,
+            bends: [0.0; 16],
+            sustain: false,
+            sostenuto: false,
+            soft: false,
+        }
+    }
+
+    /// Presses or lifts the sostenuto pedal. Pressing it catches the voices whose keys are held down.
+    fn set_sostenuto(&mut self, down: bool) {
+        if down == self.sostenuto {
+            return;
+        }
+        self.sostenuto = down;
+        for voice in &mut self.voices {
+            voice.sostenuto = down && voice.held;
+        }
+    }
+}
+
+impl Instrument for Synth {
+    /// Handles a raw MIDI message. Messages other than note on/off, pitch bend, pedals, all notes off and reset all
+    /// controllers are ignored.
+    fn handle(&mut self, message: &[u8]) {
+        let Ok(LiveEvent::Midi { channel, message }) = LiveEvent::parse(message) else {
+            return;
+        };
+        let channel = channel.as_int();
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                if self.voices.len() >= MAX_VOICES {
+                    self.voices.remove(0);
+                }
+                self.voices.push(Voice {
+                    channel,
+                    key: key.as_int(),
+                    amplitude: vel.as_int() as f64 / 127.0
+                        * if self.soft { SOFT_PEDAL_GAIN } else { 1.0 },
+                    phase: 0.0,
+                    age: 0.0,
+                    held: true,
+                    sostenuto: false,
+                    release_gain: 1.0,
+                });
+            }
+            MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                for voice in &mut self.voices {
+                    if voice.channel == channel && voice.key == key.as_int() {
+                        voice.held = false;
+                    }
+                }
+            }
+            MidiMessage::PitchBend { bend } => {
+                self.bends[channel as usize] = bend.as_f64() * self.pb_range as f64;
+            }
+            MidiMessage::Controller { controller, value } => match controller.as_int() {
+                CC_SUSTAIN => self.sustain = value.as_int() >= 64,
+                CC_SOSTENUTO => self.set_sostenuto(value.as_int() >= 64),
+                CC_SOFT => self.soft = value.as_int() >= 64,
+                // Reset all controllers
+                121 => {
+                    self.sustain = false;
+                    self.set_sostenuto(false);
+                    self.soft = false;
+                    self.bends[channel as usize] = 0.0;
+                }
+                // All notes off
+                123 => {
+                    for voice in &mut self.voices {
+                        if voice.channel == channel {
+                            voice.held = false;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    #[cfg(feature = "render")]
+    fn is_silent(&self) -> bool {
+        self.voices.is_empty()
+    }
+
+    fn render(&mut self, output: &mut [f32], channels: usize) {
+        output.fill(0.0);
+
+        let dt = 1.0 / self.sample_rate;
+        let release_coef = (-dt / RELEASE).exp();
+        let decay_coef = (-dt / DECAY).exp();
+
+        for voice in &mut self.voices {
+            // Pitch bends only change between buffers.
+            let freq = 440.0
+                * 2f64.powf((voice.key as f64 - 69.0 + self.bends[voice.channel as usize]) / 12.0);
+            let phase_inc = freq * dt;
+            let released = !voice.held && !self.sustain && !voice.sostenuto;
+            let mut decay = (-voice.age / DECAY).exp();
+
+            for frame in output.chunks_mut(channels) {
+                let osc = match WAVEFORM {
+                    Waveform::Sine => (voice.phase * TAU).sin(),
+                    Waveform::Saw => 2.0 * voice.phase - 1.0 - poly_blep(voice.phase, phase_inc),
+                };
+                let attack = (voice.age / ATTACK).min(1.0);
+                let sample = osc * voice.amplitude * attack * decay * voice.release_gain * GAIN;
+                for s in frame.iter_mut() {
+                    *s += sample as f32;
+                }
+
+                voice.phase = (voice.phase + phase_inc).fract();
+                voice.age += dt;
+                decay *= decay_coef;
+                if released {
+                    voice.release_gain *= release_coef;
+                }
+            }
+        }
+
+        self.voices
+            .retain(|v| v.amplitude * v.release_gain * (-v.age / DECAY).exp() > SILENCE);
+    }
+}
+
+/// PolyBLEP residual to subtract from a naive sawtooth at `phase` to band-limit its discontinuity.
+fn poly_blep(phase: f64, phase_inc: f64) -> f64 {
+    if phase < phase_inc {
+        let t = phase / phase_inc;
+        2.0 * t - t * t - 1.0
+    } else if phase > 1.0 - phase_inc {
+        let t = (phase - 1.0) / phase_inc;
+        t * t + 2.0 * t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// An [`Instrument`] playing to the default audio output device.
+#[cfg(feature = "preview-synth")]
+pub struct PreviewSynth {
+    instrument: Arc<Mutex<Box<dyn Instrument>>>,
+    /// Audio stream, stops playing when dropped.
+    _stream: cpal::Stream,
+}
+
+#[cfg(feature = "preview-synth")]
+impl PreviewSynth {
+    /// Starts playing to the default audio output device. The instrument is created with the sample rate of the device.
+    pub fn start(instrument: impl FnOnce(f64) -> Box<dyn Instrument>) -> Self {
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .expect("No audio output device found");
+        let config = device.default_output_config().unwrap();
+        println!(
+            "Preview synth: {} @ {} Hz",
+            device
+                .description()
+                .map_or("Unknown device".to_string(), |d| d.to_string()),
+            config.sample_rate()
+        );
+
+        let instrument = Arc::new(Mutex::new(instrument(config.sample_rate() as f64)));
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => {
+                build_stream::<f32>(&device, &config.config(), instrument.clone())
+            }
+            cpal::SampleFormat::I16 => {
+                build_stream::<i16>(&device, &config.config(), instrument.clone())
+            }
+            cpal::SampleFormat::U16 => {
+                build_stream::<u16>(&device, &config.config(), instrument.clone())
+            }
+            cpal::SampleFormat::I32 => {
+                build_stream::<i32>(&device, &config.config(), instrument.clone())
+            }
+            format => panic!("Unsupported sample format: {format}"),
+        };
+        stream.play().unwrap();
+
+        PreviewSynth {
+            instrument,
+            _stream: stream,
+        }
+    }
+}
+
+#[cfg(feature = "preview-synth")]
+impl MidiSink for PreviewSynth {
+    fn send(&mut self, message: &[u8]) {
+        self.instrument.lock().unwrap().handle(message);
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.instrument.lock().unwrap().retune(snapshot);
+    }
+}
+
+#[cfg(feature = "preview-synth")]
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    instrument: Arc<Mutex<Box<dyn Instrument>>>,
+) -> cpal::Stream {
+    let channels = config.channels as usize;
+    let mut buffer = vec![];
+    device
+        .build_output_stream(
+            config,
+            move |output: &mut [T], _| {
+                buffer.resize(output.len(), 0.0);
+                instrument.lock().unwrap().render(&mut buffer, channels);
+                for (out, sample) in output.iter_mut().zip(&buffer) {
+                    *out = T::from_sample(*sample);
+                }
+            },
+            |e| println!("WARN: Preview synth audio stream error: {e}"),
+            None,
+        )
+        .unwrap()
+}
+
+/// Renders an [`Instrument`] to a mono WAV file, as MIDI messages are sent to it in order of time.
+#[cfg(feature = "render")]
+pub struct WavRenderer {
+    instrument: Box<dyn Instrument>,
+    writer: hound::WavWriter<BufWriter<File>>,
+    sample_rate: u32,
+    buffer: Vec<f32>,
+    rendered_frames: u64,
+}
+
+#[cfg(feature = "render")]
+impl WavRenderer {
+    pub fn create(path: &str, sample_rate: u32, instrument: Box<dyn Instrument>) -> Self {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        WavRenderer {
+            instrument,
+            writer: hound::WavWriter::create(path, spec)
+                .unwrap_or_else(|e| panic!("Failed to create {path}: {e}")),
+            sample_rate,
+            buffer: vec![],
+            rendered_frames: 0,
+        }
+    }
+
+    /// Renders audio up to `time` seconds from the start of the file. Does nothing if `time` was already rendered.
+    pub fn render_until(&mut self, time: f64) {
+        self.render_until_frame((time.max(0.0) * self.sample_rate as f64).round() as u64);
+    }
+
+    fn render_until_frame(&mut self, frame: u64) {
+        if frame <= self.rendered_frames {
+            return;
+        }
+        self.buffer
+            .resize((frame - self.rendered_frames) as usize, 0.0);
+        self.instrument.render(&mut self.buffer, 1);
+        for sample in &self.buffer {
+            self.writer.write_sample(*sample).unwrap();
+        }
+        self.rendered_frames = frame;
+    }
+
+    /// Keeps rendering until all voices have decayed (or at most `max_tail` seconds), then finalizes the file.
+    ///
+    /// Returns the duration of the rendered audio in seconds.
+    pub fn finish(mut self, max_tail: f64) -> f64 {
+        let end = self.rendered_frames + (max_tail * self.sample_rate as f64) as u64;
+        while !self.instrument.is_silent() && self.rendered_frames < end {
+            // Render in 100ms chunks.
+            self.render_until_frame((self.rendered_frames + self.sample_rate as u64 / 10).min(end));
+        }
+        self.writer.finalize().unwrap();
+        self.rendered_frames as f64 / self.sample_rate as f64
+    }
+}
+
+#[cfg(feature = "render")]
+impl MidiSink for WavRenderer {
+    fn send(&mut self, message: &[u8]) {
+        self.instrument.handle(message);
+    }
+
+    fn retune(&mut self, snapshot: &TuningSnapshot) {
+        self.instrument.retune(snapshot);
+    }
+}
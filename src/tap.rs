@@ -0,0 +1,123 @@
+//! Tap tempo, to let playback breathe with a conductor (or the performer) instead of running at the tempo of the MIDI
+//! file: each tap of a key or pedal on the MIDI input is a beat, and playback is sped up or slowed down so that the
+//! beats of the score at the current position last as long as the recent taps are apart.
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use midly::live::LiveEvent;
+use midly::MidiMessage;
+
+use crate::score::Score;
+use crate::sync::Transport;
+
+/// Number of intervals between the latest taps that the tempo is averaged over.
+const TAP_HISTORY: usize = 4;
+
+/// Taps further apart than this many seconds start a new series of taps, instead of slowing down to a crawl.
+const MAX_TAP_INTERVAL: f64 = 2.0;
+
+/// Limits of the tapped tempo relative to the playback speed.
+const TAP_TEMPO_LIMITS: (f64, f64) = (0.5, 2.0);
+
+/// Tempo changes smaller than this fraction aren't printed.
+const PRINT_THRESHOLD: f64 = 0.01;
+
+/// What is tapped on the MIDI input, e.g. to set the tempo (see [`crate::TAP_TEMPO`]).
+#[allow(dead_code)]
+pub enum TapInput {
+    /// Playback runs at the tempo of the MIDI file.
+    Off,
+    /// Presses of this key.
+    Key(u8),
+    /// Presses of the pedal (or other controller) with this CC number.
+    Pedal(u8),
+}
+
+/// Keeps adjusting the speed of `transport` to the taps of `input` in the MIDI `messages` (raw, with when they were
+/// received) on a new thread, with the beats of `score`. The tapped tempo is limited relative to the playback `speed`.
+pub fn tap_tempo(
+    input: &'static TapInput,
+    messages: mpsc::Receiver<(Instant, Vec<u8>)>,
+    transport: Transport,
+    score: Score,
+    speed: f64,
+) {
+    thread::spawn(move || {
+        let mut detector = TapDetector::new(input);
+        let mut taps: Vec<Instant> = vec![];
+        let mut printed_speed = speed;
+        for (instant, raw) in messages {
+            if !detector.tapped(&raw) {
+                continue;
+            }
+
+            if taps
+                .last()
+                .is_some_and(|last| instant.duration_since(*last).as_secs_f64() > MAX_TAP_INTERVAL)
+            {
+                taps.clear();
+            }
+            taps.push(instant);
+            if taps.len() > TAP_HISTORY + 1 {
+                taps.remove(0);
+            }
+            if taps.len() < 2 {
+                continue;
+            }
+
+            let interval = taps[taps.len() - 1].duration_since(taps[0]).as_secs_f64()
+                / (taps.len() - 1) as f64;
+            let bar = score.bar_at(transport.time());
+            let beat = (bar.end - bar.start) / bar.beats as f64;
+            let tapped =
+                (beat / interval).clamp(TAP_TEMPO_LIMITS.0 * speed, TAP_TEMPO_LIMITS.1 * speed);
+            transport.set_speed(tapped);
+            if (tapped / printed_speed - 1.0).abs() > PRINT_THRESHOLD {
+                println!(
+                    "Tap tempo: {:.0} bpm ({:.0}% of the MIDI file)",
+                    60.0 / interval,
+                    tapped * 100.0
+                );
+                printed_speed = tapped;
+            }
+        }
+    });
+}
+
+/// Detects the taps of a [`TapInput`] in raw MIDI messages.
+pub struct TapDetector {
+    input: &'static TapInput,
+    /// Whether the tapped pedal is down.
+    pedal_down: bool,
+}
+
+impl TapDetector {
+    pub fn new(input: &'static TapInput) -> Self {
+        TapDetector {
+            input,
+            pedal_down: false,
+        }
+    }
+
+    /// Returns whether `raw` is a tap: a press of the key, or the pedal going down.
+    pub fn tapped(&mut self, raw: &[u8]) -> bool {
+        let Ok(LiveEvent::Midi { message, .. }) = LiveEvent::parse(raw) else {
+            return false;
+        };
+        match (self.input, message) {
+            (TapInput::Key(tap_key), MidiMessage::NoteOn { key, vel }) => {
+                key.as_int() == *tap_key && vel > 0
+            }
+            (TapInput::Pedal(cc), MidiMessage::Controller { controller, value })
+                if controller.as_int() == *cc =>
+            {
+                let was_down = self.pedal_down;
+                self.pedal_down = value >= 64;
+                self.pedal_down && !was_down
+            }
+            _ => false,
+        }
+    }
+}
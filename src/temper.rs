@@ -0,0 +1,402 @@
+//! Least-squares tempering of a passage: solves for the compromise tuning of a few pitch classes that best satisfies
+//! a set of interval targets, instead of searching by hand for mediants that split the difference between them.
+//!
+//! Targets are read from a line based file, with `#` comments:
+//!
+//! ```text
+//! root C#                  Pitch class that ratios & the resulting tuning are relative to (default A).
+//! G# == C# * 3/2           Exact constraint: G# is 3/2 of C#, modulo octaves.
+//! B ~ D# * 13/8            Target: B is as close as possible to 13/8 of D#, modulo octaves...
+//! B ~ A * 9/8 weight 2     ...and to 9/8 of A, with twice the importance of the other targets (default weight 1).
+//! A ~ 13/8                 The right side can also be a ratio relative to `root`.
+//! ```
+//!
+//! The solution minimizes the weighted sum of squared cents errors of all targets, subject to the exact constraints.
+//! `root` is always tuned to 1/1, and pitch classes that are not related to it by any targets or constraints stay at
+//! their 12edo pitch.
+
+use std::fmt::Write as _;
+use std::fs;
+
+use rational::Rational;
+
+use crate::tuner::{parse_pitch_class, JIRatio, SEMITONE_NAMES};
+use crate::tuning_file::{line_error, parse_expression, report_errors};
+
+/// Weight standing in for exact constraints, large enough to leave errors far below audibility.
+pub const EXACT_WEIGHT: f64 = 1e6;
+
+/// Weight pulling each pitch class to its 12edo pitch, so that pitch classes without a path to `root` are determined.
+const REGULARIZATION_WEIGHT: f64 = 1e-6;
+
+/// Exact constraints missed by more than this many cents are reported as conflicting.
+const CONFLICT_CENTS: f64 = 0.01;
+
+/// `<pc> == [<relative_to> *] <ratio>` or `<pc> ~ [<relative_to> *] <ratio> [weight <weight>]`
+pub struct Target {
+    pub pc: usize,
+    /// Pitch class that `ratio` is relative to, or [`None`] for `root`.
+    pub relative_to: Option<usize>,
+    pub ratio: Rational,
+    /// [`None`] for exact constraints.
+    pub weight: Option<f64>,
+    /// Line of the target, for reports.
+    pub text: String,
+}
+
+pub struct Problem {
+    pub root: usize,
+    pub targets: Vec<Target>,
+}
+
+pub struct Solution {
+    /// Tuning in cents above `root` of each pitch class involved in the targets.
+    pub cents: [Option<f64>; 12],
+    /// Error in cents of each target (tuned minus target, modulo octaves), in the order of [`Problem::targets`].
+    pub errors: Vec<f64>,
+}
+
+impl Solution {
+    /// Returns the root mean square error of the targets that aren't exact constraints, weighted by their weights.
+    pub fn rms_error(&self, problem: &Problem) -> f64 {
+        let (sum, weights) = problem
+            .targets
+            .iter()
+            .zip(&self.errors)
+            .filter_map(|(target, error)| Some((target.weight?, error)))
+            .fold((0.0, 0.0), |(sum, weights), (weight, error)| {
+                (sum + weight * error * error, weights + weight)
+            });
+        if weights == 0.0 {
+            0.0
+        } else {
+            (sum / weights).sqrt()
+        }
+    }
+}
+
+/// Loads the targets in the file at `path` (see the [module docs](self)). Prints every invalid line and exits if
+/// there are any.
+pub fn load(path: &str) -> Problem {
+    let text =
+        fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read targets {path}: {e}"));
+    parse(&text, path)
+}
+
+/// Parses the contents of a target file. `path` is only used in error messages.
+pub fn parse(text: &str, path: &str) -> Problem {
+    let mut problem = Problem {
+        root: 0,
+        targets: vec![],
+    };
+    let mut errors = vec![];
+
+    for (line_idx, raw_line) in text.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let column = raw_line.len() - raw_line.trim_start().len() + 1;
+        let error = |msg: &str| line_error(path, line_idx, column, raw_line, msg);
+
+        if let Some(root) = line.strip_prefix("root ") {
+            match parse_pitch_class(root.trim()) {
+                Some(root) => problem.root = root,
+                None => errors.push(error("Invalid root, expected `root <pitch class>`")),
+            }
+            continue;
+        }
+        match parse_target(line) {
+            Some(target) => problem.targets.push(target),
+            None => errors.push(error(
+                "Invalid target, expected `<pitch class> == [<pitch class> *] <ratio>` or \
+                 `<pitch class> ~ [<pitch class> *] <ratio> [weight <weight>]`",
+            )),
+        }
+    }
+    if problem.targets.is_empty() {
+        errors.push(format!("{path}: No targets"));
+    }
+    report_errors(&errors, path);
+    problem
+}
+
+fn parse_target(line: &str) -> Option<Target> {
+    let (pc, expression, weight) = match line.split_once("==") {
+        Some((pc, expression)) => (pc, expression, None),
+        None => {
+            let (pc, rest) = line.split_once('~')?;
+            match rest.split_once("weight") {
+                Some((expression, weight)) => {
+                    let weight = weight.trim().parse().ok().filter(|w: &f64| *w > 0.0)?;
+                    (pc, expression, Some(weight))
+                }
+                None => (pc, rest, Some(1.0)),
+            }
+        }
+    };
+    let (relative_to, ratio) = parse_expression(expression)?;
+    Some(Target {
+        pc: parse_pitch_class(pc.trim())?,
+        relative_to,
+        ratio,
+        weight,
+        text: line.to_string(),
+    })
+}
+
+/// Returns the 12edo pitch of `pc` in cents above `root`.
+fn edo_cents(pc: usize, root: usize) -> f64 {
+    100.0 * ((pc + 12 - root) % 12) as f64
+}
+
+/// Reduces an interval in cents to the octave equivalent nearest to 0.
+pub fn nearest_octave(cents: f64) -> f64 {
+    cents - 1200.0 * (cents / 1200.0).round()
+}
+
+/// Solves for the tuning minimizing the weighted sum of squared errors of the targets of `problem`.
+///
+/// The unknowns are the deviations of the pitch classes from 12edo relative to `root`, so that every target is a
+/// linear equation in them (deviation of `pc` minus deviation of `relative_to`, modulo octaves), see
+/// [`least_squares`].
+pub fn solve(problem: &Problem) -> Solution {
+    let root = problem.root;
+    // Deviation of `relative_to` and `pc` from their 12edo interval for each target, modulo octaves.
+    let target_deviation = |target: &Target| -> f64 {
+        let relative_to = target.relative_to.unwrap_or(root);
+        nearest_octave(
+            target.ratio.cents().unwrap() - edo_cents(target.pc, root)
+                + edo_cents(relative_to, root),
+        )
+    };
+
+    let equations: Vec<Equation> = problem
+        .targets
+        .iter()
+        .map(|target| Equation {
+            a: target.pc,
+            b: target.relative_to.unwrap_or(root),
+            value: target_deviation(target),
+            weight: target.weight.unwrap_or(EXACT_WEIGHT),
+        })
+        .collect();
+    let deviations = least_squares(12, root, &equations);
+
+    let mut cents = [None; 12];
+    for target in &problem.targets {
+        for pc in [Some(target.pc), target.relative_to].into_iter().flatten() {
+            cents[pc] = Some(edo_cents(pc, root) + deviations[pc]);
+        }
+    }
+    cents[root] = Some(0.0);
+    let errors = problem
+        .targets
+        .iter()
+        .map(|target| {
+            let relative_to = target.relative_to.unwrap_or(root);
+            deviations[target.pc] - deviations[relative_to] - target_deviation(target)
+        })
+        .collect();
+    Solution { cents, errors }
+}
+
+/// `x[a] - x[b] = value` with the given weight, an equation of a least squares problem.
+pub struct Equation {
+    pub a: usize,
+    pub b: usize,
+    pub value: f64,
+    pub weight: f64,
+}
+
+/// Solves for the `n` unknowns minimizing the weighted sum of squared residuals of `equations`, with the unknown
+/// `fixed` at 0. Unknowns that are not related to `fixed` by any equations are 0.
+pub fn least_squares(n: usize, fixed: usize, equations: &[Equation]) -> Vec<f64> {
+    // Normal equations A x = b.
+    let mut a = vec![vec![0.0; n]; n];
+    let mut b = vec![0.0; n];
+    for (i, row) in a.iter_mut().enumerate() {
+        row[i] += REGULARIZATION_WEIGHT;
+    }
+    for equation in equations {
+        if equation.a == equation.b {
+            continue;
+        }
+        let terms = [(equation.a, 1.0), (equation.b, -1.0)];
+        for (i, sign_i) in terms {
+            for (j, sign_j) in terms {
+                a[i][j] += equation.weight * sign_i * sign_j;
+            }
+            b[i] += equation.weight * sign_i * equation.value;
+        }
+    }
+    a[fixed] = vec![0.0; n];
+    a[fixed][fixed] = 1.0;
+    b[fixed] = 0.0;
+
+    // Gaussian elimination with partial pivoting.
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))
+            .unwrap();
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col].clone();
+            for (x, pivot) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    x
+}
+
+/// Returns the ratio with the smallest numerator & denominator within `tolerance` cents of `cents`, found by
+/// descending the Stern-Brocot tree of mediants.
+pub fn simplest_ratio(cents: f64, tolerance: f64) -> Rational {
+    let (low, high) = (
+        2f64.powf((cents - tolerance) / 1200.0),
+        2f64.powf((cents + tolerance) / 1200.0),
+    );
+    let (mut left, mut right) = ((0, 1), (1, 0));
+    loop {
+        let mediant = (left.0 + right.0, left.1 + right.1);
+        let value = mediant.0 as f64 / mediant.1 as f64;
+        if value < low {
+            left = mediant;
+        } else if value > high {
+            right = mediant;
+        } else {
+            return Rational::new(mediant.0 as i128, mediant.1 as i128);
+        }
+    }
+}
+
+/// Reports the solution of `problem`: the tuning of each pitch class with the simplest ratio within `ratio_tolerance`
+/// cents of it, the error of each target, and lines to paste into a tuning file.
+pub fn report(problem: &Problem, solution: &Solution, ratio_tolerance: f64) -> String {
+    let mut report = String::new();
+    let root = problem.root;
+    let conflicts: Vec<&Target> = problem
+        .targets
+        .iter()
+        .zip(&solution.errors)
+        .filter(|(target, error)| target.weight.is_none() && error.abs() > CONFLICT_CENTS)
+        .map(|(target, _)| target)
+        .collect();
+    for conflict in &conflicts {
+        writeln!(
+            report,
+            "WARN: Conflicting exact constraint: {}",
+            conflict.text
+        )
+        .unwrap();
+    }
+
+    writeln!(
+        report,
+        "Tempered tuning relative to {} (weighted RMS error {:.3}c):",
+        SEMITONE_NAMES[root],
+        solution.rms_error(problem)
+    )
+    .unwrap();
+    let pcs = (0..12).map(|i| (root + i) % 12);
+    for pc in pcs.clone() {
+        let Some(cents) = solution.cents[pc] else {
+            continue;
+        };
+        let ratio = simplest_ratio(cents, ratio_tolerance);
+        writeln!(
+            report,
+            "  {:<3}{cents:>9.3}c  ~ {ratio} ({:+.3}c)",
+            SEMITONE_NAMES[pc],
+            cents - ratio.cents().unwrap()
+        )
+        .unwrap();
+    }
+
+    writeln!(report, "\nTargets:").unwrap();
+    for (target, error) in problem.targets.iter().zip(&solution.errors) {
+        let weight = match target.weight {
+            Some(weight) => format!("weight {weight}"),
+            None => "exact".to_string(),
+        };
+        writeln!(report, "  {:<32}{error:+9.3}c  ({weight})", target.text).unwrap();
+    }
+
+    writeln!(
+        report,
+        "\nTuning lines (after `root {}`):",
+        SEMITONE_NAMES[root]
+    )
+    .unwrap();
+    for pc in pcs {
+        if let Some(cents) = solution.cents[pc] {
+            writeln!(report, "{} {cents:.3}c", SEMITONE_NAMES[pc]).unwrap();
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn equation(a: usize, b: usize, value: f64) -> Equation {
+        Equation {
+            a,
+            b,
+            value,
+            weight: 1.0,
+        }
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-4,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn consistent_system_is_solved_exactly() {
+        let equations = [
+            equation(1, 0, 2.0),
+            equation(2, 1, 3.0),
+            equation(2, 0, 5.0),
+        ];
+        assert_close(&least_squares(3, 0, &equations), &[0.0, 2.0, 5.0]);
+    }
+
+    #[test]
+    fn unconnected_pitch_class_stays_at_12edo() {
+        let equations = [equation(1, 0, 2.0)];
+        assert_close(&least_squares(3, 0, &equations), &[0.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn conflicting_exact_constraint_is_reported() {
+        let problem = parse("E == 3/2\nE == 7/5\nC# ~ 5/4", "test.targets");
+        let conflicting = report(&problem, &solve(&problem), 0.5);
+        assert!(conflicting.contains("WARN: Conflicting exact constraint: E == 7/5"));
+
+        let problem = parse("E == 3/2\nC# == 5/4\nC# ~ E * 5/6", "test.targets");
+        let consistent = report(&problem, &solve(&problem), 0.5);
+        assert!(!consistent.contains("Conflicting"));
+    }
+
+    #[test]
+    fn simplest_ratio_of_fifth() {
+        assert_eq!(simplest_ratio(701.955, 0.01), Rational::new(3, 2));
+    }
+}
@@ -0,0 +1,213 @@
+//! Accurate sleeping between the events of playback.
+//!
+//! Native sleeps overshoot by up to the resolution of the system timer: well under a millisecond on Linux & macOS, but
+//! 15.6 ms on Windows unless a finer timer period is requested with `timeBeginPeriod`, which Windows doesn't always
+//! honour (e.g. for minimized windows under power throttling). The effective resolution is measured at startup, and
+//! sleeps only sleep natively for as long as that can be trusted, spinning for the rest.
+
+use std::hint;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of 1 ms native sleeps timed to measure the timer resolution.
+const RESOLUTION_SAMPLES: u32 = 50;
+
+/// Extra time to spin for on top of the worst overshoot measured, in case of a worse one during playback.
+const SPIN_MARGIN: Duration = Duration::from_micros(500);
+
+/// Sleeps natively until shortly before the deadline, then spins. Holds the Windows timer period until dropped.
+pub struct Sleeper {
+    /// Time before the deadline to stop sleeping natively & start spinning.
+    accuracy: Duration,
+    /// Timer period in ms granted by `timeBeginPeriod`.
+    period: Option<u32>,
+}
+
+impl Sleeper {
+    /// Requests a timer period of `period` ms (Windows only, [`None`] to leave the system timer alone) for as long as
+    /// the sleeper lives, then measures & reports the effective timer resolution.
+    pub fn new(period: Option<u32>) -> Self {
+        let period = period.and_then(windows::begin_period);
+        let resolution = measure_resolution();
+        let ms = resolution.as_secs_f64() * 1000.0;
+        match period {
+            Some(period) => {
+                println!("Timer resolution: {ms:.3} ms (requested period of {period} ms)")
+            }
+            None => println!("Timer resolution: {ms:.3} ms"),
+        }
+        if period.is_some_and(|p| resolution > Duration::from_millis(p as u64 + 1)) {
+            println!("WARN: The requested timer period was not granted, spinning for longer before each event");
+        }
+
+        let accuracy = resolution + SPIN_MARGIN;
+        if accuracy > Duration::from_millis(4) {
+            println!(
+                "WARN: Spinning for up to {:.1} ms before each event, which takes a lot of CPU",
+                accuracy.as_secs_f64() * 1000.0
+            );
+        }
+        Sleeper { accuracy, period }
+    }
+
+    /// Sleeps for `duration`, or until `interrupt` is raised. Returns whether it was raised.
+    pub fn sleep(&self, duration: Duration, interrupt: &Interrupt) -> bool {
+        let deadline = Instant::now() + duration;
+        if duration > self.accuracy && interrupt.sleep(duration - self.accuracy) {
+            return true;
+        }
+        while Instant::now() < deadline {
+            hint::spin_loop();
+        }
+        false
+    }
+}
+
+/// A request to stop playback (e.g. Ctrl-C) that wakes up the sleeps between events right away, instead of being
+/// noticed once the next event is due, which may be many seconds later.
+#[derive(Default)]
+pub struct Interrupt {
+    raised: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl Interrupt {
+    /// Raises the interrupt, waking up all sleeps.
+    pub fn raise(&self) {
+        // Ignores a poisoned lock, as this may be called from a signal handler thread while playback panics.
+        if let Ok(mut raised) = self.raised.lock() {
+            *raised = true;
+        }
+        self.wake.notify_all();
+    }
+
+    pub fn is_raised(&self) -> bool {
+        *self.raised.lock().unwrap()
+    }
+
+    /// Sleeps natively for `duration`, or until the interrupt is raised. Returns whether it was raised.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let raised = self.raised.lock().unwrap();
+        let (raised, _) = self
+            .wake
+            .wait_timeout_while(raised, duration, |raised| !*raised)
+            .unwrap();
+        *raised
+    }
+}
+
+impl Drop for Sleeper {
+    fn drop(&mut self) {
+        if let Some(period) = self.period {
+            windows::end_period(period);
+        }
+    }
+}
+
+/// Scheduling statistics of the events of playback over each interval, see
+/// [`crate::server::VisualizerMessage::Timing`].
+pub struct TimingStats {
+    /// Length of the intervals, [`None`] to not keep statistics.
+    interval: Option<Duration>,
+    /// When the current interval started.
+    since: Instant,
+    /// Sum, least & most of the lags in seconds of the events in the current interval.
+    total_lag: f64,
+    least_lag: f64,
+    most_lag: f64,
+    events: u32,
+}
+
+impl TimingStats {
+    pub fn new(interval: Option<Duration>) -> Self {
+        TimingStats {
+            interval,
+            since: Instant::now(),
+            total_lag: 0.0,
+            least_lag: f64::INFINITY,
+            most_lag: f64::NEG_INFINITY,
+            events: 0,
+        }
+    }
+
+    /// Records an event that was sent `lag` seconds after it was due (negative if early).
+    pub fn record(&mut self, lag: f64) {
+        self.total_lag += lag;
+        self.least_lag = self.least_lag.min(lag);
+        self.most_lag = self.most_lag.max(lag);
+        self.events += 1;
+    }
+
+    /// Returns the average lag & worst-case jitter (spread between the least & most lag) in ms of the events in the
+    /// current interval once it is over, and starts the next one. [`None`] if it isn't over yet or had no events.
+    pub fn report(&mut self) -> Option<(f64, f64)> {
+        let interval = self.interval?;
+        if self.since.elapsed() < interval || self.events == 0 {
+            return None;
+        }
+        let report = (
+            self.total_lag / self.events as f64 * 1000.0,
+            (self.most_lag - self.least_lag) * 1000.0,
+        );
+        *self = TimingStats::new(self.interval);
+        Some(report)
+    }
+}
+
+/// Returns the worst overshoot of [`RESOLUTION_SAMPLES`] native sleeps of 1 ms.
+fn measure_resolution() -> Duration {
+    let target = Duration::from_millis(1);
+    (0..RESOLUTION_SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            thread::sleep(target);
+            start.elapsed().saturating_sub(target)
+        })
+        .max()
+        .unwrap()
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::mem::size_of;
+
+    use winapi::um::mmsystem::{TIMECAPS, TIMERR_NOERROR};
+    use winapi::um::timeapi::{timeBeginPeriod, timeEndPeriod, timeGetDevCaps};
+
+    /// Requests a timer period of `period` ms, or the finest period supported if that's coarser. Returns the period
+    /// granted.
+    pub fn begin_period(period: u32) -> Option<u32> {
+        let mut caps = TIMECAPS {
+            wPeriodMin: 0,
+            wPeriodMax: 0,
+        };
+        let res = unsafe { timeGetDevCaps(&mut caps, size_of::<TIMECAPS>() as u32) };
+        if res == TIMERR_NOERROR && caps.wPeriodMin > period {
+            println!(
+                "WARN: Finest supported timer period is {} ms",
+                caps.wPeriodMin
+            );
+        }
+        let period = period.max(caps.wPeriodMin);
+        if unsafe { timeBeginPeriod(period) } != TIMERR_NOERROR {
+            println!("WARN: Failed to request a timer period of {period} ms");
+            return None;
+        }
+        Some(period)
+    }
+
+    pub fn end_period(period: u32) {
+        unsafe { timeEndPeriod(period) };
+    }
+}
+
+/// Timer periods are only requested on Windows.
+#[cfg(not(windows))]
+mod windows {
+    pub fn begin_period(_period: u32) -> Option<u32> {
+        None
+    }
+
+    pub fn end_period(_period: u32) {}
+}
@@ -0,0 +1,211 @@
+//! MIDI outputs that schedule messages ahead of time, so that playback can send each message a lookahead before it is
+//! due instead of spin-sleeping to the exact instant: the ALSA sequencer's queues on Linux, and CoreMIDI timestamps on
+//! macOS. The OS then plays each message at its timestamp, which is both more accurate and much lighter on the CPU.
+
+use crate::output::MidiSink;
+
+/// Connects to the MIDI output port named `port_name` (as listed by midir), to be sent messages up to `lookahead`
+/// seconds ahead of time. Returns [`None`] if the port can't be scheduled on (e.g. on Windows).
+#[cfg(target_os = "linux")]
+pub fn connect(port_name: &str, lookahead: f64) -> Option<Box<dyn MidiSink>> {
+    alsa_seq::AlsaQueue::connect(port_name, lookahead).map(|q| Box::new(q) as Box<dyn MidiSink>)
+}
+
+/// Connects to the MIDI output port named `port_name` (as listed by midir), to be sent messages up to `lookahead`
+/// seconds ahead of time. Returns [`None`] if the port can't be scheduled on (e.g. on Windows).
+#[cfg(target_os = "macos")]
+pub fn connect(port_name: &str, lookahead: f64) -> Option<Box<dyn MidiSink>> {
+    core_midi::CoreMidiOutput::connect(port_name, lookahead)
+        .map(|o| Box::new(o) as Box<dyn MidiSink>)
+}
+
+/// Connects to the MIDI output port named `port_name` (as listed by midir), to be sent messages up to `lookahead`
+/// seconds ahead of time. Returns [`None`] if the port can't be scheduled on (e.g. on Windows).
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn connect(_port_name: &str, _lookahead: f64) -> Option<Box<dyn MidiSink>> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+mod alsa_seq {
+    use std::ffi::CString;
+    use std::time::Duration;
+
+    use alsa::seq::{Addr, EventType, MidiEvent, PortCap, PortSubscribe, PortType};
+    use alsa::{Direction, Seq};
+
+    use crate::output::MidiSink;
+
+    /// Output to an ALSA sequencer port through a queue of our own, with events scheduled in real time relative to
+    /// when they are sent.
+    pub struct AlsaQueue {
+        seq: Seq,
+        /// Our port that is subscribed to the destination port.
+        port: i32,
+        queue: i32,
+        encoder: MidiEvent,
+        /// Size of the encoder's buffer, i.e. the longest message it can encode.
+        buffer_size: usize,
+        lookahead: f64,
+        /// Delay of the messages sent from now on.
+        delay: Duration,
+    }
+
+    impl AlsaQueue {
+        pub fn connect(port_name: &str, lookahead: f64) -> Option<Self> {
+            // midir names ALSA ports "<client name>:<port name> <client>:<port>".
+            let (client, port) = port_name.rsplit(' ').next()?.split_once(':')?;
+            let dest = Addr {
+                client: client.parse().ok()?,
+                port: port.parse().ok()?,
+            };
+
+            let seq = Seq::open(None, Some(Direction::Playback), false)
+                .map_err(|e| println!("WARN: Failed to open ALSA sequencer: {e}"))
+                .ok()?;
+            let name = CString::new("JI Performer").unwrap();
+            seq.set_client_name(&name).unwrap();
+            let port = seq
+                .create_simple_port(
+                    &name,
+                    PortCap::READ | PortCap::SUBS_READ,
+                    PortType::MIDI_GENERIC | PortType::APPLICATION,
+                )
+                .unwrap();
+
+            let subscription = PortSubscribe::empty().unwrap();
+            subscription.set_sender(Addr {
+                client: seq.client_id().unwrap(),
+                port,
+            });
+            subscription.set_dest(dest);
+            seq.subscribe_port(&subscription).unwrap();
+
+            let queue = seq.alloc_named_queue(&name).unwrap();
+            seq.control_queue(queue, EventType::Start, 0, None).unwrap();
+            seq.drain_output().unwrap();
+
+            println!(
+                "Scheduling MIDI {:.0} ms ahead on ALSA sequencer queue {queue}",
+                lookahead * 1000.0
+            );
+            let buffer_size = 32;
+            Some(AlsaQueue {
+                seq,
+                port,
+                queue,
+                encoder: MidiEvent::new(buffer_size as u32).unwrap(),
+                buffer_size,
+                lookahead,
+                delay: Duration::ZERO,
+            })
+        }
+    }
+
+    impl MidiSink for AlsaQueue {
+        fn send(&mut self, message: &[u8]) {
+            if message.len() > self.buffer_size {
+                self.buffer_size = message.len();
+                self.encoder.resize_buffer(self.buffer_size as u32).unwrap();
+            }
+            let (_, event) = self.encoder.encode(message).unwrap();
+            let mut event = event.expect("Incomplete MIDI message");
+            event.set_source(self.port);
+            event.set_subs();
+            event.schedule_real(self.queue, true, self.delay);
+            self.seq.event_output(&mut event).unwrap();
+            self.seq.drain_output().unwrap();
+        }
+
+        fn lookahead(&self) -> f64 {
+            self.lookahead
+        }
+
+        fn timestamp(&mut self, delay: f64) {
+            self.delay = Duration::from_secs_f64(delay);
+        }
+    }
+
+    impl Drop for AlsaQueue {
+        fn drop(&mut self) {
+            let _ = self.seq.free_queue(self.queue);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod core_midi {
+    use coremidi::{Client, Destination, Destinations, OutputPort, PacketBuffer};
+
+    use crate::output::MidiSink;
+
+    #[repr(C)]
+    struct MachTimebaseInfo {
+        numer: u32,
+        denom: u32,
+    }
+
+    extern "C" {
+        fn mach_absolute_time() -> u64;
+        fn mach_timebase_info(info: *mut MachTimebaseInfo) -> i32;
+    }
+
+    /// Output to a CoreMIDI destination, with each packet timestamped in host time.
+    pub struct CoreMidiOutput {
+        // The client has to outlive the port.
+        _client: Client,
+        port: OutputPort,
+        destination: Destination,
+        /// Nanoseconds per host time unit.
+        timebase: f64,
+        lookahead: f64,
+        /// Delay of the messages sent from now on, in host time units.
+        delay: u64,
+    }
+
+    impl CoreMidiOutput {
+        pub fn connect(port_name: &str, lookahead: f64) -> Option<Self> {
+            // midir names CoreMIDI ports by the display names of the destinations.
+            let destination = (0..Destinations::count())
+                .filter_map(Destination::from_index)
+                .find(|d| d.display_name().as_deref() == Some(port_name))?;
+            let client = Client::new("JI Performer")
+                .map_err(|e| println!("WARN: Failed to create CoreMIDI client: {e}"))
+                .ok()?;
+            let port = client.output_port("JI Performer").unwrap();
+
+            let mut info = MachTimebaseInfo { numer: 0, denom: 0 };
+            unsafe { mach_timebase_info(&mut info) };
+
+            println!(
+                "Scheduling MIDI {:.0} ms ahead with CoreMIDI timestamps",
+                lookahead * 1000.0
+            );
+            Some(CoreMidiOutput {
+                _client: client,
+                port,
+                destination,
+                timebase: info.numer as f64 / info.denom as f64,
+                lookahead,
+                delay: 0,
+            })
+        }
+    }
+
+    impl MidiSink for CoreMidiOutput {
+        fn send(&mut self, message: &[u8]) {
+            let time = unsafe { mach_absolute_time() } + self.delay;
+            self.port
+                .send(&self.destination, &PacketBuffer::new(time, message))
+                .unwrap();
+        }
+
+        fn lookahead(&self) -> f64 {
+            self.lookahead
+        }
+
+        fn timestamp(&mut self, delay: f64) {
+            self.delay = (delay * 1e9 / self.timebase) as u64;
+        }
+    }
+}
@@ -1,18 +1,19 @@
 //! Tuning is implemented by separating each 12 edo pitch into one of 12 midi channels, and applying MPE-like pitch bend
 //! to each channel.
 
-use std::{collections::HashMap, fmt::Display, ops::Index};
-
-use midly::{
-    live::LiveEvent,
-    num::{u14, u4},
-    MidiMessage, PitchBend,
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    ops::{Div, Index, Mul},
 };
+
+use midly::{live::LiveEvent, num::u4, MidiMessage, PitchBend};
 use primefactor::PrimeFactors;
 use primes::{PrimeSet, Sieve};
 use rational::Rational;
 
-use crate::PB_RANGE;
+use crate::automation::AutomationLane;
+use crate::envelope::PitchEnvelope;
 
 pub static SEMITONE_NAMES: [&str; 12] = [
     "A", "Bb", "B", "C", "C#", "D", "Eb", "E", "F", "F#", "G", "G#",
@@ -32,7 +33,7 @@ lazy_static! {
     /// Keys of a hash map are not sorted.
     pub static ref PRIMES: HashMap<u32, usize> = {
         let mut pset = Sieve::new();
-        pset.iter().take(1000).enumerate().map(|(i, p)| (p as u32, i as usize)).collect()
+        pset.iter().take(1000).enumerate().map(|(i, p)| (p as u32, i)).collect()
     };
 
     /// Mapping of primes to their octaves.
@@ -48,10 +49,180 @@ lazy_static! {
     pub static ref PRIMES_OCTAVES: HashMap<u32, i32> = {
         PRIMES.keys().map(|p| (*p, (*p as f64).log2().floor() as i32)).collect()
     };
+
+    /// List of primes in increasing order, i.e. the prime corresponding to each index of a [`Monzo`].
+    pub static ref PRIMES_BY_INDEX: Vec<u32> = {
+        let mut primes = vec![0; PRIMES.len()];
+        for (p, i) in PRIMES.iter() {
+            primes[*i] = *p;
+        }
+        primes
+    };
 }
 
 pub type Monzo = Vec<i32>;
 
+/// Returns the pitch class index (0 is A, 1 is Bb, etc...) of a MIDI note number.
+pub fn pitch_class(key: u8) -> usize {
+    (key as usize + 3) % 12
+}
+
+/// Returns the name of a MIDI note with its octave number, e.g. 69 is `A4`, 61 is `C#4`.
+pub fn key_name(key: u8) -> String {
+    format!(
+        "{}{}",
+        SEMITONE_NAMES[pitch_class(key)],
+        (key as i32 / 12) - 1
+    )
+}
+
+/// Spelling of a pitch class, e.g. `Fx` rather than `G`, as written in the tuning file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Spelling {
+    /// Uppercase letter, A to G.
+    pub letter: char,
+    /// Accidentals in semitones, e.g. 2 for `x` and -1 for `b`.
+    pub accidentals: i32,
+}
+
+/// Spelling of each pitch class (0 is A), [`None`] for the default spelling of [`SEMITONE_NAMES`].
+pub type Spellings = [Option<Spelling>; 12];
+
+impl Spelling {
+    /// Parses a pitch class name as in [`parse_pitch_class`].
+    pub fn parse(name: &str) -> Option<Self> {
+        let (_, accidentals) = parse_note_letter(name)?;
+        Some(Spelling {
+            letter: name.chars().next()?.to_ascii_uppercase(),
+            accidentals,
+        })
+    }
+
+    /// Returns the semitones of the letter above A.
+    fn letter_semitones(&self) -> i32 {
+        parse_note_letter(&self.letter.to_string()).unwrap().0
+    }
+
+    /// Returns the name of a MIDI note of this pitch class with its octave number, which belongs to the letter as in
+    /// [`parse_key_name`], e.g. 72 spelt `B#` is `B#4`.
+    pub fn key_name(&self, key: u8) -> String {
+        let letter_key = key as i32 - self.accidentals;
+        let octave = (letter_key - (self.letter_semitones() + 9) % 12).div_euclid(12) - 1;
+        format!("{self}{octave}")
+    }
+}
+
+impl Display for Spelling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.letter)?;
+        if self.accidentals < 0 {
+            write!(
+                f,
+                "{}",
+                "b".repeat(self.accidentals.unsigned_abs() as usize)
+            )
+        } else {
+            if self.accidentals % 2 == 1 {
+                write!(f, "#")?;
+            }
+            write!(f, "{}", "x".repeat(self.accidentals as usize / 2))
+        }
+    }
+}
+
+/// Returns the name of pitch class `pc` (0 is A) as spelt in `spellings`, e.g. `Fx`, or from [`SEMITONE_NAMES`] if it
+/// has no spelling.
+pub fn spelled_name(spellings: &Spellings, pc: usize) -> String {
+    match spellings[pc] {
+        Some(spelling) => spelling.to_string(),
+        None => SEMITONE_NAMES[pc].to_string(),
+    }
+}
+
+/// Returns the name of a MIDI note with its octave number as spelt in `spellings`, see [`key_name`] &
+/// [`Spelling::key_name`].
+pub fn spelled_key_name(spellings: &Spellings, key: u8) -> String {
+    match spellings[pitch_class(key)] {
+        Some(spelling) => spelling.key_name(key),
+        None => key_name(key),
+    }
+}
+
+/// Parses a note name without octave number (see [`parse_pitch_class`]) into the semitones of its letter above A, and
+/// its accidentals in semitones.
+fn parse_note_letter(name: &str) -> Option<(i32, i32)> {
+    let mut chars = name.chars();
+    let letter = match chars.next()?.to_ascii_uppercase() {
+        'A' => 0,
+        'B' => 2,
+        'C' => 3,
+        'D' => 5,
+        'E' => 7,
+        'F' => 8,
+        'G' => 10,
+        _ => return None,
+    };
+    let mut accidentals = 0;
+    for c in chars {
+        accidentals += match c {
+            '#' | '♯' => 1,
+            'b' | '♭' => -1,
+            'x' => 2,
+            _ => return None,
+        };
+    }
+    Some((letter, accidentals))
+}
+
+/// Parses a pitch class name into its index, 0 being A.
+///
+/// The letter (A to G) is case insensitive and can be followed by any number of accidentals: `#` or `♯` (sharp), `b`
+/// or `♭` (flat) and `x` (double sharp), so any spelling is accepted, e.g. `D#`, `Eb` and `Fbb` are all 6.
+pub fn parse_pitch_class(name: &str) -> Option<usize> {
+    let (letter, accidentals) = parse_note_letter(name)?;
+    Some((letter + accidentals).rem_euclid(12) as usize)
+}
+
+/// Parses a note name with an optional octave number into a MIDI note number, e.g. `C#4`, `Bb3`, `Fx4` or `E#`. If
+/// the octave number is omitted, `default_octave` is used.
+///
+/// Octave numbers belong to the letter as in scientific pitch notation, so `Cb4` is `B3` & `B#3` is `C4`. Accidentals
+/// are as in [`parse_pitch_class`].
+///
+/// Returns [`None`] if the name is invalid or the note is out of the MIDI range.
+pub fn parse_key_name(name: &str, default_octave: i32) -> Option<u8> {
+    let split = name
+        .find(|c: char| c.is_ascii_digit() || c == '-')
+        .unwrap_or(name.len());
+    let (note, octave) = name.split_at(split);
+    let (letter, accidentals) = parse_note_letter(note)?;
+    let octave = if octave.is_empty() {
+        default_octave
+    } else {
+        octave.parse().ok()?
+    };
+    // Octave numbers start from C, letters are relative to A.
+    let key = 12 * (octave + 1) + (letter + 9) % 12 + accidentals;
+    u8::try_from(key).ok().filter(|k| *k <= 127)
+}
+
+/// Returns the monzo of a MIDI note relative to A4, given the monzo of its pitch class (which is relative to the next
+/// lowest A).
+pub fn key_monzo(pitch_class_monzo: &Monzo, key: u8) -> Monzo {
+    let mut monzo = pitch_class_monzo.clone();
+
+    // Monzos are relative to A4, so we need to shift the octave to match
+    let octaves_from_a4 = (key as i32 - 69).div_euclid(12);
+
+    if monzo.is_empty() {
+        monzo.push(octaves_from_a4);
+    } else {
+        monzo[0] += octaves_from_a4;
+    }
+
+    monzo
+}
+
 /// Trait for just intonation ratios.
 pub trait JIRatio {
     fn monzo(&self) -> Option<Monzo>;
@@ -62,7 +233,7 @@ impl JIRatio for Rational {
     /// Converts a rational number to monzo form. The length of the returned vector is proportional to the the prime limit
     /// of the rational.
     ///
-    /// Returns [`None`] if the rational is 0.
+    /// Returns [`None`] if the rational is 0, or has a prime beyond [`PRIMES`].
     fn monzo(&self) -> Option<Monzo> {
         if *self == 0 {
             return None;
@@ -89,9 +260,7 @@ impl JIRatio for Rational {
             let p = fac.integer;
             let exp = fac.exponent;
 
-            let p_idx = *PRIMES
-                .get(&(p as u32))
-                .expect("Prime not found in PRIMES map");
+            let p_idx = *PRIMES.get(&u32::try_from(p).ok()?)?;
             if p_idx >= monzo.len() {
                 monzo.resize(p_idx + 1, 0);
             }
@@ -110,9 +279,7 @@ impl JIRatio for Rational {
             let p = fac.integer;
             let exp = fac.exponent;
 
-            let p_idx = *PRIMES
-                .get(&(p as u32))
-                .expect("Prime not found in PRIMES map");
+            let p_idx = *PRIMES.get(&u32::try_from(p).ok()?)?;
             if p_idx >= monzo.len() {
                 monzo.resize(p_idx + 1, 0);
             }
@@ -141,41 +308,179 @@ impl JIRatio for Rational {
     }
 }
 
+/// Tuning of one of the 12 semitones in a [`TuningData`], relative to the next lowest A.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PitchSpec {
+    /// Leave the previous tuning of the semitone unchanged.
+    Keep,
+    /// JI interval.
+    Ratio(Rational),
+    /// Tempered interval in cents, for pitches that are not JI (e.g. 12edo or meantone notes).
+    Cents(f64),
+}
+
+impl PitchSpec {
+    /// Returns the interval in cents, or [`None`] for [`PitchSpec::Keep`].
+    pub fn cents(&self) -> Option<f64> {
+        match self {
+            PitchSpec::Keep => None,
+            PitchSpec::Ratio(ratio) => ratio.cents(),
+            PitchSpec::Cents(cents) => Some(*cents),
+        }
+    }
+
+    /// Returns the monzo of a JI interval, or [`None`] if it is kept or tempered.
+    pub fn monzo(&self) -> Option<Monzo> {
+        match self {
+            PitchSpec::Ratio(ratio) => ratio.monzo(),
+            _ => None,
+        }
+    }
+
+    pub fn is_keep(&self) -> bool {
+        *self == PitchSpec::Keep
+    }
+}
+
+impl From<Rational> for PitchSpec {
+    fn from(ratio: Rational) -> Self {
+        PitchSpec::Ratio(ratio)
+    }
+}
+
+/// Transposes the pitch up by a JI interval.
+impl Mul<Rational> for PitchSpec {
+    type Output = PitchSpec;
+
+    fn mul(self, interval: Rational) -> PitchSpec {
+        match self {
+            PitchSpec::Keep => PitchSpec::Keep,
+            PitchSpec::Ratio(ratio) => PitchSpec::Ratio(ratio * interval),
+            PitchSpec::Cents(cents) => PitchSpec::Cents(cents + interval.cents().unwrap()),
+        }
+    }
+}
+
+/// Transposes the pitch down by a JI interval.
+impl Div<Rational> for PitchSpec {
+    type Output = PitchSpec;
+
+    fn div(self, interval: Rational) -> PitchSpec {
+        self * (Rational::one() / interval)
+    }
+}
+
+impl Display for PitchSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PitchSpec::Keep => write!(f, "keep"),
+            PitchSpec::Ratio(ratio) => write!(f, "{ratio}"),
+            PitchSpec::Cents(cents) => write!(f, "{cents:.3}c"),
+        }
+    }
+}
+
+/// Which retunes of notes that are still sounding at a tuning change are deferred until the notes stop sounding (see
+/// [`Tuner::defer`]).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DeferPolicy {
+    /// Retune immediately.
+    Never,
+    /// Defer if the notes are only held by the sustain pedal (their keys are released), e.g. to keep the resonance of
+    /// a pedalled chord in tune, while retunes of held keys are heard.
+    Pedal,
+    /// Defer if the notes are held by their keys or the sustain pedal.
+    Sustained,
+    /// Delay the whole tuning change to the next moment when no notes are sounding and the sustain pedal is up (if
+    /// there is one soon enough), so that synths which glide on pitch bends don't audibly sweep. Notes starting before
+    /// then are played in the previous tuning.
+    Silence,
+}
+
+/// Returns the raw pitch bend message of `bend` on the channel of `semitone` (0 is A, 1 is Bb, etc...)
+pub fn pitch_bend_message(semitone: usize, bend: PitchBend) -> Vec<u8> {
+    let ev = LiveEvent::Midi {
+        channel: u4::try_from(semitone as u8).expect("Channel out of range"),
+        message: MidiMessage::PitchBend { bend },
+    };
+
+    let mut raw = vec![];
+    ev.write(&mut raw).unwrap();
+    raw
+}
+
+/// Returns the pitch bend retuning the channel of `semitone` (0 is A, 1 is Bb, etc...) to `cents` above the next
+/// lowest A, for a pitch bend range of +/- `pb_range` semitones.
+pub fn cents_pitch_bend(semitone: usize, cents: f64, pb_range: u16) -> PitchBend {
+    let cents_offset = cents - 100.0 * (semitone as f64);
+    // from -1 to 1 (where extrema is +/- pb_range semitones)
+    PitchBend::from_f64(cents_offset / 100.0 / pb_range as f64)
+}
+
 /// Represents a particular tuning config to be applied starting from a given `time`
 #[derive(Clone)]
 pub struct TuningData {
-    /// the JI tunings of each of the 12 semitones starting from A.
+    /// the tunings of each of the 12 semitones starting from A.
     ///
-    /// Each element is a [`Rational`] which denotes the JI interval tuning of the i-th semitone relative to the
-    /// next lowest A.
+    /// Each element is a [`PitchSpec`] which denotes the tuning of the i-th semitone relative to the next lowest A.
     ///
     /// E.g. if A4 = 1/1, then we can set the 8th element (fifth) to 3/2 to make E5 = 3/2 of A4.
     /// This will also make E6 3/2 of A5, E4 3/2 of A3, etc...
     ///
-    /// If the rational is 0-valued, leave the previous tuning unchanged.
-    pub tuning: [Rational; 12],
+    /// If the element is [`PitchSpec::Keep`], leave the previous tuning unchanged.
+    pub tuning: [PitchSpec; 12],
 
     /// Time to start applying this tuning config.
     pub time: f64,
 
     /// The ratios in monzo form (prime factorized to powers of primes), starting from A.
     ///
-    /// If an element is [`None`], keep the previous tuning for this semitone.
+    /// If an element is [`None`], the semitone keeps its previous tuning or is tempered.
     pub monzos: [Option<Monzo>; 12],
 
-    /// MIDI pitch bend information for each semitone starting from A.
-    ///
-    /// Don't use this directly, instead use [`midi_messages`] for pre-computed messages to send.
-    ///
-    /// This value depends on [`PB_RANGE`]. 0x2000 is 0 bend, 0x0000 is -PB_RANGE, 0x3FFF is +PB_RANGE.
-    ///
-    /// If an element is [`None`], keep the previous tuning for this semitone.
-    pitch_bends: [Option<PitchBend>; 12],
+    /// Bar number in the printed score, for messages & reports.
+    pub bar: Option<usize>,
 
-    /// Raw MIDI messages to be sent to the synth to apply the tuning, starting from channel 0.
-    ///
-    /// If an element is [`None`], keep the previous tuning for this semitone.
-    pub midi_messages: [Option<Vec<u8>>; 12],
+    /// Beat within [`TuningData::bar`], starting from 1.
+    pub beat: Option<f64>,
+
+    /// Short name of the tuning, e.g. the chord symbol.
+    pub label: Option<String>,
+
+    /// Notes about the tuning, possibly multiple lines.
+    pub comment: Option<String>,
+
+    /// Deferral of retunes of sounding notes for this tuning, overriding the default policy.
+    pub defer: Option<DeferPolicy>,
+
+    /// Expressive pitch envelopes of notes starting while this tuning is in effect.
+    pub envelopes: Vec<PitchEnvelope>,
+
+    /// Raw MIDI messages (e.g. CCs for scene changes of the synth) to be sent when this tuning is applied, after its
+    /// pitch bends.
+    pub extra_messages: Vec<Vec<u8>>,
+
+    /// Cue that playback pauses at right before this tuning in conductor mode (see [`crate::CONDUCTOR_MODE`]), e.g.
+    /// for a fermata, with a note shown while waiting (may be empty).
+    pub cue: Option<String>,
+
+    /// Semitone (0 is A) that the ratios of this tuning were given relative to, for spelling them in visualizer
+    /// messages (see [`ratio_name`]). [`None`] if not known, e.g. for deferred retunes.
+    pub root: Option<usize>,
+
+    /// Interval that the ratios of this tuning were multiplied by, see [`td`].
+    pub offset: Rational,
+
+    /// Spellings of the semitones given by this tuning, used in place of [`SEMITONE_NAMES`] from this tuning onwards.
+    /// [`None`] keeps the previous spelling.
+    pub spellings: Spellings,
+
+    /// Offsets added to the velocities of the notes of each semitone from this tuning onwards, e.g. to balance a
+    /// section for a synth patch. [`None`] keeps the previous offset.
+    pub velocity_offsets: [Option<i32>; 12],
+
+    /// Controller curves starting at this tuning, see [`crate::automation`].
+    pub automation: Vec<AutomationLane>,
 }
 
 impl TuningData {
@@ -183,82 +488,104 @@ impl TuningData {
     ///
     /// Don't use this function directly, use the [`td`] helper function instead.
     ///
-    /// `tuning` is an array of [`Rational`]s, each representing the JI tuning of the i-th semitone relative to the
-    /// next lowest A. If an element of `tuning` is 0-valued, leave the tuning for that semitone unchanged.
-    pub fn new(tuning: [Rational; 12], time: f64) -> Self {
-        let mut monzos = tuning.map(|r| r.monzo());
-        let mut pitch_bend_percents: [Option<f64>; 12] = [None; 12];
+    /// `tuning` is an array of [`PitchSpec`]s, each representing the tuning of the i-th semitone relative to the
+    /// next lowest A. If an element of `tuning` is [`PitchSpec::Keep`], leave the tuning for that semitone unchanged.
+    ///
+    /// The tuning is only validated (see [`TuningData::check`]) when it is added to a [`Tuner`], so that messages can
+    /// refer to the annotations set after creating it.
+    pub fn new(tuning: [PitchSpec; 12], time: f64) -> Self {
+        let monzos = tuning.map(|p| p.monzo());
 
-        let mut prev_cents = f64::MIN;
-        for i in 0..12 {
-            monzos[i] = tuning[i].monzo();
-
-            if let Some(cents) = tuning[i].cents() {
-                if cents < prev_cents && i >= 1 {
-                    println!(
-                        "WARN: Tuning data @ {time}s not in increasing order: {}, {}\nCheck for typos.",
-                        tuning[i-1],
-                        tuning[i]
-                    );
-                }
-                prev_cents = cents;
-                let cents_offset = cents - 100.0 * (i as f64);
-
-                // from -1 to 1 (where extrema is +/- PB_RANGE semitones)
-                let pb_range_percent = cents_offset / 100.0 / PB_RANGE as f64;
-
-                if pb_range_percent > 1.0 || pb_range_percent < -1.0 {
-                    panic!(
-                        "ERROR for Tuning data @ {time}s. \
-                    Pitch bend range ({PB_RANGE}) exceeded, unable to bend {cents_offset:.1} \
-                    cents for absolute interval {}/{} assigned to note {}.\n
-                    Check that this note is specified in correct octave.
-                    Is this a typo? Otherwise increase PB_RANGE in src/tuner.rs.",
-                        tuning[i].numerator(),
-                        tuning[i].denominator(),
-                        SEMITONE_NAMES[i],
-                    );
-                }
+        TuningData {
+            tuning,
+            time,
+            monzos,
+            bar: None,
+            beat: None,
+            label: None,
+            comment: None,
+            defer: None,
+            envelopes: vec![],
+            extra_messages: vec![],
+            cue: None,
+            root: None,
+            offset: Rational::one(),
+            spellings: [None; 12],
+            velocity_offsets: [None; 12],
+            automation: vec![],
+        }
+    }
+
+    /// Converts the tuning of `semitone` relative to [`TuningData::root`] (as in a line of a tuning file) to the tuning
+    /// relative to the next lowest A, as [`td`] does.
+    pub fn relative_to_a(&self, semitone: usize, pitch: PitchSpec) -> PitchSpec {
+        let pitch = pitch * self.offset;
+        if semitone < self.root.unwrap_or(0) {
+            pitch / Rational::new(2, 1)
+        } else {
+            pitch
+        }
+    }
 
-                pitch_bend_percents[i] = Some(pb_range_percent);
+    /// Returns the bar, beat & label of this tuning, e.g. `Bar 27:4.5: E#7b9`, or [`None`] if it has neither a bar
+    /// nor a label.
+    pub fn annotation(&self) -> Option<String> {
+        let mut annotation = String::new();
+        if let Some(bar) = self.bar {
+            annotation += &format!("Bar {bar}");
+            if let Some(beat) = self.beat {
+                annotation += &format!(":{beat}");
+            }
+        }
+        if let Some(label) = &self.label {
+            if !annotation.is_empty() {
+                annotation += ": ";
             }
+            annotation += label;
+        }
+        (!annotation.is_empty()).then_some(annotation)
+    }
+
+    /// Describes this tuning for messages, e.g. `Bar 23: D#9sus4 @ 77.268s`, or `Tuning data @ 77.268s` if it is not
+    /// annotated.
+    pub fn describe(&self) -> String {
+        match self.annotation() {
+            Some(annotation) => format!("{annotation} @ {}s", self.time),
+            None => format!("Tuning data @ {}s", self.time),
         }
+    }
 
-        let pitch_bends = pitch_bend_percents.map(|pb| {
-            if let Some(pb) = pb {
-                Some(PitchBend::from_f64(pb))
-            } else {
-                None
+    /// Warns if the ratios are not in increasing order, and panics if a ratio can't be reached within the pitch bend
+    /// range of +/- `pb_range` semitones.
+    pub fn check(&self, pb_range: u16) {
+        let mut prev_cents = f64::MIN;
+        for (i, pitch) in self.tuning.iter().enumerate() {
+            let Some(cents) = pitch.cents() else {
+                continue;
+            };
+            if cents < prev_cents && i >= 1 {
+                println!(
+                    "WARN: {} not in increasing order: {}, {}\nCheck for typos.",
+                    self.describe(),
+                    self.tuning[i - 1],
+                    pitch
+                );
             }
-        });
-
-        let midi_messages: [Option<Vec<u8>>; 12] = pitch_bends
-            .iter()
-            .enumerate()
-            .map(|(i, pb)| {
-                if let Some(pb) = pb {
-                    let ev = LiveEvent::Midi {
-                        channel: u4::try_from(i as u8).expect("Channel out of range"),
-                        message: MidiMessage::PitchBend { bend: pb.clone() },
-                    };
-
-                    let mut raw = vec![];
-                    ev.write(&mut raw).unwrap();
-                    Some(raw)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<Option<Vec<u8>>>>()
-            .try_into()
-            .unwrap();
+            prev_cents = cents;
+            let cents_offset = cents - 100.0 * (i as f64);
 
-        TuningData {
-            tuning,
-            time,
-            monzos,
-            pitch_bends,
-            midi_messages,
+            if cents_offset.abs() > 100.0 * pb_range as f64 {
+                panic!(
+                    "ERROR for {}. \
+                Pitch bend range ({pb_range}) exceeded, unable to bend {cents_offset:.1} \
+                cents for absolute interval {} assigned to note {}.\n
+                Check that this note is specified in correct octave.
+                Is this a typo? Otherwise increase the pitch bend range (--pb-range).",
+                    self.describe(),
+                    pitch,
+                    spelled_name(&self.spellings, i),
+                );
+            }
         }
     }
 }
@@ -285,24 +612,285 @@ impl Display for TuningData {
 /// - `offset` is the global interval offset applied to all elements of the tuning array.
 ///   Use 1/1 to specify no additional offset. Use this parameter to denote comma shifts.
 ///
-/// - `tuning` is an array of [`Rational`]s, each representing the JI tuning of the i-th semitone starting from
-///   `root`, building upwards the octave. If an element of `tuning` is 0-valued, leave the tuning for that semitone unchanged.
-pub fn td(time: f64, root: u8, offset: Rational, tuning: [Rational; 12]) -> TuningData {
+/// - `tuning` is an array of [`PitchSpec`]s, each representing the tuning of the i-th semitone starting from
+///   `root`, building upwards the octave. If an element of `tuning` is [`PitchSpec::Keep`], leave the tuning for that
+///   semitone unchanged.
+pub fn td(time: f64, root: u8, offset: Rational, tuning: [PitchSpec; 12]) -> TuningData {
     assert!(root < 12, "Root must be in range [0, 11]");
 
-    let mut new_tuning = [Rational::from(0); 12];
-    for i in 0..12 {
+    let mut new_tuning = [PitchSpec::Keep; 12];
+    for (i, pitch) in tuning.iter().enumerate() {
         let semitone = i + root as usize;
-        new_tuning[semitone % 12] = tuning[i] * offset;
+        new_tuning[semitone % 12] = *pitch * offset;
 
         if semitone >= 12 {
             // since tuning is specified in increasing order of pitch, when we wrap around the octave after applying
             // artificial root, we need to halve the frequency (lower an octave).
-            new_tuning[semitone % 12] /= 2;
+            new_tuning[semitone % 12] = new_tuning[semitone % 12] / Rational::new(2, 1);
+        }
+    }
+
+    TuningData {
+        root: Some(root as usize),
+        offset,
+        ..TuningData::new(new_tuning, time)
+    }
+}
+
+/// Builder for a [`TuningData`] with semitones given by name rather than by position in an array as with [`td`], so
+/// a misplaced element can't retune the wrong semitone.
+///
+/// ```ignore
+/// let tuning = TuningBuilder::new(18.448)
+///     .root("Bb")
+///     .set("Bb", Rational::one())
+///     .set_of("D", Rational::new(5, 4), "Bb")
+///     .keep("C#")
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct TuningBuilder {
+    /// Time the tuning is applied in seconds.
+    pub time: f64,
+    /// Semitone (0 is A) that the pitches are relative to.
+    pub root: usize,
+    /// Interval applied to all pitches, see [`td`].
+    pub offset: Rational,
+    /// Tunings relative to `root`, within the octave above it, indexed by semitone (0 is A).
+    pub pitches: [PitchSpec; 12],
+    /// Spellings of the semitones as named when setting them or the root.
+    pub spellings: Spellings,
+}
+
+impl TuningBuilder {
+    pub fn new(time: f64) -> Self {
+        TuningBuilder {
+            time,
+            root: 0,
+            offset: Rational::one(),
+            pitches: [PitchSpec::Keep; 12],
+            spellings: [None; 12],
+        }
+    }
+
+    pub fn root(&mut self, name: &str) -> &mut Self {
+        self.root = semitone(name);
+        self.spellings[self.root] = Spelling::parse(name);
+        self
+    }
+
+    pub fn offset(&mut self, offset: Rational) -> &mut Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Tunes the semitone `name` relative to the root.
+    pub fn set(&mut self, name: &str, pitch: impl Into<PitchSpec>) -> &mut Self {
+        self.pitches[semitone(name)] = pitch.into();
+        self.spellings[semitone(name)] = Spelling::parse(name);
+        self
+    }
+
+    /// Tunes the semitone `name` relative to the root, moved by octaves to be nearest to its 12edo pitch above the
+    /// root.
+    pub fn set_near(&mut self, name: &str, pitch: impl Into<PitchSpec>) -> &mut Self {
+        let semitone = semitone(name);
+        let pitch = pitch.into();
+        let edo_cents = 100.0 * ((semitone + 12 - self.root) % 12) as f64;
+        let octaves = ((pitch.cents().unwrap() - edo_cents) / 1200.0).round() as i32;
+        let shift = Rational::new(1i128 << octaves.unsigned_abs(), 1);
+        self.pitches[semitone] = if octaves < 0 {
+            pitch * shift
+        } else {
+            pitch / shift
+        };
+        self.spellings[semitone] = Spelling::parse(name);
+        self
+    }
+
+    /// Tunes the semitone `name` as `ratio` above the semitone `of`, which must be set already. The result is moved
+    /// by octaves as in [`TuningBuilder::set_near`].
+    pub fn set_of(&mut self, name: &str, ratio: Rational, of: &str) -> &mut Self {
+        let of_pitch = self.pitches[semitone(of)];
+        assert!(
+            !of_pitch.is_keep(),
+            "{of} must be set before tuning {name} relative to it"
+        );
+        self.set_near(name, of_pitch * ratio)
+    }
+
+    /// Keeps the previous tuning of the semitone `name`, undoing [`TuningBuilder::set`].
+    pub fn keep(&mut self, name: &str) -> &mut Self {
+        self.pitches[semitone(name)] = PitchSpec::Keep;
+        self.spellings[semitone(name)] = None;
+        self
+    }
+
+    pub fn build(&self) -> TuningData {
+        // `td` takes the tunings in order from the root upwards.
+        let mut tuning = [PitchSpec::Keep; 12];
+        for (semitone, pitch) in self.pitches.iter().enumerate() {
+            tuning[(semitone + 12 - self.root) % 12] = *pitch;
+        }
+        TuningData {
+            spellings: self.spellings,
+            ..td(self.time, self.root as u8, self.offset, tuning)
+        }
+    }
+}
+
+/// Parses a semitone name for [`TuningBuilder`] as in [`parse_pitch_class`], panicking if it is invalid.
+fn semitone(name: &str) -> usize {
+    parse_pitch_class(name).unwrap_or_else(|| panic!("Invalid semitone name: {name}"))
+}
+
+/// The complete tuning of all 12 semitones in effect from `time` onwards, with all "keep previous tuning" elements
+/// resolved.
+#[derive(Clone)]
+pub struct TuningSnapshot {
+    pub time: f64,
+    /// Tunings of each of the 12 semitones starting from A, relative to the next lowest A. Never
+    /// [`PitchSpec::Keep`].
+    pub tuning: [PitchSpec; 12],
+    /// [`TuningSnapshot::tuning`] in monzo form, [`None`] for tempered semitones.
+    pub monzos: [Option<Monzo>; 12],
+    /// Spellings of the semitones given by the latest tuning that spelt them, see [`TuningData::spellings`].
+    pub spellings: Spellings,
+    /// Velocity offsets given by the latest tuning that gave them, see [`TuningData::velocity_offsets`].
+    pub velocity_offsets: [Option<i32>; 12],
+}
+
+impl TuningSnapshot {
+    pub fn new(time: f64, tuning: [PitchSpec; 12]) -> Self {
+        assert!(
+            !tuning.iter().any(PitchSpec::is_keep),
+            "Snapshot must tune all 12 semitones"
+        );
+        TuningSnapshot {
+            time,
+            tuning,
+            monzos: tuning.map(|p| p.monzo()),
+            spellings: [None; 12],
+            velocity_offsets: [None; 12],
+        }
+    }
+
+    /// Returns the tuned pitch of a MIDI note in cents relative to A4.
+    pub fn key_cents(&self, key: u8) -> f64 {
+        let octaves_from_a4 = (key as i32 - 69).div_euclid(12);
+        self.tuning[pitch_class(key)].cents().unwrap() + 1200.0 * octaves_from_a4 as f64
+    }
+}
+
+/// The tuning in effect during playback of a range of the keyboard, as given by its tuning changes so far.
+#[derive(Clone)]
+pub struct CurrentTuning {
+    /// Tunings of each of the 12 semitones starting from A, 1/1 until a tuning change tunes them.
+    pub tuning: [PitchSpec; 12],
+    /// [`CurrentTuning::tuning`] in monzo form, memoized so that notes don't factorize their ratios. [`None`] for
+    /// tempered semitones.
+    pub monzos: [Option<Monzo>; 12],
+    /// Semitone that the ratios of the tuning are relative to, for spelling them in visualizer messages.
+    pub root: usize,
+    /// Spellings of the semitones given by the tuning changes so far, for naming notes.
+    pub spellings: Spellings,
+    /// Velocity offsets of the semitones given by the tuning changes so far.
+    pub velocity_offsets: [Option<i32>; 12],
+}
+
+impl Default for CurrentTuning {
+    fn default() -> Self {
+        let tuning = [PitchSpec::Ratio(Rational::one()); 12];
+        CurrentTuning {
+            tuning,
+            monzos: tuning.map(|p| p.monzo()),
+            root: 0,
+            spellings: [None; 12],
+            velocity_offsets: [None; 12],
+        }
+    }
+}
+
+impl CurrentTuning {
+    /// Applies a tuning change, keeping the pitches, spellings & velocity offsets of the semitones it doesn't give.
+    pub fn apply(&mut self, tuning_data: &TuningData) {
+        for (i, pitch) in tuning_data.tuning.iter().enumerate() {
+            if !pitch.is_keep() {
+                self.tuning[i] = *pitch;
+                self.monzos[i] = tuning_data.monzos[i].clone();
+            }
+        }
+        self.root = tuning_data.root.unwrap_or(self.root);
+        update_given(&mut self.spellings, &tuning_data.spellings);
+        update_given(&mut self.velocity_offsets, &tuning_data.velocity_offsets);
+    }
+
+    /// Returns the spelling of each semitone relative to the root with its cents from 12edo, for
+    /// [`crate::server::VisualizerMessage::Tuning`].
+    pub fn pitches(&self) -> Vec<(String, f64)> {
+        (0..12)
+            .map(|pc| {
+                (
+                    ratio_name(&self.tuning, &self.spellings, pc, self.root),
+                    cents_from_12edo(&self.tuning, pc),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Updates `curr` with the values a tuning gives for some semitones (e.g. [`TuningData::spellings`]), keeping the
+/// previous values of the others.
+fn update_given<T: Copy>(curr: &mut [Option<T>; 12], given: &[Option<T>; 12]) {
+    for (curr, given) in curr.iter_mut().zip(given) {
+        if given.is_some() {
+            *curr = *given;
         }
     }
+}
+
+/// Spells the tuning of `semitone` relative to `root` (0 is A) in `tuning`, within the octave above the root, e.g.
+/// `7/4 of D#`, or in cents above the root if either of them is tempered, e.g. `603.9c above D#`. The root is named as
+/// spelt in `spellings`.
+pub fn ratio_name(
+    tuning: &[PitchSpec; 12],
+    spellings: &Spellings,
+    semitone: usize,
+    root: usize,
+) -> String {
+    let root_name = spelled_name(spellings, root);
+    if let (PitchSpec::Ratio(pitch), PitchSpec::Ratio(root_pitch)) =
+        (tuning[semitone], tuning[root])
+    {
+        let mut ratio = pitch / root_pitch;
+        while ratio < Rational::one() {
+            ratio *= Rational::new(2, 1);
+        }
+        while ratio >= Rational::new(2, 1) {
+            ratio /= Rational::new(2, 1);
+        }
+        return format!("{ratio} of {root_name}");
+    }
+    let cents =
+        (tuning[semitone].cents().unwrap() - tuning[root].cents().unwrap()).rem_euclid(1200.0);
+    format!("{cents:.1}c above {root_name}")
+}
 
-    TuningData::new(new_tuning, time)
+/// Returns how many cents `semitone` (0 is A) of `tuning` is above its 12edo pitch.
+pub fn cents_from_12edo(tuning: &[PitchSpec; 12], semitone: usize) -> f64 {
+    tuning[semitone].cents().unwrap() - 100.0 * semitone as f64
+}
+
+/// Returns the snapshot in effect at `time`, or [`None`] if `time` is before the first snapshot.
+///
+/// `snapshots` must be sorted by time, as returned by [`Tuner::snapshots`].
+pub fn snapshot_at(snapshots: &[TuningSnapshot], time: f64) -> Option<&TuningSnapshot> {
+    let idx = snapshots.partition_point(|s| s.time <= time);
+    if idx == 0 {
+        None
+    } else {
+        Some(&snapshots[idx - 1])
+    }
 }
 
 pub struct Tuner {
@@ -312,25 +900,36 @@ pub struct Tuner {
     /// List of tunings to be applied at given times.
     /// This must be sorted by increasing time.
     tunings: Vec<TuningData>,
+
+    /// Pitch bend range in +/- semitones that the tunings are checked against.
+    pb_range: u16,
 }
 
 impl Tuner {
-    pub fn new(tunings: Vec<TuningData>) -> Self {
+    pub fn new(tunings: Vec<TuningData>, pb_range: u16) -> Self {
         let mut curr_time = 0.0;
         let mut sorted_tunings = tunings.clone();
 
-        assert!(tunings.len() >= 1, "Must have at least one tuning!");
+        assert!(!tunings.is_empty(), "Must have at least one tuning!");
 
-        if tunings[0].tuning.iter().any(|x| *x == Rational::zero()) {
-            panic!("First tuning data cannot use 0-value elements! (No way to reference a previous tuning of this semitone)");
+        if tunings[0].tuning.iter().any(PitchSpec::is_keep) {
+            panic!(
+                "First tuning data ({}) cannot keep previous tunings! (No way to reference a previous tuning of this semitone)",
+                tunings[0].describe()
+            );
+        }
+
+        for td in &tunings {
+            td.check(pb_range);
         }
 
         for td in &tunings {
             assert!(td.time >= 0.0, "Tuning time must be non-negative");
             if td.time < curr_time {
                 println!(
-                    "WARN: Tuning data not sorted by increasing time: {}",
-                    td.to_string()
+                    "WARN: {} not sorted by increasing time: {}",
+                    td.describe(),
+                    td
                 );
                 println!("Check for typo errors. Sorting automatically now...");
                 sorted_tunings.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
@@ -342,9 +941,15 @@ impl Tuner {
         Tuner {
             curr_tuning_idx: -1,
             tunings: sorted_tunings,
+            pb_range,
         }
     }
 
+    /// Pitch bend range in +/- semitones that the tunings were checked against.
+    pub fn pb_range(&self) -> u16 {
+        self.pb_range
+    }
+
     /// Query the tuner with the current playback time. If a new tuning is to be applied.
     ///
     /// Returns the new [`TuningData`] to be applied, otherwise, returns [`None`].
@@ -355,6 +960,7 @@ impl Tuner {
                 self.curr_tuning_idx += 1;
                 return Some(&self.tunings[0]);
             }
+            return None;
         }
 
         let curr_t_idx = self.curr_tuning_idx as usize;
@@ -376,18 +982,240 @@ impl Tuner {
         None
     }
 
+    /// Jumps to the tuning in effect at `time`, so that the next call to [`Tuner::update`] returns the tuning after
+    /// it.
+    ///
+    /// Returns the tuning in effect at `time` merged with all previous tunings (so that it tunes all 12 semitones, with
+    /// the spellings & velocity offsets in effect), with its extra MIDI messages, or [`None`] if `time` is before the
+    /// first tuning.
+    pub fn seek(&mut self, time: f64) -> Option<TuningData> {
+        let idx = self.index_at(time);
+        self.curr_tuning_idx = idx.map_or(-1, |idx| idx as isize);
+        let idx = idx?;
+        let current = &self.tunings[idx];
+        let snapshot = &self.snapshots()[idx];
+        Some(TuningData {
+            bar: current.bar,
+            beat: current.beat,
+            label: current.label.clone(),
+            comment: current.comment.clone(),
+            extra_messages: current.extra_messages.clone(),
+            cue: current.cue.clone(),
+            root: current.root,
+            offset: current.offset,
+            spellings: snapshot.spellings,
+            velocity_offsets: snapshot.velocity_offsets,
+            ..TuningData::new(snapshot.tuning, current.time)
+        })
+    }
+
     pub fn len(&self) -> usize {
         self.tunings.len()
     }
 
+    /// Returns the index of the tuning in effect, [`None`] before the first.
+    pub fn current_index(&self) -> Option<usize> {
+        usize::try_from(self.curr_tuning_idx).ok()
+    }
+
+    /// Returns the index of the tuning in effect at `time`, [`None`] before the first.
+    pub fn index_at(&self, time: f64) -> Option<usize> {
+        self.tunings
+            .partition_point(|td| td.time <= time)
+            .checked_sub(1)
+    }
+
+    /// Replaces every pitch with the nearest step of `edo` equal divisions of the octave above A, e.g. to compare a JI
+    /// interpretation with its 31edo approximation. The monzos are kept, so the visualizer still shows the JI
+    /// interpretation being approximated. Must be called before playback starts.
+    pub fn quantize(&mut self, edo: u32) {
+        let step = 1200.0 / edo as f64;
+        for td in &mut self.tunings {
+            let tuning = td.tuning.map(|pitch| match pitch.cents() {
+                Some(cents) => PitchSpec::Cents((cents / step).round() * step),
+                None => PitchSpec::Keep,
+            });
+            td.tuning = tuning;
+        }
+    }
+
+    /// Replaces every pitch with `adjust(time, semitone, pitch)`, given the time the pitch is applied at, e.g. to
+    /// combine the layers of [`crate::layers`] with the timeline. The adjustment may also change at `times`, where the
+    /// semitones it retunes are applied as tuning data of their own (or with the tuning data at the same time). Monzos
+    /// of pitches made tempered by the adjustment are kept, as in [`Tuner::quantize`]. Must be called before playback
+    /// starts.
+    pub fn layer(&mut self, times: &[f64], adjust: impl Fn(f64, usize, PitchSpec) -> PitchSpec) {
+        let snapshots = self.snapshots();
+        let first = self.tunings[0].time;
+        let mut points: Vec<f64> = self.tunings.iter().map(|td| td.time).collect();
+        points.extend(times.iter().map(|time| time.max(first)));
+        points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        points.dedup();
+
+        let mut layered = Vec::with_capacity(points.len());
+        let mut base = std::mem::take(&mut self.tunings).into_iter().peekable();
+        let mut prev_time = None;
+        for time in points {
+            let mut at_time = vec![];
+            while let Some(td) = base.next_if(|td| td.time == time) {
+                at_time.push(td);
+            }
+            let inserted = at_time.is_empty();
+            if inserted {
+                let mut td = TuningData::new([PitchSpec::Keep; 12], time);
+                td.comment = Some("Retuned by tuning layers".to_string());
+                at_time.push(td);
+            }
+            for td in &mut at_time {
+                let tuning: [PitchSpec; 12] = std::array::from_fn(|i| match td.tuning[i] {
+                    PitchSpec::Keep => PitchSpec::Keep,
+                    pitch => adjust(time, i, pitch),
+                });
+                td.monzos = std::array::from_fn(|i| tuning[i].monzo().or(td.monzos[i].take()));
+                td.tuning = tuning;
+            }
+            // Semitones kept by the timeline but retuned by a change of the adjustment are applied with the last
+            // tuning data at the time, after which the snapshot is in effect.
+            let snapshot = snapshot_at(&snapshots, time).unwrap();
+            let last = at_time.last_mut().unwrap();
+            for i in 0..12 {
+                let pitch = snapshot.tuning[i];
+                let changed =
+                    prev_time.is_some_and(|prev| adjust(prev, i, pitch) != adjust(time, i, pitch));
+                if last.tuning[i].is_keep() && changed {
+                    last.tuning[i] = adjust(time, i, pitch);
+                    last.monzos[i] = last.tuning[i].monzo().or(snapshot.monzos[i].clone());
+                }
+            }
+            if !inserted || !last.tuning.iter().all(PitchSpec::is_keep) {
+                layered.extend(at_time);
+            }
+            prev_time = Some(time);
+        }
+        for td in &layered {
+            td.check(self.pb_range);
+        }
+        self.tunings = layered;
+    }
+
+    /// Splits the tuning data at index `idx` so that the retuning of `semitone` (0 is A, 1 is Bb, etc...) is applied
+    /// later at `time` instead, while the other semitones are still retuned at the original time.
+    ///
+    /// Indices of tuning data after `time` will be shifted by one. Must be called before playback starts.
+    pub fn defer(&mut self, idx: usize, semitone: usize, time: f64) {
+        let original = &self.tunings[idx];
+        assert!(idx > 0, "Cannot defer the first tuning data");
+        assert!(
+            time >= original.time,
+            "Cannot defer tuning to an earlier time"
+        );
+
+        let mut kept = original.tuning;
+        let mut deferred = [PitchSpec::Keep; 12];
+        deferred[semitone] = kept[semitone];
+        kept[semitone] = PitchSpec::Keep;
+
+        let mut kept_spellings = original.spellings;
+        kept_spellings[semitone] = None;
+
+        let mut deferred = TuningData::new(deferred, time);
+        deferred.spellings[semitone] = original.spellings[semitone];
+        deferred.comment = Some(format!(
+            "{} retune deferred from {}",
+            spelled_name(&original.spellings, semitone),
+            original.describe()
+        ));
+        let original = original.clone();
+        self.tunings[idx] = TuningData {
+            bar: original.bar,
+            beat: original.beat,
+            label: original.label,
+            comment: original.comment,
+            defer: original.defer,
+            envelopes: original.envelopes,
+            extra_messages: original.extra_messages,
+            cue: original.cue,
+            root: original.root,
+            offset: original.offset,
+            spellings: kept_spellings,
+            velocity_offsets: original.velocity_offsets,
+            automation: original.automation,
+            ..TuningData::new(kept, original.time)
+        };
+        let insert_idx = self.tunings.partition_point(|td| td.time <= time);
+        self.tunings.insert(insert_idx, deferred);
+    }
+
+    /// Delays the tuning data at index `idx` to `time`, which must be before the next tuning data.
+    ///
+    /// Must be called before playback starts.
+    pub fn delay(&mut self, idx: usize, time: f64) {
+        assert!(
+            time >= self.tunings[idx].time,
+            "Cannot delay tuning to an earlier time"
+        );
+        assert!(
+            self.tunings
+                .get(idx + 1)
+                .is_none_or(|next| time < next.time),
+            "Cannot delay tuning past the next tuning"
+        );
+        self.tunings[idx].time = time;
+    }
+
+    /// Moves the tuning data at `time` to `to` during playback, unless it has already been applied. It stays between the
+    /// tuning data before & after it. Returns the time it was moved to, or [`None`] if there is no such tuning data.
+    pub fn nudge(&mut self, time: f64, to: f64) -> Option<f64> {
+        let idx = self.tunings.iter().position(|td| td.time == time)?;
+        if idx as isize <= self.curr_tuning_idx {
+            return None;
+        }
+        let prev = idx.checked_sub(1).map_or(0.0, |i| self.tunings[i].time);
+        let next = self
+            .tunings
+            .get(idx + 1)
+            .map_or(f64::INFINITY, |td| td.time);
+        self.tunings[idx].time = to.clamp(prev, next);
+        Some(self.tunings[idx].time)
+    }
+
+    /// Returns the complete tuning in effect after each tuning change, in order of time.
+    pub fn snapshots(&self) -> Vec<TuningSnapshot> {
+        let mut snapshots: Vec<TuningSnapshot> = Vec::with_capacity(self.tunings.len());
+
+        for td in &self.tunings {
+            // The first tuning data is guaranteed to keep no previous tunings.
+            let mut snapshot = match snapshots.last() {
+                Some(prev) => prev.clone(),
+                None => TuningSnapshot::new(td.time, td.tuning),
+            };
+            snapshot.time = td.time;
+            for i in 0..12 {
+                if !td.tuning[i].is_keep() {
+                    snapshot.tuning[i] = td.tuning[i];
+                    snapshot.monzos[i] = td.monzos[i].clone();
+                }
+                if td.spellings[i].is_some() {
+                    snapshot.spellings[i] = td.spellings[i];
+                }
+                if td.velocity_offsets[i].is_some() {
+                    snapshot.velocity_offsets[i] = td.velocity_offsets[i];
+                }
+            }
+            snapshots.push(snapshot);
+        }
+
+        snapshots
+    }
+
     /// Prints the tunings as semicolon separated values "CSV"
     ///
     /// Copy and paste & import into some spreadsheet softwares and use ; as delimiter.
     pub fn print_csv(&self) {
-        println!("time;A;Bb;B;C;C#;D;D#;E;F;F#;G;G#;A pf;Bb pf;B pf;C pf;C# pf;D pf;D# pf;E pf;F pf;F# pf;G pf;G# pf");
+        println!("time;A;Bb;B;C;C#;D;D#;E;F;F#;G;G#;A pf;Bb pf;B pf;C pf;C# pf;D pf;D# pf;E pf;F pf;F# pf;G pf;G# pf;annotation");
         for t in &self.tunings {
             println!(
-                "{};{};{};{};{};{};{};{};{};{};{};{};{};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?}",
+                "{};{};{};{};{};{};{};{};{};{};{};{};{};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{:?};{}",
                 t.time,
                 t.tuning[0],
                 t.tuning[1],
@@ -401,18 +1229,19 @@ impl Tuner {
                 t.tuning[9],
                 t.tuning[10],
                 t.tuning[11],
-                t.tuning[0].monzo(),
-                t.tuning[1].monzo(),
-                t.tuning[2].monzo(),
-                t.tuning[3].monzo(),
-                t.tuning[4].monzo(),
-                t.tuning[5].monzo(),
-                t.tuning[6].monzo(),
-                t.tuning[7].monzo(),
-                t.tuning[8].monzo(),
-                t.tuning[9].monzo(),
-                t.tuning[10].monzo(),
-                t.tuning[11].monzo(),
+                t.monzos[0],
+                t.monzos[1],
+                t.monzos[2],
+                t.monzos[3],
+                t.monzos[4],
+                t.monzos[5],
+                t.monzos[6],
+                t.monzos[7],
+                t.monzos[8],
+                t.monzos[9],
+                t.monzos[10],
+                t.monzos[11],
+                t.annotation().unwrap_or_default(),
             );
         }
     }
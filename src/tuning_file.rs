@@ -0,0 +1,1343 @@
+//! Loader for tuning timelines stored as `.tuning` files, so that the tunings of a piece are data shipped alongside its
+//! MIDI file rather than code.
+//!
+//! The format is line based. Lines starting with `#` are ignored, every other line is a field name followed by its
+//! value:
+//!
+//! ```text
+//! root C#          Pitch class that the ratios of a tuning are relative to (default A).
+//! offset 5/4       Interval multiplied to all ratios of a tuning (default 1/1), e.g. to denote comma shifts.
+//! subgroup 2.3.5.7.11.13.19
+//!                  Optional JI subgroup of the piece (before the first tuning). Every ratio tuned by the file,
+//!                  times `offset`, must only use these primes, to catch stray primes from arithmetic mistakes.
+//!
+//! tuning 18.448    Starts a new tuning, applied at the given time in seconds.
+//! bar 5            Optional bar number & beat (starting from 1) of the tuning in the printed score, and a short
+//! beat 1           label, shown in messages & reports as e.g. "Bar 5:1: A# harm 7".
+//! label A# harm 7
+//! comment (A#, E# common)
+//!                  Notes about the tuning. Repeat for multiple lines.
+//! defer pedal      Whether retunes of notes still sounding at this tuning are deferred until they stop sounding:
+//!                  `never`, `pedal` (only notes held by the sustain pedal), `sustained` (held by keys or the
+//!                  pedal) or `silence` (the whole tuning waits for no notes to sound and the pedal to be up).
+//!                  Defaults to `DEFER_SUSTAINED_RETUNES` in `main.rs`.
+//! root Bb          `root` & `offset` after `tuning` only apply to this tuning. Before the first `tuning`,
+//!                  they set the defaults of all tunings.
+//! D 25/24          Tuning of a pitch class relative to `root`, within the octave above `root`. Pitch classes that
+//!                  are not listed keep their previous tuning (the first tuning must list all 12).
+//! Eb 603.9c        Tempered tuning of a pitch class in cents above `root`, for notes that are not JI.
+//! E 5/4 of C#      Tuning of a pitch class as a ratio above another pitch class tuned earlier in the same tuning, in
+//!                  the octave nearest its 12edo pitch.
+//! C# keep          Keeps the previous tuning of a pitch class, e.g. to undo a line of the tuning in a variant.
+//! envelope C#5 scoop -10c 0.15
+//! envelope E vibrato 8c 5.5
+//!                  Expressive pitch envelope of the notes of a pitch class (or a single key, e.g. `C#5`) starting
+//!                  while this tuning is in effect: `scoop` starts the given cents off the tuned pitch and glides to
+//!                  it in the given seconds, `vibrato` goes up to the given cents above & below it at the given rate
+//!                  in Hz until the note stops sounding. See [`crate::envelope`].
+//! cue fermata      Playback pauses right before this tuning in conductor mode (see `CONDUCTOR_MODE` in `main.rs`)
+//!                  until it is advanced, e.g. at a fermata or between movements. The text is optional, and shown
+//!                  while waiting.
+//! cc 1 64          MIDI messages sent when this tuning is applied, e.g. for synth-side scene changes: a control
+//! midi C0 05       change (controller & value) on channel 1, or on all tuned channels for pedals, or any raw MIDI
+//!                  message in hex bytes.
+//! velocity -10     Offset added to the velocities of the notes (of all pitch classes, or of one e.g. `velocity
+//! velocity C# +6   C# +6`) from this tuning onwards, until changed by a later `velocity` line, to balance a section
+//!                  for a synth patch.
+//! volume 100       Volume (CC 7) & expression (CC 11) sent when this tuning is applied, on the channels of all
+//! expression G# 90 pitch classes, or of one.
+//! automation expression 0:40 1.5:100 3:80
+//!                  Curve of a controller (a CC number, or `modulation`, `breath`, `volume`, `expression`,
+//!                  `sustain`, `sostenuto` or `soft`) from this tuning on, with breakpoints of seconds after the
+//!                  tuning & CC value, interpolated linearly during playback. See [`crate::automation`].
+//! anchor F# = G# * 8/9
+//!                  Re-roots the following pitch classes of this tuning on F#, tuned as 8/9 of the G# in effect
+//!                  (previous tunings & lines above). The ratios after it are relative to F# in any octave, e.g.
+//!                  `F# 1/1`, `A 7/6`. The right side is as in `expect`, `anchor F#` keeps F# as it is. Must come
+//!                  after `root` & `offset`.
+//! expect G# == D * 7/5 -- tritone
+//!                  Sanity check of the tuning in effect after this tuning is applied, modulo octaves. The
+//!                  right side is a ratio relative to `root`, a pitch class, or a pitch class times a ratio.
+//!                  Text after `--` is shown when the expectation fails.
+//!
+//! variant 19-16    Lines up to `end` only apply if the variant is selected (see [`parse`]), overriding the pitch
+//! D 19/16          classes, `root` or `offset` of the tuning they are in. Use variants to keep alternative
+//! end              tunings of a passage in the file for A/B comparisons.
+//! ```
+//!
+//! Pitch classes can be spelt with any accidentals, e.g. `E#`, `Fb` or `Gx` (see [`parse_pitch_class`]). The spelling
+//! of a pitch class (or root) is kept from its tuning onwards to name its notes in debug printing, reports and
+//! visualizer messages (see [`crate::tuner::Spelling`]). See `ondine.tuning` for an example.
+//!
+//! Files are validated against the grammar published in the README (see `Tuning files`), which is kept in sync with
+//! [`FIELDS`]. Invalid lines are all reported at once when the file is loaded, with their line & column and the expected
+//! syntax, along with suggestions for typos of field names and for ratios beyond the pitch bend range (e.g. `E 15/88`).
+//! With `LENIENT_TUNING_FILES` in `main.rs`, pitch classes that can't be tuned (beyond the pitch bend range, or with a
+//! prime beyond [`PRIMES`]) are skipped with a warning instead, keeping their previous tuning. With
+//! `OCTAVE_REDUCE_TUNINGS`, ratios beyond the pitch bend range that are in range an octave or more away are moved there
+//! with a warning.
+
+use std::fs;
+
+use midly::live::LiveEvent;
+use primefactor::PrimeFactors;
+use rational::Rational;
+
+use crate::automation::{self, AutomationLane};
+use crate::envelope::{EnvelopeShape, PitchEnvelope};
+use crate::score::{CC_EXPRESSION, CC_VOLUME, PEDAL_CCS};
+use crate::tuner::{
+    parse_key_name, parse_pitch_class, pitch_class, DeferPolicy, PitchSpec, Tuner, TuningBuilder,
+    TuningData, PRIMES, SEMITONE_NAMES,
+};
+use crate::{LENIENT_TUNING_FILES, OCTAVE_REDUCE_TUNINGS};
+
+/// Expectations involving tempered pitches pass if they are within this many cents.
+const TEMPERED_TOLERANCE_CENTS: f64 = 0.001;
+
+/// Syntax of each field, shown in errors for invalid values. Pitch classes (`D 25/24`) are not listed.
+const FIELDS: &[(&str, &str)] = &[
+    ("root", "root <pitch class>"),
+    ("offset", "offset <ratio>"),
+    ("subgroup", "subgroup <prime>.<prime>..."),
+    ("tuning", "tuning <seconds>"),
+    ("bar", "bar <number>"),
+    ("beat", "beat <beat, from 1>"),
+    ("label", "label <text>"),
+    ("comment", "comment <text>"),
+    ("defer", "defer never|pedal|sustained|silence"),
+    (
+        "envelope",
+        "envelope <pitch class or key> scoop|vibrato <cents>c <seconds or Hz>",
+    ),
+    ("cue", "cue [<text>]"),
+    ("cc", "cc <controller> <value>"),
+    ("midi", "midi <hex bytes>"),
+    ("velocity", "velocity [<pitch class>] <offset>"),
+    ("volume", "volume [<pitch class>] <value>"),
+    ("expression", "expression [<pitch class>] <value>"),
+    (
+        "automation",
+        "automation <controller> <seconds>:<value> [<seconds>:<value>...]",
+    ),
+    (
+        "anchor",
+        "anchor <pitch class> [= <pitch class> [* <ratio>] | <ratio>]",
+    ),
+    (
+        "expect",
+        "expect <pitch class> == [<pitch class> *] <ratio> [-- <message>]",
+    ),
+    ("variant", "variant <name>"),
+    ("end", "end"),
+];
+
+/// Syntax of pitch class fields, e.g. `D 25/24`.
+const PITCH_SYNTAX: &str = "<pitch class> <ratio>|<cents>c|<ratio> of <pitch class>|keep";
+
+struct Entry {
+    tuning: TuningBuilder,
+    /// Index of the `tuning` line.
+    line: usize,
+    /// Lines that tune each pitch class with a ratio or cents, checked against the pitch bend range once the tuning is
+    /// complete.
+    pitch_lines: [Option<PitchLine>; 12],
+    /// Ratio relative to `root` that pitch classes after an `anchor` line are relative to.
+    anchor: Option<Rational>,
+    bar: Option<usize>,
+    beat: Option<f64>,
+    label: Option<String>,
+    comment: Option<String>,
+    defer: Option<DeferPolicy>,
+    envelopes: Vec<PitchEnvelope>,
+    extra_messages: Vec<Vec<u8>>,
+    cue: Option<String>,
+    expectations: Vec<Expectation>,
+    velocity_offsets: [Option<i32>; 12],
+    automation: Vec<AutomationLane>,
+}
+
+/// `<pc> <ratio or cents>`
+#[derive(Clone, Copy)]
+struct PitchLine {
+    /// Line index & column of the ratio or cents.
+    line: usize,
+    column: usize,
+    /// Anchor of the tuning when the line was parsed.
+    anchor: Option<Rational>,
+}
+
+/// `expect <pc> == [<relative_to> *] <ratio>`
+struct Expectation {
+    /// Line number & contents for error messages.
+    line: usize,
+    text: String,
+    pc: usize,
+    relative_to: Option<usize>,
+    ratio: Rational,
+}
+
+impl Entry {
+    /// Converts the tuning of `pc` relative to the next lowest A to the tuning relative to `root`, i.e. the inverse of
+    /// what [`crate::tuner::td`] does.
+    fn relative_to_root(&self, pc: usize, pitch: PitchSpec) -> PitchSpec {
+        let TuningBuilder { root, offset, .. } = self.tuning;
+        if pc < root {
+            pitch / offset * Rational::new(2, 1)
+        } else {
+            pitch / offset
+        }
+    }
+}
+
+/// Loads the tuning file at `path` with the given variants selected, for a pitch bend range of +/- `pb_range`
+/// semitones. Returns every invalid line if there are any (see [`parse`]), or the error reading the file.
+pub fn load(path: &str, variants: &[String], pb_range: u16) -> Result<Tuner, Vec<String>> {
+    let (_, tunings) = read(path, variants, pb_range)?;
+    Ok(Tuner::new(tunings, pb_range))
+}
+
+/// Like [`load`], but returns the contents of the file with its tunings in order of appearance, for rewriting it.
+pub fn read(
+    path: &str,
+    variants: &[String],
+    pb_range: u16,
+) -> Result<(String, Vec<TuningData>), Vec<String>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| vec![format!("{path}: Failed to read tuning file: {e}")])?;
+    let tunings = parse(&text, path, variants, pb_range)?;
+    Ok((text, tunings))
+}
+
+/// Returns the names of the variants declared in the contents of a tuning file, in order of their first block.
+pub fn variants(text: &str) -> Vec<String> {
+    let mut variants: Vec<String> = vec![];
+    for line in text.lines() {
+        if let Some(("variant", name)) = line.trim().split_once(char::is_whitespace) {
+            let name = name.trim();
+            if !variants.iter().any(|v| v == name) {
+                variants.push(name.to_string());
+            }
+        }
+    }
+    variants
+}
+
+/// Parses the contents of a tuning file, applying the `variant` blocks named in `variants` and skipping all others.
+/// `path` is only used in error messages.
+///
+/// Returns all invalid lines if there are any, each with its line & column, the expected syntax of the field and a
+/// suggested fix where there is an obvious one, e.g. for a typo of a field name or a ratio beyond the pitch bend range
+/// of +/- `pb_range` semitones. Failed expectations (see `expect`) are returned along with them. Print them with
+/// [`report_errors`].
+pub fn parse(
+    text: &str,
+    path: &str,
+    variants: &[String],
+    pb_range: u16,
+) -> Result<Vec<TuningData>, Vec<String>> {
+    // Root & offset set before the first tuning.
+    let mut defaults = TuningBuilder::new(0.0);
+    let mut entries: Vec<Entry> = vec![];
+    // Tuning relative to A after the entries before the last one.
+    let mut current = [PitchSpec::Keep; 12];
+    let mut declared_variants: Vec<&str> = vec![];
+    // Whether the variant block the current line is in (if any) is selected.
+    let mut in_variant: Option<bool> = None;
+    // Primes of the declared subgroup, if any.
+    let mut subgroup: Option<Vec<u128>> = None;
+    let mut errors: Vec<String> = vec![];
+
+    for (line_idx, raw_line) in text.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (field, value) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(field, value)| (field, value.trim()));
+        // Columns (from 1) of the field & value in the line.
+        let field_column = raw_line.len() - raw_line.trim_start().len() + 1;
+        let value_column = field_column + line.len() - line[field.len()..].trim_start().len();
+        let field_error =
+            |msg: &str| -> String { line_error(path, line_idx, field_column, raw_line, msg) };
+        let error =
+            |msg: &str| -> String { line_error(path, line_idx, value_column, raw_line, msg) };
+        // Error for a value that doesn't match the syntax of the field.
+        let invalid = |what: &str| -> String {
+            match syntax(field) {
+                Some(syntax) => error(&format!("Invalid {what}, expected `{syntax}`")),
+                None => error(&format!("Invalid {what}")),
+            }
+        };
+
+        let result = (|| -> Result<(), String> {
+            match field {
+                "variant" => {
+                    if in_variant.is_some() {
+                        return Err(field_error("Variants can't be nested"));
+                    }
+                    if value.is_empty() || value.contains(char::is_whitespace) {
+                        return Err(invalid("variant name"));
+                    }
+                    if !declared_variants.contains(&value) {
+                        declared_variants.push(value);
+                    }
+                    in_variant = Some(variants.iter().any(|v| v == value));
+                    return Ok(());
+                }
+                "end" => {
+                    if in_variant.take().is_none() {
+                        return Err(field_error("end outside of a variant"));
+                    }
+                    return Ok(());
+                }
+                "tuning" if in_variant.is_some() => {
+                    return Err(field_error(
+                        "Tunings can't be started inside a variant, close it with `end` first",
+                    ));
+                }
+                _ if in_variant == Some(false) => return Ok(()),
+                _ => {}
+            }
+
+            match field {
+                "tuning" => {
+                    let time = value
+                        .parse()
+                        .ok()
+                        .filter(|t: &f64| *t >= 0.0)
+                        .ok_or_else(|| invalid("time"))?;
+                    if let Some(entry) = entries.last() {
+                        for (pc, pitch) in entry.tuning.build().tuning.iter().enumerate() {
+                            if !pitch.is_keep() {
+                                current[pc] = *pitch;
+                            }
+                        }
+                    }
+                    entries.push(Entry {
+                        tuning: TuningBuilder {
+                            time,
+                            ..defaults.clone()
+                        },
+                        line: line_idx,
+                        pitch_lines: [None; 12],
+                        anchor: None,
+                        bar: None,
+                        beat: None,
+                        label: None,
+                        comment: None,
+                        defer: None,
+                        envelopes: vec![],
+                        extra_messages: vec![],
+                        cue: None,
+                        expectations: vec![],
+                        velocity_offsets: [None; 12],
+                        automation: vec![],
+                    });
+                }
+                "root" | "offset" if entries.last().is_some_and(|e| e.anchor.is_some()) => {
+                    return Err(field_error(&format!(
+                        "{field} after anchor, move it before the anchor"
+                    )));
+                }
+                "root" => {
+                    if parse_pitch_class(value).is_none() {
+                        return Err(invalid("pitch class"));
+                    }
+                    match entries.last_mut() {
+                        Some(entry) => entry.tuning.root(value),
+                        None => defaults.root(value),
+                    };
+                }
+                "offset" => {
+                    let offset = parse_ratio(value).ok_or_else(|| invalid("ratio"))?;
+                    match entries.last_mut() {
+                        Some(entry) => entry.tuning.offset(offset),
+                        None => defaults.offset(offset),
+                    };
+                }
+                "subgroup" => {
+                    if !entries.is_empty() {
+                        return Err(field_error("subgroup after the first tuning"));
+                    }
+                    let primes: Option<Vec<u128>> = value
+                        .split('.')
+                        .map(|p| p.trim().parse().ok().filter(|p| is_prime(*p)))
+                        .collect();
+                    subgroup = Some(primes.ok_or_else(|| invalid("subgroup"))?);
+                }
+                "anchor" => {
+                    let Some(entry) = entries.last_mut() else {
+                        return Err(field_error("anchor before the first tuning"));
+                    };
+                    let (pc, expression) = value.split_once('=').unwrap_or((value, ""));
+                    let pc = parse_pitch_class(pc.trim()).ok_or_else(|| invalid("pitch class"))?;
+                    let (relative_to, ratio) = if expression.trim().is_empty() {
+                        (Some(pc), Rational::one())
+                    } else {
+                        parse_expression(expression).ok_or_else(|| invalid("anchor"))?
+                    };
+                    let anchor = match relative_to {
+                        Some(relative_to) => {
+                            let tuned = match entry.tuning.pitches[relative_to] {
+                                PitchSpec::Keep => {
+                                    entry.relative_to_root(relative_to, current[relative_to])
+                                }
+                                pitch => pitch,
+                            };
+                            match tuned {
+                                PitchSpec::Ratio(tuned) => tuned * ratio,
+                                PitchSpec::Keep => {
+                                    return Err(error(&format!(
+                                        "{} is not tuned yet",
+                                        SEMITONE_NAMES[relative_to]
+                                    )))
+                                }
+                                PitchSpec::Cents(_) => {
+                                    return Err(error(&format!(
+                                        "{} is tempered",
+                                        SEMITONE_NAMES[relative_to]
+                                    )))
+                                }
+                            }
+                        }
+                        None => ratio,
+                    };
+                    entry.anchor = Some(anchor);
+                }
+                "bar" | "beat" | "label" | "comment" | "defer" | "cue" => {
+                    let Some(entry) = entries.last_mut() else {
+                        return Err(field_error(&format!("{field} before the first tuning")));
+                    };
+                    match field {
+                        "bar" => entry.bar = Some(value.parse().map_err(|_| invalid("bar"))?),
+                        "beat" => {
+                            let beat = value.parse().ok().filter(|b: &f64| *b >= 1.0);
+                            entry.beat = Some(beat.ok_or_else(|| invalid("beat"))?);
+                        }
+                        "label" => entry.label = Some(value.to_string()),
+                        "cue" => entry.cue = Some(value.to_string()),
+                        "defer" => {
+                            entry.defer = Some(match value {
+                                "never" => DeferPolicy::Never,
+                                "pedal" => DeferPolicy::Pedal,
+                                "sustained" => DeferPolicy::Sustained,
+                                "silence" => DeferPolicy::Silence,
+                                _ => return Err(invalid("defer policy")),
+                            })
+                        }
+                        _ => match &mut entry.comment {
+                            Some(comment) => {
+                                comment.push('\n');
+                                comment.push_str(value);
+                            }
+                            None => entry.comment = Some(value.to_string()),
+                        },
+                    }
+                }
+                "cc" | "midi" => {
+                    let Some(entry) = entries.last_mut() else {
+                        return Err(field_error(&format!("{field} before the first tuning")));
+                    };
+                    let messages = if field == "cc" {
+                        parse_cc(value)
+                    } else {
+                        parse_midi(value).map(|message| vec![message])
+                    };
+                    entry
+                        .extra_messages
+                        .extend(messages.ok_or_else(|| invalid("MIDI message"))?);
+                }
+                "velocity" => {
+                    let Some(entry) = entries.last_mut() else {
+                        return Err(field_error("velocity before the first tuning"));
+                    };
+                    let (pcs, offset) =
+                        parse_channel_value(value).ok_or_else(|| invalid("pitch class"))?;
+                    let offset = offset
+                        .trim_start_matches('+')
+                        .parse()
+                        .ok()
+                        .filter(|o: &i32| o.abs() < 128)
+                        .ok_or_else(|| invalid("velocity offset"))?;
+                    for pc in pcs {
+                        entry.velocity_offsets[pc] = Some(offset);
+                    }
+                }
+                "volume" | "expression" => {
+                    let Some(entry) = entries.last_mut() else {
+                        return Err(field_error(&format!("{field} before the first tuning")));
+                    };
+                    let (pcs, value) =
+                        parse_channel_value(value).ok_or_else(|| invalid("pitch class"))?;
+                    let value: u8 = value
+                        .parse()
+                        .ok()
+                        .filter(|v| *v < 128)
+                        .ok_or_else(|| invalid("CC value"))?;
+                    let controller = if field == "volume" {
+                        CC_VOLUME
+                    } else {
+                        CC_EXPRESSION
+                    };
+                    entry
+                        .extra_messages
+                        .extend(pcs.map(|pc| vec![0xB0 | pc as u8, controller, value]));
+                }
+                "automation" => {
+                    let Some(entry) = entries.last_mut() else {
+                        return Err(field_error("automation before the first tuning"));
+                    };
+                    let lane =
+                        automation::parse_lane(value).ok_or_else(|| invalid("automation"))?;
+                    entry.automation.push(lane);
+                }
+                "envelope" => {
+                    let Some(entry) = entries.last_mut() else {
+                        return Err(field_error("envelope before the first tuning"));
+                    };
+                    let envelope = parse_envelope(value).ok_or_else(|| invalid("envelope"))?;
+                    entry.envelopes.push(envelope);
+                }
+                "expect" => {
+                    let Some(entry) = entries.last_mut() else {
+                        return Err(field_error("expect before the first tuning"));
+                    };
+                    let expectation = value
+                        .split(" -- ")
+                        .next()
+                        .and_then(|value| value.split_once("=="))
+                        .and_then(|(pc, expected)| {
+                            let (relative_to, ratio) = parse_expression(expected)?;
+                            Some(Expectation {
+                                line: line_idx + 1,
+                                text: line.to_string(),
+                                pc: parse_pitch_class(pc.trim())?,
+                                relative_to,
+                                ratio,
+                            })
+                        })
+                        .ok_or_else(|| invalid("expectation"))?;
+                    entry.expectations.push(expectation);
+                }
+                _ => {
+                    let Some(pc) = parse_pitch_class(field) else {
+                        return Err(match suggest_field(field) {
+                            Some(suggestion) => field_error(&format!(
+                                "Unknown field `{field}`, did you mean `{suggestion}`?"
+                            )),
+                            None => field_error(&format!("Unknown field `{field}`")),
+                        });
+                    };
+                    let Some(entry) = entries.last_mut() else {
+                        return Err(field_error("Pitch class tuned before the first tuning"));
+                    };
+                    if !entry.tuning.pitches[pc].is_keep() && in_variant.is_none() {
+                        return Err(field_error(&format!("{} tuned twice", SEMITONE_NAMES[pc])));
+                    }
+                    if value == "keep" {
+                        entry.tuning.keep(field);
+                    } else if let Some((ratio, of)) = value.split_once(" of ") {
+                        let ratio = parse_ratio(ratio).ok_or_else(|| invalid("ratio"))?;
+                        let of_pc =
+                            parse_pitch_class(of.trim()).ok_or_else(|| invalid("pitch class"))?;
+                        if entry.tuning.pitches[of_pc].is_keep() {
+                            return Err(error(&format!(
+                                "{} is not tuned in this tuning",
+                                SEMITONE_NAMES[of_pc]
+                            )));
+                        }
+                        entry.tuning.set_of(field, ratio, of.trim());
+                    } else {
+                        let pitch = parse_pitch(value).ok_or_else(|| invalid("ratio or cents"))?;
+                        match entry.anchor {
+                            // Place the note in the octave expected by `td`, near its 12edo pitch above the root.
+                            Some(anchor) => entry.tuning.set_near(field, pitch * anchor),
+                            None => entry.tuning.set(field, pitch),
+                        };
+                    }
+                    entry.pitch_lines[pc] = match value {
+                        "keep" => None,
+                        _ if value.contains(" of ") => None,
+                        _ => Some(PitchLine {
+                            line: line_idx,
+                            column: value_column,
+                            anchor: entry.anchor,
+                        }),
+                    };
+                }
+            }
+            Ok(())
+        })();
+        if let Err(error) = result {
+            errors.push(error);
+        }
+    }
+
+    if in_variant.is_some() {
+        errors.push(format!("{path}: Variant not closed with `end`"));
+    }
+    if entries.is_empty() {
+        errors.push(format!(
+            "{path}: No tunings, start one with `{}`",
+            syntax("tuning").unwrap()
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let lines: Vec<&str> = text.lines().collect();
+    let first = &entries[0];
+    let untuned: Vec<&str> = (0..12)
+        .filter(|pc| first.tuning.pitches[*pc].is_keep())
+        .map(|pc| SEMITONE_NAMES[pc])
+        .collect();
+    if !untuned.is_empty() {
+        errors.push(line_error(
+            path,
+            first.line,
+            1,
+            lines[first.line],
+            &format!(
+                "The first tuning must tune all 12 pitch classes, missing {}",
+                untuned.join(" ")
+            ),
+        ));
+    }
+    // Primes are checked first, as the other checks build the tunings into monzos.
+    for (idx, entry) in entries.iter_mut().enumerate() {
+        for pc in 0..12 {
+            if let Some(error) = check_primes(entry, pc, &lines, path) {
+                match LENIENT_TUNING_FILES {
+                    true => skip_pitch(entry, pc, idx == 0, &error),
+                    false => errors.push(error),
+                }
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    for (idx, entry) in entries.iter_mut().enumerate() {
+        for pc in 0..12 {
+            let Some(pitch_line) = entry.pitch_lines[pc] else {
+                continue;
+            };
+            if let Some(error) = check_bend_range(entry, pc, &pitch_line, &lines, path, pb_range) {
+                if OCTAVE_REDUCE_TUNINGS && reduce_octaves(entry, pc, &error, pb_range) {
+                    continue;
+                }
+                match LENIENT_TUNING_FILES {
+                    true => skip_pitch(entry, pc, idx == 0, &error),
+                    false => errors.push(error),
+                }
+            }
+        }
+    }
+    for entry in &entries {
+        if let Some(subgroup) = &subgroup {
+            errors.extend(check_subgroup(entry, subgroup, &lines, path));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    for variant in variants {
+        if !declared_variants.contains(&variant.as_str()) {
+            println!("WARN: Variant {variant} is not declared in {path}");
+        }
+    }
+    if !declared_variants.is_empty() {
+        println!(
+            "Tuning variants in {path}: {} (selected: {})",
+            declared_variants.join(", "),
+            if variants.is_empty() {
+                "none".to_string()
+            } else {
+                variants.join(", ")
+            }
+        );
+    }
+
+    let tunings: Vec<TuningData> = entries
+        .iter()
+        .map(|entry| {
+            let mut tuning = entry.tuning.build();
+            tuning.bar = entry.bar;
+            tuning.beat = entry.beat;
+            tuning.label = entry.label.clone();
+            tuning.comment = entry.comment.clone();
+            tuning.defer = entry.defer;
+            tuning.envelopes = entry.envelopes.clone();
+            tuning.extra_messages = entry.extra_messages.clone();
+            tuning.cue = entry.cue.clone();
+            tuning.velocity_offsets = entry.velocity_offsets;
+            tuning.automation = entry.automation.clone();
+            tuning
+        })
+        .collect();
+
+    let errors = check_expectations(&entries, &tunings, path);
+    match errors.is_empty() {
+        true => Ok(tunings),
+        false => Err(errors),
+    }
+}
+
+/// Checks the expectations of each entry against the tuning in effect after it is applied (in order of appearance),
+/// returning an error for each expectation that fails or is of a pitch class that isn't tuned yet.
+fn check_expectations(entries: &[Entry], tunings: &[TuningData], path: &str) -> Vec<String> {
+    let mut current = [PitchSpec::Keep; 12];
+    let mut errors = vec![];
+
+    for (entry, tuning) in entries.iter().zip(tunings) {
+        for (pc, pitch) in tuning.tuning.iter().enumerate() {
+            if !pitch.is_keep() {
+                current[pc] = *pitch;
+            }
+        }
+
+        for expectation in &entry.expectations {
+            let untuned = [Some(expectation.pc), expectation.relative_to]
+                .into_iter()
+                .flatten()
+                .find(|pc| current[*pc].is_keep());
+            if let Some(pc) = untuned {
+                errors.push(format!(
+                    "{path}:{}: {} is not tuned yet: {}",
+                    expectation.line, SEMITONE_NAMES[pc], expectation.text
+                ));
+                continue;
+            }
+
+            // Both relative to A.
+            let expected = match expectation.relative_to {
+                Some(pc) => current[pc] * expectation.ratio,
+                None => PitchSpec::Ratio(entry.tuning.offset * expectation.ratio),
+            };
+            let actual = current[expectation.pc];
+
+            // Compare to the nearest octave of the expected pitch.
+            let difference = actual.cents().unwrap() - expected.cents().unwrap();
+            let octaves = (difference / 1200.0).round() as i32;
+            let actual = actual / octaves_ratio(octaves);
+            let deviation = difference - 1200.0 * octaves as f64;
+            let passed = match (actual, expected) {
+                (PitchSpec::Ratio(actual), PitchSpec::Ratio(expected)) => actual == expected,
+                _ => deviation.abs() < TEMPERED_TOLERANCE_CENTS,
+            };
+            if passed {
+                continue;
+            }
+            errors.push(format!(
+                "{path}:{}: {}: {}\n  {} is {} ({deviation:+.3}c from expected {})",
+                expectation.line,
+                tuning.describe(),
+                expectation.text,
+                SEMITONE_NAMES[expectation.pc],
+                actual / entry.tuning.offset,
+                expected / entry.tuning.offset,
+            ));
+        }
+    }
+    errors
+}
+
+/// Returns an error for each pitch class tuned by `entry` to a ratio with primes outside `subgroup`, at the line that
+/// tunes it (or the `tuning` line, for pitch classes tuned relative to another one).
+fn check_subgroup(entry: &Entry, subgroup: &[u128], lines: &[&str], path: &str) -> Vec<String> {
+    let subgroup_name = subgroup
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(".");
+    // Line index of each error, to report them in order.
+    let mut errors = vec![];
+    for (pc, pitch) in entry.tuning.build().tuning.iter().enumerate() {
+        let PitchSpec::Ratio(ratio) = *pitch else {
+            continue;
+        };
+        let stray: Vec<String> = [ratio.numerator(), ratio.denominator()]
+            .into_iter()
+            .flat_map(|n| {
+                PrimeFactors::from(n as u128)
+                    .iter()
+                    .map(|f| f.integer)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|p| !subgroup.contains(p))
+            .map(|p| p.to_string())
+            .collect();
+        if stray.is_empty() {
+            continue;
+        }
+        let (line, column) = match entry.pitch_lines[pc] {
+            Some(pitch_line) => (pitch_line.line, pitch_line.column),
+            None => (entry.line, 1),
+        };
+        let msg = format!(
+            "{} is tuned to {} (times offset {}), with prime {} outside the subgroup {subgroup_name}",
+            SEMITONE_NAMES[pc],
+            entry.relative_to_root(pc, *pitch),
+            entry.tuning.offset,
+            stray.join(" & ")
+        );
+        errors.push((line, line_error(path, line, column, lines[line], &msg)));
+    }
+    errors.sort_by_key(|(line, _)| *line);
+    errors.into_iter().map(|(_, error)| error).collect()
+}
+
+/// Returns an error if `pc` is tuned by `entry` to a ratio (times `offset`) with a prime beyond [`PRIMES`], which
+/// monzos can't be made of.
+fn check_primes(entry: &Entry, pc: usize, lines: &[&str], path: &str) -> Option<String> {
+    let PitchSpec::Ratio(ratio) = entry.tuning.pitches[pc] * entry.tuning.offset else {
+        return None;
+    };
+    let prime = [ratio.numerator(), ratio.denominator()]
+        .into_iter()
+        .flat_map(|n| {
+            PrimeFactors::from(n as u128)
+                .iter()
+                .map(|f| f.integer)
+                .collect::<Vec<_>>()
+        })
+        .find(|p| u32::try_from(*p).map_or(true, |p| !PRIMES.contains_key(&p)))?;
+    let (line, column) = match entry.pitch_lines[pc] {
+        Some(pitch_line) => (pitch_line.line, pitch_line.column),
+        None => (entry.line, 1),
+    };
+    let msg = format!(
+        "{} is tuned to {} (times offset {}), with prime {prime} beyond the {} primes that monzos are made of",
+        SEMITONE_NAMES[pc],
+        entry.tuning.pitches[pc],
+        entry.tuning.offset,
+        PRIMES.len()
+    );
+    Some(line_error(path, line, column, lines[line], &msg))
+}
+
+/// Moves the tuning of `pc` in `entry` by the octaves that bring it within the pitch bend range of +/- `pb_range`
+/// semitones, if any, with a warning of `error`. Returns whether it was moved.
+fn reduce_octaves(entry: &mut Entry, pc: usize, error: &str, pb_range: u16) -> bool {
+    let cents = entry.tuning.build().tuning[pc].cents().unwrap() - 100.0 * pc as f64;
+    let octaves = -(cents / 1200.0).round() as i32;
+    if (cents + 1200.0 * octaves as f64).abs() > 100.0 * pb_range as f64 {
+        return false;
+    }
+    let pitch = entry.tuning.pitches[pc] * octaves_ratio(octaves);
+    println!(
+        "WARN: Moved {} by {octaves:+} octaves to {pitch} at {}, into the pitch bend range:",
+        SEMITONE_NAMES[pc],
+        position(entry)
+    );
+    println!("  {}", error.replace('\n', "\n  "));
+    entry.tuning.pitches[pc] = pitch;
+    true
+}
+
+/// Returns the bar (if given) & time of `entry`, e.g. `bar 5 (18.448s)`.
+fn position(entry: &Entry) -> String {
+    let time = entry.tuning.time;
+    match entry.bar {
+        Some(bar) => format!("bar {bar} ({time}s)"),
+        None => format!("{time}s"),
+    }
+}
+
+/// Skips the tuning of `pc` in `entry` that can't be applied, so that it keeps its previous tuning (or is tuned to
+/// 12edo above the root in the `first` tuning), with a prominent warning of `error` and where it is in the piece.
+fn skip_pitch(entry: &mut Entry, pc: usize, first: bool, error: &str) {
+    let fallback = match first {
+        true => "is tuned to 12edo",
+        false => "keeps its previous tuning",
+    };
+    println!(
+        "WARN: !!! Skipping the tuning of {} at {}, it {fallback} !!!",
+        SEMITONE_NAMES[pc],
+        position(entry)
+    );
+    println!("  {}", error.replace('\n', "\n  "));
+    entry.tuning.pitches[pc] = match first {
+        true => PitchSpec::Cents(100.0 * ((pc + 12 - entry.tuning.root) % 12) as f64),
+        false => PitchSpec::Keep,
+    };
+    entry.pitch_lines[pc] = None;
+}
+
+/// Whether `n` is a prime number.
+fn is_prime(n: u128) -> bool {
+    n >= 2
+        && (2..)
+            .take_while(|d| d * d <= n)
+            .all(|d| !n.is_multiple_of(d))
+}
+
+/// Formats an error at `column` (from 1) of the line at index `line_idx`, showing the line with a caret at the column.
+///
+/// `column` is a byte offset into `line` (plus 1), as found by slicing it. It is reported & marked in characters, so
+/// that errors after e.g. `C♯` point at the right character.
+pub fn line_error(path: &str, line_idx: usize, column: usize, line: &str, msg: &str) -> String {
+    let column = line
+        .get(..column - 1)
+        .map_or(column, |prefix| prefix.chars().count() + 1);
+    format!(
+        "{path}:{}:{column}: {msg}\n  {}\n  {}^",
+        line_idx + 1,
+        line.trim_end(),
+        " ".repeat(column - 1)
+    )
+}
+
+/// Prints `errors` and exits if there are any, for the files that a command can't go on without.
+pub fn report_errors(errors: &[String], path: &str) {
+    if !errors.is_empty() {
+        exit_with_errors(errors, path);
+    }
+}
+
+/// Prints `errors` of the file at `path` and exits.
+pub fn exit_with_errors(errors: &[String], path: &str) -> ! {
+    print_errors(errors, path);
+    std::process::exit(1);
+}
+
+/// Prints `errors` of the file at `path`, e.g. of a tuning file loaded during a performance, which goes on without it.
+pub fn print_errors(errors: &[String], path: &str) {
+    for error in errors {
+        println!("ERROR: {error}");
+    }
+    println!("{} errors in {path}", errors.len());
+}
+
+/// Returns the syntax of `field` (see [`FIELDS`]), or of pitch classes if it is one.
+fn syntax(field: &str) -> Option<&'static str> {
+    match FIELDS.iter().find(|(name, _)| *name == field) {
+        Some((_, syntax)) => Some(syntax),
+        None => parse_pitch_class(field).map(|_| PITCH_SYNTAX),
+    }
+}
+
+/// Returns the field name closest to an unknown `field`, if it is at most 2 edits away (e.g. `tunning` or `lable`).
+fn suggest_field(field: &str) -> Option<&'static str> {
+    FIELDS
+        .iter()
+        .map(|(name, _)| (edit_distance(field, name), *name))
+        .filter(|(distance, name)| *distance <= 2 && *distance < name.len())
+        .min()
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance between `a` & `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            row.push(
+                (prev[j] + (ca != *cb) as usize)
+                    .min(prev[j + 1] + 1)
+                    .min(row[j] + 1),
+            );
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+/// Returns an error if the tuning of `pc` in `entry`, given on `pitch_line`, is further from its 12edo pitch than the
+/// pitch bend range of +/- `pb_range` semitones. Suggests the closest pitch within range that is one character away
+/// from how it is written (for typos like `15/88`), or else the closest that is an octave away from it.
+fn check_bend_range(
+    entry: &Entry,
+    pc: usize,
+    pitch_line: &PitchLine,
+    lines: &[&str],
+    path: &str,
+    pb_range: u16,
+) -> Option<String> {
+    // Offset from 12edo in cents if the pitch class is tuned as `pitch` (relative to root) on this line.
+    let bend_cents = |pitch: PitchSpec| -> f64 {
+        let mut tuning = entry.tuning.clone();
+        let name = SEMITONE_NAMES[pc];
+        match pitch_line.anchor {
+            Some(anchor) => tuning.set_near(name, pitch * anchor),
+            None => tuning.set(name, pitch),
+        };
+        tuning.build().tuning[pc].cents().unwrap() - 100.0 * pc as f64
+    };
+    let range = 100.0 * pb_range as f64;
+    let line = lines[pitch_line.line];
+    let value = line[pitch_line.column - 1..].trim();
+    let cents = bend_cents(parse_pitch(value).unwrap());
+    if cents.abs() <= range {
+        return None;
+    }
+
+    let octaves: Vec<(String, PitchSpec)> = (-3..=3)
+        .map(|octaves| {
+            let pitch = parse_pitch(value).unwrap() * octaves_ratio(octaves);
+            (pitch.to_string(), pitch)
+        })
+        .collect();
+    let typos: Vec<(String, PitchSpec)> = (0..value.len())
+        .filter(|i| value.is_char_boundary(*i))
+        .map(|i| {
+            format!(
+                "{}{}",
+                &value[..i],
+                &value[i..].chars().skip(1).collect::<String>()
+            )
+        })
+        .filter_map(|text| Some((text.clone(), parse_pitch(&text)?)))
+        .collect();
+    // A dropped or doubled character is a likelier slip than a wrong octave, so typos are suggested first.
+    let closest = |candidates: Vec<(String, PitchSpec)>| {
+        candidates
+            .into_iter()
+            .map(|(text, pitch)| (bend_cents(pitch).abs(), text))
+            .filter(|(cents, _)| *cents <= range)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    };
+    let suggestion = closest(typos).or_else(|| closest(octaves));
+
+    let msg = format!(
+        "{} {value} is {cents:+.1}c from 12edo, beyond the pitch bend range of {pb_range} semitones (see \
+        --pb-range)",
+        SEMITONE_NAMES[pc]
+    );
+    let msg = match suggestion {
+        Some((_, text)) => format!("{msg}. Did you mean {text}?"),
+        None => msg,
+    };
+    Some(line_error(
+        path,
+        pitch_line.line,
+        pitch_line.column,
+        line,
+        &msg,
+    ))
+}
+
+/// Returns the ratio of a (possibly negative) number of octaves.
+fn octaves_ratio(octaves: i32) -> Rational {
+    let shift = Rational::new(1i128 << octaves.unsigned_abs(), 1);
+    if octaves < 0 {
+        Rational::one() / shift
+    } else {
+        shift
+    }
+}
+
+/// Returns the contents of a tuning file with the times of its tunings (in order of appearance, as returned by
+/// [`parse`]) replaced by `times`, keeping the time of tunings that are [`None`] and everything else as is.
+pub fn set_times(text: &str, times: &[Option<f64>]) -> String {
+    let mut times = times.iter();
+    text.split_inclusive('\n')
+        .map(|line| {
+            if line.split_whitespace().next() != Some("tuning") {
+                return line.to_string();
+            }
+            match times.next() {
+                Some(Some(time)) => {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    let newline = &line[line.trim_end().len()..];
+                    format!("{indent}tuning {time:.3}{newline}")
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the contents of a tuning file with the pitch class `name` (e.g. `Eb`) of its `tuning`th tuning (in order of
+/// appearance, as returned by [`parse`]) tuned to `value`, e.g. `5/4`. The line of the pitch class outside of variants
+/// is replaced, unless it is after an `anchor`, in which case it is removed and the line is added before the first
+/// anchor instead. Without one, the line is added at the end of the tuning.
+pub fn set_pitch(text: &str, tuning: usize, name: &str, value: &str) -> String {
+    let pc = parse_pitch_class(name).unwrap_or_else(|| panic!("Invalid pitch class: {name}"));
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(String::from).collect();
+    let mut starts =
+        (0..lines.len()).filter(|&i| lines[i].split_whitespace().next() == Some("tuning"));
+    let start = starts
+        .nth(tuning)
+        .unwrap_or_else(|| panic!("No tuning #{tuning} to set {name} in"));
+    let end = starts.next().unwrap_or(lines.len());
+
+    let mut in_variant = false;
+    let mut anchor = None;
+    let mut existing = None;
+    let mut last = start;
+    for (i, line) in lines.iter().enumerate().take(end).skip(start + 1) {
+        let Some(field) = line
+            .split_whitespace()
+            .next()
+            .filter(|field| !field.starts_with('#'))
+        else {
+            continue;
+        };
+        last = i;
+        match field {
+            "variant" => in_variant = true,
+            "end" => in_variant = false,
+            "anchor" => anchor = anchor.or(Some(i)),
+            _ if !in_variant && parse_pitch_class(field) == Some(pc) => existing = Some(i),
+            _ => {}
+        }
+    }
+
+    let line = |neighbour: &str| {
+        let indent = &neighbour[..neighbour.len() - neighbour.trim_start().len()];
+        format!("{indent}{name} {value}\n")
+    };
+    match (existing, anchor) {
+        (Some(i), anchor) if anchor.is_none_or(|anchor| i < anchor) => {
+            let newline = &lines[i][lines[i].trim_end().len()..];
+            let indent = &lines[i][..lines[i].len() - lines[i].trim_start().len()];
+            lines[i] = format!("{indent}{name} {value}{newline}");
+        }
+        (existing, Some(anchor)) => {
+            if let Some(i) = existing {
+                lines.remove(i);
+            }
+            let new_line = line(&lines[anchor]);
+            lines.insert(anchor, new_line);
+        }
+        (_, None) => {
+            if !lines[last].ends_with('\n') {
+                lines[last].push('\n');
+            }
+            let new_line = line(&lines[last]);
+            lines.insert(last + 1, new_line);
+        }
+    }
+    lines.concat()
+}
+
+/// Parses the right side of `expect` & `anchor`: a ratio, a pitch class, or a pitch class times a ratio.
+pub fn parse_expression(s: &str) -> Option<(Option<usize>, Rational)> {
+    match s.split_once('*') {
+        Some((pc, ratio)) => Some((Some(parse_pitch_class(pc.trim())?), parse_ratio(ratio)?)),
+        None => match parse_pitch_class(s.trim()) {
+            Some(pc) => Some((Some(pc), Rational::one())),
+            None => Some((None, parse_ratio(s)?)),
+        },
+    }
+}
+
+/// Parses `<pc or key> scoop <cents>c <seconds>` or `<pc or key> vibrato <cents>c <Hz>`.
+fn parse_envelope(s: &str) -> Option<PitchEnvelope> {
+    let [note, shape, cents, param] = s.split_whitespace().collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let (semitone, key) = if note.contains(|c: char| c.is_ascii_digit()) {
+        let key = parse_key_name(note, 4)?;
+        (pitch_class(key), Some(key))
+    } else {
+        (parse_pitch_class(note)?, None)
+    };
+    let cents = cents.strip_suffix('c')?.parse().ok()?;
+    let param: f64 = param.parse().ok().filter(|p: &f64| *p > 0.0)?;
+    let shape = match shape {
+        "scoop" => EnvelopeShape::Scoop {
+            cents,
+            duration: param,
+        },
+        "vibrato" => EnvelopeShape::Vibrato { cents, rate: param },
+        _ => return None,
+    };
+    Some(PitchEnvelope {
+        semitone,
+        key,
+        shape,
+    })
+}
+
+/// Parses `<controller> <value>` as control change messages, on channel 0 or on all 12 tuned channels for pedals (as in
+/// playback).
+fn parse_cc(s: &str) -> Option<Vec<Vec<u8>>> {
+    let (controller, value) = s.split_once(char::is_whitespace)?;
+    let controller: u8 = controller.parse().ok().filter(|c| *c < 128)?;
+    let value: u8 = value.trim().parse().ok().filter(|v| *v < 128)?;
+    let channels = if PEDAL_CCS.contains(&controller) {
+        0..12
+    } else {
+        0..1
+    };
+    Some(
+        channels
+            .map(|channel| vec![0xB0 | channel, controller, value])
+            .collect(),
+    )
+}
+
+/// Parses `[<pitch class>] <value>` into the pitch classes it applies to (all of them if omitted) and the value.
+fn parse_channel_value(s: &str) -> Option<(std::ops::Range<usize>, &str)> {
+    match s.split_once(char::is_whitespace) {
+        Some((pc, value)) => {
+            let pc = parse_pitch_class(pc)?;
+            Some((pc..pc + 1, value.trim()))
+        }
+        None => Some((0..12, s)),
+    }
+}
+
+/// Parses a raw MIDI message of space separated hex bytes, e.g. `C0 05`.
+fn parse_midi(s: &str) -> Option<Vec<u8>> {
+    let bytes = s
+        .split_whitespace()
+        .map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    LiveEvent::parse(&bytes).ok()?;
+    Some(bytes)
+}
+
+/// Parses the tuning of a pitch class: a ratio, or cents suffixed with `c`, e.g. `603.9c`.
+pub fn parse_pitch(s: &str) -> Option<PitchSpec> {
+    match s.strip_suffix('c') {
+        Some(cents) => cents
+            .trim()
+            .parse()
+            .ok()
+            .filter(|c: &f64| c.is_finite())
+            .map(PitchSpec::Cents),
+        None => parse_ratio(s).map(PitchSpec::Ratio),
+    }
+}
+
+/// Parses a positive ratio, e.g. `5/4` or `2`.
+pub fn parse_ratio(s: &str) -> Option<Rational> {
+    let (numerator, denominator) = s.split_once('/').unwrap_or((s, "1"));
+    let numerator: i128 = numerator.trim().parse().ok()?;
+    let denominator: i128 = denominator.trim().parse().ok()?;
+    (numerator > 0 && denominator > 0).then(|| Rational::new(numerator, denominator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A first tuning of all 12 pitch classes, that the error cases append their lines to.
+    const FIRST_TUNING: &str = "tuning 0
+A 1/1
+Bb 16/15
+B 9/8
+C 6/5
+C# 5/4
+D 4/3
+D# 45/32
+E 3/2
+F 8/5
+F# 5/3
+G 9/5
+G# 15/8
+";
+
+    /// `ondine.tuning` reproduces the tunings of the former `ondine.rs`, as printed by [`Tuner::print_csv`] before the
+    /// annotation column was added, with pitch classes that keep their tuning printed as `0/1`.
+    #[test]
+    fn ondine_matches_csv() {
+        let text = fs::read_to_string("ondine.tuning").unwrap();
+        let tunings = parse(&text, "ondine.tuning", &[], crate::PB_RANGE).unwrap();
+        let csv = fs::read_to_string("ondine_tunings.csv").unwrap();
+        let rows: Vec<&str> = csv.lines().skip(1).collect();
+        assert_eq!(tunings.len(), rows.len());
+        for (t, row) in tunings.iter().zip(rows) {
+            let pitches = t.tuning.iter().map(|pitch| match pitch {
+                PitchSpec::Keep => "0/1".to_string(),
+                pitch => pitch.to_string(),
+            });
+            let monzos = t.monzos.iter().map(|monzo| format!("{monzo:?}"));
+            let fields: Vec<String> = std::iter::once(t.time.to_string())
+                .chain(pitches)
+                .chain(monzos)
+                .collect();
+            assert_eq!(fields.join(";"), row, "tuning at {}", t.time);
+        }
+    }
+
+    #[test]
+    fn columns_are_characters() {
+        // `C♯ ` is 5 bytes, the value starts at the 4th character.
+        let error = line_error("test.tuning", 2, 6, "C♯ 5/x", "Invalid pitch");
+        assert_eq!(error, "test.tuning:3:4: Invalid pitch\n  C♯ 5/x\n     ^");
+    }
+
+    #[test]
+    fn suggests_fields() {
+        assert_eq!(suggest_field("tunning"), Some("tuning"));
+        assert_eq!(suggest_field("lable"), Some("label"));
+        assert_eq!(suggest_field("xyzzy"), None);
+    }
+
+    /// Returns the errors of a tuning file of [`FIRST_TUNING`] followed by `lines`.
+    fn errors(lines: &str) -> Vec<String> {
+        parse(&format!("{FIRST_TUNING}{lines}"), "test.tuning", &[], 4)
+            .err()
+            .expect("no errors")
+    }
+
+    #[test]
+    fn parses_first_tuning() {
+        let tunings = parse(FIRST_TUNING, "test.tuning", &[], 4).unwrap();
+        assert_eq!(tunings.len(), 1);
+        assert_eq!(tunings[0].tuning[7], PitchSpec::Ratio(Rational::new(3, 2)));
+    }
+
+    #[test]
+    fn invalid_pitch() {
+        assert_eq!(
+            errors("tuning 1\nC♯ 5/x\n"),
+            ["test.tuning:15:4: Invalid ratio or cents, expected \
+              `<pitch class> <ratio>|<cents>c|<ratio> of <pitch class>|keep`\n  C♯ 5/x\n     ^"]
+        );
+    }
+
+    #[test]
+    fn unknown_field() {
+        assert_eq!(
+            errors("tunning 1\n"),
+            ["test.tuning:14:1: Unknown field `tunning`, did you mean `tuning`?\n  tunning 1\n  ^"]
+        );
+    }
+
+    #[test]
+    fn beyond_bend_range() {
+        assert_eq!(
+            errors("tuning 1\nE 15/88\n"),
+            ["test.tuning:15:3: E 15/88 is -3763.0c from 12edo, beyond the pitch bend range of 4 semitones \
+              (see --pb-range). Did you mean 15/8?\n  E 15/88\n    ^"]
+        );
+    }
+
+    #[test]
+    fn unclosed_variant() {
+        assert_eq!(
+            errors("tuning 1\nvariant 19-16\nD 19/16\n"),
+            ["test.tuning: Variant not closed with `end`"]
+        );
+    }
+
+    #[test]
+    fn failed_expectations() {
+        assert_eq!(
+            errors("tuning 1\nexpect E == A * 5/4\ntuning 2\nexpect C# == 6/5 -- minor third\n"),
+            [
+                "test.tuning:15: Tuning data @ 1s: expect E == A * 5/4\n  E is 3/2 (+315.641c from expected 5/4)",
+                "test.tuning:17: Tuning data @ 2s: expect C# == 6/5 -- minor third\n  \
+                 C# is 5/4 (+70.672c from expected 6/5)"
+            ]
+        );
+    }
+
+    #[test]
+    fn unreadable_file() {
+        let errors = load("missing.tuning", &[], 4).err().expect("no errors");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("missing.tuning: Failed to read tuning file: "));
+    }
+}
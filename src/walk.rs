@@ -0,0 +1,86 @@
+//! Dry-run walkthrough of the tuning timeline (`walk`), a study mode for the interpretation: steps through the tuning
+//! changes of the timeline as played back, printing for each the chord sounding as it is applied, the tuning of every
+//! pitch class, the commas it retunes them by and the comment of the tuning, without sending anything to an output.
+
+use std::fmt::Write as _;
+
+use crate::analysis::ONSET_TOLERANCE;
+use crate::chord;
+use crate::diff;
+use crate::score::Score;
+use crate::tuner::{
+    cents_from_12edo, pitch_class, ratio_name, spelled_name, PitchSpec, Tuner, TuningSnapshot,
+};
+
+/// Describes the tuning change `idx` of `tuner` (with the `snapshots` of `tuner`) in `score`, see the
+/// [module docs](self).
+pub fn report(score: &Score, tuner: &Tuner, snapshots: &[TuningSnapshot], idx: usize) -> String {
+    let snapshot = &snapshots[idx];
+    // The root of the latest tuning that gave one, as in playback.
+    let root = (0..=idx).rev().find_map(|i| tuner[i].root).unwrap_or(0);
+    let sounding: Vec<usize> = score
+        .notes
+        .iter()
+        .take_while(|note| note.start <= snapshot.time + ONSET_TOLERANCE)
+        .filter(|note| note.release > snapshot.time)
+        .map(|note| pitch_class(note.key))
+        .collect();
+
+    let mut report = String::new();
+    writeln!(
+        report,
+        "[{idx}/{}] {} (bar {} of the MIDI file)",
+        snapshots.len() - 1,
+        tuner[idx].describe(),
+        score.position(snapshot.time)
+    )
+    .unwrap();
+    let tones: Vec<(usize, PitchSpec)> = sounding
+        .iter()
+        .map(|pc| (*pc, snapshot.tuning[*pc]))
+        .collect();
+    match chord::caption(&tones, &snapshot.tuning, &snapshot.spellings, root) {
+        chord if chord.is_empty() => writeln!(report, "Chord: -").unwrap(),
+        chord => writeln!(report, "Chord: {chord}").unwrap(),
+    }
+    for pc in 0..12 {
+        writeln!(
+            report,
+            "  {} {:<3} {:<18} {:+8.2}c",
+            if sounding.contains(&pc) { "*" } else { " " },
+            spelled_name(&snapshot.spellings, pc),
+            ratio_name(&snapshot.tuning, &snapshot.spellings, pc, root),
+            cents_from_12edo(&snapshot.tuning, pc)
+        )
+        .unwrap();
+    }
+
+    let changes = match idx.checked_sub(1) {
+        Some(prev) => diff::changes(&snapshots[prev], snapshot),
+        None => vec![],
+    };
+    if !changes.is_empty() {
+        writeln!(report, "Retunes:").unwrap();
+    }
+    for change in &changes {
+        let comma = change
+            .comma()
+            .map_or(String::new(), |comma| format!(" by {comma}"));
+        writeln!(
+            report,
+            "  {:<3} {} -> {} ({:+.2}c{comma})",
+            spelled_name(&snapshot.spellings, change.semitone),
+            change.from,
+            change.to,
+            change.cents()
+        )
+        .unwrap();
+    }
+    if let Some(comment) = &tuner[idx].comment {
+        writeln!(report, "Comment:").unwrap();
+        for line in comment.lines() {
+            writeln!(report, "  {line}").unwrap();
+        }
+    }
+    report
+}